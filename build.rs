@@ -1,4 +1,34 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tonic_build::compile_protos("proto/sql_parser.proto")?;
+    // 描述符集供 gRPC 反射服务使用
+    let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR")?);
+    tonic_build::configure()
+        .file_descriptor_set_path(out_dir.join("sql_parser_descriptor.bin"))
+        .compile_protos(
+            &["proto/sql_parser.proto", "proto/sql_ast.proto"],
+            &["proto"],
+        )?;
+
+    // 记录实际使用的 sqlparser 版本，用于持久化缓存失效
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    println!(
+        "cargo:rustc-env=SQLPARSER_VERSION={}",
+        locked_version("sqlparser").unwrap_or_else(|| "unknown".to_string())
+    );
     Ok(())
 }
+
+fn locked_version(package: &str) -> Option<String> {
+    let lock = std::fs::read_to_string("Cargo.lock").ok()?;
+    let name_line = format!("name = \"{package}\"");
+    let mut lines = lock.lines();
+    while let Some(line) = lines.next() {
+        if line.trim() == name_line {
+            let version = lines.next()?.trim();
+            return version
+                .strip_prefix("version = \"")
+                .and_then(|v| v.strip_suffix('"'))
+                .map(str::to_string);
+        }
+    }
+    None
+}
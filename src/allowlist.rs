@@ -0,0 +1,230 @@
+use crate::config::{AllowlistConfig, AllowlistMode};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+// 指纹哈希白名单。文件每行一个哈希（GET /fingerprints 中的 hash），# 之后为注释；
+// 学习模式下放行所有查询，并把未登记的哈希连同指纹注释追加到文件
+pub struct Allowlist {
+    path: PathBuf,
+    mode: AllowlistMode,
+    max_in_values: usize,
+    reload_interval: u64,
+    current: RwLock<(HashSet<String>, Option<SystemTime>)>,
+    // 追加写入串行进行，避免同一哈希被写入两次
+    append: Mutex<()>,
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn parse(content: &str) -> Result<HashSet<String>, String> {
+    let mut hashes = HashSet::new();
+    for (i, line) in content.lines().enumerate() {
+        let hash = line.split('#').next().unwrap_or_default().trim();
+        if hash.is_empty() {
+            continue;
+        }
+        if hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!(
+                "line {}: '{hash}' is a legacy 64-bit fingerprint hash, regenerate it from GET /fingerprints",
+                i + 1
+            ));
+        }
+        if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("line {}: invalid fingerprint hash '{hash}'", i + 1));
+        }
+        hashes.insert(hash.to_ascii_lowercase());
+    }
+    Ok(hashes)
+}
+
+fn read(path: &Path) -> Result<HashSet<String>, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    parse(&content).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+impl Allowlist {
+    // 学习模式下文件可以不存在，首次学习时创建
+    pub fn from_config(config: &AllowlistConfig) -> Result<Arc<Self>, String> {
+        let path = config
+            .file
+            .clone()
+            .ok_or("allowlist.file is required when the allowlist is enabled")?;
+        let hashes = if config.mode == AllowlistMode::Learn && !path.exists() {
+            HashSet::new()
+        } else {
+            read(&path)?
+        };
+        Ok(Arc::new(Self {
+            current: RwLock::new((hashes, modified_time(&path))),
+            path,
+            mode: config.mode,
+            max_in_values: config.max_in_values,
+            reload_interval: config.reload_interval,
+            append: Mutex::new(()),
+        }))
+    }
+
+    pub fn mode(&self) -> AllowlistMode {
+        self.mode
+    }
+
+    pub fn max_in_values(&self) -> usize {
+        self.max_in_values
+    }
+
+    pub fn len(&self) -> usize {
+        self.current.read().unwrap().0.len()
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.current.read().unwrap().0.contains(hash)
+    }
+
+    // 返回是否新增；已登记（包括其他请求刚刚学到）时返回 false
+    pub fn learn(&self, hash: &str, fingerprint: &str) -> Result<bool, String> {
+        let _guard = self.append.lock().unwrap();
+        if self.contains(hash) {
+            return Ok(false);
+        }
+        let comment = fingerprint.split_whitespace().collect::<Vec<_>>().join(" ");
+        let before = modified_time(&self.path);
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| writeln!(file, "{hash}  # {comment}"))
+            .map_err(|e| format!("Failed to append to {}: {e}", self.path.display()))?;
+        let mut current = self.current.write().unwrap();
+        current.0.insert(hash.to_string());
+        // 自己写入引起的修改不需要重新加载；写入前文件已被外部修改时留给 reload 处理
+        if current.1 == before {
+            current.1 = modified_time(&self.path);
+        }
+        Ok(true)
+    }
+
+    // 文件修改时间变化时重新加载；加载失败时保留旧的白名单
+    pub fn reload_if_changed(&self) -> Result<bool, String> {
+        let _guard = self.append.lock().unwrap();
+        let modified = modified_time(&self.path);
+        if self.current.read().unwrap().1 == modified {
+            return Ok(false);
+        }
+        let hashes = read(&self.path)?;
+        *self.current.write().unwrap() = (hashes, modified);
+        Ok(true)
+    }
+
+    pub fn spawn_reloader(self: &Arc<Self>) {
+        if self.reload_interval == 0 {
+            return;
+        }
+        let allowlist = self.clone();
+        let interval = Duration::from_secs(self.reload_interval);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let reloader = allowlist.clone();
+                match tokio::task::spawn_blocking(move || reloader.reload_if_changed()).await {
+                    Ok(Ok(true)) => {
+                        tracing::info!("Reloaded allowlist ({} fingerprints)", allowlist.len())
+                    }
+                    Ok(Ok(false)) => {}
+                    Ok(Err(e)) => tracing::warn!("Failed to reload allowlist: {e}"),
+                    Err(e) => tracing::warn!("Allowlist reload task failed: {e}"),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(path: &Path, mode: AllowlistMode) -> AllowlistConfig {
+        AllowlistConfig {
+            enabled: true,
+            file: Some(path.to_path_buf()),
+            mode,
+            ..AllowlistConfig::default()
+        }
+    }
+
+    // 第 n 个测试用哈希
+    fn hash(n: u32) -> String {
+        format!("{n:064x}")
+    }
+
+    #[test]
+    fn test_parse_and_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("allowlist.txt");
+        let reviewed = crate::registry::fingerprint_hash("SELECT ?");
+        std::fs::write(
+            &path,
+            format!("# 已审核\n{}  # SELECT ?\n\n", reviewed.to_uppercase()),
+        )
+        .unwrap();
+        let allowlist = Allowlist::from_config(&config(&path, AllowlistMode::Enforce)).unwrap();
+        assert!(allowlist.contains(&reviewed));
+        assert_eq!(allowlist.len(), 1);
+        assert!(!allowlist.reload_if_changed().unwrap());
+
+        std::fs::write(&path, format!("{}\n{}\n", hash(1), hash(2))).unwrap();
+        let later = SystemTime::now() + Duration::from_secs(5);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(allowlist.reload_if_changed().unwrap());
+        assert!(!allowlist.contains(&reviewed));
+        assert_eq!(allowlist.len(), 2);
+
+        // 无效内容不替换当前白名单；旧版 64 位哈希给出单独的提示
+        for (content, expected) in [
+            ("not-a-hash\n", "invalid fingerprint hash"),
+            ("9f3b2c1d0e4a5b6c\n", "legacy 64-bit fingerprint hash"),
+        ] {
+            std::fs::write(&path, content).unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(later + Duration::from_secs(5))
+                .unwrap();
+            let error = allowlist.reload_if_changed().unwrap_err();
+            assert!(error.contains("line 1") && error.contains(expected), "{error}");
+            assert_eq!(allowlist.len(), 2);
+        }
+
+        let missing = dir.path().join("missing.txt");
+        assert!(Allowlist::from_config(&config(&missing, AllowlistMode::Enforce)).is_err());
+    }
+
+    #[test]
+    fn test_learn_appends_new_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("learned.txt");
+        let allowlist = Allowlist::from_config(&config(&path, AllowlistMode::Learn)).unwrap();
+        assert!(allowlist
+            .learn(&hash(1), "SELECT *\nFROM t WHERE id = ?")
+            .unwrap());
+        assert!(!allowlist.learn(&hash(1), "SELECT ?").unwrap());
+        assert!(!allowlist.reload_if_changed().unwrap());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("{}  # SELECT * FROM t WHERE id = ?\n", hash(1))
+        );
+        let reloaded = Allowlist::from_config(&config(&path, AllowlistMode::Enforce)).unwrap();
+        assert!(reloaded.contains(&hash(1)));
+    }
+}
@@ -0,0 +1,645 @@
+use crate::source::LineIndex;
+use crate::tokenize::Position;
+use serde::ser::{self, Serialize};
+use serde_json::{Map, Value};
+use sqlparser::ast::{Expr, Spanned, Statement, TableFactor, Visit, Visitor};
+use sqlparser::tokenizer::{Location, Span};
+use std::cell::Cell;
+use std::fmt::{self, Display};
+use std::ops::ControlFlow;
+
+// sqlparser 的 AST 携带 Span 与 AttachedToken（SELECT、WITH 等关键字的原始词法单元）。
+// 默认输出去掉这两类字段：缓存的 AST 来自规范化后的 SQL，其中的位置对调用方没有意义，
+// 也使 JSON 结构与升级前保持一致。
+pub fn to_value<T: Serialize + ?Sized>(ast: &T) -> Result<Value, String> {
+    ast.serialize(AstSerializer { spans: None })
+        .map(Node::into_value)
+        .map_err(|e| e.0)
+}
+
+// with_spans：Statement、Expr、TableFactor 节点增加 "span" 键，Ident 保留自身的 span，
+// 位置换算为相对 sql（解析所用的原始输入）的行列与字节偏移
+pub fn to_value_with_spans(statements: &[Statement], sql: &str) -> Result<Value, String> {
+    let mut collector = SpanCollector(Vec::new());
+    for statement in statements {
+        let _ = statement.visit(&mut collector);
+    }
+    let spans = Spans {
+        spans: collector.0,
+        next: Cell::new(0),
+        lines: LineIndex::new(sql),
+    };
+    let value = statements
+        .serialize(AstSerializer {
+            spans: Some(&spans),
+        })
+        .map_err(|e| e.0)?
+        .into_value();
+    // 序列化与遍历的节点顺序不一致时宁可报错，也不输出错位的位置
+    if spans.next.get() != spans.spans.len() {
+        return Err("AST spans do not line up with the serialized nodes".to_string());
+    }
+    Ok(value)
+}
+
+// 将相对某条语句文本的位置平移为相对整个输入，start 为该语句在输入中的起点
+pub fn shift_spans(value: &mut Value, start: Position) {
+    match value {
+        Value::Array(items) => items.iter_mut().for_each(|item| shift_spans(item, start)),
+        Value::Object(fields) => {
+            for (key, value) in fields.iter_mut() {
+                if key != "span" || !value.is_object() {
+                    shift_spans(value, start);
+                    continue;
+                }
+                for position in ["start", "end"] {
+                    let position = &mut value[position];
+                    let (Some(line), Some(column), Some(offset)) = (
+                        position["line"].as_u64(),
+                        position["column"].as_u64(),
+                        position["offset"].as_u64(),
+                    ) else {
+                        continue;
+                    };
+                    if line == 1 {
+                        position["column"] = (column + start.column - 1).into();
+                    }
+                    position["line"] = (line + start.line - 1).into();
+                    position["offset"] = (offset + start.offset as u64).into();
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+// Visitor 按先序遍历节点，与 serde 序列化各节点的顺序一致
+struct SpanCollector(Vec<Span>);
+
+impl Visitor for SpanCollector {
+    type Break = ();
+
+    fn pre_visit_statement(&mut self, statement: &Statement) -> ControlFlow<()> {
+        self.0.push(statement.span());
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<()> {
+        self.0.push(expr.span());
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_table_factor(&mut self, table_factor: &TableFactor) -> ControlFlow<()> {
+        self.0.push(table_factor.span());
+        ControlFlow::Continue(())
+    }
+}
+
+struct Spans<'a> {
+    spans: Vec<Span>,
+    next: Cell<usize>,
+    lines: LineIndex<'a>,
+}
+
+impl Spans<'_> {
+    fn next(&self) -> Option<Span> {
+        let next = self.next.get();
+        self.next.set(next + 1);
+        self.spans.get(next).copied()
+    }
+
+    // 未实现位置信息的节点为空 Span（行号 0），不输出
+    fn to_json(&self, span: Span) -> Option<Value> {
+        if span == Span::empty() {
+            return None;
+        }
+        let position = |location: Location| Position {
+            line: location.line,
+            column: location.column,
+            offset: self.lines.offset_of(location),
+        };
+        serde_json::to_value(SpanJson {
+            start: position(span.start),
+            end: position(span.end),
+        })
+        .ok()
+    }
+}
+
+#[derive(serde::Serialize)]
+struct SpanJson {
+    start: Position,
+    end: Position,
+}
+
+enum Node {
+    Value(Value),
+    // Ident 等结构体中的 span 字段，由外层决定是否保留
+    Span(Value),
+    // AttachedToken，总是丢弃
+    Skip,
+}
+
+impl Node {
+    fn into_value(self) -> Value {
+        match self {
+            Node::Value(value) | Node::Span(value) => value,
+            Node::Skip => Value::Null,
+        }
+    }
+}
+
+fn span_from_json(value: &Value) -> Option<Span> {
+    let location = |key: &str| {
+        Some(Location {
+            line: value[key]["line"].as_u64()?,
+            column: value[key]["column"].as_u64()?,
+        })
+    };
+    Some(Span::new(location("start")?, location("end")?))
+}
+
+#[derive(Debug)]
+struct Error(String);
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+#[derive(Clone, Copy)]
+struct AstSerializer<'a> {
+    spans: Option<&'a Spans<'a>>,
+}
+
+impl AstSerializer<'_> {
+    fn is_node(name: &str) -> bool {
+        matches!(name, "Statement" | "Expr" | "TableFactor")
+    }
+
+    // 进入节点时立即取出其位置，保证先于子节点
+    fn node_span(self, name: &str) -> Option<Span> {
+        match self.spans {
+            Some(spans) if Self::is_node(name) => spans.next(),
+            _ => None,
+        }
+    }
+
+    fn variant(self, variant: &str, value: Value, span: Option<Span>) -> Value {
+        let mut object = Map::new();
+        object.insert(variant.to_string(), value);
+        if let Some(span) = span.and_then(|span| self.spans?.to_json(span)) {
+            object.insert("span".to_string(), span);
+        }
+        Value::Object(object)
+    }
+
+    fn field(self, node: Node) -> Option<Value> {
+        match node {
+            Node::Value(value) => Some(value),
+            Node::Span(value) => self.spans?.to_json(span_from_json(&value)?),
+            Node::Skip => None,
+        }
+    }
+}
+
+impl<'a> ser::Serializer for AstSerializer<'a> {
+    type Ok = Node;
+    type Error = Error;
+    type SerializeSeq = SeqSerializer<'a>;
+    type SerializeTuple = SeqSerializer<'a>;
+    type SerializeTupleStruct = SeqSerializer<'a>;
+    type SerializeTupleVariant = TupleVariantSerializer<'a>;
+    type SerializeMap = MapSerializer<'a>;
+    type SerializeStruct = StructSerializer<'a>;
+    type SerializeStructVariant = StructVariantSerializer<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<Node, Error> {
+        Ok(Node::Value(Value::Bool(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Node, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Node, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Node, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Node, Error> {
+        Ok(Node::Value(v.into()))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Node, Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Node, Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Node, Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Node, Error> {
+        Ok(Node::Value(v.into()))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Node, Error> {
+        Ok(Node::Value(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Node, Error> {
+        Ok(Node::Value(v.into()))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Node, Error> {
+        Ok(Node::Value(Value::String(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Node, Error> {
+        Ok(Node::Value(Value::String(v.to_string())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Node, Error> {
+        Ok(Node::Value(Value::Array(
+            v.iter().map(|b| Value::from(*b)).collect(),
+        )))
+    }
+
+    fn serialize_none(self) -> Result<Node, Error> {
+        Ok(Node::Value(Value::Null))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Node, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Node, Error> {
+        Ok(Node::Value(Value::Null))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Node, Error> {
+        Ok(Node::Value(Value::Null))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Node, Error> {
+        // 单元变体输出为字符串，没有位置可附加，但仍需消耗一个位置
+        self.node_span(name);
+        Ok(Node::Value(Value::String(variant.to_string())))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Node, Error> {
+        if name == "AttachedToken" {
+            return Ok(Node::Skip);
+        }
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Node, Error> {
+        let span = self.node_span(name);
+        // 只包含 AttachedToken 的变体（如 Expr::Wildcard）退化为单元变体
+        Ok(Node::Value(match self.field(value.serialize(self)?) {
+            Some(value) => self.variant(variant, value, span),
+            None => Value::String(variant.to_string()),
+        }))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer<'a>, Error> {
+        Ok(SeqSerializer {
+            ser: self,
+            items: Vec::with_capacity(len.unwrap_or_default()),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer<'a>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer<'a>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<TupleVariantSerializer<'a>, Error> {
+        let span = self.node_span(name);
+        Ok(TupleVariantSerializer {
+            seq: self.serialize_seq(Some(len))?,
+            variant,
+            span,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer<'a>, Error> {
+        Ok(MapSerializer {
+            ser: self,
+            map: Map::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<StructSerializer<'a>, Error> {
+        Ok(StructSerializer {
+            ser: self,
+            name,
+            map: Map::new(),
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<StructVariantSerializer<'a>, Error> {
+        let span = self.node_span(name);
+        Ok(StructVariantSerializer {
+            fields: self.serialize_struct(variant, len)?,
+            variant,
+            span,
+        })
+    }
+}
+
+struct SeqSerializer<'a> {
+    ser: AstSerializer<'a>,
+    items: Vec<Value>,
+}
+
+impl SeqSerializer<'_> {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        if let Some(value) = self.ser.field(value.serialize(self.ser)?) {
+            self.items.push(value);
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer<'_> {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        Ok(Node::Value(Value::Array(self.items)))
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer<'_> {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        Ok(Node::Value(Value::Array(self.items)))
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer<'_> {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        Ok(Node::Value(Value::Array(self.items)))
+    }
+}
+
+struct TupleVariantSerializer<'a> {
+    seq: SeqSerializer<'a>,
+    variant: &'static str,
+    span: Option<Span>,
+}
+
+impl ser::SerializeTupleVariant for TupleVariantSerializer<'_> {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.seq.push(value)
+    }
+
+    // 去掉 AttachedToken 后只剩一个字段时按 newtype 变体输出（如 Expr::QualifiedWildcard）
+    fn end(self) -> Result<Node, Error> {
+        let ser = self.seq.ser;
+        let mut items = self.seq.items;
+        let value = match items.len() {
+            0 => return Ok(Node::Value(Value::String(self.variant.to_string()))),
+            1 => items.pop().unwrap_or_default(),
+            _ => Value::Array(items),
+        };
+        Ok(Node::Value(ser.variant(self.variant, value, self.span)))
+    }
+}
+
+struct MapSerializer<'a> {
+    ser: AstSerializer<'a>,
+    map: Map<String, Value>,
+    key: Option<String>,
+}
+
+impl ser::SerializeMap for MapSerializer<'_> {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(match key.serialize(self.ser)?.into_value() {
+            Value::String(key) => key,
+            key => key.to_string(),
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.key.take().unwrap_or_default();
+        if let Some(value) = self.ser.field(value.serialize(self.ser)?) {
+            self.map.insert(key, value);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        Ok(Node::Value(Value::Object(self.map)))
+    }
+}
+
+struct StructSerializer<'a> {
+    ser: AstSerializer<'a>,
+    name: &'static str,
+    map: Map<String, Value>,
+}
+
+impl ser::SerializeStruct for StructSerializer<'_> {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        // Span 自身的 start/end 字段原样保留，由外层决定去留
+        let node = value.serialize(self.ser)?;
+        let value = match node {
+            Node::Span(_) | Node::Skip => self.ser.field(node),
+            Node::Value(value) => Some(value),
+        };
+        if let Some(value) = value {
+            self.map.insert(key.to_string(), value);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        let value = Value::Object(self.map);
+        Ok(match self.name {
+            "Span" => Node::Span(value),
+            _ => Node::Value(value),
+        })
+    }
+}
+
+struct StructVariantSerializer<'a> {
+    fields: StructSerializer<'a>,
+    variant: &'static str,
+    span: Option<Span>,
+}
+
+impl ser::SerializeStructVariant for StructVariantSerializer<'_> {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        ser::SerializeStruct::serialize_field(&mut self.fields, key, value)
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        let ser = self.fields.ser;
+        let value = Value::Object(self.fields.map);
+        Ok(Node::Value(ser.variant(self.variant, value, self.span)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use sqlparser::dialect::GenericDialect;
+    use sqlparser::parser::Parser;
+
+    fn parse(sql: &str) -> Vec<Statement> {
+        Parser::parse_sql(&GenericDialect {}, sql).unwrap()
+    }
+
+    #[test]
+    fn test_strips_spans_and_tokens() {
+        let value = to_value(&parse("WITH c AS (SELECT t.*, * FROM t) SELECT x FROM c")).unwrap();
+        let text = value.to_string();
+        assert!(!text.contains("span"), "{text}");
+        assert!(!text.contains("token"), "{text}");
+        let query = &value[0]["Query"];
+        assert_eq!(
+            query["with"]["cte_tables"][0]["alias"]["name"],
+            json!({"value": "c", "quote_style": null})
+        );
+        let projection = &query["with"]["cte_tables"][0]["query"]["body"]["Select"]["projection"];
+        assert!(projection[0]["QualifiedWildcard"].is_array());
+        assert!(projection[1]["Wildcard"].is_object());
+    }
+
+    #[test]
+    fn test_spans_relative_to_input() {
+        let sql = "SELECT a,\n  \"中\" + b FROM t";
+        let value = to_value_with_spans(&parse(sql), sql).unwrap();
+        let select = &value[0]["Query"]["body"]["Select"];
+        let concat = &select["projection"][1]["UnnamedExpr"];
+        let span = &concat["span"];
+        assert_eq!(span["start"], json!({"line": 2, "column": 3, "offset": 12}));
+        assert_eq!(span["end"]["offset"], sql.find(" FROM").unwrap());
+        assert_eq!(
+            &sql[span["start"]["offset"].as_u64().unwrap() as usize
+                ..span["end"]["offset"].as_u64().unwrap() as usize],
+            "\"中\" + b"
+        );
+        // Ident 保留自身的 span
+        let b = &concat["BinaryOp"]["right"];
+        assert_eq!(b["Identifier"]["span"]["start"]["column"], 9);
+        let table = &select["from"][0]["relation"];
+        assert_eq!(table["span"]["start"]["offset"], sql.rfind('t').unwrap());
+    }
+
+    #[test]
+    fn test_spans_line_up_for_nested_nodes() {
+        let sql = "WITH r AS (SELECT id FROM u) \
+                   SELECT CASE WHEN x.a IN (1, 2) THEN count(*) ELSE -b END, t.* \
+                   FROM t JOIN (SELECT a FROM s) AS x USING (a) LEFT JOIN r ON r.id = t.id \
+                   WHERE EXISTS (SELECT 1 FROM v WHERE v.k = t.k) ORDER BY 1 LIMIT 5; \
+                   INSERT INTO t (a) SELECT a FROM s; UPDATE t SET a = a + 1 WHERE id = 3; \
+                   CREATE TABLE n (id INT PRIMARY KEY, c VARCHAR(10) DEFAULT 'x')";
+        let value = to_value_with_spans(&parse(sql), sql).unwrap();
+        let stripped = to_value(&parse(sql)).unwrap();
+        assert_eq!(value.as_array().unwrap().len(), 4);
+        assert!(!stripped.to_string().contains("\"span\""));
+        // sqlparser 的语句位置由子节点合并而来，不含开头的关键字
+        assert_eq!(
+            value[2]["span"]["start"]["offset"],
+            sql.find("t SET").unwrap()
+        );
+    }
+}
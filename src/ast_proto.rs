@@ -0,0 +1,1501 @@
+use crate::sql_parser::ast as pb;
+use serde::Serialize;
+use sqlparser::ast;
+use std::fmt::Display;
+
+// sqlparser AST 转换为 proto/sql_ast.proto 中的类型化消息。
+// 节点只有在未建模的字段全部为默认值时才转换为类型化消息，否则在最近的
+// Statement / SetExpr / TableFactor / Expr / DataType 处退回 Raw（JSON + SQL），
+// 因此转换不丢失信息。
+pub fn statements(statements: &[ast::Statement]) -> Vec<pb::Statement> {
+    statements.iter().map(statement).collect()
+}
+
+fn raw<T: Serialize + Display>(node: &T) -> pb::Raw {
+    pb::Raw {
+        json: crate::ast_json::to_value(node)
+            .map(|json| json.to_string())
+            .unwrap_or_default(),
+        sql: node.to_string(),
+    }
+}
+
+fn require(condition: bool) -> Option<()> {
+    condition.then_some(())
+}
+
+// 可选子节点：不存在时为 Some(None)，存在但无法表示时为 None
+fn optional<T, U>(node: Option<&T>, convert: impl FnOnce(&T) -> Option<U>) -> Option<Option<U>> {
+    match node {
+        Some(node) => convert(node).map(Some),
+        None => Some(None),
+    }
+}
+
+fn ident(ident: &ast::Ident) -> pb::Ident {
+    pb::Ident {
+        value: ident.value.clone(),
+        quote_style: ident.quote_style.map(String::from).unwrap_or_default(),
+    }
+}
+
+fn ident_list(idents: &[ast::Ident]) -> pb::IdentList {
+    pb::IdentList {
+        idents: idents.iter().map(ident).collect(),
+    }
+}
+
+fn object_name(name: &ast::ObjectName) -> pb::ObjectName {
+    pb::ObjectName {
+        parts: name.0.iter().map(ident).collect(),
+    }
+}
+
+fn boxed(expr_: &ast::Expr) -> Option<Box<pb::Expr>> {
+    Some(Box::new(expr(expr_)))
+}
+
+// ---------- 语句 ----------
+
+fn statement(statement: &ast::Statement) -> pb::Statement {
+    use pb::statement::Kind;
+    let kind = match statement {
+        ast::Statement::Query(q) => query(q).map(Kind::Query),
+        ast::Statement::Insert(i) => insert(i).map(Kind::Insert),
+        ast::Statement::Update {
+            table,
+            assignments,
+            from,
+            selection,
+            returning: None,
+            or: None,
+        } => update(table, assignments, from.as_ref(), selection.as_ref()).map(Kind::Update),
+        ast::Statement::Delete(d) => delete(d).map(Kind::Delete),
+        _ => None,
+    };
+    pb::Statement {
+        kind: Some(kind.unwrap_or_else(|| Kind::Raw(raw(statement)))),
+    }
+}
+
+fn insert(insert: &ast::Insert) -> Option<pb::Insert> {
+    let ast::Insert {
+        or,
+        ignore,
+        into,
+        table_name,
+        table_alias,
+        columns,
+        overwrite,
+        source,
+        partitioned,
+        after_columns,
+        table,
+        on,
+        returning,
+        replace_into,
+        priority,
+        insert_alias,
+    } = insert;
+    require(
+        or.is_none()
+            && !ignore
+            && !overwrite
+            && partitioned.is_none()
+            && after_columns.is_empty()
+            && !table
+            && on.is_none()
+            && returning.is_none()
+            && !replace_into
+            && priority.is_none()
+            && insert_alias.is_none(),
+    )?;
+    Some(pb::Insert {
+        into: *into,
+        table_name: Some(object_name(table_name)),
+        table_alias: table_alias.as_ref().map(ident),
+        columns: columns.iter().map(ident).collect(),
+        source: optional(source.as_deref(), query)?,
+    })
+}
+
+fn update(
+    table: &ast::TableWithJoins,
+    assignments: &[ast::Assignment],
+    from: Option<&ast::TableWithJoins>,
+    selection: Option<&ast::Expr>,
+) -> Option<pb::Update> {
+    Some(pb::Update {
+        table: Some(table_with_joins(table)?),
+        assignments: assignments.iter().map(assignment).collect(),
+        from: optional(from, table_with_joins)?,
+        selection: selection.map(expr),
+    })
+}
+
+fn assignment(assignment: &ast::Assignment) -> pb::Assignment {
+    let (tuple, targets) = match &assignment.target {
+        ast::AssignmentTarget::ColumnName(name) => (false, vec![object_name(name)]),
+        ast::AssignmentTarget::Tuple(names) => (true, names.iter().map(object_name).collect()),
+    };
+    pb::Assignment {
+        tuple,
+        targets,
+        value: Some(expr(&assignment.value)),
+    }
+}
+
+fn delete(delete: &ast::Delete) -> Option<pb::Delete> {
+    let ast::Delete {
+        tables,
+        from,
+        using,
+        selection,
+        returning,
+        order_by,
+        limit,
+    } = delete;
+    require(
+        tables.is_empty()
+            && using.is_none()
+            && returning.is_none()
+            && order_by.is_empty()
+            && limit.is_none(),
+    )?;
+    let (from_keyword, from) = match from {
+        ast::FromTable::WithFromKeyword(from) => (true, from),
+        ast::FromTable::WithoutKeyword(from) => (false, from),
+    };
+    Some(pb::Delete {
+        from_keyword,
+        from: from.iter().map(table_with_joins).collect::<Option<_>>()?,
+        selection: selection.as_ref().map(expr),
+    })
+}
+
+// ---------- 查询 ----------
+
+fn query(query: &ast::Query) -> Option<pb::Query> {
+    let ast::Query {
+        with: with_,
+        body,
+        order_by,
+        limit,
+        limit_by,
+        offset,
+        fetch,
+        locks,
+        for_clause,
+        settings,
+        format_clause,
+    } = query;
+    require(
+        limit_by.is_empty()
+            && fetch.is_none()
+            && locks.is_empty()
+            && for_clause.is_none()
+            && settings.is_none()
+            && format_clause.is_none(),
+    )?;
+    let order_by = match order_by {
+        Some(order_by) => {
+            require(order_by.interpolate.is_none() && !order_by.exprs.is_empty())?;
+            order_by
+                .exprs
+                .iter()
+                .map(order_by_expr)
+                .collect::<Option<_>>()?
+        }
+        None => Vec::new(),
+    };
+    Some(pb::Query {
+        with: optional(with_.as_ref(), with)?,
+        body: Some(Box::new(set_expr(body))),
+        order_by,
+        limit: limit.as_ref().and_then(boxed),
+        offset: offset.as_ref().map(|offset| {
+            Box::new(pb::Offset {
+                value: boxed(&offset.value),
+                rows: offset_rows(offset.rows) as i32,
+            })
+        }),
+    })
+}
+
+fn with(with: &ast::With) -> Option<pb::With> {
+    Some(pb::With {
+        recursive: with.recursive,
+        ctes: with.cte_tables.iter().map(cte).collect::<Option<_>>()?,
+    })
+}
+
+fn cte(cte: &ast::Cte) -> Option<pb::Cte> {
+    let ast::Cte {
+        alias,
+        query: q,
+        from,
+        materialized,
+        closing_paren_token: _,
+    } = cte;
+    require(from.is_none() && materialized.is_none())?;
+    Some(pb::Cte {
+        alias: Some(table_alias(alias)?),
+        query: Some(query(q)?),
+    })
+}
+
+fn table_alias(alias: &ast::TableAlias) -> Option<pb::TableAlias> {
+    let columns = alias
+        .columns
+        .iter()
+        .map(|column| column.data_type.is_none().then(|| ident(&column.name)))
+        .collect::<Option<_>>()?;
+    Some(pb::TableAlias {
+        name: Some(ident(&alias.name)),
+        columns,
+    })
+}
+
+fn order_by_expr(order_by: &ast::OrderByExpr) -> Option<pb::OrderByExpr> {
+    let ast::OrderByExpr {
+        expr: e,
+        asc,
+        nulls_first,
+        with_fill,
+    } = order_by;
+    require(with_fill.is_none())?;
+    Some(pb::OrderByExpr {
+        expr: Some(expr(e)),
+        asc: *asc,
+        nulls_first: *nulls_first,
+    })
+}
+
+fn offset_rows(rows: ast::OffsetRows) -> pb::OffsetRows {
+    match rows {
+        ast::OffsetRows::None => pb::OffsetRows::None,
+        ast::OffsetRows::Row => pb::OffsetRows::Row,
+        ast::OffsetRows::Rows => pb::OffsetRows::Rows,
+    }
+}
+
+fn set_expr(set_expr_: &ast::SetExpr) -> pb::SetExpr {
+    use pb::set_expr::Kind;
+    let kind = match set_expr_ {
+        ast::SetExpr::Select(s) => select(s).map(|s| Kind::Select(Box::new(s))),
+        ast::SetExpr::Query(q) => query(q).map(|q| Kind::Query(Box::new(q))),
+        ast::SetExpr::SetOperation {
+            op,
+            set_quantifier: quantifier,
+            left,
+            right,
+        } => Some(Kind::SetOperation(Box::new(pb::SetOperation {
+            op: set_operator(*op) as i32,
+            quantifier: set_quantifier(*quantifier) as i32,
+            left: Some(Box::new(set_expr(left))),
+            right: Some(Box::new(set_expr(right))),
+        }))),
+        ast::SetExpr::Values(values) => Some(Kind::Values(pb::Values {
+            explicit_row: values.explicit_row,
+            rows: values
+                .rows
+                .iter()
+                .map(|row| pb::ExprList {
+                    exprs: row.iter().map(expr).collect(),
+                })
+                .collect(),
+        })),
+        _ => None,
+    };
+    pb::SetExpr {
+        kind: Some(kind.unwrap_or_else(|| Kind::Raw(raw(set_expr_)))),
+    }
+}
+
+fn set_operator(op: ast::SetOperator) -> pb::SetOperator {
+    match op {
+        ast::SetOperator::Union => pb::SetOperator::Union,
+        ast::SetOperator::Except => pb::SetOperator::Except,
+        ast::SetOperator::Intersect => pb::SetOperator::Intersect,
+    }
+}
+
+fn set_quantifier(quantifier: ast::SetQuantifier) -> pb::SetQuantifier {
+    match quantifier {
+        ast::SetQuantifier::None => pb::SetQuantifier::None,
+        ast::SetQuantifier::All => pb::SetQuantifier::All,
+        ast::SetQuantifier::Distinct => pb::SetQuantifier::Distinct,
+        ast::SetQuantifier::ByName => pb::SetQuantifier::ByName,
+        ast::SetQuantifier::AllByName => pb::SetQuantifier::AllByName,
+        ast::SetQuantifier::DistinctByName => pb::SetQuantifier::DistinctByName,
+    }
+}
+
+fn select(select: &ast::Select) -> Option<pb::Select> {
+    let ast::Select {
+        select_token: _,
+        distinct,
+        top,
+        top_before_distinct,
+        projection,
+        into,
+        from,
+        lateral_views,
+        prewhere,
+        selection,
+        group_by,
+        cluster_by,
+        distribute_by,
+        sort_by,
+        having,
+        named_window,
+        qualify,
+        window_before_qualify,
+        value_table_mode,
+        connect_by,
+    } = select;
+    require(
+        top.is_none()
+            && !top_before_distinct
+            && into.is_none()
+            && lateral_views.is_empty()
+            && prewhere.is_none()
+            && cluster_by.is_empty()
+            && distribute_by.is_empty()
+            && sort_by.is_empty()
+            && named_window.is_empty()
+            && qualify.is_none()
+            && !window_before_qualify
+            && value_table_mode.is_none()
+            && connect_by.is_none(),
+    )?;
+    let distinct = match distinct {
+        None => None,
+        Some(ast::Distinct::Distinct) => Some(pb::Distinct { on: Vec::new() }),
+        Some(ast::Distinct::On(exprs)) if !exprs.is_empty() => Some(pb::Distinct {
+            on: exprs.iter().map(expr).collect(),
+        }),
+        Some(ast::Distinct::On(_)) => return None,
+    };
+    let (group_by_all, group_by) = match group_by {
+        ast::GroupByExpr::All(modifiers) if modifiers.is_empty() => (true, Vec::new()),
+        ast::GroupByExpr::Expressions(exprs, modifiers) if modifiers.is_empty() => {
+            (false, exprs.iter().map(expr).collect())
+        }
+        _ => return None,
+    };
+    Some(pb::Select {
+        distinct,
+        projection: projection.iter().map(select_item).collect::<Option<_>>()?,
+        from: from.iter().map(table_with_joins).collect::<Option<_>>()?,
+        selection: selection.as_ref().and_then(boxed),
+        group_by_all,
+        group_by,
+        having: having.as_ref().and_then(boxed),
+    })
+}
+
+fn select_item(item: &ast::SelectItem) -> Option<pb::SelectItem> {
+    use pb::select_item::Kind;
+    let no_options = ast::WildcardAdditionalOptions::default();
+    let kind = match item {
+        ast::SelectItem::UnnamedExpr(e) => Kind::Expr(expr(e)),
+        ast::SelectItem::ExprWithAlias { expr: e, alias } => Kind::Aliased(pb::ExprWithAlias {
+            expr: Some(expr(e)),
+            alias: Some(ident(alias)),
+        }),
+        ast::SelectItem::QualifiedWildcard(name, options) if *options == no_options => {
+            Kind::QualifiedWildcard(object_name(name))
+        }
+        ast::SelectItem::Wildcard(options) if *options == no_options => {
+            Kind::Wildcard(pb::Wildcard {})
+        }
+        _ => return None,
+    };
+    Some(pb::SelectItem { kind: Some(kind) })
+}
+
+fn table_with_joins(table: &ast::TableWithJoins) -> Option<pb::TableWithJoins> {
+    Some(pb::TableWithJoins {
+        relation: Some(table_factor(&table.relation)),
+        joins: table.joins.iter().map(join).collect::<Option<_>>()?,
+    })
+}
+
+fn join(join: &ast::Join) -> Option<pb::Join> {
+    use ast::JoinOperator as Op;
+    require(!join.global)?;
+    let (kind, constraint) = match &join.join_operator {
+        Op::Inner(c) => (pb::JoinKind::Inner, Some(c)),
+        Op::LeftOuter(c) => (pb::JoinKind::LeftOuter, Some(c)),
+        Op::RightOuter(c) => (pb::JoinKind::RightOuter, Some(c)),
+        Op::FullOuter(c) => (pb::JoinKind::FullOuter, Some(c)),
+        Op::CrossJoin => (pb::JoinKind::Cross, None),
+        _ => return None,
+    };
+    Some(pb::Join {
+        relation: Some(table_factor(&join.relation)),
+        kind: kind as i32,
+        constraint: constraint.and_then(join_constraint),
+    })
+}
+
+fn join_constraint(constraint: &ast::JoinConstraint) -> Option<pb::JoinConstraint> {
+    use pb::join_constraint::Kind;
+    let kind = match constraint {
+        ast::JoinConstraint::On(e) => Kind::On(expr(e)),
+        ast::JoinConstraint::Using(columns) => Kind::Using(ident_list(columns)),
+        ast::JoinConstraint::Natural => Kind::Natural(true),
+        ast::JoinConstraint::None => return None,
+    };
+    Some(pb::JoinConstraint { kind: Some(kind) })
+}
+
+fn table_factor(factor: &ast::TableFactor) -> pb::TableFactor {
+    use pb::table_factor::Kind;
+    let kind = match factor {
+        ast::TableFactor::Table {
+            name,
+            alias,
+            args: None,
+            with_hints,
+            version: None,
+            with_ordinality: false,
+            partitions,
+            json_path: None,
+        } if with_hints.is_empty() && partitions.is_empty() => {
+            optional(alias.as_ref(), table_alias).map(|alias| {
+                Kind::Table(pb::Table {
+                    name: Some(object_name(name)),
+                    alias,
+                })
+            })
+        }
+        ast::TableFactor::Derived {
+            lateral,
+            subquery,
+            alias,
+        } => query(subquery)
+            .zip(optional(alias.as_ref(), table_alias))
+            .map(|(subquery, alias)| {
+                Kind::Derived(pb::Derived {
+                    lateral: *lateral,
+                    subquery: Some(subquery),
+                    alias,
+                })
+            }),
+        _ => None,
+    };
+    pb::TableFactor {
+        kind: Some(kind.unwrap_or_else(|| Kind::Raw(raw(factor)))),
+    }
+}
+
+// ---------- 表达式 ----------
+
+fn expr(expr_: &ast::Expr) -> pb::Expr {
+    pb::Expr {
+        kind: Some(expr_kind(expr_).unwrap_or_else(|| pb::expr::Kind::Raw(raw(expr_)))),
+    }
+}
+
+fn expr_kind(expr_: &ast::Expr) -> Option<pb::expr::Kind> {
+    use pb::expr::Kind;
+    Some(match expr_ {
+        ast::Expr::Identifier(i) => Kind::Identifier(ident(i)),
+        ast::Expr::CompoundIdentifier(parts) => Kind::CompoundIdentifier(ident_list(parts)),
+        ast::Expr::Value(value) => Kind::Value(literal(value)?),
+        ast::Expr::BinaryOp { left, op, right } => Kind::BinaryOp(Box::new(pb::BinaryOp {
+            left: boxed(left),
+            op: binary_operator(op)? as i32,
+            right: boxed(right),
+        })),
+        ast::Expr::UnaryOp { op, expr: e } => Kind::UnaryOp(Box::new(pb::UnaryOp {
+            op: unary_operator(*op)? as i32,
+            expr: boxed(e),
+        })),
+        ast::Expr::Nested(e) => Kind::Nested(Box::new(expr(e))),
+        ast::Expr::IsNull(e) => Kind::IsNull(Box::new(expr(e))),
+        ast::Expr::IsNotNull(e) => Kind::IsNotNull(Box::new(expr(e))),
+        ast::Expr::InList {
+            expr: e,
+            list,
+            negated,
+        } => Kind::InList(Box::new(pb::InList {
+            expr: boxed(e),
+            list: list.iter().map(expr).collect(),
+            negated: *negated,
+        })),
+        ast::Expr::InSubquery {
+            expr: e,
+            subquery,
+            negated,
+        } => Kind::InSubquery(Box::new(pb::InSubquery {
+            expr: boxed(e),
+            subquery: Some(Box::new(query(subquery)?)),
+            negated: *negated,
+        })),
+        ast::Expr::Between {
+            expr: e,
+            negated,
+            low,
+            high,
+        } => Kind::Between(Box::new(pb::Between {
+            expr: boxed(e),
+            negated: *negated,
+            low: boxed(low),
+            high: boxed(high),
+        })),
+        ast::Expr::Like {
+            negated,
+            any: false,
+            expr: e,
+            pattern,
+            escape_char,
+        } => Kind::Like(Box::new(like(*negated, e, pattern, escape_char))),
+        ast::Expr::ILike {
+            negated,
+            any: false,
+            expr: e,
+            pattern,
+            escape_char,
+        } => Kind::Ilike(Box::new(like(*negated, e, pattern, escape_char))),
+        ast::Expr::Function(f) => Kind::Function(function(f)?),
+        ast::Expr::Cast {
+            kind,
+            expr: e,
+            data_type: target,
+            format: None,
+        } => Kind::Cast(Box::new(pb::Cast {
+            kind: cast_kind(kind) as i32,
+            expr: boxed(e),
+            data_type: Some(data_type(target)),
+        })),
+        ast::Expr::Case {
+            operand,
+            conditions,
+            results,
+            else_result,
+        } if conditions.len() == results.len() => Kind::Case(Box::new(pb::Case {
+            operand: operand.as_deref().and_then(boxed),
+            whens: conditions
+                .iter()
+                .zip(results)
+                .map(|(condition, result)| pb::CaseWhen {
+                    condition: Some(expr(condition)),
+                    result: Some(expr(result)),
+                })
+                .collect(),
+            else_result: else_result.as_deref().and_then(boxed),
+        })),
+        ast::Expr::Exists { subquery, negated } => Kind::Exists(Box::new(pb::Exists {
+            subquery: Some(Box::new(query(subquery)?)),
+            negated: *negated,
+        })),
+        ast::Expr::Subquery(q) => Kind::Subquery(Box::new(query(q)?)),
+        _ => return None,
+    })
+}
+
+fn literal(value: &ast::Value) -> Option<pb::Literal> {
+    use pb::literal::Kind;
+    let kind = match value {
+        ast::Value::Number(value, long) => Kind::Number(pb::Number {
+            value: value.clone(),
+            long: *long,
+        }),
+        ast::Value::SingleQuotedString(s) => Kind::SingleQuotedString(s.clone()),
+        ast::Value::DoubleQuotedString(s) => Kind::DoubleQuotedString(s.clone()),
+        ast::Value::Boolean(b) => Kind::Boolean(*b),
+        ast::Value::Null => Kind::Null(pb::Null {}),
+        ast::Value::Placeholder(p) => Kind::Placeholder(p.clone()),
+        _ => return None,
+    };
+    Some(pb::Literal { kind: Some(kind) })
+}
+
+fn binary_operator(op: &ast::BinaryOperator) -> Option<pb::BinaryOperator> {
+    use ast::BinaryOperator as Op;
+    use pb::BinaryOperator as Pb;
+    Some(match op {
+        Op::Plus => Pb::Plus,
+        Op::Minus => Pb::Minus,
+        Op::Multiply => Pb::Multiply,
+        Op::Divide => Pb::Divide,
+        Op::Modulo => Pb::Modulo,
+        Op::StringConcat => Pb::StringConcat,
+        Op::Gt => Pb::Gt,
+        Op::Lt => Pb::Lt,
+        Op::GtEq => Pb::GtEq,
+        Op::LtEq => Pb::LtEq,
+        Op::Spaceship => Pb::Spaceship,
+        Op::Eq => Pb::Eq,
+        Op::NotEq => Pb::NotEq,
+        Op::And => Pb::And,
+        Op::Or => Pb::Or,
+        Op::Xor => Pb::Xor,
+        Op::BitwiseOr => Pb::BitwiseOr,
+        Op::BitwiseAnd => Pb::BitwiseAnd,
+        Op::BitwiseXor => Pb::BitwiseXor,
+        _ => return None,
+    })
+}
+
+fn unary_operator(op: ast::UnaryOperator) -> Option<pb::UnaryOperator> {
+    match op {
+        ast::UnaryOperator::Plus => Some(pb::UnaryOperator::Plus),
+        ast::UnaryOperator::Minus => Some(pb::UnaryOperator::Minus),
+        ast::UnaryOperator::Not => Some(pb::UnaryOperator::Not),
+        _ => None,
+    }
+}
+
+fn like(
+    negated: bool,
+    expr_: &ast::Expr,
+    pattern: &ast::Expr,
+    escape_char: &Option<String>,
+) -> pb::Like {
+    pb::Like {
+        negated,
+        expr: boxed(expr_),
+        pattern: boxed(pattern),
+        escape_char: escape_char.clone(),
+    }
+}
+
+fn function(function: &ast::Function) -> Option<pb::Function> {
+    let ast::Function {
+        name,
+        uses_odbc_syntax,
+        parameters,
+        args,
+        filter,
+        null_treatment,
+        over,
+        within_group,
+    } = function;
+    require(
+        !uses_odbc_syntax
+            && matches!(parameters, ast::FunctionArguments::None)
+            && filter.is_none()
+            && null_treatment.is_none()
+            && over.is_none()
+            && within_group.is_empty(),
+    )?;
+    let args = match args {
+        ast::FunctionArguments::None => None,
+        ast::FunctionArguments::List(list) => Some(function_args(list)?),
+        ast::FunctionArguments::Subquery(_) => return None,
+    };
+    Some(pb::Function {
+        name: Some(object_name(name)),
+        args,
+    })
+}
+
+fn function_args(list: &ast::FunctionArgumentList) -> Option<pb::FunctionArgs> {
+    use pb::function_arg::Kind;
+    require(list.clauses.is_empty())?;
+    let duplicate_treatment = match list.duplicate_treatment {
+        None => pb::DuplicateTreatment::None,
+        Some(ast::DuplicateTreatment::Distinct) => pb::DuplicateTreatment::Distinct,
+        Some(ast::DuplicateTreatment::All) => pb::DuplicateTreatment::All,
+    };
+    let args = list
+        .args
+        .iter()
+        .map(|arg| {
+            let kind = match arg {
+                ast::FunctionArg::Unnamed(ast::FunctionArgExpr::Expr(e)) => Kind::Expr(expr(e)),
+                ast::FunctionArg::Unnamed(ast::FunctionArgExpr::QualifiedWildcard(name)) => {
+                    Kind::QualifiedWildcard(object_name(name))
+                }
+                ast::FunctionArg::Unnamed(ast::FunctionArgExpr::Wildcard) => {
+                    Kind::Wildcard(pb::Wildcard {})
+                }
+                ast::FunctionArg::Named { .. } | ast::FunctionArg::ExprNamed { .. } => return None,
+            };
+            Some(pb::FunctionArg { kind: Some(kind) })
+        })
+        .collect::<Option<_>>()?;
+    Some(pb::FunctionArgs {
+        duplicate_treatment: duplicate_treatment as i32,
+        args,
+    })
+}
+
+fn cast_kind(kind: &ast::CastKind) -> pb::CastKind {
+    match kind {
+        ast::CastKind::Cast => pb::CastKind::Cast,
+        ast::CastKind::TryCast => pb::CastKind::TryCast,
+        ast::CastKind::SafeCast => pb::CastKind::SafeCast,
+        ast::CastKind::DoubleColon => pb::CastKind::DoubleColon,
+    }
+}
+
+// ---------- 数据类型 ----------
+
+fn data_type(data_type: &ast::DataType) -> pb::DataType {
+    use pb::data_type::Kind;
+    let kind = scalar_type(data_type)
+        .map(Kind::Scalar)
+        .unwrap_or_else(|| Kind::Raw(raw(data_type)));
+    pb::DataType { kind: Some(kind) }
+}
+
+fn scalar_type(data_type: &ast::DataType) -> Option<pb::ScalarType> {
+    use ast::DataType as T;
+    use pb::TypeName as N;
+    let len = |n: &Option<u64>| n.iter().copied().collect::<Vec<_>>();
+    let (name, args) = match data_type {
+        T::Character(n) => (N::Character, char_length(n)?),
+        T::Char(n) => (N::Char, char_length(n)?),
+        T::CharacterVarying(n) => (N::CharacterVarying, char_length(n)?),
+        T::CharVarying(n) => (N::CharVarying, char_length(n)?),
+        T::Varchar(n) => (N::Varchar, char_length(n)?),
+        T::Nvarchar(n) => (N::Nvarchar, char_length(n)?),
+        T::Text => (N::Text, Vec::new()),
+        T::Uuid => (N::Uuid, Vec::new()),
+        T::Binary(n) => (N::Binary, len(n)),
+        T::Varbinary(n) => (N::Varbinary, len(n)),
+        T::Blob(n) => (N::Blob, len(n)),
+        T::Bytea => (N::Bytea, Vec::new()),
+        T::Numeric(info) => (N::Numeric, exact_number(info)),
+        T::Decimal(info) => (N::Decimal, exact_number(info)),
+        T::Dec(info) => (N::Dec, exact_number(info)),
+        T::Float(n) => (N::Float, len(n)),
+        T::Real => (N::Real, Vec::new()),
+        T::Double => (N::Double, Vec::new()),
+        T::DoublePrecision => (N::DoublePrecision, Vec::new()),
+        T::TinyInt(n) => (N::Tinyint, len(n)),
+        T::UnsignedTinyInt(n) => (N::UnsignedTinyint, len(n)),
+        T::SmallInt(n) => (N::Smallint, len(n)),
+        T::UnsignedSmallInt(n) => (N::UnsignedSmallint, len(n)),
+        T::MediumInt(n) => (N::Mediumint, len(n)),
+        T::UnsignedMediumInt(n) => (N::UnsignedMediumint, len(n)),
+        T::Int(n) => (N::Int, len(n)),
+        T::UnsignedInt(n) => (N::UnsignedInt, len(n)),
+        T::Integer(n) => (N::Integer, len(n)),
+        T::UnsignedInteger(n) => (N::UnsignedInteger, len(n)),
+        T::BigInt(n) => (N::Bigint, len(n)),
+        T::UnsignedBigInt(n) => (N::UnsignedBigint, len(n)),
+        T::Bool => (N::Bool, Vec::new()),
+        T::Boolean => (N::Boolean, Vec::new()),
+        T::Date => (N::Date, Vec::new()),
+        T::Time(n, _) => (N::Time, len(n)),
+        T::Datetime(n) => (N::Datetime, len(n)),
+        T::Timestamp(n, _) => (N::Timestamp, len(n)),
+        T::Interval => (N::Interval, Vec::new()),
+        T::JSON => (N::Json, Vec::new()),
+        T::JSONB => (N::Jsonb, Vec::new()),
+        _ => return None,
+    };
+    let time_zone = match data_type {
+        T::Time(_, tz) | T::Timestamp(_, tz) => time_zone(*tz),
+        _ => pb::TimeZone::None,
+    };
+    Some(pb::ScalarType {
+        name: name as i32,
+        args,
+        time_zone: time_zone as i32,
+    })
+}
+
+// 只表示不带单位的整数长度；VARCHAR(MAX) 等退回 Raw
+fn char_length(length: &Option<ast::CharacterLength>) -> Option<Vec<u64>> {
+    match length {
+        None => Some(Vec::new()),
+        Some(ast::CharacterLength::IntegerLength { length, unit: None }) => Some(vec![*length]),
+        Some(_) => None,
+    }
+}
+
+fn exact_number(info: &ast::ExactNumberInfo) -> Vec<u64> {
+    match info {
+        ast::ExactNumberInfo::None => Vec::new(),
+        ast::ExactNumberInfo::Precision(p) => vec![*p],
+        ast::ExactNumberInfo::PrecisionAndScale(p, s) => vec![*p, *s],
+    }
+}
+
+fn time_zone(tz: ast::TimezoneInfo) -> pb::TimeZone {
+    match tz {
+        ast::TimezoneInfo::None => pb::TimeZone::None,
+        ast::TimezoneInfo::WithTimeZone => pb::TimeZone::WithTimeZone,
+        ast::TimezoneInfo::WithoutTimeZone => pb::TimeZone::WithoutTimeZone,
+        ast::TimezoneInfo::Tz => pb::TimeZone::Tz,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+    use serde::de::DeserializeOwned;
+    use sqlparser::ast::helpers::attached_token::AttachedToken;
+    use sqlparser::dialect::{GenericDialect, MySqlDialect, PostgreSqlDialect};
+    use sqlparser::parser::Parser;
+    use sqlparser::tokenizer::Span;
+
+    // 反向转换只用于往返测试：proto -> sqlparser，再与原始 JSON 对比
+
+    fn from_raw<T: DeserializeOwned>(raw: &pb::Raw) -> T {
+        let mut json = serde_json::from_str(&raw.json).unwrap();
+        restore_spans(&mut json);
+        serde_json::from_value(json).unwrap()
+    }
+
+    // Raw 中的 JSON 去掉了 Span 与 AttachedToken，反序列化前按结构补回空值
+    fn restore_spans(json: &mut serde_json::Value) {
+        match json {
+            serde_json::Value::Array(items) => items.iter_mut().for_each(restore_spans),
+            serde_json::Value::Object(fields) => {
+                fields.values_mut().for_each(restore_spans);
+                let token = || serde_json::to_value(AttachedToken::empty()).unwrap();
+                let missing = [
+                    ("quote_style", "span", serde_json::to_value(Span::empty()).unwrap()),
+                    ("projection", "select_token", token()),
+                    ("cte_tables", "with_token", token()),
+                    ("materialized", "closing_paren_token", token()),
+                    ("opt_ilike", "wildcard_token", token()),
+                ];
+                for (marker, field, value) in missing {
+                    if fields.contains_key(marker) {
+                        fields.entry(field).or_insert(value);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn to_ident(ident: &pb::Ident) -> ast::Ident {
+        ast::Ident {
+            value: ident.value.clone(),
+            quote_style: ident.quote_style.chars().next(),
+            span: Span::empty(),
+        }
+    }
+
+    fn to_idents(idents: &[pb::Ident]) -> Vec<ast::Ident> {
+        idents.iter().map(to_ident).collect()
+    }
+
+    fn to_object_name(name: &pb::ObjectName) -> ast::ObjectName {
+        ast::ObjectName(to_idents(&name.parts))
+    }
+
+    fn to_statement(statement: &pb::Statement) -> ast::Statement {
+        use pb::statement::Kind;
+        match statement.kind.as_ref().unwrap() {
+            Kind::Query(q) => ast::Statement::Query(Box::new(to_query(q))),
+            Kind::Insert(i) => ast::Statement::Insert(ast::Insert {
+                or: None,
+                ignore: false,
+                into: i.into,
+                table_name: to_object_name(i.table_name.as_ref().unwrap()),
+                table_alias: i.table_alias.as_ref().map(to_ident),
+                columns: to_idents(&i.columns),
+                overwrite: false,
+                source: i.source.as_ref().map(|q| Box::new(to_query(q))),
+                partitioned: None,
+                after_columns: Vec::new(),
+                table: false,
+                on: None,
+                returning: None,
+                replace_into: false,
+                priority: None,
+                insert_alias: None,
+            }),
+            Kind::Update(u) => ast::Statement::Update {
+                table: to_table_with_joins(u.table.as_ref().unwrap()),
+                assignments: u.assignments.iter().map(to_assignment).collect(),
+                from: u.from.as_ref().map(to_table_with_joins),
+                selection: u.selection.as_ref().map(to_expr),
+                returning: None,
+                or: None,
+            },
+            Kind::Delete(d) => {
+                let from = d.from.iter().map(to_table_with_joins).collect();
+                ast::Statement::Delete(ast::Delete {
+                    tables: Vec::new(),
+                    from: if d.from_keyword {
+                        ast::FromTable::WithFromKeyword(from)
+                    } else {
+                        ast::FromTable::WithoutKeyword(from)
+                    },
+                    using: None,
+                    selection: d.selection.as_ref().map(to_expr),
+                    returning: None,
+                    order_by: Vec::new(),
+                    limit: None,
+                })
+            }
+            Kind::Raw(raw) => from_raw(raw),
+        }
+    }
+
+    fn to_assignment(assignment: &pb::Assignment) -> ast::Assignment {
+        let mut targets = assignment.targets.iter().map(to_object_name);
+        ast::Assignment {
+            target: if assignment.tuple {
+                ast::AssignmentTarget::Tuple(targets.collect())
+            } else {
+                ast::AssignmentTarget::ColumnName(targets.next().unwrap())
+            },
+            value: to_expr(assignment.value.as_ref().unwrap()),
+        }
+    }
+
+    fn to_query(query: &pb::Query) -> ast::Query {
+        ast::Query {
+            with: query.with.as_ref().map(|with| ast::With {
+                with_token: AttachedToken::empty(),
+                recursive: with.recursive,
+                cte_tables: with
+                    .ctes
+                    .iter()
+                    .map(|cte| ast::Cte {
+                        alias: to_table_alias(cte.alias.as_ref().unwrap()),
+                        query: Box::new(to_query(cte.query.as_ref().unwrap())),
+                        from: None,
+                        materialized: None,
+                        closing_paren_token: AttachedToken::empty(),
+                    })
+                    .collect(),
+            }),
+            body: Box::new(to_set_expr(query.body.as_ref().unwrap())),
+            order_by: (!query.order_by.is_empty()).then(|| ast::OrderBy {
+                exprs: query.order_by.iter().map(to_order_by_expr).collect(),
+                interpolate: None,
+            }),
+            limit: query.limit.as_deref().map(to_expr),
+            limit_by: Vec::new(),
+            offset: query.offset.as_ref().map(|offset| ast::Offset {
+                value: to_expr(offset.value.as_ref().unwrap()),
+                rows: match offset.rows() {
+                    pb::OffsetRows::None => ast::OffsetRows::None,
+                    pb::OffsetRows::Row => ast::OffsetRows::Row,
+                    pb::OffsetRows::Rows => ast::OffsetRows::Rows,
+                },
+            }),
+            fetch: None,
+            locks: Vec::new(),
+            for_clause: None,
+            settings: None,
+            format_clause: None,
+        }
+    }
+
+    fn to_table_alias(alias: &pb::TableAlias) -> ast::TableAlias {
+        ast::TableAlias {
+            name: to_ident(alias.name.as_ref().unwrap()),
+            columns: alias
+                .columns
+                .iter()
+                .map(|column| ast::TableAliasColumnDef {
+                    name: to_ident(column),
+                    data_type: None,
+                })
+                .collect(),
+        }
+    }
+
+    fn to_order_by_expr(order_by: &pb::OrderByExpr) -> ast::OrderByExpr {
+        ast::OrderByExpr {
+            expr: to_expr(order_by.expr.as_ref().unwrap()),
+            asc: order_by.asc,
+            nulls_first: order_by.nulls_first,
+            with_fill: None,
+        }
+    }
+
+    fn to_set_expr(set_expr: &pb::SetExpr) -> ast::SetExpr {
+        use pb::set_expr::Kind;
+        match set_expr.kind.as_ref().unwrap() {
+            Kind::Select(s) => ast::SetExpr::Select(Box::new(to_select(s))),
+            Kind::Query(q) => ast::SetExpr::Query(Box::new(to_query(q))),
+            Kind::SetOperation(op) => ast::SetExpr::SetOperation {
+                op: match op.op() {
+                    pb::SetOperator::Union => ast::SetOperator::Union,
+                    pb::SetOperator::Except => ast::SetOperator::Except,
+                    pb::SetOperator::Intersect => ast::SetOperator::Intersect,
+                    pb::SetOperator::Unspecified => unreachable!(),
+                },
+                set_quantifier: match op.quantifier() {
+                    pb::SetQuantifier::None => ast::SetQuantifier::None,
+                    pb::SetQuantifier::All => ast::SetQuantifier::All,
+                    pb::SetQuantifier::Distinct => ast::SetQuantifier::Distinct,
+                    pb::SetQuantifier::ByName => ast::SetQuantifier::ByName,
+                    pb::SetQuantifier::AllByName => ast::SetQuantifier::AllByName,
+                    pb::SetQuantifier::DistinctByName => ast::SetQuantifier::DistinctByName,
+                },
+                left: Box::new(to_set_expr(op.left.as_ref().unwrap())),
+                right: Box::new(to_set_expr(op.right.as_ref().unwrap())),
+            },
+            Kind::Values(values) => ast::SetExpr::Values(ast::Values {
+                explicit_row: values.explicit_row,
+                rows: values
+                    .rows
+                    .iter()
+                    .map(|row| row.exprs.iter().map(to_expr).collect())
+                    .collect(),
+            }),
+            Kind::Raw(raw) => from_raw(raw),
+        }
+    }
+
+    fn to_select(select: &pb::Select) -> ast::Select {
+        ast::Select {
+            select_token: AttachedToken::empty(),
+            distinct: select.distinct.as_ref().map(|distinct| {
+                if distinct.on.is_empty() {
+                    ast::Distinct::Distinct
+                } else {
+                    ast::Distinct::On(distinct.on.iter().map(to_expr).collect())
+                }
+            }),
+            top: None,
+            top_before_distinct: false,
+            projection: select.projection.iter().map(to_select_item).collect(),
+            into: None,
+            from: select.from.iter().map(to_table_with_joins).collect(),
+            lateral_views: Vec::new(),
+            prewhere: None,
+            selection: select.selection.as_deref().map(to_expr),
+            group_by: if select.group_by_all {
+                ast::GroupByExpr::All(Vec::new())
+            } else {
+                ast::GroupByExpr::Expressions(
+                    select.group_by.iter().map(to_expr).collect(),
+                    Vec::new(),
+                )
+            },
+            cluster_by: Vec::new(),
+            distribute_by: Vec::new(),
+            sort_by: Vec::new(),
+            having: select.having.as_deref().map(to_expr),
+            named_window: Vec::new(),
+            qualify: None,
+            window_before_qualify: false,
+            value_table_mode: None,
+            connect_by: None,
+        }
+    }
+
+    fn to_select_item(item: &pb::SelectItem) -> ast::SelectItem {
+        use pb::select_item::Kind;
+        match item.kind.as_ref().unwrap() {
+            Kind::Expr(e) => ast::SelectItem::UnnamedExpr(to_expr(e)),
+            Kind::Aliased(aliased) => ast::SelectItem::ExprWithAlias {
+                expr: to_expr(aliased.expr.as_ref().unwrap()),
+                alias: to_ident(aliased.alias.as_ref().unwrap()),
+            },
+            Kind::QualifiedWildcard(name) => {
+                ast::SelectItem::QualifiedWildcard(to_object_name(name), Default::default())
+            }
+            Kind::Wildcard(_) => ast::SelectItem::Wildcard(Default::default()),
+        }
+    }
+
+    fn to_table_with_joins(table: &pb::TableWithJoins) -> ast::TableWithJoins {
+        ast::TableWithJoins {
+            relation: to_table_factor(table.relation.as_ref().unwrap()),
+            joins: table.joins.iter().map(to_join).collect(),
+        }
+    }
+
+    fn to_join(join: &pb::Join) -> ast::Join {
+        use pb::join_constraint::Kind;
+        let constraint = match join.constraint.as_ref().and_then(|c| c.kind.as_ref()) {
+            Some(Kind::On(e)) => ast::JoinConstraint::On(to_expr(e)),
+            Some(Kind::Using(columns)) => ast::JoinConstraint::Using(to_idents(&columns.idents)),
+            Some(Kind::Natural(_)) => ast::JoinConstraint::Natural,
+            None => ast::JoinConstraint::None,
+        };
+        ast::Join {
+            relation: to_table_factor(join.relation.as_ref().unwrap()),
+            global: false,
+            join_operator: match join.kind() {
+                pb::JoinKind::Inner => ast::JoinOperator::Inner(constraint),
+                pb::JoinKind::LeftOuter => ast::JoinOperator::LeftOuter(constraint),
+                pb::JoinKind::RightOuter => ast::JoinOperator::RightOuter(constraint),
+                pb::JoinKind::FullOuter => ast::JoinOperator::FullOuter(constraint),
+                pb::JoinKind::Cross => ast::JoinOperator::CrossJoin,
+                pb::JoinKind::Unspecified => unreachable!(),
+            },
+        }
+    }
+
+    fn to_table_factor(factor: &pb::TableFactor) -> ast::TableFactor {
+        use pb::table_factor::Kind;
+        match factor.kind.as_ref().unwrap() {
+            Kind::Table(table) => ast::TableFactor::Table {
+                name: to_object_name(table.name.as_ref().unwrap()),
+                alias: table.alias.as_ref().map(to_table_alias),
+                args: None,
+                with_hints: Vec::new(),
+                version: None,
+                with_ordinality: false,
+                partitions: Vec::new(),
+                json_path: None,
+            },
+            Kind::Derived(derived) => ast::TableFactor::Derived {
+                lateral: derived.lateral,
+                subquery: Box::new(to_query(derived.subquery.as_ref().unwrap())),
+                alias: derived.alias.as_ref().map(to_table_alias),
+            },
+            Kind::Raw(raw) => from_raw(raw),
+        }
+    }
+
+    fn to_boxed(expr: &Option<Box<pb::Expr>>) -> Box<ast::Expr> {
+        Box::new(to_expr(expr.as_ref().unwrap()))
+    }
+
+    fn to_expr(expr: &pb::Expr) -> ast::Expr {
+        use pb::expr::Kind;
+        match expr.kind.as_ref().unwrap() {
+            Kind::Identifier(i) => ast::Expr::Identifier(to_ident(i)),
+            Kind::CompoundIdentifier(parts) => {
+                ast::Expr::CompoundIdentifier(to_idents(&parts.idents))
+            }
+            Kind::Value(literal) => ast::Expr::Value(to_value(literal)),
+            Kind::BinaryOp(op) => ast::Expr::BinaryOp {
+                left: to_boxed(&op.left),
+                op: to_binary_operator(op.op()),
+                right: to_boxed(&op.right),
+            },
+            Kind::UnaryOp(op) => ast::Expr::UnaryOp {
+                op: match op.op() {
+                    pb::UnaryOperator::Plus => ast::UnaryOperator::Plus,
+                    pb::UnaryOperator::Minus => ast::UnaryOperator::Minus,
+                    pb::UnaryOperator::Not => ast::UnaryOperator::Not,
+                    pb::UnaryOperator::Unspecified => unreachable!(),
+                },
+                expr: to_boxed(&op.expr),
+            },
+            Kind::Nested(e) => ast::Expr::Nested(Box::new(to_expr(e))),
+            Kind::IsNull(e) => ast::Expr::IsNull(Box::new(to_expr(e))),
+            Kind::IsNotNull(e) => ast::Expr::IsNotNull(Box::new(to_expr(e))),
+            Kind::InList(in_list) => ast::Expr::InList {
+                expr: to_boxed(&in_list.expr),
+                list: in_list.list.iter().map(to_expr).collect(),
+                negated: in_list.negated,
+            },
+            Kind::InSubquery(in_subquery) => ast::Expr::InSubquery {
+                expr: to_boxed(&in_subquery.expr),
+                subquery: Box::new(to_query(in_subquery.subquery.as_ref().unwrap())),
+                negated: in_subquery.negated,
+            },
+            Kind::Between(between) => ast::Expr::Between {
+                expr: to_boxed(&between.expr),
+                negated: between.negated,
+                low: to_boxed(&between.low),
+                high: to_boxed(&between.high),
+            },
+            Kind::Like(like) => ast::Expr::Like {
+                negated: like.negated,
+                any: false,
+                expr: to_boxed(&like.expr),
+                pattern: to_boxed(&like.pattern),
+                escape_char: like.escape_char.clone(),
+            },
+            Kind::Ilike(like) => ast::Expr::ILike {
+                negated: like.negated,
+                any: false,
+                expr: to_boxed(&like.expr),
+                pattern: to_boxed(&like.pattern),
+                escape_char: like.escape_char.clone(),
+            },
+            Kind::Function(function) => ast::Expr::Function(to_function(function)),
+            Kind::Cast(cast) => ast::Expr::Cast {
+                kind: match cast.kind() {
+                    pb::CastKind::Cast => ast::CastKind::Cast,
+                    pb::CastKind::TryCast => ast::CastKind::TryCast,
+                    pb::CastKind::SafeCast => ast::CastKind::SafeCast,
+                    pb::CastKind::DoubleColon => ast::CastKind::DoubleColon,
+                    pb::CastKind::Unspecified => unreachable!(),
+                },
+                expr: to_boxed(&cast.expr),
+                data_type: to_data_type(cast.data_type.as_ref().unwrap()),
+                format: None,
+            },
+            Kind::Case(case) => ast::Expr::Case {
+                operand: case.operand.as_deref().map(|e| Box::new(to_expr(e))),
+                conditions: case
+                    .whens
+                    .iter()
+                    .map(|when| to_expr(when.condition.as_ref().unwrap()))
+                    .collect(),
+                results: case
+                    .whens
+                    .iter()
+                    .map(|when| to_expr(when.result.as_ref().unwrap()))
+                    .collect(),
+                else_result: case.else_result.as_deref().map(|e| Box::new(to_expr(e))),
+            },
+            Kind::Exists(exists) => ast::Expr::Exists {
+                subquery: Box::new(to_query(exists.subquery.as_ref().unwrap())),
+                negated: exists.negated,
+            },
+            Kind::Subquery(q) => ast::Expr::Subquery(Box::new(to_query(q))),
+            Kind::Raw(raw) => from_raw(raw),
+        }
+    }
+
+    fn to_value(literal: &pb::Literal) -> ast::Value {
+        use pb::literal::Kind;
+        match literal.kind.as_ref().unwrap() {
+            Kind::Number(n) => ast::Value::Number(n.value.clone(), n.long),
+            Kind::SingleQuotedString(s) => ast::Value::SingleQuotedString(s.clone()),
+            Kind::DoubleQuotedString(s) => ast::Value::DoubleQuotedString(s.clone()),
+            Kind::Boolean(b) => ast::Value::Boolean(*b),
+            Kind::Null(_) => ast::Value::Null,
+            Kind::Placeholder(p) => ast::Value::Placeholder(p.clone()),
+        }
+    }
+
+    fn to_binary_operator(op: pb::BinaryOperator) -> ast::BinaryOperator {
+        use ast::BinaryOperator as Op;
+        use pb::BinaryOperator as Pb;
+        match op {
+            Pb::Plus => Op::Plus,
+            Pb::Minus => Op::Minus,
+            Pb::Multiply => Op::Multiply,
+            Pb::Divide => Op::Divide,
+            Pb::Modulo => Op::Modulo,
+            Pb::StringConcat => Op::StringConcat,
+            Pb::Gt => Op::Gt,
+            Pb::Lt => Op::Lt,
+            Pb::GtEq => Op::GtEq,
+            Pb::LtEq => Op::LtEq,
+            Pb::Spaceship => Op::Spaceship,
+            Pb::Eq => Op::Eq,
+            Pb::NotEq => Op::NotEq,
+            Pb::And => Op::And,
+            Pb::Or => Op::Or,
+            Pb::Xor => Op::Xor,
+            Pb::BitwiseOr => Op::BitwiseOr,
+            Pb::BitwiseAnd => Op::BitwiseAnd,
+            Pb::BitwiseXor => Op::BitwiseXor,
+            Pb::Unspecified => unreachable!(),
+        }
+    }
+
+    fn to_function(function: &pb::Function) -> ast::Function {
+        use pb::function_arg::Kind;
+        let args = match &function.args {
+            None => ast::FunctionArguments::None,
+            Some(args) => ast::FunctionArguments::List(ast::FunctionArgumentList {
+                duplicate_treatment: match args.duplicate_treatment() {
+                    pb::DuplicateTreatment::None => None,
+                    pb::DuplicateTreatment::Distinct => Some(ast::DuplicateTreatment::Distinct),
+                    pb::DuplicateTreatment::All => Some(ast::DuplicateTreatment::All),
+                },
+                args: args
+                    .args
+                    .iter()
+                    .map(|arg| {
+                        ast::FunctionArg::Unnamed(match arg.kind.as_ref().unwrap() {
+                            Kind::Expr(e) => ast::FunctionArgExpr::Expr(to_expr(e)),
+                            Kind::QualifiedWildcard(name) => {
+                                ast::FunctionArgExpr::QualifiedWildcard(to_object_name(name))
+                            }
+                            Kind::Wildcard(_) => ast::FunctionArgExpr::Wildcard,
+                        })
+                    })
+                    .collect(),
+                clauses: Vec::new(),
+            }),
+        };
+        ast::Function {
+            name: to_object_name(function.name.as_ref().unwrap()),
+            parameters: ast::FunctionArguments::None,
+            args,
+            filter: None,
+            null_treatment: None,
+            over: None,
+            within_group: Vec::new(),
+            uses_odbc_syntax: false,
+        }
+    }
+
+    fn to_data_type(data_type: &pb::DataType) -> ast::DataType {
+        use ast::DataType as T;
+        use pb::TypeName as N;
+        let scalar = match data_type.kind.as_ref().unwrap() {
+            pb::data_type::Kind::Scalar(scalar) => scalar,
+            pb::data_type::Kind::Raw(raw) => return from_raw(raw),
+        };
+        let len = scalar.args.first().copied();
+        let char_len = len.map(|length| ast::CharacterLength::IntegerLength { length, unit: None });
+        let exact = match scalar.args[..] {
+            [] => ast::ExactNumberInfo::None,
+            [p] => ast::ExactNumberInfo::Precision(p),
+            [p, s] => ast::ExactNumberInfo::PrecisionAndScale(p, s),
+            _ => unreachable!(),
+        };
+        let tz = match scalar.time_zone() {
+            pb::TimeZone::None => ast::TimezoneInfo::None,
+            pb::TimeZone::WithTimeZone => ast::TimezoneInfo::WithTimeZone,
+            pb::TimeZone::WithoutTimeZone => ast::TimezoneInfo::WithoutTimeZone,
+            pb::TimeZone::Tz => ast::TimezoneInfo::Tz,
+        };
+        match scalar.name() {
+            N::Character => T::Character(char_len),
+            N::Char => T::Char(char_len),
+            N::CharacterVarying => T::CharacterVarying(char_len),
+            N::CharVarying => T::CharVarying(char_len),
+            N::Varchar => T::Varchar(char_len),
+            N::Nvarchar => T::Nvarchar(char_len),
+            N::Text => T::Text,
+            N::Uuid => T::Uuid,
+            N::Binary => T::Binary(len),
+            N::Varbinary => T::Varbinary(len),
+            N::Blob => T::Blob(len),
+            N::Bytea => T::Bytea,
+            N::Numeric => T::Numeric(exact),
+            N::Decimal => T::Decimal(exact),
+            N::Dec => T::Dec(exact),
+            N::Float => T::Float(len),
+            N::Real => T::Real,
+            N::Double => T::Double,
+            N::DoublePrecision => T::DoublePrecision,
+            N::Tinyint => T::TinyInt(len),
+            N::UnsignedTinyint => T::UnsignedTinyInt(len),
+            N::Smallint => T::SmallInt(len),
+            N::UnsignedSmallint => T::UnsignedSmallInt(len),
+            N::Mediumint => T::MediumInt(len),
+            N::UnsignedMediumint => T::UnsignedMediumInt(len),
+            N::Int => T::Int(len),
+            N::UnsignedInt => T::UnsignedInt(len),
+            N::Integer => T::Integer(len),
+            N::UnsignedInteger => T::UnsignedInteger(len),
+            N::Bigint => T::BigInt(len),
+            N::UnsignedBigint => T::UnsignedBigInt(len),
+            N::Bool => T::Bool,
+            N::Boolean => T::Boolean,
+            N::Date => T::Date,
+            N::Time => T::Time(len, tz),
+            N::Datetime => T::Datetime(len),
+            N::Timestamp => T::Timestamp(len, tz),
+            N::Interval => T::Interval,
+            N::Json => T::JSON,
+            N::Jsonb => T::JSONB,
+            N::Unspecified => unreachable!(),
+        }
+    }
+
+    fn parse(dialect: &dyn sqlparser::dialect::Dialect, sql: &str) -> Vec<ast::Statement> {
+        Parser::parse_sql(dialect, sql).unwrap()
+    }
+
+    // 转换、编码、解码、再转换回来，JSON 表示应与原始 AST 完全一致
+    fn assert_round_trip(
+        dialect: &dyn sqlparser::dialect::Dialect,
+        sql: &str,
+    ) -> Vec<pb::Statement> {
+        let original = parse(dialect, sql);
+        let encoded = statements(&original)
+            .into_iter()
+            .map(|statement| statement.encode_to_vec())
+            .collect::<Vec<_>>();
+        let decoded = encoded
+            .iter()
+            .map(|bytes| pb::Statement::decode(bytes.as_slice()).unwrap())
+            .collect::<Vec<_>>();
+        let restored = decoded.iter().map(to_statement).collect::<Vec<_>>();
+        assert_eq!(
+            crate::ast_json::to_value(&restored).unwrap(),
+            crate::ast_json::to_value(&original).unwrap(),
+            "round trip changed the AST of: {sql}"
+        );
+        decoded
+    }
+
+    fn has_raw(statements: &[pb::Statement]) -> bool {
+        format!("{statements:?}").contains("Raw(")
+    }
+
+    #[test]
+    fn test_round_trip_matches_json() {
+        let generic = [
+            "SELECT a, b AS c, t.*, * FROM t WHERE a > 1 AND b IS NOT NULL ORDER BY a DESC NULLS LAST LIMIT 10 OFFSET 5",
+            "SELECT DISTINCT u.id, count(*), count(DISTINCT o.id) FROM users AS u LEFT JOIN orders o ON u.id = o.user_id CROSS JOIN x GROUP BY u.id HAVING count(*) > 2",
+            "WITH RECURSIVE r (n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM r WHERE n < 10) SELECT * FROM r",
+            "SELECT a FROM t1 UNION SELECT a FROM t2 EXCEPT (SELECT a FROM t3)",
+            "SELECT CASE WHEN a IN (1, 2) THEN 'x' ELSE \"y\" END, CAST(b AS DECIMAL(10,2)), -c, NOT d FROM t",
+            "SELECT * FROM t WHERE a BETWEEN 1 AND 2 AND b LIKE 'x%' ESCAPE '!' AND c NOT ILIKE 'y' AND d IN (SELECT d FROM u) AND EXISTS (SELECT 1)",
+            "SELECT (SELECT max(x) FROM s) AS m, CURRENT_TIMESTAMP, now() FROM (SELECT 1 AS x) AS d (x) JOIN e USING (x)",
+            "INSERT INTO t (a, b) VALUES (1, 'a'), (?, NULL)",
+            "UPDATE t SET a = a + 1, b = DEFAULT WHERE id = 3",
+            "DELETE FROM t WHERE id IN (1, 2, 3)",
+            "CREATE TABLE t (id INT PRIMARY KEY, name VARCHAR(20))",
+            "SELECT row_number() OVER (PARTITION BY a ORDER BY b) FROM t",
+            "SELECT CAST(a AS TIMESTAMP WITH TIME ZONE), CAST(b AS VARCHAR), CAST(c AS BIGINT), CAST(d AS INT[]) FROM t",
+        ];
+        for sql in generic {
+            assert_round_trip(&GenericDialect {}, sql);
+        }
+        let mysql = [
+            "SELECT `a` FROM `db`.`t` LIMIT 1",
+            "INSERT IGNORE INTO t VALUES (1)",
+            "INSERT INTO t (a) VALUES (1) ON DUPLICATE KEY UPDATE a = 2",
+        ];
+        for sql in mysql {
+            assert_round_trip(&MySqlDialect {}, sql);
+        }
+        let postgres = [
+            "SELECT a::text, $1 FROM t WHERE b = $2 FOR UPDATE",
+            "SELECT DISTINCT ON (a) a, b FROM t",
+            "INSERT INTO t (a) VALUES (1) RETURNING id",
+            "SELECT 1 FROM t FULL OUTER JOIN u ON TRUE NATURAL JOIN v",
+        ];
+        for sql in postgres {
+            assert_round_trip(&PostgreSqlDialect {}, sql);
+        }
+    }
+
+    #[test]
+    fn test_common_statements_are_fully_typed() {
+        for sql in [
+            "SELECT a, count(*) AS n FROM t JOIN u ON t.id = u.id WHERE b = 'x' GROUP BY a ORDER BY n DESC LIMIT 5",
+            "INSERT INTO t (a, b) SELECT a, b FROM s",
+            "UPDATE t SET a = 1 WHERE id = 2",
+            "DELETE FROM t WHERE a IS NULL",
+        ] {
+            let statements = assert_round_trip(&GenericDialect {}, sql);
+            assert!(!has_raw(&statements), "unexpected Raw node for: {sql}");
+        }
+    }
+
+    #[test]
+    fn test_unmodeled_nodes_fall_back_to_raw() {
+        let statements = assert_round_trip(
+            &GenericDialect {},
+            "SELECT row_number() OVER (ORDER BY a) FROM t; CREATE TABLE t (a INT)",
+        );
+        // 窗口函数只在该表达式处退回 Raw，外层查询仍是类型化的
+        let Some(pb::statement::Kind::Query(query)) = &statements[0].kind else {
+            panic!("expected a typed query");
+        };
+        let Some(pb::set_expr::Kind::Select(select)) = &query.body.as_ref().unwrap().kind else {
+            panic!("expected a typed select");
+        };
+        let Some(pb::select_item::Kind::Expr(e)) = &select.projection[0].kind else {
+            panic!("expected an unnamed expression");
+        };
+        let Some(pb::expr::Kind::Raw(raw)) = &e.kind else {
+            panic!("expected a raw expression");
+        };
+        assert_eq!(raw.sql, "row_number() OVER (ORDER BY a)");
+
+        let Some(pb::statement::Kind::Raw(raw)) = &statements[1].kind else {
+            panic!("expected a raw statement");
+        };
+        assert_eq!(raw.sql, "CREATE TABLE t (a INT)");
+        assert!(raw.json.starts_with("{\"CreateTable\""));
+    }
+}
@@ -0,0 +1,534 @@
+use crate::config::AuthConfig;
+use crate::ErrorResponse;
+use axum::extract::{Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tonic::Status;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+// 配额记录数达到该值时，清理已过窗口的记录
+const PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PrincipalKind {
+    ApiKey,
+    Jwt,
+}
+
+impl PrincipalKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PrincipalKind::ApiKey => "api_key",
+            PrincipalKind::Jwt => "jwt",
+        }
+    }
+}
+
+// 认证通过后的调用方，HTTP 与 gRPC 都放在请求扩展中。
+// 配额与限流按 kind + name 计数，JWT 的 sub 与同名的 API Key 互不影响
+#[derive(Clone, Debug)]
+pub struct Principal {
+    pub kind: PrincipalKind,
+    pub name: String,
+    // None 表示允许所有接口
+    pub endpoints: Option<HashSet<String>>,
+    // 每个配额窗口内允许的请求数，0 表示不限制
+    pub quota: u64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AuthError {
+    Unauthenticated(String),
+    Forbidden(String),
+    QuotaExceeded(String),
+}
+
+impl From<AuthError> for Status {
+    fn from(e: AuthError) -> Self {
+        match e {
+            AuthError::Unauthenticated(msg) => Status::unauthenticated(msg),
+            AuthError::Forbidden(msg) => Status::permission_denied(msg),
+            AuthError::QuotaExceeded(msg) => Status::resource_exhausted(msg),
+        }
+    }
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        let (status, error) = match self {
+            AuthError::Unauthenticated(msg) => (StatusCode::UNAUTHORIZED, msg),
+            AuthError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
+            AuthError::QuotaExceeded(msg) => (StatusCode::TOO_MANY_REQUESTS, msg),
+        };
+        let mut response = (
+            status,
+            Json(ErrorResponse {
+                error,
+                code: None,
+                elapsed_ms: None,
+            }),
+        )
+            .into_response();
+        if status == StatusCode::UNAUTHORIZED {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, "Bearer".parse().unwrap());
+        }
+        response
+    }
+}
+
+#[derive(Deserialize)]
+struct Claims {
+    sub: String,
+    #[serde(default)]
+    scope: Option<String>,
+    #[serde(default)]
+    endpoints: Option<Vec<String>>,
+    #[serde(default)]
+    quota: Option<u64>,
+}
+
+struct JwtVerifier {
+    hs256_secret: Option<DecodingKey>,
+    jwks: Option<JwkSet>,
+    issuer: Option<String>,
+    audience: Option<String>,
+    default_quota: u64,
+}
+
+impl JwtVerifier {
+    fn verify(&self, token: &str) -> Result<Principal, AuthError> {
+        let invalid = |e: jsonwebtoken::errors::Error| {
+            AuthError::Unauthenticated(format!("Invalid token: {e}"))
+        };
+        let header = jsonwebtoken::decode_header(token).map_err(invalid)?;
+
+        let key = match header.alg {
+            Algorithm::HS256 => self.hs256_secret.clone(),
+            Algorithm::RS256 => self.jwks.as_ref().and_then(|jwks| {
+                let jwk = match &header.kid {
+                    Some(kid) => jwks.find(kid),
+                    None if jwks.keys.len() == 1 => jwks.keys.first(),
+                    None => None,
+                }?;
+                DecodingKey::from_jwk(jwk).ok()
+            }),
+            _ => None,
+        }
+        .ok_or_else(|| {
+            AuthError::Unauthenticated(format!("No verification key for {:?} token", header.alg))
+        })?;
+
+        let mut validation = Validation::new(header.alg);
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        let claims = jsonwebtoken::decode::<Claims>(token, &key, &validation)
+            .map_err(invalid)?
+            .claims;
+        let endpoints = claims.endpoints.or_else(|| {
+            claims
+                .scope
+                .map(|scope| scope.split_whitespace().map(str::to_string).collect())
+        });
+
+        Ok(Principal {
+            kind: PrincipalKind::Jwt,
+            name: claims.sub,
+            endpoints: endpoints.map(|e| e.into_iter().collect()),
+            quota: claims.quota.unwrap_or(self.default_quota),
+        })
+    }
+}
+
+pub struct Authenticator {
+    // 以 API Key 的 SHA-256 摘要为键，查找耗时与密钥内容无关
+    api_keys: HashMap<[u8; 32], Principal>,
+    jwt: Option<JwtVerifier>,
+    quota_window: Duration,
+    usage: Mutex<HashMap<(PrincipalKind, String), (Instant, u64)>>,
+}
+
+impl Authenticator {
+    pub fn from_config(config: &AuthConfig) -> Result<Arc<Self>, String> {
+        let api_keys = config
+            .api_keys
+            .iter()
+            .map(|entry| {
+                let principal = Principal {
+                    kind: PrincipalKind::ApiKey,
+                    name: entry.name.clone(),
+                    endpoints: (!entry.endpoints.is_empty())
+                        .then(|| entry.endpoints.iter().cloned().collect()),
+                    quota: entry.quota,
+                };
+                (key_digest(&entry.key), principal)
+            })
+            .collect();
+
+        let jwt = &config.jwt;
+        let jwks = match &jwt.jwks_file {
+            Some(path) => {
+                let content = std::fs::read_to_string(path)
+                    .map_err(|e| format!("failed to read JWKS file {}: {e}", path.display()))?;
+                Some(
+                    serde_json::from_str::<JwkSet>(&content)
+                        .map_err(|e| format!("invalid JWKS file {}: {e}", path.display()))?,
+                )
+            }
+            None => None,
+        };
+        let jwt = (jwt.hs256_secret.is_some() || jwks.is_some()).then(|| JwtVerifier {
+            hs256_secret: jwt
+                .hs256_secret
+                .as_ref()
+                .map(|secret| DecodingKey::from_secret(secret.as_bytes())),
+            jwks,
+            issuer: jwt.issuer.clone(),
+            audience: jwt.audience.clone(),
+            default_quota: jwt.default_quota,
+        });
+
+        Ok(Arc::new(Self {
+            api_keys,
+            jwt,
+            quota_window: Duration::from_secs(config.quota_window),
+            usage: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    // 凭据可以是静态 API Key，也可以是 JWT
+    pub fn authenticate(&self, credential: &str) -> Result<Principal, AuthError> {
+        if let Some(principal) = self.api_keys.get(&key_digest(credential)) {
+            return Ok(principal.clone());
+        }
+        match &self.jwt {
+            Some(jwt) if credential.split('.').count() == 3 => jwt.verify(credential),
+            _ => Err(AuthError::Unauthenticated("Invalid API key".to_string())),
+        }
+    }
+
+    // 检查接口权限并消耗配额
+    pub fn authorize(&self, principal: &Principal, endpoint: &str) -> Result<(), AuthError> {
+        if let Some(endpoints) = &principal.endpoints {
+            if !endpoints.contains(endpoint) {
+                return Err(AuthError::Forbidden(format!(
+                    "'{}' is not allowed to call {endpoint}",
+                    principal.name
+                )));
+            }
+        }
+
+        if principal.quota == 0 {
+            return Ok(());
+        }
+        let key = (principal.kind, principal.name.clone());
+        let mut usage = self.usage.lock().unwrap();
+        if usage.len() >= PRUNE_THRESHOLD && !usage.contains_key(&key) {
+            usage.retain(|_, (window_start, _)| window_start.elapsed() < self.quota_window);
+        }
+        let (window_start, count) = usage.entry(key).or_insert((Instant::now(), 0));
+        if window_start.elapsed() >= self.quota_window {
+            *window_start = Instant::now();
+            *count = 0;
+        }
+        if *count >= principal.quota {
+            return Err(AuthError::QuotaExceeded(format!(
+                "Quota of {} requests per {}s exceeded for '{}'",
+                principal.quota,
+                self.quota_window.as_secs(),
+                principal.name
+            )));
+        }
+        *count += 1;
+        Ok(())
+    }
+}
+
+fn key_digest(key: &str) -> [u8; 32] {
+    Sha256::digest(key.as_bytes()).into()
+}
+
+fn bearer_or_api_key(authorization: Option<&str>, api_key: Option<&str>) -> Option<String> {
+    api_key
+        .or_else(|| authorization.and_then(|value| value.strip_prefix("Bearer ")))
+        .map(|value| value.trim().to_string())
+}
+
+// HTTP 中间件：接口名取路径的第一段，例如 /parse -> parse
+pub async fn http_middleware(
+    State(auth): State<Arc<Authenticator>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let headers = request.headers();
+    let credential = bearer_or_api_key(
+        headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok()),
+        headers.get(API_KEY_HEADER).and_then(|v| v.to_str().ok()),
+    );
+    let Some(credential) = credential else {
+        return AuthError::Unauthenticated("Missing credentials".to_string()).into_response();
+    };
+
+    let endpoint = request
+        .uri()
+        .path()
+        .trim_start_matches('/')
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    let principal = match auth
+        .authenticate(&credential)
+        .and_then(|principal| auth.authorize(&principal, &endpoint).map(|_| principal))
+    {
+        Ok(principal) => principal,
+        Err(e) => return e.into_response(),
+    };
+
+    tracing::Span::current().record("principal", principal.name.as_str());
+    request.extensions_mut().insert(principal);
+    next.run(request).await
+}
+
+// gRPC 拦截器：拦截器无法得知调用的方法，因此这里只做认证，
+// 无凭据的请求放行（HealthCheck 无需认证），由各方法调用 authorize 检查
+#[allow(clippy::result_large_err)] // 签名由 tonic 的 Interceptor 决定
+pub fn grpc_interceptor(
+    auth: Option<Arc<Authenticator>>,
+) -> impl Fn(tonic::Request<()>) -> Result<tonic::Request<()>, Status> + Clone {
+    move |mut request: tonic::Request<()>| {
+        let Some(auth) = &auth else {
+            return Ok(request);
+        };
+        let metadata = request.metadata();
+        let credential = bearer_or_api_key(
+            metadata.get("authorization").and_then(|v| v.to_str().ok()),
+            metadata.get(API_KEY_HEADER).and_then(|v| v.to_str().ok()),
+        );
+        if let Some(credential) = credential {
+            let principal = auth.authenticate(&credential)?;
+            request.extensions_mut().insert(principal);
+        }
+        Ok(request)
+    }
+}
+
+pub fn authorize_grpc<T>(
+    auth: Option<&Authenticator>,
+    request: &tonic::Request<T>,
+    endpoint: &str,
+) -> Result<(), AuthError> {
+    let Some(auth) = auth else {
+        return Ok(());
+    };
+    let principal = request
+        .extensions()
+        .get::<Principal>()
+        .ok_or_else(|| AuthError::Unauthenticated("Missing credentials".to_string()))?;
+    auth.authorize(principal, endpoint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ApiKeyConfig, JwtConfig};
+    use jsonwebtoken::{EncodingKey, Header};
+
+    fn config() -> AuthConfig {
+        AuthConfig {
+            enabled: true,
+            api_keys: vec![
+                ApiKeyConfig {
+                    name: "batch".to_string(),
+                    key: "batch-secret".to_string(),
+                    endpoints: vec!["fingerprint".to_string()],
+                    quota: 2,
+                },
+                ApiKeyConfig {
+                    name: "admin".to_string(),
+                    key: "admin-secret".to_string(),
+                    endpoints: Vec::new(),
+                    quota: 0,
+                },
+            ],
+            jwt: JwtConfig {
+                hs256_secret: Some("jwt-secret".to_string()),
+                jwks_file: Some("testdata/auth/jwks.json".into()),
+                issuer: Some("https://issuer.example".to_string()),
+                ..JwtConfig::default()
+            },
+            quota_window: 3600,
+        }
+    }
+
+    fn token(header: Header, key: &EncodingKey, claims: serde_json::Value) -> String {
+        jsonwebtoken::encode(&header, &claims, key).unwrap()
+    }
+
+    fn claims(extra: serde_json::Value) -> serde_json::Value {
+        let mut claims = serde_json::json!({
+            "sub": "ide-plugin",
+            "iss": "https://issuer.example",
+            "exp": 4_102_444_800u64,
+        });
+        claims
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        claims
+    }
+
+    #[test]
+    fn test_api_key_endpoints_and_quota() {
+        let auth = Authenticator::from_config(&config()).unwrap();
+        assert!(matches!(
+            auth.authenticate("wrong"),
+            Err(AuthError::Unauthenticated(_))
+        ));
+
+        let batch = auth.authenticate("batch-secret").unwrap();
+        assert!(matches!(
+            auth.authorize(&batch, "parse"),
+            Err(AuthError::Forbidden(_))
+        ));
+        assert!(auth.authorize(&batch, "fingerprint").is_ok());
+        assert!(auth.authorize(&batch, "fingerprint").is_ok());
+        assert!(matches!(
+            auth.authorize(&batch, "fingerprint"),
+            Err(AuthError::QuotaExceeded(_))
+        ));
+
+        let admin = auth.authenticate("admin-secret").unwrap();
+        for _ in 0..10 {
+            assert!(auth.authorize(&admin, "parse").is_ok());
+        }
+
+        // 与 API Key 同名的 JWT 调用方单独计数
+        let jwt = Principal {
+            kind: PrincipalKind::Jwt,
+            ..batch.clone()
+        };
+        assert!(auth.authorize(&jwt, "fingerprint").is_ok());
+    }
+
+    #[test]
+    fn test_expired_quota_windows_are_pruned() {
+        let config = AuthConfig {
+            quota_window: 1,
+            ..config()
+        };
+        let auth = Authenticator::from_config(&config).unwrap();
+        let principal = |name: String| Principal {
+            kind: PrincipalKind::Jwt,
+            name,
+            endpoints: None,
+            quota: 1,
+        };
+        let stale = Instant::now() - Duration::from_secs(2);
+        auth.usage.lock().unwrap().extend(
+            (0..PRUNE_THRESHOLD).map(|i| ((PrincipalKind::Jwt, format!("sub-{i}")), (stale, 1))),
+        );
+        assert!(auth.authorize(&principal("new".to_string()), "parse").is_ok());
+        assert_eq!(auth.usage.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_hs256_jwt() {
+        let auth = Authenticator::from_config(&config()).unwrap();
+        let key = EncodingKey::from_secret(b"jwt-secret");
+
+        let valid = token(
+            Header::new(Algorithm::HS256),
+            &key,
+            claims(serde_json::json!({ "scope": "parse", "quota": 5 })),
+        );
+        let principal = auth.authenticate(&valid).unwrap();
+        assert_eq!(principal.name, "ide-plugin");
+        assert_eq!(principal.quota, 5);
+        assert!(auth.authorize(&principal, "parse").is_ok());
+        assert!(auth.authorize(&principal, "fingerprint").is_err());
+
+        let wrong_issuer = token(
+            Header::new(Algorithm::HS256),
+            &key,
+            claims(serde_json::json!({ "iss": "https://other.example" })),
+        );
+        assert!(auth.authenticate(&wrong_issuer).is_err());
+
+        let expired = token(
+            Header::new(Algorithm::HS256),
+            &key,
+            claims(serde_json::json!({ "exp": 1 })),
+        );
+        assert!(auth.authenticate(&expired).is_err());
+    }
+
+    #[test]
+    fn test_rs256_jwt_with_jwks() {
+        let auth = Authenticator::from_config(&config()).unwrap();
+        let key =
+            EncodingKey::from_rsa_pem(include_bytes!("../testdata/auth/rs256-key.pem")).unwrap();
+
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some("test-key".to_string());
+        let valid = token(
+            header.clone(),
+            &key,
+            claims(serde_json::json!({ "endpoints": ["parse"] })),
+        );
+        let principal = auth.authenticate(&valid).unwrap();
+        assert_eq!(
+            principal.endpoints,
+            Some(HashSet::from(["parse".to_string()]))
+        );
+
+        header.kid = Some("unknown-key".to_string());
+        let unknown_kid = token(header, &key, claims(serde_json::json!({})));
+        assert!(auth.authenticate(&unknown_kid).is_err());
+    }
+
+    #[test]
+    fn test_grpc_interceptor() {
+        let auth = Authenticator::from_config(&config()).unwrap();
+        let interceptor = grpc_interceptor(Some(auth.clone()));
+
+        // 无凭据时放行，由方法自行检查
+        let request = interceptor(tonic::Request::new(())).unwrap();
+        let status = Status::from(authorize_grpc(Some(&auth), &request, "parse").unwrap_err());
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+        let mut request = tonic::Request::new(());
+        request
+            .metadata_mut()
+            .insert(API_KEY_HEADER, "wrong".parse().unwrap());
+        let status = interceptor(request).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+        let mut request = tonic::Request::new(());
+        request
+            .metadata_mut()
+            .insert("authorization", "Bearer batch-secret".parse().unwrap());
+        let request = interceptor(request).unwrap();
+        let status = Status::from(authorize_grpc(Some(&auth), &request, "parse").unwrap_err());
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        assert!(authorize_grpc(Some(&auth), &request, "fingerprint").is_ok());
+    }
+}
@@ -0,0 +1,595 @@
+use crate::{CacheKey, CacheValue};
+use axum::body::Bytes;
+use clap::ValueEnum;
+use moka::future::Cache;
+use redb::{Database, ReadableTable, ReadableTableMetadata, Table, TableDefinition};
+use serde::{Deserialize, Serialize};
+use sqlparser::ast::Statement;
+use std::future::Future;
+use std::hash::Hash;
+use std::path::Path;
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub type StatementsValue = Arc<Result<Vec<Statement>, String>>;
+pub type FingerprintKey = (String, String, usize);
+pub type FingerprintValue = Result<String, String>;
+
+// 持久化缓存的版本前缀：升级服务、sqlparser 或存储格式后，旧的 AST 自动失效
+pub const CACHE_VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    "+sqlparser-",
+    env!("SQLPARSER_VERSION"),
+    "+msgpack+ttl"
+);
+
+const AST_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("ast_cache");
+// 写入时间（秒，大端）+ 条目键，按写入顺序淘汰过期或超出容量的条目
+const WRITTEN_TABLE: TableDefinition<&[u8], ()> = TableDefinition::new("ast_cache_written");
+const KEY_SEPARATOR: u8 = 0x1f;
+// 写穿模式的写入队列长度与单个事务最多提交的条目数
+const WRITE_QUEUE: usize = 1024;
+const WRITE_BATCH: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum PersistMode {
+    /// Read through to disk on memory miss, write every new entry to disk
+    WriteThrough,
+    /// Load all entries on startup, write the in-memory cache back on shutdown
+    Snapshot,
+}
+
+// 基于 redb 的磁盘缓存层，条目数与存活时间有上限，超出时先淘汰最早写入的条目
+pub struct DiskCache {
+    db: Database,
+    max_capacity: u64,
+    ttl: Duration,
+}
+
+impl DiskCache {
+    pub fn open(dir: &Path, max_capacity: u64, ttl: Duration) -> Result<Self, String> {
+        std::fs::create_dir_all(dir).map_err(db_err)?;
+        let db = Database::create(dir.join("ast_cache.redb")).map_err(db_err)?;
+        let disk = Self {
+            db,
+            max_capacity,
+            ttl,
+        };
+
+        // 清理其他版本写入的条目，并按当前的容量与存活时间淘汰
+        let prefix = version_prefix();
+        let txn = disk.db.begin_write().map_err(db_err)?;
+        {
+            let mut table = txn.open_table(AST_TABLE).map_err(db_err)?;
+            let mut written = txn.open_table(WRITTEN_TABLE).map_err(db_err)?;
+            table
+                .retain(|key, _| key.starts_with(&prefix))
+                .map_err(db_err)?;
+            written
+                .retain(|index, _| index.get(8..).is_some_and(|key| key.starts_with(&prefix)))
+                .map_err(db_err)?;
+            disk.evict(&mut table, &mut written, unix_now())?;
+        }
+        txn.commit().map_err(db_err)?;
+
+        Ok(disk)
+    }
+
+    fn expired(&self, written_at: u64, now: u64) -> bool {
+        now.saturating_sub(written_at) >= self.ttl.as_secs()
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Result<Option<CacheValue>, String> {
+        let txn = self.db.begin_read().map_err(db_err)?;
+        let table = txn.open_table(AST_TABLE).map_err(db_err)?;
+        let value = table.get(encode_key(key).as_slice()).map_err(db_err)?;
+        // 过期条目由下一次写入清理
+        Ok(value
+            .and_then(|v| decode_value(v.value()))
+            .filter(|(written_at, _)| !self.expired(*written_at, unix_now()))
+            .map(|(_, value)| value))
+    }
+
+    // 一个事务写入一批条目，提交前淘汰过期与超出容量的条目
+    pub fn put_batch(&self, entries: &[(CacheKey, CacheValue)]) -> Result<(), String> {
+        self.write(entries, unix_now(), false).map(|_| ())
+    }
+
+    // 返回写入后磁盘上的条目数
+    fn write(
+        &self,
+        entries: &[(CacheKey, CacheValue)],
+        now: u64,
+        replace: bool,
+    ) -> Result<u64, String> {
+        let txn = self.db.begin_write().map_err(db_err)?;
+        let len = {
+            let mut table = txn.open_table(AST_TABLE).map_err(db_err)?;
+            let mut written = txn.open_table(WRITTEN_TABLE).map_err(db_err)?;
+            let mut rows = Vec::with_capacity(entries.len());
+            for (key, value) in entries {
+                let key = encode_key(key);
+                let previous = table
+                    .get(key.as_slice())
+                    .map_err(db_err)?
+                    .and_then(|old| decode_value(old.value()))
+                    .map(|(written_at, _)| written_at);
+                rows.push((key, value, previous));
+            }
+            if replace {
+                table.retain(|_, _| false).map_err(db_err)?;
+                written.retain(|_, _| false).map_err(db_err)?;
+            }
+            for (key, value, previous) in rows {
+                // 快照整体替换时沿用条目原来的写入时间，重启不会延长存活时间；
+                // 其余情况重写条目会刷新写入时间
+                let written_at = match previous {
+                    Some(previous) if replace => previous,
+                    Some(previous) => {
+                        written
+                            .remove(index_key(previous, &key).as_slice())
+                            .map_err(db_err)?;
+                        now
+                    }
+                    None => now,
+                };
+                table
+                    .insert(key.as_slice(), encode_value(value, written_at).as_slice())
+                    .map_err(db_err)?;
+                written
+                    .insert(index_key(written_at, &key).as_slice(), ())
+                    .map_err(db_err)?;
+            }
+            self.evict(&mut table, &mut written, now)?;
+            table.len().map_err(db_err)?
+        };
+        txn.commit().map_err(db_err)?;
+        Ok(len)
+    }
+
+    fn evict(
+        &self,
+        table: &mut Table<&[u8], &[u8]>,
+        written: &mut Table<&[u8], ()>,
+        now: u64,
+    ) -> Result<(), String> {
+        loop {
+            let oldest = match written.first().map_err(db_err)? {
+                Some((index, _)) => index.value().to_vec(),
+                None => return Ok(()),
+            };
+            let written_at = u64::from_be_bytes(oldest[..8].try_into().unwrap());
+            let full = table.len().map_err(db_err)? > self.max_capacity;
+            if !full && !self.expired(written_at, now) {
+                return Ok(());
+            }
+            written.remove(oldest.as_slice()).map_err(db_err)?;
+            table.remove(&oldest[8..]).map_err(db_err)?;
+        }
+    }
+
+    pub fn load_all(&self) -> Result<Vec<(CacheKey, CacheValue)>, String> {
+        let txn = self.db.begin_read().map_err(db_err)?;
+        let table = txn.open_table(AST_TABLE).map_err(db_err)?;
+        let now = unix_now();
+        let mut entries = Vec::new();
+        for item in table.iter().map_err(db_err)? {
+            let (key, value) = item.map_err(db_err)?;
+            if let (Some(key), Some((written_at, value))) =
+                (decode_key(key.value()), decode_value(value.value()))
+            {
+                if !self.expired(written_at, now) {
+                    entries.push((key, value));
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    // 用给定条目整体替换磁盘内容（快照模式），已超过存活时间的条目不再写回
+    pub fn replace_all(&self, entries: Vec<(CacheKey, CacheValue)>) -> Result<usize, String> {
+        let len = self.write(&entries, unix_now(), true)?;
+        Ok(len as usize)
+    }
+}
+
+// 写穿模式的后台写入线程：请求只把条目放入有界队列，线程把积压的条目合并到一个事务中提交。
+// 队列已满时丢弃新条目，磁盘层只是加速，不影响结果；线程无法启动时只读取磁盘
+fn spawn_writer(disk: Arc<DiskCache>) -> Option<SyncSender<(CacheKey, CacheValue)>> {
+    let (sender, receiver) = mpsc::sync_channel::<(CacheKey, CacheValue)>(WRITE_QUEUE);
+    let spawned = std::thread::Builder::new()
+        .name("cache-writer".to_string())
+        .spawn(move || {
+            while let Ok(first) = receiver.recv() {
+                let mut batch = vec![first];
+                batch.extend(receiver.try_iter().take(WRITE_BATCH - 1));
+                if let Err(e) = disk.put_batch(&batch) {
+                    tracing::warn!("Persistent cache write failed: {e}");
+                }
+            }
+        });
+    match spawned {
+        Ok(_) => Some(sender),
+        Err(e) => {
+            tracing::warn!("Failed to start persistent cache writer: {e}");
+            None
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+fn index_key(written_at: u64, key: &[u8]) -> Vec<u8> {
+    let mut index = written_at.to_be_bytes().to_vec();
+    index.extend_from_slice(key);
+    index
+}
+
+fn db_err(e: impl std::fmt::Display) -> String {
+    format!("persistent cache error: {e}")
+}
+
+fn version_prefix() -> Vec<u8> {
+    let mut prefix = CACHE_VERSION.as_bytes().to_vec();
+    prefix.push(KEY_SEPARATOR);
+    prefix
+}
+
+fn encode_key((sql, dialect): &CacheKey) -> Vec<u8> {
+    let mut key = version_prefix();
+    key.extend_from_slice(dialect.as_bytes());
+    key.push(KEY_SEPARATOR);
+    key.extend_from_slice(sql.as_bytes());
+    key
+}
+
+// 值的磁盘格式：首字节为类型，随后 8 字节为写入时间（秒，小端），
+// 类型 0 后接 AST（MessagePack），类型 1 后接解析错误文本
+fn encode_value(value: &CacheValue, written_at: u64) -> Vec<u8> {
+    let (tag, payload) = match value {
+        Ok(ast) => (0, ast.as_ref()),
+        Err(e) => (1, e.as_bytes()),
+    };
+    let mut bytes = Vec::with_capacity(9 + payload.len());
+    bytes.push(tag);
+    bytes.extend_from_slice(&written_at.to_le_bytes());
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+fn decode_value(bytes: &[u8]) -> Option<(u64, CacheValue)> {
+    let (tag, rest) = bytes.split_first()?;
+    let (written_at, payload) = rest.split_first_chunk::<8>()?;
+    let value = match tag {
+        0 => Ok(Bytes::copy_from_slice(payload)),
+        1 => Err(String::from_utf8(payload.to_vec()).ok()?),
+        _ => return None,
+    };
+    Some((u64::from_le_bytes(*written_at), value))
+}
+
+fn decode_key(bytes: &[u8]) -> Option<CacheKey> {
+    let rest = bytes.strip_prefix(version_prefix().as_slice())?;
+    let split = rest.iter().position(|b| *b == KEY_SEPARATOR)?;
+    let dialect = std::str::from_utf8(&rest[..split]).ok()?;
+    let sql = std::str::from_utf8(&rest[split + 1..]).ok()?;
+    Some((sql.to_string(), dialect.to_string()))
+}
+
+// 内存缓存 + 可选磁盘缓存
+#[derive(Clone)]
+pub struct AstCache {
+    memory: Cache<CacheKey, CacheValue>,
+    disk: Option<(Arc<DiskCache>, PersistMode)>,
+    // 写穿模式下的后台写入队列
+    writer: Option<SyncSender<(CacheKey, CacheValue)>>,
+}
+
+impl AstCache {
+    pub fn new(memory: Cache<CacheKey, CacheValue>) -> Self {
+        Self {
+            memory,
+            disk: None,
+            writer: None,
+        }
+    }
+
+    pub fn with_disk(mut self, disk: DiskCache, mode: PersistMode) -> Self {
+        let disk = Arc::new(disk);
+        if mode == PersistMode::WriteThrough {
+            self.writer = spawn_writer(disk.clone());
+        }
+        self.disk = Some((disk, mode));
+        self
+    }
+
+    pub async fn get(&self, key: &CacheKey) -> Option<CacheValue> {
+        if let Some(value) = self.memory.get(key).await {
+            return Some(value);
+        }
+
+        let Some((disk, PersistMode::WriteThrough)) = &self.disk else {
+            return None;
+        };
+        let disk = disk.clone();
+        let lookup = key.clone();
+        let value = match tokio::task::spawn_blocking(move || disk.get(&lookup)).await {
+            Ok(Ok(value)) => value?,
+            Ok(Err(e)) => {
+                tracing::warn!("Persistent cache read failed: {e}");
+                return None;
+            }
+            Err(e) => {
+                tracing::warn!("Persistent cache read task failed: {e}");
+                return None;
+            }
+        };
+        self.memory.insert(key.clone(), value.clone()).await;
+        Some(value)
+    }
+
+    pub async fn insert(&self, key: CacheKey, value: CacheValue) {
+        if let Some(writer) = &self.writer {
+            // 写盘不阻塞请求
+            if let Err(TrySendError::Full(_)) = writer.try_send((key.clone(), value.clone())) {
+                tracing::debug!("Persistent cache write queue is full, entry not persisted");
+            }
+        }
+        self.memory.insert(key, value).await;
+    }
+
+    // 启动时从快照加载
+    pub async fn load_snapshot(&self) -> Result<usize, String> {
+        let Some((disk, PersistMode::Snapshot)) = &self.disk else {
+            return Ok(0);
+        };
+        let disk = disk.clone();
+        let entries = tokio::task::spawn_blocking(move || disk.load_all())
+            .await
+            .map_err(|e| format!("snapshot load task failed: {e}"))??;
+        let count = entries.len();
+        for (key, value) in entries {
+            self.memory.insert(key, value).await;
+        }
+        Ok(count)
+    }
+
+    // 关闭时写入快照
+    pub async fn save_snapshot(&self) -> Result<usize, String> {
+        let Some((disk, PersistMode::Snapshot)) = &self.disk else {
+            return Ok(0);
+        };
+        let disk = disk.clone();
+        let entries: Vec<_> = self
+            .memory
+            .iter()
+            .map(|(key, value)| ((*key).clone(), value))
+            .collect();
+        tokio::task::spawn_blocking(move || disk.replace_all(entries))
+            .await
+            .map_err(|e| format!("snapshot save task failed: {e}"))?
+    }
+}
+
+// 各类结果独立缓存、独立容量：解析后的语句在 parse 与 fingerprint 之间共享
+#[derive(Clone)]
+pub struct Caches {
+    pub ast: AstCache,
+    pub statements: Cache<CacheKey, StatementsValue>,
+    pub fingerprints: Cache<FingerprintKey, FingerprintValue>,
+    // 构造指纹缓存键时是否统一关键字大小写
+    pub fold_keyword_case: bool,
+}
+
+pub fn build_cache<K, V>(max_capacity: u64, ttl: Duration) -> Cache<K, V>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    Cache::builder()
+        .max_capacity(max_capacity)
+        .time_to_live(ttl)
+        .build()
+}
+
+// 命中缓存直接返回，否则计算并写入；并发的相同请求只计算一次。
+// 计算返回 Err 时不写入缓存（如超出限制、超时），由调用方直接返回给客户端。
+pub async fn try_get_or_compute<K, V, E>(
+    cache: &Cache<K, V>,
+    key: K,
+    init: impl Future<Output = Result<V, E>>,
+) -> Result<(V, bool), E>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    E: Clone + Send + Sync + 'static,
+{
+    let entry = cache
+        .entry(key)
+        .or_try_insert_with(init)
+        .await
+        .map_err(|e| (*e).clone())?;
+    let cached = !entry.is_fresh();
+    Ok((entry.into_value(), cached))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_cache() -> Cache<CacheKey, CacheValue> {
+        build_cache(100, Duration::from_secs(60))
+    }
+
+    fn key(sql: &str) -> CacheKey {
+        (sql.to_string(), "mysql".to_string())
+    }
+
+    fn open(dir: &Path) -> DiskCache {
+        DiskCache::open(dir, 100, Duration::from_secs(60)).unwrap()
+    }
+
+    fn ast() -> CacheValue {
+        Ok(Bytes::from_static(b"\x91\x01"))
+    }
+
+    #[test]
+    fn test_key_roundtrip() {
+        let k = key("SELECT 1");
+        assert_eq!(decode_key(&encode_key(&k)), Some(k));
+        assert_eq!(
+            decode_key(b"0.0.0+sqlparser-0.1\x1fmysql\x1fSELECT 1"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_disk_cache_persists_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let disk = open(dir.path());
+            disk.put_batch(&[(key("SELECT 1"), ast())]).unwrap();
+            disk.put_batch(&[(key("SELEC"), Err("bad".to_string()))])
+                .unwrap();
+        }
+
+        let disk = open(dir.path());
+        assert_eq!(
+            disk.get(&key("SELECT 1")).unwrap(),
+            Some(Ok(Bytes::from_static(b"\x91\x01")))
+        );
+        assert_eq!(
+            disk.get(&key("SELEC")).unwrap(),
+            Some(Err("bad".to_string()))
+        );
+        assert_eq!(disk.get(&key("SELECT 2")).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let cache =
+            AstCache::new(memory_cache()).with_disk(open(dir.path()), PersistMode::Snapshot);
+        cache
+            .insert(key("SELECT 1"), Ok(Bytes::from_static(b"\x91\x01")))
+            .await;
+        assert_eq!(cache.save_snapshot().await.unwrap(), 1);
+        drop(cache);
+
+        let cache =
+            AstCache::new(memory_cache()).with_disk(open(dir.path()), PersistMode::Snapshot);
+        assert_eq!(cache.load_snapshot().await.unwrap(), 1);
+        assert_eq!(
+            cache.get(&key("SELECT 1")).await,
+            Some(Ok(Bytes::from_static(b"\x91\x01")))
+        );
+    }
+
+    #[test]
+    fn test_snapshot_keeps_written_at() {
+        let dir = tempfile::tempdir().unwrap();
+        let disk = open(dir.path());
+        let now = unix_now();
+        disk.write(&[(key("SELECT 1"), ast())], now - 30, false).unwrap();
+        disk.write(&[(key("SELECT 2"), ast())], now - 90, false).unwrap();
+
+        // 重启后写回快照不刷新写入时间，已过期的条目被丢弃
+        let entries = ["SELECT 1", "SELECT 2", "SELECT 3"].map(|sql| (key(sql), ast()));
+        assert_eq!(disk.replace_all(entries.to_vec()).unwrap(), 2);
+        let txn = disk.db.begin_read().unwrap();
+        let table = txn.open_table(AST_TABLE).unwrap();
+        let written_at = |sql: &str| {
+            let value = table.get(encode_key(&key(sql)).as_slice()).unwrap();
+            value.and_then(|v| decode_value(v.value())).map(|(written_at, _)| written_at)
+        };
+        assert_eq!(written_at("SELECT 1"), Some(now - 30));
+        assert_eq!(written_at("SELECT 2"), None);
+        assert!(written_at("SELECT 3").unwrap() >= now);
+    }
+
+    #[tokio::test]
+    async fn test_write_through_reads_from_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        open(dir.path())
+            .put_batch(&[(key("SELECT 1"), ast())])
+            .unwrap();
+
+        let cache = AstCache::new(memory_cache())
+            .with_disk(open(dir.path()), PersistMode::WriteThrough);
+        assert_eq!(cache.get(&key("SELECT 1")).await, Some(ast()));
+
+        // 新条目由后台线程写入磁盘
+        cache.insert(key("SELECT 2"), ast()).await;
+        let (disk, _) = cache.disk.clone().unwrap();
+        let mut persisted = None;
+        for _ in 0..100 {
+            persisted = disk.get(&key("SELECT 2")).unwrap();
+            if persisted.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(persisted, Some(ast()));
+    }
+
+    #[test]
+    fn test_disk_cache_evicts_oldest_and_expired() {
+        let dir = tempfile::tempdir().unwrap();
+        let disk = DiskCache::open(dir.path(), 2, Duration::from_secs(60)).unwrap();
+        let now = unix_now();
+        disk.write(&[(key("SELECT 1"), ast())], now - 2, false).unwrap();
+        disk.write(&[(key("SELECT 2"), ast())], now - 1, false).unwrap();
+        // 重写已有条目会刷新其写入时间
+        disk.write(&[(key("SELECT 1"), ast())], now, false).unwrap();
+        disk.write(&[(key("SELECT 3"), ast())], now, false).unwrap();
+        assert_eq!(disk.get(&key("SELECT 2")).unwrap(), None);
+        assert_eq!(disk.get(&key("SELECT 1")).unwrap(), Some(ast()));
+        assert_eq!(disk.load_all().unwrap().len(), 2);
+
+        // 过期条目不再返回，下一次写入时删除
+        let dir = tempfile::tempdir().unwrap();
+        let disk = open(dir.path());
+        disk.write(&[(key("SELECT 1"), ast())], now - 60, false).unwrap();
+        disk.write(&[(key("SELECT 2"), ast())], now, false).unwrap();
+        assert_eq!(disk.get(&key("SELECT 1")).unwrap(), None);
+        assert_eq!(disk.load_all().unwrap(), vec![(key("SELECT 2"), ast())]);
+        disk.put_batch(&[(key("SELECT 3"), ast())]).unwrap();
+        let txn = disk.db.begin_read().unwrap();
+        assert_eq!(txn.open_table(AST_TABLE).unwrap().len().unwrap(), 2);
+        assert_eq!(txn.open_table(WRITTEN_TABLE).unwrap().len().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_try_get_or_compute_reports_hits() {
+        let cache: Cache<FingerprintKey, FingerprintValue> =
+            build_cache(10, Duration::from_secs(60));
+        let key = ("SELECT 1".to_string(), "mysql".to_string(), 0);
+
+        let computed = try_get_or_compute(&cache, key.clone(), async {
+            Err::<FingerprintValue, _>("timeout")
+        })
+        .await;
+        assert_eq!(computed, Err("timeout"));
+
+        let (value, cached) = try_get_or_compute(&cache, key.clone(), async {
+            Ok::<_, &str>(Ok("SELECT ?".to_string()))
+        })
+        .await
+        .unwrap();
+        assert_eq!(value, Ok("SELECT ?".to_string()));
+        assert!(!cached);
+
+        let (value, cached) = try_get_or_compute::<_, _, &str>(&cache, key, async { unreachable!() })
+            .await
+            .unwrap();
+        assert_eq!(value, Ok("SELECT ?".to_string()));
+        assert!(cached);
+    }
+}
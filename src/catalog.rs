@@ -0,0 +1,461 @@
+use crate::config::CatalogConfig;
+use serde::{Deserialize, Serialize};
+use sqlparser::ast::{ObjectName, Statement};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use utoipa::ToSchema;
+
+// 表结构目录的 JSON 形式：PUT /catalogs/{namespace} 的请求体、GET 的响应与持久化文件
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CatalogDefinition {
+    // 表名（可带 schema 前缀，如 public.users）到按顺序排列的列名；列为空表示列未知，不做列检查
+    #[serde(default)]
+    #[schema(example = json!({"users": ["id", "name", "email"], "orders": ["id", "user_id", "total"]}))]
+    pub tables: BTreeMap<String, Vec<String>>,
+
+    // 内置函数之外允许调用的函数（自定义函数、存储过程等）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schema(example = json!(["mask_email"]))]
+    pub functions: Vec<String>,
+}
+
+// 名称不区分大小写；表名按后缀匹配，FROM users 可以匹配 public.users
+#[derive(Debug, Default)]
+pub struct Catalog {
+    definition: CatalogDefinition,
+    // 小写的表名各部分与声明时的列名，与 definition.tables 一一对应
+    tables: Vec<(Vec<String>, Vec<String>)>,
+    functions: HashSet<String>,
+}
+
+fn lowercase_path(name: &str) -> Vec<String> {
+    name.split('.').map(str::to_lowercase).collect()
+}
+
+fn object_name(name: &ObjectName) -> String {
+    name.0
+        .iter()
+        .map(|ident| ident.value.as_str())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+impl Catalog {
+    pub fn new(definition: CatalogDefinition) -> Result<Self, String> {
+        let mut catalog = Catalog::default();
+        for (name, columns) in definition.tables {
+            catalog.insert(name, columns)?;
+        }
+        catalog.add_functions(definition.functions)?;
+        Ok(catalog)
+    }
+
+    // 由 CREATE TABLE / CREATE VIEW 语句构建，其他语句忽略并计入返回的 skipped。
+    // CREATE TABLE ... AS SELECT 与未列出列名的视图按查询的输出列推导
+    pub fn from_ddl(
+        statements: &[Statement],
+        functions: Vec<String>,
+    ) -> Result<(Self, usize), String> {
+        let mut catalog = Catalog::default();
+        let mut skipped = 0;
+        for statement in statements {
+            let (name, columns) = match statement {
+                Statement::CreateTable(create) => {
+                    let columns = if !create.columns.is_empty() {
+                        create
+                            .columns
+                            .iter()
+                            .map(|c| c.name.value.clone())
+                            .collect()
+                    } else if let Some(query) = &create.query {
+                        catalog.derived_columns(query)
+                    } else if let Some(like) = &create.like {
+                        let path = lowercase_path(&object_name(like));
+                        catalog
+                            .table(&path)
+                            .map(<[String]>::to_vec)
+                            .unwrap_or_default()
+                    } else {
+                        Vec::new()
+                    };
+                    (&create.name, columns)
+                }
+                Statement::CreateView {
+                    name,
+                    columns,
+                    query,
+                    ..
+                } => {
+                    let columns = if columns.is_empty() {
+                        catalog.derived_columns(query)
+                    } else {
+                        columns.iter().map(|c| c.name.value.clone()).collect()
+                    };
+                    (name, columns)
+                }
+                _ => {
+                    skipped += 1;
+                    continue;
+                }
+            };
+            catalog.insert(object_name(name), columns)?;
+        }
+        if catalog.tables.is_empty() {
+            return Err("No CREATE TABLE or CREATE VIEW statements found in ddl".to_string());
+        }
+        catalog.add_functions(functions)?;
+        Ok((catalog, skipped))
+    }
+
+    // 推导不出（包含未命名的表达式或未知的列）时记为列未知
+    fn derived_columns(&self, query: &sqlparser::ast::Query) -> Vec<String> {
+        crate::validate::output_columns(self, query)
+            .filter(|columns| columns.iter().all(|c| !c.is_empty()))
+            .unwrap_or_default()
+    }
+
+    // 同名（不区分大小写）的表后定义的覆盖先定义的
+    fn insert(&mut self, name: String, columns: Vec<String>) -> Result<(), String> {
+        let path = lowercase_path(&name);
+        if path.iter().any(String::is_empty) {
+            return Err(format!("Invalid table name '{name}'"));
+        }
+        let mut seen = HashSet::new();
+        for column in &columns {
+            if column.is_empty() {
+                return Err(format!("Table '{name}' has an empty column name"));
+            }
+            if !seen.insert(column.to_lowercase()) {
+                return Err(format!("Table '{name}' has duplicate column '{column}'"));
+            }
+        }
+        if let Some(index) = self.tables.iter().position(|(p, _)| *p == path) {
+            let (existing, _) = self.tables.remove(index);
+            self.definition
+                .tables
+                .retain(|key, _| lowercase_path(key) != existing);
+        }
+        self.tables.push((path, columns.clone()));
+        self.definition.tables.insert(name, columns);
+        Ok(())
+    }
+
+    fn add_functions(&mut self, functions: Vec<String>) -> Result<(), String> {
+        for function in functions {
+            if function.is_empty() {
+                return Err("Function names must not be empty".to_string());
+            }
+            if self.functions.insert(function.to_lowercase()) {
+                self.definition.functions.push(function);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn definition(&self) -> &CatalogDefinition {
+        &self.definition
+    }
+
+    pub fn table_count(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn column_count(&self) -> usize {
+        self.tables.iter().map(|(_, columns)| columns.len()).sum()
+    }
+
+    pub fn function_count(&self) -> usize {
+        self.functions.len()
+    }
+
+    // path 为小写的表名各部分；返回声明时的列名，列未知时为空。
+    // 完全一致优先，否则取一方为另一方后缀的第一张表
+    pub fn table(&self, path: &[String]) -> Option<&[String]> {
+        let suffix_of = |long: &[String], short: &[String]| long.ends_with(short);
+        self.tables
+            .iter()
+            .find(|(p, _)| p == path)
+            .or_else(|| {
+                self.tables
+                    .iter()
+                    .find(|(p, _)| suffix_of(p, path) || suffix_of(path, p))
+            })
+            .map(|(_, columns)| columns.as_slice())
+    }
+
+    // 完整名称或最后一部分已登记即可
+    pub fn has_function(&self, name: &ObjectName) -> bool {
+        let full = object_name(name).to_lowercase();
+        let last = name
+            .0
+            .last()
+            .map(|i| i.value.to_lowercase())
+            .unwrap_or_default();
+        self.functions.contains(&full) || self.functions.contains(&last)
+    }
+}
+
+#[derive(Debug)]
+pub enum CatalogError {
+    // 命名空间无效、数量超出上限等，调用方可修正
+    Invalid(String),
+    // 写入或删除持久化文件失败
+    Storage(String),
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CatalogError::Invalid(e) | CatalogError::Storage(e) => f.write_str(e),
+        }
+    }
+}
+
+// 命名空间用作文件名，只允许字母、数字、下划线与连字符
+pub fn validate_namespace(namespace: &str) -> Result<(), String> {
+    let valid = !namespace.is_empty()
+        && namespace.len() <= 64
+        && namespace
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid namespace '{namespace}': expected 1-64 letters, digits, '_' or '-'"
+        ))
+    }
+}
+
+// 按命名空间保存的表结构目录。配置 dir 时每个命名空间对应 <namespace>.json，
+// 启动时载入，注册与删除时同步写入
+pub struct CatalogStore {
+    dir: Option<PathBuf>,
+    max_namespaces: usize,
+    namespaces: RwLock<HashMap<String, Arc<Catalog>>>,
+    // 文件写入与内存更新串行进行，保证两者一致
+    write: Mutex<()>,
+}
+
+impl Default for CatalogStore {
+    fn default() -> Self {
+        Self::new(&CatalogConfig::default())
+    }
+}
+
+impl CatalogStore {
+    fn new(config: &CatalogConfig) -> Self {
+        Self {
+            dir: config.dir.clone(),
+            max_namespaces: config.max_namespaces,
+            namespaces: RwLock::new(HashMap::new()),
+            write: Mutex::new(()),
+        }
+    }
+
+    pub fn from_config(config: &CatalogConfig) -> Result<Arc<Self>, String> {
+        let store = Self::new(config);
+        let Some(dir) = &store.dir else {
+            return Ok(Arc::new(store));
+        };
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+        let entries =
+            std::fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {e}", dir.display()))?;
+        let mut namespaces = HashMap::new();
+        for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            let Some(namespace) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .filter(|_| path.extension().is_some_and(|e| e == "json"))
+            else {
+                continue;
+            };
+            validate_namespace(namespace).map_err(|e| format!("{}: {e}", path.display()))?;
+            let catalog = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| serde_json::from_str(&content).map_err(|e| e.to_string()))
+                .and_then(Catalog::new)
+                .map_err(|e| format!("Failed to load {}: {e}", path.display()))?;
+            namespaces.insert(namespace.to_string(), Arc::new(catalog));
+        }
+        *store.namespaces.write().unwrap() = namespaces;
+        Ok(Arc::new(store))
+    }
+
+    pub fn len(&self) -> usize {
+        self.namespaces.read().unwrap().len()
+    }
+
+    pub fn get(&self, namespace: &str) -> Option<Arc<Catalog>> {
+        self.namespaces.read().unwrap().get(namespace).cloned()
+    }
+
+    // 返回是否替换了已有的命名空间
+    pub async fn put(
+        self: &Arc<Self>,
+        namespace: &str,
+        catalog: Catalog,
+    ) -> Result<bool, CatalogError> {
+        validate_namespace(namespace).map_err(CatalogError::Invalid)?;
+        let (store, namespace) = (self.clone(), namespace.to_string());
+        tokio::task::spawn_blocking(move || {
+            let _guard = store.write.lock().unwrap();
+            let exists = store.namespaces.read().unwrap().contains_key(&namespace);
+            if !exists && store.len() >= store.max_namespaces {
+                return Err(CatalogError::Invalid(format!(
+                    "Too many namespaces (max {}), delete one first",
+                    store.max_namespaces
+                )));
+            }
+            if let Some(dir) = &store.dir {
+                let path = dir.join(format!("{namespace}.json"));
+                let tmp = path.with_extension("tmp");
+                let content = serde_json::to_string_pretty(catalog.definition())
+                    .map_err(|e| CatalogError::Storage(e.to_string()))?;
+                std::fs::write(&tmp, content)
+                    .and_then(|()| std::fs::rename(&tmp, &path))
+                    .map_err(|e| {
+                        CatalogError::Storage(format!("Failed to write {}: {e}", path.display()))
+                    })?;
+            }
+            store
+                .namespaces
+                .write()
+                .unwrap()
+                .insert(namespace, Arc::new(catalog));
+            Ok(exists)
+        })
+        .await
+        .expect("catalog write task panicked")
+    }
+
+    // 返回命名空间是否存在
+    pub async fn remove(self: &Arc<Self>, namespace: &str) -> Result<bool, CatalogError> {
+        let (store, namespace) = (self.clone(), namespace.to_string());
+        tokio::task::spawn_blocking(move || {
+            let _guard = store.write.lock().unwrap();
+            if !store.namespaces.read().unwrap().contains_key(&namespace) {
+                return Ok(false);
+            }
+            if let Some(dir) = &store.dir {
+                let path = dir.join(format!("{namespace}.json"));
+                match std::fs::remove_file(&path) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => {
+                        return Err(CatalogError::Storage(format!(
+                            "Failed to remove {}: {e}",
+                            path.display()
+                        )))
+                    }
+                }
+            }
+            store.namespaces.write().unwrap().remove(&namespace);
+            Ok(true)
+        })
+        .await
+        .expect("catalog write task panicked")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlparser::dialect::PostgreSqlDialect;
+    use sqlparser::parser::Parser;
+
+    fn path(name: &str) -> Vec<String> {
+        lowercase_path(name)
+    }
+
+    #[test]
+    fn test_catalog_from_ddl() {
+        let ddl = "CREATE TABLE public.Users (id INT PRIMARY KEY, Name TEXT, email TEXT);\n\
+                   CREATE INDEX users_email ON public.users (email);\n\
+                   CREATE TABLE orders (id INT, user_id INT, total NUMERIC);\n\
+                   CREATE VIEW big_orders AS SELECT o.id, o.total AS amount FROM orders o WHERE total > 100;\n\
+                   CREATE TABLE archive AS SELECT id, 1 + 1 FROM orders;";
+        let statements = Parser::parse_sql(&PostgreSqlDialect {}, ddl).unwrap();
+        let (catalog, skipped) = Catalog::from_ddl(&statements, vec!["mask".to_string()]).unwrap();
+        assert_eq!(skipped, 1);
+        assert_eq!(catalog.table_count(), 4);
+        assert_eq!(
+            catalog.table(&path("users")).unwrap(),
+            ["id", "Name", "email"]
+        );
+        assert_eq!(catalog.table(&path("PUBLIC.USERS")).unwrap().len(), 3);
+        assert_eq!(
+            catalog.table(&path("big_orders")).unwrap(),
+            ["id", "amount"]
+        );
+        // 含未命名表达式的查询推导不出列
+        assert_eq!(catalog.table(&path("archive")).unwrap(), [] as [String; 0]);
+        assert!(catalog.table(&path("other.users")).is_none());
+        assert!(catalog.has_function(&ObjectName(vec!["MASK".into()])));
+        assert!(Catalog::from_ddl(
+            &Parser::parse_sql(&PostgreSqlDialect {}, "SELECT 1").unwrap(),
+            vec![]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_definition_validation() {
+        let definition = |json: serde_json::Value| {
+            Catalog::new(serde_json::from_value::<CatalogDefinition>(json).unwrap())
+        };
+        let catalog = definition(serde_json::json!({"tables": {"t": ["a", "b"]}})).unwrap();
+        assert_eq!(catalog.column_count(), 2);
+        assert!(definition(serde_json::json!({"tables": {"t": ["a", "A"]}}))
+            .unwrap_err()
+            .contains("duplicate column"));
+        assert!(definition(serde_json::json!({"tables": {"s..t": []}})).is_err());
+        // 大小写不同的同名表只保留后一个
+        let catalog = definition(serde_json::json!({"tables": {"T": ["a"], "t": ["b"]}})).unwrap();
+        assert_eq!(catalog.table_count(), 1);
+        assert_eq!(catalog.definition().tables.len(), 1);
+        assert!(validate_namespace("app_1-prod").is_ok());
+        assert!(validate_namespace("../etc").is_err());
+        assert!(validate_namespace("").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_store_persists_namespaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = CatalogConfig {
+            dir: Some(dir.path().to_path_buf()),
+            max_namespaces: 1,
+        };
+        let store = CatalogStore::from_config(&config).unwrap();
+        let catalog = || {
+            Catalog::new(CatalogDefinition {
+                tables: BTreeMap::from([("t".to_string(), vec!["a".to_string()])]),
+                functions: Vec::new(),
+            })
+            .unwrap()
+        };
+        assert!(!store.put("app", catalog()).await.unwrap());
+        assert!(store.put("app", catalog()).await.unwrap());
+        assert!(matches!(
+            store.put("other", catalog()).await,
+            Err(CatalogError::Invalid(_))
+        ));
+        assert!(matches!(
+            store.put("a/b", catalog()).await,
+            Err(CatalogError::Invalid(_))
+        ));
+
+        let reloaded = CatalogStore::from_config(&config).unwrap();
+        assert_eq!(
+            reloaded.get("app").unwrap().definition(),
+            catalog().definition()
+        );
+        assert!(reloaded.remove("app").await.unwrap());
+        assert!(!reloaded.remove("app").await.unwrap());
+        assert_eq!(CatalogStore::from_config(&config).unwrap().len(), 0);
+    }
+}
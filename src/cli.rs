@@ -0,0 +1,475 @@
+use crate::config::Config;
+use crate::limits::Limits;
+use crate::pool::WorkTiming;
+use crate::slowlog::SlowLogFormat;
+use crate::SqlError;
+use clap::{Args, Subcommand, ValueEnum};
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+// 退出码：全部成功为 0，存在 SQL 错误为 1，输入无法读取或方言无效为 2
+pub const EXIT_SQL_ERROR: i32 = 1;
+pub const EXIT_INPUT_ERROR: i32 = 2;
+
+// 不启动服务的离线子命令，与 HTTP / gRPC 接口共用解析与指纹代码
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    #[command(about = "Parse SQL into a JSON AST")]
+    Parse {
+        #[command(flatten)]
+        input: InputArgs,
+
+        #[arg(long, help = "Drop null, false and empty fields from the AST")]
+        compact: bool,
+
+        #[arg(long, help = "Annotate AST nodes with source positions")]
+        with_spans: bool,
+    },
+
+    #[command(about = "Generate SQL fingerprints")]
+    Fingerprint {
+        #[command(flatten)]
+        input: InputArgs,
+
+        #[arg(
+            long,
+            default_value_t = 0,
+            help = "Keep at most this many IN list values, 0 to keep all"
+        )]
+        max_in_values: usize,
+    },
+
+    #[command(about = "Re-render SQL from its AST (comments are not preserved)")]
+    Format {
+        #[command(flatten)]
+        input: InputArgs,
+    },
+
+    #[command(about = "Report statements that fail to parse")]
+    Lint {
+        #[command(flatten)]
+        input: InputArgs,
+    },
+
+    #[command(about = "Summarize slow query logs by fingerprint, ranked by total time")]
+    Digest {
+        #[arg(
+            required = true,
+            help = "MySQL slow query logs or PostgreSQL logs, glob patterns, or - for stdin"
+        )]
+        inputs: Vec<String>,
+
+        #[arg(long, value_enum, default_value_t = SlowLogFormat::Auto, help = "Log format")]
+        log_format: SlowLogFormat,
+
+        #[arg(
+            short,
+            long,
+            help = "SQL dialect, defaults to the one matching the log format"
+        )]
+        dialect: Option<String>,
+
+        #[arg(
+            long,
+            default_value_t = 0,
+            help = "Keep at most this many IN list values, 0 to keep all"
+        )]
+        max_in_values: usize,
+
+        #[arg(
+            long,
+            default_value_t = 20,
+            help = "Show the top N fingerprints, 0 for all"
+        )]
+        limit: usize,
+    },
+}
+
+impl Command {
+    fn input(&self) -> Option<&InputArgs> {
+        match self {
+            Command::Parse { input, .. }
+            | Command::Fingerprint { input, .. }
+            | Command::Format { input }
+            | Command::Lint { input } => Some(input),
+            Command::Digest { .. } => None,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct InputArgs {
+    #[arg(
+        required = true,
+        help = "SQL files, directories (*.sql, recursively), glob patterns, or - for stdin"
+    )]
+    inputs: Vec<String>,
+
+    #[arg(short, long, default_value = "generic", help = "SQL dialect")]
+    dialect: String,
+
+    #[arg(long, value_enum, default_value_t = OutputMode::Ndjson, help = "Output format")]
+    output: OutputMode,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputMode {
+    // 每个输入一行 JSON
+    Ndjson,
+    // 所有结果组成一个 JSON 数组
+    Json,
+}
+
+#[derive(Debug, PartialEq)]
+enum Source {
+    Stdin,
+    File(PathBuf),
+}
+
+impl Source {
+    fn name(&self) -> String {
+        match self {
+            Source::Stdin => "-".to_string(),
+            Source::File(path) => path.display().to_string(),
+        }
+    }
+
+    fn read(&self) -> Result<String, String> {
+        let mut sql = String::new();
+        match self {
+            Source::Stdin => std::io::stdin()
+                .read_to_string(&mut sql)
+                .map(|_| sql)
+                .map_err(|e| format!("Failed to read stdin: {e}")),
+            Source::File(path) => std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {e}", path.display())),
+        }
+    }
+}
+
+// 目录展开为其下所有 .sql 文件；不存在的路径按 glob 模式匹配
+fn expand(inputs: &[String]) -> Result<Vec<Source>, String> {
+    let mut sources = Vec::new();
+    for input in inputs {
+        let path = Path::new(input);
+        if input == "-" {
+            sources.push(Source::Stdin);
+        } else if path.is_dir() {
+            let pattern = path.join("**").join("*.sql");
+            sources.extend(glob_files(&pattern.to_string_lossy())?);
+        } else if path.exists() {
+            sources.push(Source::File(path.to_path_buf()));
+        } else {
+            let matched = glob_files(input)?;
+            if matched.is_empty() {
+                return Err(format!("No such file or matching files: {input}"));
+            }
+            sources.extend(matched);
+        }
+    }
+    Ok(sources)
+}
+
+fn glob_files(pattern: &str) -> Result<Vec<Source>, String> {
+    let paths = glob::glob(pattern).map_err(|e| format!("Invalid glob pattern {pattern}: {e}"))?;
+    let mut files: Vec<PathBuf> = paths
+        .filter_map(Result::ok)
+        .filter(|path| path.is_file())
+        .collect();
+    files.sort();
+    Ok(files.into_iter().map(Source::File).collect())
+}
+
+pub async fn run(command: &Command, config: &Config, out: &mut impl Write) -> i32 {
+    let Some(input) = command.input() else {
+        return digest(command, config, out).await;
+    };
+    if let Err(e) = crate::get_dialect(&input.dialect) {
+        eprintln!("{e}");
+        return EXIT_INPUT_ERROR;
+    }
+    let sources = match expand(&input.inputs) {
+        Ok(sources) => sources,
+        Err(e) => {
+            eprintln!("{e}");
+            return EXIT_INPUT_ERROR;
+        }
+    };
+
+    let limits = Limits::from_config(&config.limits);
+    let mut exit = 0;
+    let mut results = Vec::new();
+    for source in sources {
+        let file = source.name();
+        let (mut result, code) = match source.read() {
+            Ok(sql) => run_one(command, &input.dialect, &limits, &sql).await,
+            Err(e) => (json!({ "error": e }), EXIT_INPUT_ERROR),
+        };
+        exit = exit.max(code);
+        if let Value::Object(fields) = &mut result {
+            fields.insert("file".to_string(), Value::String(file));
+        }
+        match input.output {
+            OutputMode::Ndjson => {
+                let _ = writeln!(out, "{result}");
+            }
+            OutputMode::Json => results.push(result),
+        }
+    }
+    if input.output == OutputMode::Json {
+        let _ = writeln!(
+            out,
+            "{}",
+            serde_json::to_string_pretty(&results).unwrap_or_default()
+        );
+    }
+    exit
+}
+
+// 返回单个输入的结果对象与退出码
+async fn run_one(command: &Command, dialect: &str, limits: &Limits, sql: &str) -> (Value, i32) {
+    let timing = WorkTiming::default();
+    let result = match command {
+        Command::Parse {
+            compact,
+            with_spans,
+            ..
+        } => {
+            let ast = if *with_spans {
+                crate::parse_with_spans(limits, &timing, sql, dialect, *compact).await
+            } else {
+                crate::parse_sql_impl(sql, dialect, limits, &timing)
+                    .await
+                    .and_then(|ast| crate::ast_value(&ast, *compact))
+            };
+            ast.map(|ast| json!({ "ast": ast }))
+        }
+        Command::Fingerprint { max_in_values, .. } => {
+            crate::fingerprint_sql_impl(sql, dialect, *max_in_values, limits, &timing)
+                .await
+                .map(|fingerprint| json!({ "fingerprint": fingerprint }))
+        }
+        Command::Format { .. } => format_sql(sql, dialect, limits, &timing)
+            .await
+            .map(|sql| json!({ "sql": sql })),
+        Command::Digest { .. } => unreachable!("digest reads all inputs at once"),
+        Command::Lint { .. } => match crate::split_impl(sql, dialect, limits, &timing).await {
+            Ok(statements) => {
+                let count = statements.len();
+                let errors: Vec<_> = statements
+                    .into_iter()
+                    .filter(|statement| statement.error.is_some())
+                    .collect();
+                let code = if errors.is_empty() { 0 } else { EXIT_SQL_ERROR };
+                return (json!({ "statements": count, "errors": errors }), code);
+            }
+            Err(e) => Err(e),
+        },
+    };
+    match result {
+        Ok(value) => (value, 0),
+        Err(e) => {
+            let mut error = json!({ "error": e.to_string() });
+            if let Some(code) = e.code() {
+                error["code"] = code.into();
+            }
+            (error, EXIT_SQL_ERROR)
+        }
+    }
+}
+
+// 所有输入合并为一份报告，以格式化的 JSON 输出
+async fn digest(command: &Command, config: &Config, out: &mut impl Write) -> i32 {
+    let Command::Digest {
+        inputs,
+        log_format,
+        dialect,
+        max_in_values,
+        limit,
+    } = command
+    else {
+        unreachable!("only digest takes no InputArgs");
+    };
+    let params = crate::DigestParams {
+        format: *log_format,
+        dialect: dialect.clone(),
+        max_in_values: *max_in_values,
+        limit: *limit,
+    };
+    let log = expand(inputs).and_then(|sources| {
+        sources
+            .iter()
+            .map(Source::read)
+            .collect::<Result<Vec<_>, _>>()
+            .map(|logs| logs.join("\n"))
+    });
+    let report = match log {
+        Ok(log) => {
+            let limits = Limits::from_config(&config.limits);
+            crate::digest_impl(&log, &params, &limits, &WorkTiming::default()).await
+        }
+        Err(e) => Err(SqlError::Invalid(e)),
+    };
+    match report {
+        Ok(report) => {
+            let _ = writeln!(
+                out,
+                "{}",
+                serde_json::to_string_pretty(&report).unwrap_or_default()
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            EXIT_INPUT_ERROR
+        }
+    }
+}
+
+async fn format_sql(
+    sql: &str,
+    dialect: &str,
+    limits: &Limits,
+    timing: &WorkTiming,
+) -> Result<String, SqlError> {
+    limits.check_sql_length(sql)?;
+    let (sql, dialect, limits_) = (sql.to_string(), dialect.to_string(), limits.clone());
+    limits
+        .run_blocking(timing, move || {
+            let statements = crate::parse_statements(&sql, &dialect, &limits_)?;
+            Ok(statements
+                .iter()
+                .map(|statement| format!("{statement};\n"))
+                .collect())
+        })
+        .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct TestCli {
+        #[command(subcommand)]
+        command: Command,
+    }
+
+    async fn run_args(args: &[&str]) -> (i32, Vec<Value>) {
+        let cli =
+            TestCli::try_parse_from(std::iter::once("sql-ast-api").chain(args.iter().copied()))
+                .unwrap();
+        let mut out = Vec::new();
+        let code = run(&cli.command, &Config::default(), &mut out).await;
+        let lines = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        (code, lines)
+    }
+
+    #[test]
+    fn test_expand_directories_and_globs() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        for name in ["b.sql", "a.sql", "sub/c.sql", "notes.txt"] {
+            std::fs::write(dir.path().join(name), "SELECT 1").unwrap();
+        }
+        let names = |sources: Vec<Source>| {
+            sources
+                .iter()
+                .map(|source| match source {
+                    Source::File(path) => path.strip_prefix(dir.path()).unwrap().to_path_buf(),
+                    Source::Stdin => PathBuf::from("-"),
+                })
+                .collect::<Vec<_>>()
+        };
+        let root = dir.path().to_string_lossy().to_string();
+
+        let sources = expand(&[root.clone(), "-".to_string()]).unwrap();
+        assert_eq!(
+            names(sources),
+            ["a.sql", "b.sql", "sub/c.sql", "-"].map(PathBuf::from)
+        );
+        let sources = expand(&[format!("{root}/*.txt")]).unwrap();
+        assert_eq!(names(sources), [PathBuf::from("notes.txt")]);
+        assert!(expand(&[format!("{root}/*.missing")]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_commands_and_exit_codes() {
+        let dir = tempfile::tempdir().unwrap();
+        let good = dir.path().join("good.sql");
+        let bad = dir.path().join("bad.sql");
+        std::fs::write(&good, "select a from t where b in (1, 2, 3)").unwrap();
+        std::fs::write(&bad, "SELECT 1;\nSELEC 2;").unwrap();
+        let (good, bad) = (good.to_str().unwrap(), bad.to_str().unwrap());
+
+        let (code, lines) = run_args(&["fingerprint", "--max-in-values", "1", good]).await;
+        assert_eq!(code, 0);
+        assert_eq!(lines[0]["fingerprint"], "SELECT a FROM t WHERE b IN (?)");
+        assert_eq!(lines[0]["file"], good);
+
+        let (code, lines) = run_args(&["format", good]).await;
+        assert_eq!(code, 0);
+        assert_eq!(lines[0]["sql"], "SELECT a FROM t WHERE b IN (1, 2, 3);\n");
+
+        let (code, lines) = run_args(&["parse", "--compact", good, bad]).await;
+        assert_eq!(code, EXIT_SQL_ERROR);
+        assert!(lines[0]["ast"][0]["Query"].is_object());
+        assert!(lines[1]["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Failed to parse SQL"));
+
+        let (code, lines) = run_args(&["lint", "-d", "mysql", bad]).await;
+        assert_eq!(code, EXIT_SQL_ERROR);
+        assert_eq!(lines[0]["statements"], 2);
+        assert_eq!(lines[0]["errors"][0]["text"], "SELEC 2");
+        assert_eq!(lines[0]["errors"][0]["start"]["line"], 2);
+
+        let (code, _) = run_args(&["lint", "-d", "oracle", good]).await;
+        assert_eq!(code, EXIT_INPUT_ERROR);
+    }
+
+    #[tokio::test]
+    async fn test_digest_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("slow.log");
+        std::fs::write(
+            &log,
+            "# Time: 2024-01-15T10:23:45Z\n\
+             # Query_time: 1.5  Lock_time: 0.0 Rows_sent: 1  Rows_examined: 100\n\
+             SELECT * FROM t WHERE id = 1;\n\
+             # Query_time: 0.5  Lock_time: 0.0 Rows_sent: 1  Rows_examined: 100\n\
+             select * from t where id = 2;\n",
+        )
+        .unwrap();
+        let log = log.to_str().unwrap();
+
+        let cli = TestCli::try_parse_from(["sql-ast-api", "digest", log]).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(run(&cli.command, &Config::default(), &mut out).await, 0);
+        let report: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(report["format"], "mysql");
+        assert_eq!(
+            report["entries"][0]["fingerprint"],
+            "SELECT * FROM t WHERE id = ?"
+        );
+        assert_eq!(report["entries"][0]["count"], 2);
+        assert_eq!(report["entries"][0]["total_ms"], 2000.0);
+
+        let cli =
+            TestCli::try_parse_from(["sql-ast-api", "digest", "--log-format", "postgresql", log]);
+        let mut out = Vec::new();
+        assert_eq!(
+            run(&cli.unwrap().command, &Config::default(), &mut out).await,
+            0
+        );
+        let report: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(report["queries"], 0);
+    }
+}
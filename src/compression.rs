@@ -0,0 +1,41 @@
+use crate::config::CompressionConfig;
+use axum::Router;
+use tonic::codec::CompressionEncoding;
+use tower_http::compression::predicate::{NotForContentType, Predicate, SizeAbove};
+use tower_http::compression::CompressionLayer;
+use tower_http::decompression::RequestDecompressionLayer;
+
+// gRPC 支持的压缩算法，按 grpc-accept-encoding 协商
+pub const GRPC_ENCODINGS: [CompressionEncoding; 2] =
+    [CompressionEncoding::Gzip, CompressionEncoding::Zstd];
+
+// HTTP 响应按 Accept-Encoding 协商 gzip / br / zstd。
+// gRPC（含 gRPC-Web）响应由 tonic 自行压缩，这里跳过
+pub fn apply_http(router: Router, config: &CompressionConfig) -> Router {
+    let mut router = router;
+    if config.decompress_requests {
+        // 不支持的 Content-Encoding 返回 415
+        router = router.layer(RequestDecompressionLayer::new());
+    }
+    if config.enabled {
+        let predicate = SizeAbove::new(config.min_size)
+            .and(NotForContentType::GRPC)
+            .and(NotForContentType::IMAGES)
+            .and(NotForContentType::SSE);
+        router = router.layer(CompressionLayer::new().compress_when(predicate));
+    }
+    router
+}
+
+// tonic 没有按大小跳过压缩的选项，逐个响应判断
+pub fn grpc_response<T: prost::Message>(
+    message: T,
+    config: &CompressionConfig,
+) -> tonic::Response<T> {
+    let small = message.encoded_len() < usize::from(config.min_size);
+    let mut response = tonic::Response::new(message);
+    if small {
+        response.disable_compression();
+    }
+    response
+}
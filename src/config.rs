@@ -0,0 +1,837 @@
+use crate::cache::PersistMode;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+// 环境变量前缀，例如 SQL_AST_API_SERVER_HTTP_PORT=8080
+pub const ENV_PREFIX: &str = "SQL_AST_API_";
+
+// 优先级：默认值 < 配置文件 < 环境变量 < 命令行参数
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub cache: CacheConfig,
+    pub endpoints: EndpointsConfig,
+    pub limits: LimitsConfig,
+    pub rate_limit: RateLimitConfig,
+    pub compression: CompressionConfig,
+    pub tls: TlsConfig,
+    pub auth: AuthConfig,
+    pub registry: RegistryConfig,
+    pub allowlist: AllowlistConfig,
+    pub catalog: CatalogConfig,
+    pub logging: LoggingConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub http_port: u16,
+    pub grpc_port: u16,
+    // 收到 SIGTERM/SIGINT 后等待进行中请求完成的最长时间（秒）
+    pub shutdown_timeout: u64,
+    // 停止接受新连接前保持“未就绪”的时间（秒），留给负载均衡摘除实例
+    pub shutdown_delay: u64,
+    // 在 HTTP 端口上同时提供 gRPC（按 Content-Type 区分），不再单独监听 grpc_port
+    pub single_port: bool,
+    // 允许跨域调用 HTTP API 与 gRPC-Web 的来源（如 https://app.example.com），"*" 表示任意来源；
+    // 为空时允许任意来源
+    pub cors_allowed_origins: Vec<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            http_port: 3000,
+            grpc_port: 50051,
+            shutdown_timeout: 30,
+            shutdown_delay: 0,
+            single_port: false,
+            cors_allowed_origins: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    pub max_capacity: u64,
+    pub ttl: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+    pub persist_mode: PersistMode,
+    // 磁盘缓存的条目数上限与存活时间（秒），超出时先淘汰最早写入的条目
+    pub disk_max_capacity: u64,
+    pub disk_ttl: u64,
+    pub statement_capacity: u64,
+    pub fingerprint_capacity: u64,
+    pub normalize_keyword_case: bool,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            max_capacity: 10000,
+            ttl: 3600,
+            dir: None,
+            persist_mode: PersistMode::WriteThrough,
+            disk_max_capacity: 100_000,
+            disk_ttl: 7 * 24 * 3600,
+            statement_capacity: 10000,
+            fingerprint_capacity: 10000,
+            normalize_keyword_case: false,
+        }
+    }
+}
+
+// 各接口开关，同时作用于 HTTP 与 gRPC
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EndpointsConfig {
+    pub parse: bool,
+    pub fingerprint: bool,
+    pub tokenize: bool,
+    // 仅 HTTP：POST /split
+    pub split: bool,
+    // 仅 HTTP：GET /fingerprints，需同时启用 registry
+    pub fingerprints: bool,
+    // POST /check 与 CheckSql，需同时启用 allowlist
+    pub check: bool,
+    // 仅 HTTP：POST /digest 上传慢查询日志
+    pub digest: bool,
+    // 仅 HTTP：PUT/GET/DELETE /catalogs/{namespace} 管理表结构目录
+    pub catalogs: bool,
+    // 仅 HTTP：POST /validate 按表结构目录做语义检查
+    pub validate: bool,
+    // 仅 HTTP：POST /expand 按表结构目录展开 SELECT *
+    pub expand: bool,
+    pub swagger_ui: bool,
+    pub web_ui: bool,
+    pub grpc: bool,
+    // gRPC 服务反射（grpcurl list / describe）
+    pub grpc_reflection: bool,
+    // 浏览器通过 gRPC-Web 调用 gRPC 服务
+    pub grpc_web: bool,
+    pub metrics: bool,
+}
+
+impl Default for EndpointsConfig {
+    fn default() -> Self {
+        Self {
+            parse: true,
+            fingerprint: true,
+            tokenize: true,
+            split: true,
+            fingerprints: true,
+            check: true,
+            digest: true,
+            catalogs: true,
+            validate: true,
+            expand: true,
+            swagger_ui: true,
+            web_ui: true,
+            grpc: true,
+            grpc_reflection: true,
+            grpc_web: true,
+            metrics: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    pub max_body_bytes: usize,
+    // 解析器递归深度（sqlparser 的 recursion limit）
+    pub max_nesting_depth: usize,
+    // 解析工作线程数，0 表示使用 CPU 核数
+    pub worker_threads: usize,
+    // 等待工作线程的任务数上限，队列满时直接拒绝
+    pub queue_depth: usize,
+    // 以下各项为 0 表示不限制
+    pub max_sql_length: usize,
+    pub max_statements: usize,
+    pub parse_timeout_ms: u64,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 2 * 1024 * 1024,
+            max_nesting_depth: 50,
+            worker_threads: 0,
+            queue_depth: 1024,
+            max_sql_length: 1024 * 1024,
+            max_statements: 1000,
+            parse_timeout_ms: 10_000,
+        }
+    }
+}
+
+// 限流作用于 parse / fingerprint，HTTP 与 gRPC 共享同一组令牌桶和并发上限
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    // 每个客户端（API Key 调用方或 IP）每秒补充的令牌数，0 表示不限制
+    pub requests_per_second: f64,
+    // 令牌桶容量，即允许的突发请求数
+    pub burst: u32,
+    // 同时进行的解析请求上限，0 表示不限制
+    pub max_concurrency: usize,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 0.0,
+            burst: 20,
+            max_concurrency: 0,
+        }
+    }
+}
+
+// HTTP 按 Accept-Encoding 协商 gzip / br / zstd，gRPC 按 grpc-accept-encoding 协商 gzip / zstd
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    // 小于该字节数的响应不压缩
+    pub min_size: u16,
+    // 解压带 Content-Encoding / grpc-encoding 的请求体。
+    // HTTP 按解压后的大小检查 max_body_bytes，gRPC 则按压缩后的消息大小检查
+    pub decompress_requests: bool,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_size: 1024,
+            decompress_requests: true,
+        }
+    }
+}
+
+// HTTP 与 gRPC 监听共用同一套证书
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cert: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<PathBuf>,
+    // 设置后启用 mTLS，要求客户端证书由该 CA 签发
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_ca: Option<PathBuf>,
+    pub client_auth_optional: bool,
+    // 证书文件变化检测间隔（秒），0 表示不热加载
+    pub reload_interval: u64,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cert: None,
+            key: None,
+            client_ca: None,
+            client_auth_optional: false,
+            reload_interval: 30,
+        }
+    }
+}
+
+// 启用后 /health、/swagger-ui 与 Web 界面之外的接口都需要认证
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub enabled: bool,
+    pub api_keys: Vec<ApiKeyConfig>,
+    pub jwt: JwtConfig,
+    // 配额统计窗口（秒）
+    pub quota_window: u64,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            api_keys: Vec::new(),
+            jwt: JwtConfig::default(),
+            quota_window: 86400,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKeyConfig {
+    pub name: String,
+    pub key: String,
+    // 允许访问的接口（如 parse、fingerprint），为空表示全部
+    #[serde(default)]
+    pub endpoints: Vec<String>,
+    // 每个配额窗口内的请求数上限，0 表示不限制
+    #[serde(default)]
+    pub quota: u64,
+}
+
+// JWT 的 sub 作为调用方名称，scope（空格分隔）或 endpoints 声明允许的接口，quota 声明配额
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JwtConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hs256_secret: Option<String>,
+    // RS256 公钥（JWKS 格式）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwks_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audience: Option<String>,
+    pub default_quota: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+// 指纹登记：记录 /fingerprint 与 GenerateFingerprint 产生的指纹，新指纹首次出现时写日志并调用 webhook
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RegistryConfig {
+    pub enabled: bool,
+    // 达到上限后不再记录新指纹
+    pub max_fingerprints: usize,
+    // 持久化文件（JSON），启动时载入，按 save_interval 及停机时写入
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    // 写入间隔（秒）
+    pub save_interval: u64,
+    // 新指纹事件以 JSON POST 到该地址
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhook_url: Option<String>,
+    // webhook 请求超时（秒）
+    pub webhook_timeout: u64,
+}
+
+impl Default for RegistryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_fingerprints: 100_000,
+            file: None,
+            save_interval: 60,
+            webhook_url: None,
+            webhook_timeout: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AllowlistMode {
+    // 只放行白名单中的指纹
+    #[default]
+    Enforce,
+    // 放行所有查询，并把未登记的指纹追加到白名单文件
+    Learn,
+}
+
+impl AllowlistMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AllowlistMode::Enforce => "enforce",
+            AllowlistMode::Learn => "learn",
+        }
+    }
+}
+
+// 指纹白名单：POST /check 与 CheckSql 按指纹哈希决定放行或拒绝
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AllowlistConfig {
+    pub enabled: bool,
+    // 每行一个指纹哈希，# 之后为注释
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    pub mode: AllowlistMode,
+    // 生成指纹时保留的 IN 列表值个数，需与构建白名单时一致
+    pub max_in_values: usize,
+    // 检查文件修改的间隔（秒），0 表示不自动重新加载
+    pub reload_interval: u64,
+}
+
+impl Default for AllowlistConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            file: None,
+            mode: AllowlistMode::Enforce,
+            max_in_values: 0,
+            reload_interval: 10,
+        }
+    }
+}
+
+// 表结构目录：按命名空间注册，供 POST /validate 与 POST /expand 使用
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CatalogConfig {
+    // 持久化目录，每个命名空间一个 <namespace>.json；未配置时只保存在内存中
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+    pub max_namespaces: usize,
+}
+
+impl Default for CatalogConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            max_namespaces: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    pub level: String,
+    pub format: LogFormat,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            format: LogFormat::Text,
+        }
+    }
+}
+
+impl Config {
+    // 读取配置文件（TOML，或按扩展名识别 YAML）并应用环境变量覆盖，不做校验
+    pub fn load(
+        path: Option<&Path>,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, Vec<String>> {
+        let mut table = match path {
+            Some(path) => read_file(path).map_err(|e| vec![e])?,
+            None => toml::Table::new(),
+        };
+        apply_env(&mut table, env)?;
+
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| vec![format!("invalid configuration: {}", e.message())])
+    }
+
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        if self.server.host.parse::<std::net::IpAddr>().is_err() {
+            errors.push(format!(
+                "server.host: invalid IP address '{}'",
+                self.server.host
+            ));
+        }
+        for origin in &self.server.cors_allowed_origins {
+            let valid = origin == "*"
+                || ((origin.starts_with("http://") || origin.starts_with("https://"))
+                    && axum::http::HeaderValue::from_str(origin).is_ok());
+            if !valid {
+                errors.push(format!(
+                    "server.cors_allowed_origins: expected \"*\" or an http:// or https:// origin, got '{origin}'"
+                ));
+            }
+        }
+        if self.limits.max_body_bytes == 0 {
+            errors.push("limits.max_body_bytes: must be greater than 0".to_string());
+        }
+        if self.limits.max_nesting_depth == 0 {
+            errors.push("limits.max_nesting_depth: must be greater than 0".to_string());
+        }
+        if self.limits.queue_depth == 0 {
+            errors.push("limits.queue_depth: must be greater than 0".to_string());
+        }
+        let rate = self.rate_limit.requests_per_second;
+        if !rate.is_finite() || rate < 0.0 {
+            errors.push(format!(
+                "rate_limit.requests_per_second: must be a non-negative number, got {rate}"
+            ));
+        } else if rate > 0.0 && self.rate_limit.burst == 0 {
+            errors.push("rate_limit.burst: must be greater than 0".to_string());
+        }
+        if self.tls.enabled {
+            for (name, path) in [("tls.cert", &self.tls.cert), ("tls.key", &self.tls.key)] {
+                match path {
+                    None => errors.push(format!("{name}: required when TLS is enabled")),
+                    Some(path) if !path.is_file() => {
+                        errors.push(format!("{name}: file not found: {}", path.display()))
+                    }
+                    Some(_) => {}
+                }
+            }
+            if let Some(path) = self.tls.client_ca.as_ref().filter(|p| !p.is_file()) {
+                errors.push(format!("tls.client_ca: file not found: {}", path.display()));
+            }
+        }
+        if self.auth.enabled {
+            let jwt = &self.auth.jwt;
+            if self.auth.api_keys.is_empty()
+                && jwt.hs256_secret.is_none()
+                && jwt.jwks_file.is_none()
+            {
+                errors.push(
+                    "auth: at least one API key, jwt.hs256_secret or jwt.jwks_file is required"
+                        .to_string(),
+                );
+            }
+            if let Some(path) = jwt.jwks_file.as_ref().filter(|p| !p.is_file()) {
+                errors.push(format!(
+                    "auth.jwt.jwks_file: file not found: {}",
+                    path.display()
+                ));
+            }
+            if self.auth.quota_window == 0 {
+                errors.push("auth.quota_window: must be greater than 0".to_string());
+            }
+        }
+        if self.registry.enabled {
+            let registry = &self.registry;
+            if registry.max_fingerprints == 0 {
+                errors.push("registry.max_fingerprints: must be greater than 0".to_string());
+            }
+            if registry.file.is_some() && registry.save_interval == 0 {
+                errors.push("registry.save_interval: must be greater than 0".to_string());
+            }
+            if let Some(url) = &registry.webhook_url {
+                if !url.starts_with("http://") && !url.starts_with("https://") {
+                    errors.push(format!(
+                        "registry.webhook_url: expected an http:// or https:// URL, got '{url}'"
+                    ));
+                }
+            }
+        }
+        if self.allowlist.enabled {
+            match &self.allowlist.file {
+                None => errors.push("allowlist.file: required when the allowlist is enabled".to_string()),
+                // 学习模式下首次学习时创建
+                Some(path) if self.allowlist.mode == AllowlistMode::Enforce && !path.is_file() => {
+                    errors.push(format!("allowlist.file: file not found: {}", path.display()))
+                }
+                Some(_) => {}
+            }
+        }
+        if self.catalog.max_namespaces == 0 {
+            errors.push("catalog.max_namespaces: must be greater than 0".to_string());
+        }
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(&self.logging.level) {
+            errors.push(format!("logging.level: {e}"));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    // 输出时隐藏密钥
+    pub fn to_toml(&self) -> String {
+        const REDACTED: &str = "<redacted>";
+        let mut config = self.clone();
+        for api_key in &mut config.auth.api_keys {
+            api_key.key = REDACTED.to_string();
+        }
+        if let Some(secret) = &mut config.auth.jwt.hs256_secret {
+            *secret = REDACTED.to_string();
+        }
+        // webhook 地址中常带有令牌
+        if let Some(url) = &mut config.registry.webhook_url {
+            *url = REDACTED.to_string();
+        }
+        toml::to_string_pretty(&config).expect("configuration is always serializable")
+    }
+}
+
+fn read_file(path: &Path) -> Result<toml::Table, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read config file {}: {e}", path.display()))?;
+
+    let is_yaml = matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("yaml" | "yml")
+    );
+    let parsed = if is_yaml {
+        serde_yaml::from_str::<serde_yaml::Value>(&content)
+            .map_err(|e| e.to_string())
+            .and_then(|value| toml::Value::deserialize(value).map_err(|e| e.to_string()))
+    } else {
+        content.parse::<toml::Value>().map_err(|e| e.to_string())
+    };
+
+    match parsed {
+        Ok(toml::Value::Table(table)) => Ok(table),
+        Ok(_) => Err(format!(
+            "config file {} must contain a table",
+            path.display()
+        )),
+        Err(e) => Err(format!(
+            "failed to parse config file {}: {e}",
+            path.display()
+        )),
+    }
+}
+
+// SQL_AST_API_<SECTION>_<KEY>，嵌套表的键依次拼接（如 SQL_AST_API_AUTH_JWT_HS256_SECRET）。
+// 目标字段为字符串，或未出现在默认配置中（均为可选的字符串与路径）时原样作为字符串，
+// 其余按 TOML 字面量解析，失败时作为字符串，由反序列化报告类型错误
+fn apply_env(
+    table: &mut toml::Table,
+    env: impl IntoIterator<Item = (String, String)>,
+) -> Result<(), Vec<String>> {
+    let defaults = toml::Table::try_from(Config::default()).expect("default config serializes");
+
+    let mut errors = Vec::new();
+    for (name, raw) in env {
+        let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let Some((path, default)) = resolve_env_key(&defaults, &key.to_lowercase())
+            .filter(|(path, _)| path.len() > 1)
+        else {
+            errors.push(format!("{name}: unknown configuration section"));
+            continue;
+        };
+
+        let value = match default {
+            None | Some(toml::Value::String(_)) => toml::Value::String(raw),
+            Some(_) => format!("v = {raw}")
+                .parse::<toml::Table>()
+                .ok()
+                .and_then(|mut t| t.remove("v"))
+                .unwrap_or(toml::Value::String(raw)),
+        };
+
+        if let Err(e) = insert_path(table, &path, value) {
+            errors.push(e);
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn insert_path(table: &mut toml::Table, path: &[String], value: toml::Value) -> Result<(), String> {
+    let (field, tables) = path.split_last().expect("path is not empty");
+    let mut target = table;
+    for (depth, name) in tables.iter().enumerate() {
+        match target
+            .entry(name.as_str())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        {
+            toml::Value::Table(nested) => target = nested,
+            _ => return Err(format!("{}: expected a table", tables[..=depth].join("."))),
+        }
+    }
+    target.insert(field.clone(), value);
+    Ok(())
+}
+
+// 按默认配置的结构逐层匹配（键名本身可能含下划线，优先匹配较长的键名），
+// 返回键的路径与默认值；嵌套表中没有匹配的键时，剩余部分整体作为字段名
+fn resolve_env_key<'a>(
+    table: &'a toml::Table,
+    key: &str,
+) -> Option<(Vec<String>, Option<&'a toml::Value>)> {
+    let mut names: Vec<&String> = table.keys().collect();
+    names.sort_by_key(|name| std::cmp::Reverse(name.len()));
+    for name in names {
+        let value = &table[name];
+        if key == name {
+            return Some((vec![name.clone()], Some(value)));
+        }
+        let rest = key.strip_prefix(name.as_str()).and_then(|rest| rest.strip_prefix('_'));
+        if let (Some(rest), toml::Value::Table(nested)) = (rest, value) {
+            let (mut path, default) =
+                resolve_env_key(nested, rest).unwrap_or_else(|| (vec![rest.to_string()], None));
+            path.insert(0, name.clone());
+            return Some((path, default));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_defaults_roundtrip() {
+        let config = Config::load(None, Vec::new()).unwrap();
+        assert_eq!(config.server.http_port, 3000);
+        assert_eq!(config.cache.persist_mode, PersistMode::WriteThrough);
+
+        let reparsed: Config = toml::from_str(&config.to_toml()).unwrap();
+        assert_eq!(reparsed.to_toml(), config.to_toml());
+    }
+
+    #[test]
+    fn test_file_and_env_overrides() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "[server]\nhttp_port = 8080\n\n[cache]\nttl = 60\npersist_mode = \"snapshot\"\n",
+        )
+        .unwrap();
+
+        let config = Config::load(
+            Some(&path),
+            env(&[
+                ("SQL_AST_API_SERVER_HTTP_PORT", "9090"),
+                ("SQL_AST_API_CACHE_DIR", "/var/cache/sql-ast-api"),
+                ("SQL_AST_API_ENDPOINTS_GRPC", "false"),
+                ("UNRELATED", "1"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.server.http_port, 9090);
+        assert_eq!(config.cache.ttl, 60);
+        assert_eq!(config.cache.persist_mode, PersistMode::Snapshot);
+        assert_eq!(
+            config.cache.dir.as_deref(),
+            Some(Path::new("/var/cache/sql-ast-api"))
+        );
+        assert!(!config.endpoints.grpc);
+    }
+
+    #[test]
+    fn test_env_nested_keys_and_string_fields() {
+        let config = Config::load(
+            None,
+            env(&[
+                ("SQL_AST_API_AUTH_JWT_HS256_SECRET", "12345"),
+                ("SQL_AST_API_AUTH_JWT_ISSUER", "true"),
+                ("SQL_AST_API_LOGGING_LEVEL", "1"),
+                ("SQL_AST_API_SERVER_CORS_ALLOWED_ORIGINS", r#"["https://app.example.com"]"#),
+                ("SQL_AST_API_LIMITS_MAX_BODY_BYTES", "1024"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.auth.jwt.hs256_secret.as_deref(), Some("12345"));
+        assert_eq!(config.auth.jwt.issuer.as_deref(), Some("true"));
+        assert_eq!(config.logging.level, "1");
+        assert_eq!(config.server.cors_allowed_origins, ["https://app.example.com"]);
+        assert_eq!(config.limits.max_body_bytes, 1024);
+
+        let errors = Config::load(None, env(&[("SQL_AST_API_AUTH_JWT_ALGORITHM", "HS256")]))
+            .unwrap_err();
+        assert!(errors[0].contains("algorithm"), "{errors:?}");
+        let errors = Config::load(None, env(&[("SQL_AST_API_NOPE_KEY", "1")])).unwrap_err();
+        assert!(errors[0].contains("unknown configuration section"), "{errors:?}");
+    }
+
+    #[test]
+    fn test_yaml_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        std::fs::write(
+            &path,
+            "server:\n  grpc_port: 6000\nlogging:\n  format: json\n",
+        )
+        .unwrap();
+
+        let config = Config::load(Some(&path), Vec::new()).unwrap();
+        assert_eq!(config.server.grpc_port, 6000);
+        assert_eq!(config.logging.format, LogFormat::Json);
+    }
+
+    #[test]
+    fn test_unknown_keys_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[cache]\nttl_seconds = 60\n").unwrap();
+        let errors = Config::load(Some(&path), Vec::new()).unwrap_err();
+        assert!(errors[0].contains("ttl_seconds"), "{errors:?}");
+
+        let errors = Config::load(None, env(&[("SQL_AST_API_CACHE_SIZE", "1")])).unwrap_err();
+        assert!(errors[0].contains("size"), "{errors:?}");
+
+        let errors = Config::load(None, env(&[("SQL_AST_API_BOGUS", "1")])).unwrap_err();
+        assert!(errors[0].contains("SQL_AST_API_BOGUS"), "{errors:?}");
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let config = Config::load(
+            None,
+            env(&[
+                ("SQL_AST_API_SERVER_HOST", "not-an-ip"),
+                ("SQL_AST_API_LIMITS_MAX_BODY_BYTES", "0"),
+                ("SQL_AST_API_REGISTRY_ENABLED", "true"),
+                ("SQL_AST_API_REGISTRY_WEBHOOK_URL", "hooks.example.com"),
+                ("SQL_AST_API_ALLOWLIST_ENABLED", "true"),
+                ("SQL_AST_API_CATALOG_MAX_NAMESPACES", "0"),
+            ]),
+        )
+        .unwrap();
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 5, "{errors:?}");
+        assert!(errors[2].starts_with("registry.webhook_url"), "{errors:?}");
+        assert!(errors[3].starts_with("allowlist.file"), "{errors:?}");
+        assert!(errors[4].starts_with("catalog.max_namespaces"), "{errors:?}");
+
+        let mut config = Config::default();
+        config.server.cors_allowed_origins =
+            ["*", "https://app.example.com", "app.example.com"].map(String::from).to_vec();
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(errors[0].contains("'app.example.com'"), "{errors:?}");
+    }
+
+    #[test]
+    fn test_tls_requires_cert_and_key() {
+        let config = Config::load(None, env(&[("SQL_AST_API_TLS_ENABLED", "true")])).unwrap();
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert!(errors[0].starts_with("tls.cert"), "{errors:?}");
+    }
+
+    #[test]
+    fn test_print_config_redacts_secrets() {
+        let config = Config::load(
+            None,
+            env(&[
+                (
+                    "SQL_AST_API_AUTH_API_KEYS",
+                    r#"[{ name = "batch", key = "s3cret", endpoints = ["parse"] }]"#,
+                ),
+                ("SQL_AST_API_AUTH_ENABLED", "true"),
+                ("SQL_AST_API_REGISTRY_WEBHOOK_URL", "https://hooks.example.com/s3cret"),
+            ]),
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.auth.api_keys[0].key, "s3cret");
+
+        let printed = config.to_toml();
+        assert!(!printed.contains("s3cret"));
+        assert!(printed.contains("<redacted>"));
+    }
+}
@@ -0,0 +1,204 @@
+use crate::catalog::Catalog;
+use crate::validate::{self, Diagnostic, DiagnosticCode, Expansion, Finding};
+use sqlparser::ast::{
+    Query, Select, SelectItem, SetExpr, Spanned, Statement, VisitMut, VisitorMut,
+};
+use sqlparser::dialect::Dialect;
+use std::ops::ControlFlow;
+
+pub struct Expanded {
+    // 按方言重新渲染的 SQL，每条语句以 ;\n 结尾
+    pub sql: String,
+    // 已展开的 * 与 alias.* 个数
+    pub expanded: usize,
+    // 无法展开、保留原样的通配符
+    pub unresolved: Vec<Diagnostic>,
+}
+
+// 按表结构目录把每个查询块中的 * 与 alias.* 展开为限定的列清单，sql 为解析所用的原始输入
+pub fn expand(
+    catalog: &Catalog,
+    mut statements: Vec<Statement>,
+    sql: &str,
+    dialect: &dyn Dialect,
+) -> Expanded {
+    // 只有名称需要引号时才会用到，取该方言的引号（PostgreSQL 为 "，MySQL 为 `）
+    let quote = dialect.identifier_quote_style("").unwrap_or('"');
+    let mut rewriter = Rewriter {
+        expansions: validate::wildcard_expansions(catalog, &statements, quote),
+        expanded: 0,
+        findings: Vec::new(),
+    };
+    let _ = statements.visit(&mut rewriter);
+    Expanded {
+        sql: statements
+            .iter()
+            .map(|statement| format!("{statement};\n"))
+            .collect(),
+        expanded: rewriter.expanded,
+        unresolved: validate::diagnostics(rewriter.findings, sql),
+    }
+}
+
+// 按通配符的位置把分析阶段记录的展开结果替换回 AST；
+// 每个 Query 都会被访问到，这里只处理其自身的 SELECT 与集合运算分支
+struct Rewriter {
+    expansions: Vec<Expansion>,
+    expanded: usize,
+    findings: Vec<Finding>,
+}
+
+impl Rewriter {
+    fn set_expr(&mut self, body: &mut SetExpr) {
+        match body {
+            SetExpr::Select(select) => self.select(select),
+            SetExpr::SetOperation { left, right, .. } => {
+                self.set_expr(left);
+                self.set_expr(right);
+            }
+            _ => {}
+        }
+    }
+
+    fn select(&mut self, select: &mut Select) {
+        let mut projection = Vec::with_capacity(select.projection.len());
+        for item in std::mem::take(&mut select.projection) {
+            let wildcard = match &item {
+                SelectItem::Wildcard(options) | SelectItem::QualifiedWildcard(_, options) => {
+                    options.wildcard_token.0.span
+                }
+                _ => {
+                    projection.push(item);
+                    continue;
+                }
+            };
+            let expansion = self
+                .expansions
+                .iter()
+                .find(|expansion| expansion.wildcard == wildcard);
+            let message = match expansion.map(|expansion| &expansion.items) {
+                Some(Ok(items)) => {
+                    projection.extend(items.iter().cloned());
+                    self.expanded += 1;
+                    continue;
+                }
+                Some(Err(message)) => message.clone(),
+                // 分析器不处理的语句（如 EXPLAIN）
+                None => format!("Cannot expand '{item}' in this statement"),
+            };
+            self.findings.push(Finding {
+                code: DiagnosticCode::UnresolvedWildcard,
+                message,
+                span: item.span(),
+            });
+            projection.push(item);
+        }
+        select.projection = projection;
+    }
+}
+
+impl VisitorMut for Rewriter {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &mut Query) -> ControlFlow<()> {
+        self.set_expr(&mut query.body);
+        ControlFlow::Continue(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::CatalogDefinition;
+    use sqlparser::dialect::{MySqlDialect, PostgreSqlDialect};
+    use sqlparser::parser::Parser;
+
+    fn catalog() -> Catalog {
+        Catalog::new(
+            serde_json::from_value::<CatalogDefinition>(serde_json::json!({
+                "tables": {
+                    "public.users": ["id", "name", "Display Name"],
+                    "orders": ["id", "user_id", "total"],
+                    "items": ["order_id", "sku"]
+                }
+            }))
+            .unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn run(dialect: &dyn Dialect, sql: &str) -> Expanded {
+        let statements = Parser::parse_sql(dialect, sql).unwrap();
+        expand(&catalog(), statements, sql, dialect)
+    }
+
+    fn rewrite(sql: &str) -> String {
+        let expanded = run(&PostgreSqlDialect {}, sql);
+        assert_eq!(expanded.unresolved, vec![], "{sql}");
+        expanded.sql.trim_end().trim_end_matches(';').to_string()
+    }
+
+    #[test]
+    fn test_expand_wildcards() {
+        assert_eq!(
+            rewrite("SELECT * FROM users"),
+            r#"SELECT users.id, users.name, users."Display Name" FROM users"#
+        );
+        assert_eq!(
+            rewrite("SELECT o.*, u.name FROM public.users AS u JOIN orders o ON o.user_id = u.id"),
+            "SELECT o.id, o.user_id, o.total, u.name FROM public.users AS u \
+             JOIN orders AS o ON o.user_id = u.id"
+        );
+        // USING 合并的列只输出一次且不加限定
+        assert_eq!(
+            rewrite(
+                "SELECT * FROM orders JOIN (SELECT order_id AS id, sku FROM items) i USING (id)"
+            ),
+            "SELECT id, orders.user_id, orders.total, i.sku FROM orders \
+             JOIN (SELECT order_id AS id, sku FROM items) AS i USING(id)"
+        );
+        // CTE 与派生表先展开，外层按其输出列展开
+        assert_eq!(
+            rewrite(
+                "WITH big AS (SELECT * FROM orders WHERE total > 100) \
+                 SELECT b.* FROM big b WHERE b.id IN (SELECT order_id FROM (SELECT * FROM items) x)"
+            ),
+            "WITH big AS (SELECT orders.id, orders.user_id, orders.total FROM orders WHERE total > 100) \
+             SELECT b.id, b.user_id, b.total FROM big AS b \
+             WHERE b.id IN (SELECT order_id FROM (SELECT items.order_id, items.sku FROM items) AS x)"
+        );
+        assert_eq!(
+            rewrite("CREATE VIEW v AS SELECT * FROM items i(o, s) UNION SELECT * FROM items"),
+            "CREATE VIEW v AS SELECT i.o, i.s FROM items AS i (o, s) \
+             UNION SELECT items.order_id, items.sku FROM items"
+        );
+    }
+
+    #[test]
+    fn test_unresolved_wildcards() {
+        let sql = "SELECT m.*, i.* FROM missing m JOIN items i ON i.sku = m.sku; SELECT 1";
+        let expanded = run(&MySqlDialect {}, sql);
+        assert_eq!(expanded.expanded, 1);
+        assert_eq!(
+            expanded.sql,
+            "SELECT m.*, i.order_id, i.sku FROM missing AS m JOIN items AS i ON i.sku = m.sku;\n\
+             SELECT 1;\n"
+        );
+        let [unresolved] = expanded.unresolved.as_slice() else {
+            panic!("{:?}", expanded.unresolved);
+        };
+        assert_eq!(unresolved.code, DiagnosticCode::UnresolvedWildcard);
+        assert_eq!(
+            unresolved.message,
+            "Cannot expand 'm.*': columns of 'm' are unknown"
+        );
+        assert_eq!(unresolved.start.as_ref().unwrap().offset, 7);
+
+        // 需要引号的列名按方言加引号
+        let expanded = run(&MySqlDialect {}, "SELECT * FROM users");
+        assert_eq!(
+            expanded.sql,
+            "SELECT users.id, users.name, users.`Display Name` FROM users;\n"
+        );
+    }
+}
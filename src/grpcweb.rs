@@ -0,0 +1,365 @@
+use axum::body::{Body, Bytes};
+use axum::extract::{ConnectInfo, State};
+use axum::http::{header, HeaderMap, HeaderValue, Request, Response, StatusCode, Version};
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use http_body_util::{BodyExt, LengthLimitError, Limited};
+use hyper::body::Frame;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tonic::service::Routes;
+use tonic::transport::server::TcpConnectInfo;
+use tonic::Status;
+use tower::{BoxError, Layer, Service, ServiceExt};
+
+const GRPC_CONTENT_TYPE: &str = "application/grpc";
+// gRPC-Web 把 trailers 编码为响应体中的最后一帧，帧标志最高位为 1
+const TRAILERS_FLAG: u8 = 0x80;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Encoding {
+    Binary,
+    // application/grpc-web-text：请求体与响应体均为 base64
+    Text,
+}
+
+impl Encoding {
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        match content_type(headers)? {
+            "application/grpc-web" | "application/grpc-web+proto" => Some(Encoding::Binary),
+            "application/grpc-web-text" | "application/grpc-web-text+proto" => Some(Encoding::Text),
+            _ => None,
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Encoding::Binary => "application/grpc-web+proto",
+            Encoding::Text => "application/grpc-web-text+proto",
+        }
+    }
+}
+
+fn content_type(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+    value.split(';').next().map(str::trim)
+}
+
+// max_body_bytes 限制需要整体缓冲的 grpc-web-text 请求体；二进制请求体直接交给 tonic，
+// 由其消息大小上限约束
+#[derive(Clone, Copy)]
+pub struct GrpcWebLayer {
+    max_body_bytes: usize,
+}
+
+impl GrpcWebLayer {
+    pub fn new(max_body_bytes: usize) -> Self {
+        Self { max_body_bytes }
+    }
+}
+
+impl<S> Layer<S> for GrpcWebLayer {
+    type Service = GrpcWeb<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcWeb {
+            inner,
+            max_body_bytes: self.max_body_bytes,
+        }
+    }
+}
+
+// 将 gRPC-Web 请求转换为普通 gRPC 请求交给 tonic，响应中的 trailers 再编码回响应体；
+// 其他请求原样转发
+#[derive(Clone)]
+pub struct GrpcWeb<S> {
+    inner: S,
+    max_body_bytes: usize,
+}
+
+impl<S> Service<Request<BoxBody>> for GrpcWeb<S>
+where
+    S: Service<Request<BoxBody>, Response = Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Into<BoxError>,
+{
+    type Response = Response<BoxBody>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: Request<BoxBody>) -> Self::Future {
+        // 使用已经 poll_ready 过的实例，留下一个克隆供下次调用
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let Some(encoding) = Encoding::from_headers(request.headers()) else {
+            let future = inner.call(request);
+            return Box::pin(async move { future.await.map_err(Into::into) });
+        };
+        let max_body_bytes = self.max_body_bytes;
+        Box::pin(async move {
+            let request = match into_grpc_request(request, encoding, max_body_bytes).await {
+                Ok(request) => request,
+                Err(status) => {
+                    let mut response = status.into_http();
+                    response.headers_mut().insert(
+                        header::CONTENT_TYPE,
+                        HeaderValue::from_static(encoding.content_type()),
+                    );
+                    return Ok(response);
+                }
+            };
+            let response = inner.call(request).await.map_err(Into::into)?;
+            Ok(into_grpc_web_response(response, encoding))
+        })
+    }
+}
+
+async fn into_grpc_request(
+    request: Request<BoxBody>,
+    encoding: Encoding,
+    max_body_bytes: usize,
+) -> Result<Request<BoxBody>, Status> {
+    let (mut parts, body) = request.into_parts();
+    parts.version = Version::HTTP_2;
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(GRPC_CONTENT_TYPE),
+    );
+    parts.headers.remove(header::CONTENT_LENGTH);
+
+    let body = match encoding {
+        Encoding::Binary => body,
+        Encoding::Text => {
+            let text = Limited::new(body, max_body_bytes)
+                .collect()
+                .await
+                .map_err(|e| match e.downcast::<LengthLimitError>() {
+                    Ok(_) => Status::resource_exhausted(format!(
+                        "Request body too large (max {max_body_bytes} bytes)"
+                    )),
+                    Err(e) => Status::from_error(e),
+                })?
+                .to_bytes();
+            let decoded = decode_text(&text).map_err(|e| {
+                Status::invalid_argument(format!("Invalid grpc-web-text request body: {e}"))
+            })?;
+            tonic::body::boxed(http_body_util::Full::new(Bytes::from(decoded)))
+        }
+    };
+    Ok(Request::from_parts(parts, body))
+}
+
+// 客户端可能分段编码后拼接，每段各自带填充
+fn decode_text(text: &[u8]) -> Result<Vec<u8>, base64::DecodeError> {
+    let text: Vec<u8> = text
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    let mut decoded = Vec::new();
+    let (mut start, mut i) = (0, 0);
+    while i < text.len() {
+        if text[i] == b'=' {
+            while i < text.len() && text[i] == b'=' {
+                i += 1;
+            }
+            decoded.extend(STANDARD.decode(&text[start..i])?);
+            start = i;
+        } else {
+            i += 1;
+        }
+    }
+    if start < text.len() {
+        decoded.extend(STANDARD.decode(&text[start..])?);
+    }
+    Ok(decoded)
+}
+
+fn into_grpc_web_response(response: Response<BoxBody>, encoding: Encoding) -> Response<BoxBody> {
+    let (mut parts, body) = response.into_parts();
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(encoding.content_type()),
+    );
+    parts.headers.remove(header::CONTENT_LENGTH);
+
+    // 逐帧转换，服务端流式响应（如 Health/Watch）不会被缓冲
+    let body = body.map_frame(move |frame| {
+        let data = match frame.into_data() {
+            Ok(data) => data,
+            Err(frame) => match frame.into_trailers() {
+                Ok(trailers) => encode_trailers(&trailers),
+                Err(_) => Bytes::new(),
+            },
+        };
+        Frame::data(match encoding {
+            Encoding::Binary => data,
+            Encoding::Text => Bytes::from(STANDARD.encode(&data)),
+        })
+    });
+    Response::from_parts(parts, tonic::body::boxed(body))
+}
+
+fn encode_trailers(trailers: &HeaderMap) -> Bytes {
+    let mut block = Vec::new();
+    for (name, value) in trailers {
+        block.extend_from_slice(name.as_str().as_bytes());
+        block.push(b':');
+        block.extend_from_slice(value.as_bytes());
+        block.extend_from_slice(b"\r\n");
+    }
+    let mut frame = Vec::with_capacity(5 + block.len());
+    frame.push(TRAILERS_FLAG);
+    frame.extend_from_slice(&(block.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&block);
+    Bytes::from(frame)
+}
+
+// 单端口模式下的 gRPC 服务
+#[derive(Clone)]
+pub struct Multiplexer {
+    pub routes: Routes,
+    pub grpc_web: Option<GrpcWebLayer>,
+}
+
+fn is_grpc(headers: &HeaderMap) -> bool {
+    content_type(headers).is_some_and(|value| value.starts_with(GRPC_CONTENT_TYPE))
+}
+
+// 单端口模式：Content-Type 为 application/grpc* 的请求交给 tonic，其余请求继续走 HTTP 路由
+pub async fn multiplex(
+    State(grpc): State<Multiplexer>,
+    request: axum::extract::Request,
+    next: Next,
+) -> axum::response::Response {
+    if !is_grpc(request.headers()) {
+        return next.run(request).await;
+    }
+    let mut request = request.map(tonic::body::boxed);
+    // tonic 通过 TcpConnectInfo 取得客户端地址（用于按 IP 限流）
+    if let Some(ConnectInfo(addr)) = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .copied()
+    {
+        request.extensions_mut().insert(TcpConnectInfo {
+            local_addr: None,
+            remote_addr: Some(addr),
+        });
+    }
+    let result = match grpc.grpc_web {
+        Some(layer) => layer.layer(grpc.routes).oneshot(request).await,
+        None => grpc.routes.oneshot(request).await,
+    };
+    match result {
+        Ok(response) => response.map(Body::new),
+        Err(e) => {
+            tracing::error!("gRPC request failed: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+
+    // 回显请求体，并以 grpc-status: 0 结束
+    async fn echo(request: Request<BoxBody>) -> Result<Response<BoxBody>, Infallible> {
+        assert_eq!(content_type(request.headers()), Some(GRPC_CONTENT_TYPE));
+        let body = request.into_body().collect().await.unwrap().to_bytes();
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", HeaderValue::from_static("0"));
+        let frames = [
+            Ok::<_, Status>(Frame::data(body)),
+            Ok(Frame::trailers(trailers)),
+        ];
+        let body = http_body_util::StreamBody::new(tokio_stream::iter(frames));
+        Ok(Response::new(tonic::body::boxed(body)))
+    }
+
+    async fn call(content_type: &str, body: &'static [u8]) -> (String, Bytes) {
+        let request = Request::post("/svc/Method")
+            .header(header::CONTENT_TYPE, content_type)
+            .body(tonic::body::boxed(http_body_util::Full::new(Bytes::from(
+                body,
+            ))))
+            .unwrap();
+        let response = GrpcWebLayer::new(1024)
+            .layer(tower::service_fn(echo))
+            .oneshot(request)
+            .await
+            .unwrap();
+        let content_type = content_type_of(&response);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (content_type, body)
+    }
+
+    fn content_type_of(response: &Response<BoxBody>) -> String {
+        content_type(response.headers())
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_binary_trailers_are_encoded_in_body() {
+        let message = b"\x00\x00\x00\x00\x02hi";
+        let (content_type, body) = call("application/grpc-web+proto", message).await;
+        assert_eq!(content_type, "application/grpc-web+proto");
+        assert_eq!(&body[..7], message);
+        assert_eq!(&body[7..], b"\x80\x00\x00\x00\x0fgrpc-status:0\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_text_mode_uses_base64() {
+        // 两段分别编码的请求体
+        let (content_type, body) = call("application/grpc-web-text", b"AAAAAAI=aGk=").await;
+        assert_eq!(content_type, "application/grpc-web-text+proto");
+        let decoded = decode_text(&body).unwrap();
+        assert_eq!(&decoded[..7], b"\x00\x00\x00\x00\x02hi");
+        assert_eq!(decoded[7], TRAILERS_FLAG);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_text_body_is_rejected() {
+        let request = Request::post("/svc/Method")
+            .header(header::CONTENT_TYPE, "application/grpc-web-text")
+            .body(tonic::body::boxed(http_body_util::Full::new(
+                Bytes::from_static(b"%%%"),
+            )))
+            .unwrap();
+        let response = GrpcWebLayer::new(1024)
+            .layer(tower::service_fn(echo))
+            .oneshot(request)
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["grpc-status"], "3");
+    }
+
+    #[tokio::test]
+    async fn test_text_body_is_limited() {
+        let request = Request::post("/svc/Method")
+            .header(header::CONTENT_TYPE, "application/grpc-web-text")
+            .body(tonic::body::boxed(http_body_util::Full::new(Bytes::from(
+                "AAAA".repeat(300),
+            ))))
+            .unwrap();
+        let response = GrpcWebLayer::new(1024)
+            .layer(tower::service_fn(echo))
+            .oneshot(request)
+            .await
+            .unwrap();
+        // RESOURCE_EXHAUSTED
+        assert_eq!(response.headers()["grpc-status"], "8");
+    }
+}
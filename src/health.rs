@@ -0,0 +1,116 @@
+use std::sync::Arc;
+use tokio::sync::watch;
+use tonic_health::server::HealthReporter;
+use tonic_health::ServingStatus;
+
+// 就绪状态：缓存预热期间与停机排空期间不接收新流量，进程本身仍然存活
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Readiness {
+    Ready,
+    WarmingUp,
+    Draining,
+}
+
+impl Readiness {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Readiness::Ready => "ready",
+            Readiness::WarmingUp => "warming_up",
+            Readiness::Draining => "draining",
+        }
+    }
+
+    pub fn is_ready(self) -> bool {
+        self == Readiness::Ready
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct State {
+    warming_up: bool,
+    draining: bool,
+}
+
+impl State {
+    fn readiness(self) -> Readiness {
+        if self.draining {
+            Readiness::Draining
+        } else if self.warming_up {
+            Readiness::WarmingUp
+        } else {
+            Readiness::Ready
+        }
+    }
+}
+
+pub struct Health {
+    state: watch::Sender<State>,
+}
+
+impl Health {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            state: watch::Sender::new(State::default()),
+        })
+    }
+
+    pub fn readiness(&self) -> Readiness {
+        self.state.borrow().readiness()
+    }
+
+    pub fn set_warming_up(&self, warming_up: bool) {
+        self.state
+            .send_modify(|state| state.warming_up = warming_up);
+    }
+
+    // 排空开始后不再恢复就绪
+    pub fn start_draining(&self) {
+        self.state.send_modify(|state| state.draining = true);
+    }
+
+    // 将就绪状态同步到标准 grpc.health.v1 服务：整体状态（""）与给定的各个服务
+    pub fn spawn_grpc_reporter(&self, mut reporter: HealthReporter, services: &[&'static str]) {
+        let mut state = self.state.subscribe();
+        let services: Vec<&'static str> = std::iter::once("")
+            .chain(services.iter().copied())
+            .collect();
+        tokio::spawn(async move {
+            loop {
+                let status = if state.borrow_and_update().readiness().is_ready() {
+                    ServingStatus::Serving
+                } else {
+                    ServingStatus::NotServing
+                };
+                for service in &services {
+                    reporter.set_service_status(*service, status).await;
+                }
+                if state.changed().await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readiness_transitions() {
+        let health = Health::new();
+        assert_eq!(health.readiness(), Readiness::Ready);
+
+        health.set_warming_up(true);
+        assert_eq!(health.readiness(), Readiness::WarmingUp);
+        health.set_warming_up(false);
+        assert!(health.readiness().is_ready());
+
+        // 排空优先于预热
+        health.set_warming_up(true);
+        health.start_draining();
+        assert_eq!(health.readiness(), Readiness::Draining);
+        health.set_warming_up(false);
+        assert_eq!(health.readiness().as_str(), "draining");
+    }
+}
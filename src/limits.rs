@@ -0,0 +1,148 @@
+use crate::config::LimitsConfig;
+use crate::pool::{WorkTiming, WorkerPool};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+// 超出限制的请求不写入缓存，并返回各自的错误码
+#[derive(Clone, Debug, PartialEq)]
+pub enum LimitError {
+    BodyTooLarge,
+    SqlTooLong { length: usize, max: usize },
+    TooManyStatements { count: usize, max: usize },
+    NestingTooDeep { max: usize },
+    ParseTimeout { timeout: Duration },
+    QueueFull { depth: usize },
+}
+
+impl LimitError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            LimitError::BodyTooLarge => "body_too_large",
+            LimitError::SqlTooLong { .. } => "sql_too_long",
+            LimitError::TooManyStatements { .. } => "too_many_statements",
+            LimitError::NestingTooDeep { .. } => "nesting_too_deep",
+            LimitError::ParseTimeout { .. } => "parse_timeout",
+            LimitError::QueueFull { .. } => "queue_full",
+        }
+    }
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitError::BodyTooLarge => write!(f, "Request body too large"),
+            LimitError::SqlTooLong { length, max } => {
+                write!(f, "SQL is too long: {length} bytes (max {max})")
+            }
+            LimitError::TooManyStatements { count, max } => {
+                write!(f, "Too many statements: {count} (max {max})")
+            }
+            LimitError::NestingTooDeep { max } => {
+                write!(f, "SQL is nested too deeply (max depth {max})")
+            }
+            LimitError::ParseTimeout { timeout } => {
+                write!(f, "Parsing timed out after {}ms", timeout.as_millis())
+            }
+            LimitError::QueueFull { depth } => {
+                write!(f, "Server is busy: parse queue is full ({depth} pending)")
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Limits {
+    pub max_sql_length: usize,
+    pub max_statements: usize,
+    pub max_nesting_depth: usize,
+    pub parse_timeout: Option<Duration>,
+    pub pool: Arc<WorkerPool>,
+}
+
+impl Limits {
+    pub fn from_config(config: &LimitsConfig) -> Self {
+        Self {
+            max_sql_length: config.max_sql_length,
+            max_statements: config.max_statements,
+            max_nesting_depth: config.max_nesting_depth,
+            parse_timeout: (config.parse_timeout_ms > 0)
+                .then(|| Duration::from_millis(config.parse_timeout_ms)),
+            pool: WorkerPool::new(config.worker_threads, config.queue_depth),
+        }
+    }
+
+    pub fn check_sql_length(&self, sql: &str) -> Result<(), LimitError> {
+        if self.max_sql_length > 0 && sql.len() > self.max_sql_length {
+            return Err(LimitError::SqlTooLong {
+                length: sql.len(),
+                max: self.max_sql_length,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn check_statement_count(&self, count: usize) -> Result<(), LimitError> {
+        if self.max_statements > 0 && count > self.max_statements {
+            return Err(LimitError::TooManyStatements {
+                count,
+                max: self.max_statements,
+            });
+        }
+        Ok(())
+    }
+
+    // 在工作线程池中执行，超时（含排队时间）由 parse_timeout 控制
+    pub async fn run_blocking<T, F>(&self, timing: &WorkTiming, f: F) -> Result<T, LimitError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        self.pool.run(timing, self.parse_timeout, f).await
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::from_config(&LimitsConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_disables_limits() {
+        let limits = Limits::from_config(&LimitsConfig {
+            max_sql_length: 0,
+            max_statements: 0,
+            parse_timeout_ms: 0,
+            ..LimitsConfig::default()
+        });
+        assert!(limits.check_sql_length(&"x".repeat(10_000_000)).is_ok());
+        assert!(limits.check_statement_count(usize::MAX).is_ok());
+        assert!(limits.parse_timeout.is_none());
+
+        let limits = Limits::default();
+        let err = limits.check_statement_count(1001).unwrap_err();
+        assert_eq!(err.code(), "too_many_statements");
+        assert_eq!(err.to_string(), "Too many statements: 1001 (max 1000)");
+    }
+
+    #[tokio::test]
+    async fn test_run_blocking_timeout() {
+        let limits = Limits {
+            parse_timeout: Some(Duration::from_millis(100)),
+            ..Limits::default()
+        };
+        let timing = WorkTiming::default();
+        assert_eq!(limits.run_blocking(&timing, || 42).await, Ok(42));
+
+        let err = limits
+            .run_blocking(&timing, || std::thread::sleep(Duration::from_millis(500)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "parse_timeout");
+    }
+}
@@ -1,12 +1,40 @@
+mod allowlist;
+mod ast_json;
+mod ast_proto;
+mod auth;
+mod cache;
+mod catalog;
+mod cli;
+mod compression;
+mod config;
+mod expand;
+mod grpcweb;
+mod health;
+mod limits;
+mod metrics;
+mod output;
+mod pool;
+mod ratelimit;
+mod registry;
+mod source;
+mod slowlog;
+mod split;
+mod tls;
+mod tokenize;
+mod validate;
+
 use axum::{
-    extract::{Json, State},
-    http::StatusCode,
+    body::Bytes,
+    extract::{
+        rejection::{JsonRejection, QueryRejection},
+        DefaultBodyLimit, FromRequest, FromRequestParts, Json, Path, Query, State,
+    },
+    http::{request::Parts, StatusCode},
     response::{Html, IntoResponse, Response},
-    routing::{get, post},
+    routing::{get, post, put},
     Router,
 };
 use clap::Parser as ClapParser;
-use moka::future::Cache;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use sqlparser::ast::{Expr, Statement, Value, VisitMut, VisitorMut};
@@ -14,23 +42,55 @@ use sqlparser::dialect::{
     AnsiDialect, Dialect, GenericDialect, HiveDialect, MsSqlDialect, MySqlDialect,
     PostgreSqlDialect, SQLiteDialect, SnowflakeDialect,
 };
-use sqlparser::parser::Parser;
+use sqlparser::keywords::Keyword;
+use sqlparser::parser::{Parser, ParserError};
+use sqlparser::tokenizer::{Token, Tokenizer, Whitespace};
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tonic::{transport::Server, Request, Response as TonicResponse, Status};
-use tower_http::cors::CorsLayer;
-use utoipa::{OpenApi, ToSchema};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tower_http::trace::TraceLayer;
+use utoipa::{IntoParams, OpenApi, ToSchema};
 use utoipa_swagger_ui::SwaggerUi;
 
+use allowlist::Allowlist;
+use auth::Authenticator;
+use cache::{
+    build_cache, try_get_or_compute, AstCache, Caches, DiskCache, PersistMode, StatementsValue,
+};
+use catalog::{Catalog, CatalogDefinition, CatalogError, CatalogStore};
+use config::{AllowlistMode, CompressionConfig, Config, EndpointsConfig, LogFormat};
+use grpcweb::{GrpcWebLayer, Multiplexer};
+use health::Health;
+use limits::{LimitError, Limits};
+use output::OutputFormat;
+use pool::WorkTiming;
+use ratelimit::RateLimiter;
+use registry::FingerprintRegistry;
+use source::{LineIndex, SourceCursor};
+use tls::{ClientIdentity, ReloadableTls};
+use tokenize::{SqlToken, TokenError, TokenList};
+
 // Include generated gRPC code
 pub mod sql_parser {
     tonic::include_proto!("sql_parser");
+
+    // oneof 中的消息大小差异较大，生成代码不逐一装箱
+    #[allow(clippy::large_enum_variant)]
+    pub mod ast {
+        tonic::include_proto!("sql_parser.ast");
+    }
+
+    pub const FILE_DESCRIPTOR_SET: &[u8] =
+        tonic::include_file_descriptor_set!("sql_parser_descriptor");
 }
 
 type CacheKey = (String, String);
-type CacheValue = Result<serde_json::Value, String>;
+// AST 以 MessagePack 形式缓存，见 output::encode_ast
+type CacheValue = Result<Bytes, String>;
 
 // 全局 Dialect 缓存，避免重复创建
 static DIALECTS: Lazy<HashMap<&'static str, Arc<dyn Dialect + Send + Sync>>> = Lazy::new(|| {
@@ -82,39 +142,202 @@ static DIALECTS: Lazy<HashMap<&'static str, Arc<dyn Dialect + Send + Sync>>> = L
 #[command(name = "sql-ast-api")]
 #[command(about = "SQL to AST API server", long_about = None)]
 struct CliArgs {
-    #[arg(long, default_value = "127.0.0.1", help = "Server host")]
-    host: String,
+    // 不带子命令时启动服务
+    #[command(subcommand)]
+    command: Option<cli::Command>,
+
+    #[arg(long, help = "Configuration file (TOML, or YAML by extension)")]
+    config: Option<PathBuf>,
+
+    #[arg(long, help = "Print the effective configuration and exit")]
+    print_config: bool,
+
+    #[arg(long, help = "Server host [default: 127.0.0.1]")]
+    host: Option<String>,
+
+    #[arg(short, long, help = "HTTP server port [default: 3000]")]
+    port: Option<u16>,
+
+    #[arg(long, help = "gRPC server port [default: 50051]")]
+    grpc_port: Option<u16>,
+
+    #[arg(long, help = "Maximum cache capacity (number of entries) [default: 10000]")]
+    cache_max_capacity: Option<u64>,
+
+    #[arg(long, help = "Cache TTL in seconds (time to live) [default: 3600]")]
+    cache_ttl: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Directory for the persistent cache tier (disabled when not set)"
+    )]
+    cache_dir: Option<PathBuf>,
+
+    #[arg(long, value_enum, help = "Persistent cache mode [default: write-through]")]
+    cache_persist_mode: Option<PersistMode>,
+
+    #[arg(long, help = "Maximum number of entries in the persistent cache [default: 100000]")]
+    cache_disk_max_capacity: Option<u64>,
+
+    #[arg(long, help = "Persistent cache TTL in seconds [default: 604800]")]
+    cache_disk_ttl: Option<u64>,
 
-    #[arg(short, long, default_value_t = 3000, help = "HTTP server port")]
-    port: u16,
+    #[arg(
+        long,
+        help = "Maximum number of cached parsed statement lists shared by parse and fingerprint [default: 10000]"
+    )]
+    statement_cache_capacity: Option<u64>,
 
-    #[arg(long, default_value_t = 50051, help = "gRPC server port")]
-    grpc_port: u16,
+    #[arg(
+        long,
+        help = "Maximum number of cached fingerprint results [default: 10000]"
+    )]
+    fingerprint_cache_capacity: Option<u64>,
 
     #[arg(
         long,
-        default_value_t = 10000,
-        help = "Maximum cache capacity (number of entries)"
+        help = "Uppercase keywords when building fingerprint cache keys (identifiers that are also keywords then share entries across case variants)"
     )]
-    cache_max_capacity: u64,
+    normalize_keyword_case: bool,
+
+    #[arg(long, help = "Per-client request rate limit in requests per second, 0 to disable [default: 0]")]
+    rate_limit_rps: Option<f64>,
+
+    #[arg(long, help = "Per-client burst size for the rate limit [default: 20]")]
+    rate_limit_burst: Option<u32>,
+
+    #[arg(long, help = "Maximum concurrent parse requests across HTTP and gRPC, 0 for unlimited [default: 0]")]
+    max_concurrency: Option<usize>,
+
+    #[arg(long, help = "Serve gRPC and gRPC-Web on the HTTP port instead of a separate gRPC port")]
+    single_port: bool,
+
+    #[arg(long, help = "Seconds to wait for in-flight requests on shutdown [default: 30]")]
+    shutdown_timeout: Option<u64>,
 
     #[arg(
         long,
-        default_value_t = 3600,
-        help = "Cache TTL in seconds (time to live)"
+        requires = "tls_key",
+        help = "PEM certificate chain; enables TLS on both listeners together with --tls-key"
     )]
-    cache_ttl: u64,
+    tls_cert: Option<PathBuf>,
+
+    #[arg(long, requires = "tls_cert", help = "PEM private key for --tls-cert")]
+    tls_key: Option<PathBuf>,
+
+    #[arg(long, help = "PEM CA bundle used to verify client certificates (enables mTLS)")]
+    tls_client_ca: Option<PathBuf>,
+
+    #[arg(long, help = "Log level or filter directive [default: info]")]
+    log_level: Option<String>,
+
+    #[arg(long, value_enum, help = "Log format [default: text]")]
+    log_format: Option<LogFormat>,
+}
+
+impl CliArgs {
+    // 命令行参数优先级最高
+    fn apply_to(&self, config: &mut Config) {
+        if let Some(host) = &self.host {
+            config.server.host = host.clone();
+        }
+        if let Some(port) = self.port {
+            config.server.http_port = port;
+        }
+        if let Some(port) = self.grpc_port {
+            config.server.grpc_port = port;
+        }
+        if self.single_port {
+            config.server.single_port = true;
+        }
+        if let Some(timeout) = self.shutdown_timeout {
+            config.server.shutdown_timeout = timeout;
+        }
+        if let Some(capacity) = self.cache_max_capacity {
+            config.cache.max_capacity = capacity;
+        }
+        if let Some(ttl) = self.cache_ttl {
+            config.cache.ttl = ttl;
+        }
+        if let Some(dir) = &self.cache_dir {
+            config.cache.dir = Some(dir.clone());
+        }
+        if let Some(mode) = self.cache_persist_mode {
+            config.cache.persist_mode = mode;
+        }
+        if let Some(capacity) = self.cache_disk_max_capacity {
+            config.cache.disk_max_capacity = capacity;
+        }
+        if let Some(ttl) = self.cache_disk_ttl {
+            config.cache.disk_ttl = ttl;
+        }
+        if let Some(capacity) = self.statement_cache_capacity {
+            config.cache.statement_capacity = capacity;
+        }
+        if let Some(capacity) = self.fingerprint_cache_capacity {
+            config.cache.fingerprint_capacity = capacity;
+        }
+        if self.normalize_keyword_case {
+            config.cache.normalize_keyword_case = true;
+        }
+        if let Some(rps) = self.rate_limit_rps {
+            config.rate_limit.requests_per_second = rps;
+        }
+        if let Some(burst) = self.rate_limit_burst {
+            config.rate_limit.burst = burst;
+        }
+        if let Some(max) = self.max_concurrency {
+            config.rate_limit.max_concurrency = max;
+        }
+        if let (Some(cert), Some(key)) = (&self.tls_cert, &self.tls_key) {
+            config.tls.enabled = true;
+            config.tls.cert = Some(cert.clone());
+            config.tls.key = Some(key.clone());
+        }
+        if let Some(ca) = &self.tls_client_ca {
+            config.tls.client_ca = Some(ca.clone());
+        }
+        if let Some(level) = &self.log_level {
+            config.logging.level = level.clone();
+        }
+        if let Some(format) = self.log_format {
+            config.logging.format = format;
+        }
+    }
+
+    fn load_config(&self) -> Result<Config, Vec<String>> {
+        let mut config = Config::load(self.config.as_deref(), std::env::vars())?;
+        self.apply_to(&mut config);
+        config.validate()?;
+        Ok(config)
+    }
 }
 
 #[derive(Clone)]
 struct AppState {
-    cache: Cache<CacheKey, CacheValue>,
+    caches: Caches,
+    limits: Limits,
+    auth: Option<Arc<Authenticator>>,
+    limiter: Option<Arc<RateLimiter>>,
+    health: Arc<Health>,
+    registry: Option<Arc<FingerprintRegistry>>,
+    allowlist: Option<Arc<Allowlist>>,
+    catalogs: Arc<CatalogStore>,
 }
 
 #[derive(OpenApi)]
 #[openapi(
-    paths(parse_sql, fingerprint_sql, health_check),
-    components(schemas(SqlRequest, SqlResponse, ErrorResponse, HealthResponse, FingerprintRequest, FingerprintResponse))
+    paths(parse_sql, fingerprint_sql, list_fingerprints, check_sql, tokenize_sql, split_sql, digest_log, put_catalog, get_catalog, delete_catalog, validate_sql, expand_sql, health_check, health_live, health_ready),
+    components(schemas(
+        SqlRequest, SqlResponse, ErrorResponse, HealthResponse, FingerprintRequest, FingerprintResponse,
+        TokenizeRequest, TokenizeResponse, SqlToken, TokenError, tokenize::TokenKind, tokenize::Position,
+        SplitRequest, SplitResponse, SplitStatement, DigestResponse, slowlog::DigestReport,
+        slowlog::DigestEntry, slowlog::SlowLogFormat, FingerprintsResponse, registry::FingerprintEntry,
+        registry::SortKey, registry::SortOrder, CheckRequest, CheckResponse, CheckDecision,
+        CatalogRequest, CatalogResponse, CatalogDetail, CatalogDefinition, ValidateRequest,
+        ValidateResponse, ExpandRequest, ExpandResponse, validate::Diagnostic,
+        validate::DiagnosticCode
+    ))
 )]
 struct ApiDoc;
 
@@ -130,6 +353,32 @@ struct SqlRequest {
     #[serde(default)]
     #[schema(example = false, default = false)]
     no_cache: bool,
+
+    // 去掉 AST 中值为 null、false、空数组或空对象的字段
+    #[serde(default)]
+    #[schema(example = false, default = false)]
+    compact: bool,
+
+    // 为语句、表达式、表引用与标识符附加相对原始输入的起止位置；不使用缓存
+    #[serde(default)]
+    #[schema(example = false, default = false)]
+    with_spans: bool,
+
+    // 按语句边界切分后逐条解析，失败的语句在 ast 中以 {"error": {...}} 占位
+    #[serde(default)]
+    #[schema(example = false, default = false)]
+    recover: bool,
+}
+
+impl SqlRequest {
+    fn options(&self) -> ParseOptions {
+        ParseOptions {
+            no_cache: self.no_cache,
+            compact: self.compact,
+            with_spans: self.with_spans,
+            recover: self.recover,
+        }
+    }
 }
 
 fn default_dialect() -> String {
@@ -145,6 +394,15 @@ struct SqlResponse {
     #[schema(example = false)]
     cached: Option<bool>,
 
+    // 在解析线程池中排队等待与执行的耗时，命中缓存时省略
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 0.012)]
+    queue_ms: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 1.105)]
+    parse_ms: Option<f64>,
+
     #[schema(example = 1.234)]
     elapsed_ms: f64,
 }
@@ -154,6 +412,11 @@ struct ErrorResponse {
     #[schema(example = "Failed to parse SQL: sql parser error: ...")]
     error: String,
 
+    // 超出服务端限制时的错误码，如 sql_too_long、too_many_statements、nesting_too_deep、parse_timeout
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "sql_too_long")]
+    code: Option<String>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schema(example = 0.123)]
     elapsed_ms: Option<f64>,
@@ -161,6 +424,7 @@ struct ErrorResponse {
 
 #[derive(Serialize, ToSchema)]
 struct HealthResponse {
+    // 就绪检查时为 ready、warming_up 或 draining
     #[schema(example = "ok")]
     status: String,
 
@@ -180,6 +444,10 @@ struct FingerprintRequest {
     #[serde(default)]
     #[schema(example = 3, default = 0)]
     max_in_values: usize,
+
+    #[serde(default)]
+    #[schema(example = false, default = false)]
+    no_cache: bool,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -187,192 +455,1026 @@ struct FingerprintResponse {
     #[schema(example = "SELECT * FROM users WHERE id = ? AND name = ? AND age IN (?, ?, ?)")]
     fingerprint: String,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = false)]
+    cached: Option<bool>,
+
+    // 在解析线程池中排队等待与执行的耗时，命中缓存时省略
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 0.012)]
+    queue_ms: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 1.105)]
+    parse_ms: Option<f64>,
+
     #[schema(example = 1.234)]
     elapsed_ms: f64,
 }
 
-enum ApiResponse {
-    Success(SqlResponse),
-    Error(ErrorResponse),
-}
+#[derive(Deserialize, ToSchema)]
+struct TokenizeRequest {
+    #[schema(example = "SELECT name FROM users WHERE id = 1 -- comment")]
+    sql: String,
 
-impl IntoResponse for ApiResponse {
-    fn into_response(self) -> Response {
-        match self {
-            ApiResponse::Success(response) => (StatusCode::OK, Json(response)).into_response(),
-            ApiResponse::Error(error) => (StatusCode::BAD_REQUEST, Json(error)).into_response(),
-        }
-    }
+    #[serde(default = "default_dialect")]
+    #[schema(example = "mysql", default = "generic")]
+    dialect: String,
 }
 
-enum FingerprintApiResponse {
-    Success(FingerprintResponse),
-    Error(ErrorResponse),
+#[derive(Serialize, ToSchema)]
+struct TokenizeResponse {
+    tokens: Vec<SqlToken>,
+
+    // 分词中途失败时返回，tokens 为出错位置之前的部分结果
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<TokenError>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 0.012)]
+    queue_ms: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 0.105)]
+    parse_ms: Option<f64>,
+
+    #[schema(example = 0.234)]
+    elapsed_ms: f64,
 }
 
-impl IntoResponse for FingerprintApiResponse {
-    fn into_response(self) -> Response {
-        match self {
-            FingerprintApiResponse::Success(response) => (StatusCode::OK, Json(response)).into_response(),
-            FingerprintApiResponse::Error(error) => (StatusCode::BAD_REQUEST, Json(error)).into_response(),
-        }
-    }
+#[derive(Deserialize, ToSchema)]
+struct SplitRequest {
+    #[schema(example = "CREATE TABLE t (a INT);\n-- 初始数据\nINSERT INTO t VALUES (1);")]
+    sql: String,
+
+    #[serde(default = "default_dialect")]
+    #[schema(example = "mysql", default = "generic")]
+    dialect: String,
 }
 
-fn get_dialect(dialect_name: &str) -> Result<Arc<dyn Dialect + Send + Sync>, String> {
-    DIALECTS
-        .get(dialect_name.to_lowercase().as_str())
-        .cloned()
-        .ok_or_else(|| {
-            format!(
-                "Unsupported dialect: {dialect_name}. Supported dialects: generic, mysql, postgresql, sqlite, hive, snowflake, mssql, ansi"
-            )
-        })
+#[derive(Serialize, ToSchema)]
+struct SplitStatement {
+    // 原文片段（含语句前的注释），与 [start.offset, end.offset) 对应
+    #[schema(example = "-- 初始数据\nINSERT INTO t VALUES (1)")]
+    text: String,
+    start: tokenize::Position,
+    end: tokenize::Position,
+
+    // 解析成功时为语句种类，即 AST 中的变体名，如 Query、Insert、CreateTable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "Insert")]
+    kind: Option<String>,
+
+    // 该语句解析失败的原因，不影响其他语句
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
 }
 
-// SQL 规范化，提高缓存命中率
-fn normalize_sql(sql: &str) -> String {
-    sql.split_whitespace().collect::<Vec<_>>().join(" ")
+#[derive(Serialize, ToSchema)]
+struct SplitResponse {
+    statements: Vec<SplitStatement>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 0.012)]
+    queue_ms: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 0.405)]
+    parse_ms: Option<f64>,
+
+    #[schema(example = 0.534)]
+    elapsed_ms: f64,
 }
 
-// SQL指纹生成器：将字面量替换为?
-struct FingerprintVisitor {
-    max_in_values: usize,
+#[derive(Deserialize, ToSchema)]
+struct CheckRequest {
+    #[schema(example = "SELECT * FROM users WHERE id = 1")]
+    sql: String,
+
+    #[serde(default = "default_dialect")]
+    #[schema(example = "mysql", default = "generic")]
+    dialect: String,
 }
 
-impl VisitorMut for FingerprintVisitor {
-    type Break = ();
+#[derive(Serialize, ToSchema)]
+struct CheckDecision {
+    allowed: bool,
 
-    fn pre_visit_expr(&mut self, expr: &mut Expr) -> std::ops::ControlFlow<Self::Break> {
-        match expr {
-            // 将所有字面量值替换为占位符
-            Expr::Value(Value::Number(_, _))
-            | Expr::Value(Value::SingleQuotedString(_))
-            | Expr::Value(Value::DoubleQuotedString(_))
-            | Expr::Value(Value::NationalStringLiteral(_))
-            | Expr::Value(Value::HexStringLiteral(_))
-            | Expr::Value(Value::Boolean(_)) => {
-                *expr = Expr::Value(Value::Placeholder("?".to_string()));
-            }
-            // 处理IN表达式，限制列表长度
-            Expr::InList { expr: inner_expr, list, negated: _ } => {
-                // 先递归处理内部表达式
-                let _ = self.pre_visit_expr(inner_expr);
-                
-                // 限制IN列表的长度
-                if self.max_in_values > 0 && list.len() > self.max_in_values {
-                    list.truncate(self.max_in_values);
-                }
-                
-                // 将列表中的每个表达式转换为占位符
-                for item in list.iter_mut() {
-                    let _ = self.pre_visit_expr(item);
-                }
-                
-                return std::ops::ControlFlow::Continue(());
-            }
-            _ => {}
-        }
-        std::ops::ControlFlow::Continue(())
-    }
+    // enforce 或 learn
+    #[schema(example = "enforce")]
+    mode: &'static str,
+
+    // SQL 无法解析时省略，原因见 error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "SELECT * FROM users WHERE id = ?")]
+    fingerprint: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "6f540be5517aaffe1774bebe9a2c0eba835e11cd8e1b07ea44046ae795008704")]
+    hash: Option<String>,
+
+    // 学习模式下本次新加入白名单
+    learned: bool,
+
+    // 解析失败的原因；强制模式下无法解析的 SQL 一律拒绝
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+
+    cached: bool,
 }
 
-fn generate_sql_fingerprint(mut statements: Vec<Statement>, max_in_values: usize) -> String {
-    let mut visitor = FingerprintVisitor { max_in_values };
-    
-    for stmt in statements.iter_mut() {
-        let _ = stmt.visit(&mut visitor);
-    }
-    
-    statements
-        .iter()
-        .map(|stmt| stmt.to_string())
-        .collect::<Vec<_>>()
-        .join("; ")
+#[derive(Serialize, ToSchema)]
+struct CheckResponse {
+    #[serde(flatten)]
+    decision: CheckDecision,
+
+    #[schema(example = 0.087)]
+    elapsed_ms: f64,
 }
 
-async fn parse_sql_impl(sql: &str, dialect_name: &str) -> CacheValue {
-    let dialect = match get_dialect(dialect_name) {
-        Ok(d) => d,
-        Err(e) => return Err(e),
-    };
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct FingerprintsParams {
+    #[serde(default)]
+    #[param(inline)]
+    sort: registry::SortKey,
 
-    match Parser::parse_sql(&*dialect, sql) {
-        Ok(ast) => match serde_json::to_value(&ast) {
-            Ok(json_ast) => Ok(json_ast),
-            Err(e) => Err(format!("Failed to serialize AST: {e}")),
-        },
-        Err(e) => Err(format!("Failed to parse SQL: {e}")),
-    }
+    #[serde(default)]
+    #[param(inline)]
+    order: registry::SortOrder,
+
+    #[serde(default)]
+    #[param(default = 0)]
+    offset: usize,
+
+    // 每页条数，最大 1000
+    #[serde(default = "default_page_size")]
+    #[param(default = 50, maximum = 1000)]
+    limit: usize,
 }
 
-#[utoipa::path(
-    post,
-    path = "/parse",
-    request_body = SqlRequest,
-    responses(
-        (status = 200, description = "SQL parsed successfully", body = SqlResponse),
-        (status = 400, description = "Invalid SQL or unsupported dialect", body = ErrorResponse)
-    ),
-    tag = "SQL Parser"
-)]
-async fn parse_sql(State(state): State<AppState>, Json(payload): Json<SqlRequest>) -> ApiResponse {
-    let start = Instant::now();
+const MAX_PAGE_SIZE: usize = 1000;
 
-    // SQL 规范化，提高缓存命中率
-    let normalized_sql = if payload.no_cache {
-        payload.sql.clone()
-    } else {
-        normalize_sql(&payload.sql)
-    };
+fn default_page_size() -> usize {
+    50
+}
 
-    let cache_key = (normalized_sql.clone(), payload.dialect.clone());
+#[derive(Serialize, ToSchema)]
+struct FingerprintsResponse {
+    // 登记的指纹总数
+    total: usize,
+    offset: usize,
+    limit: usize,
+    fingerprints: Vec<registry::FingerprintEntry>,
+}
 
-    // 如果未禁用缓存，先尝试从缓存获取
-    if !payload.no_cache {
-        if let Some(cached_result) = state.cache.get(&cache_key).await {
-            let elapsed = start.elapsed().as_secs_f64() * 1000.0;
-            match cached_result {
-                Ok(ast) => {
-                    return ApiResponse::Success(SqlResponse {
-                        ast,
-                        cached: Some(true),
-                        elapsed_ms: elapsed,
-                    });
-                }
-                Err(e) => {
-                    return ApiResponse::Error(ErrorResponse {
-                        error: e,
-                        elapsed_ms: Some(elapsed),
-                    });
-                }
-            }
-        }
-    }
+#[derive(Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DigestParams {
+    // 日志格式，auto 时根据内容识别
+    #[serde(default)]
+    #[param(inline)]
+    format: slowlog::SlowLogFormat,
 
-    let result = parse_sql_impl(&normalized_sql, &payload.dialect).await;
-    let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+    // 缺省时 MySQL 日志按 mysql、PostgreSQL 日志按 postgresql 方言解析
+    #[param(example = "mysql")]
+    dialect: Option<String>,
 
-    // 只有在未禁用缓存时才存入缓存
-    if !payload.no_cache {
-        state.cache.insert(cache_key, result.clone()).await;
-    }
+    #[serde(default)]
+    #[param(default = 0)]
+    max_in_values: usize,
 
-    match result {
-        Ok(ast) => ApiResponse::Success(SqlResponse {
-            ast,
-            cached: Some(false),
-            elapsed_ms: elapsed,
-        }),
-        Err(e) => ApiResponse::Error(ErrorResponse {
-            error: e,
-            elapsed_ms: Some(elapsed),
-        }),
-    }
+    // 只返回总耗时最高的前 N 个指纹，0 表示全部
+    #[serde(default = "default_digest_limit")]
+    #[param(default = 20)]
+    limit: usize,
 }
 
-#[utoipa::path(
-    post,
+fn default_digest_limit() -> usize {
+    20
+}
+
+#[derive(Serialize, ToSchema)]
+struct DigestResponse {
+    #[serde(flatten)]
+    report: slowlog::DigestReport,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 0.012)]
+    queue_ms: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 85.3)]
+    parse_ms: Option<f64>,
+
+    #[schema(example = 92.1)]
+    elapsed_ms: f64,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CatalogRequest {
+    // CREATE TABLE / CREATE VIEW 脚本，与 tables 二选一；其他语句忽略
+    #[schema(example = "CREATE TABLE users (id INT PRIMARY KEY, name TEXT, email TEXT);")]
+    ddl: Option<String>,
+
+    // 解析 ddl 所用的方言
+    #[serde(default = "default_dialect")]
+    #[schema(example = "postgresql", default = "generic")]
+    dialect: String,
+
+    #[serde(flatten)]
+    definition: CatalogDefinition,
+}
+
+#[derive(Serialize, ToSchema)]
+struct CatalogResponse {
+    #[schema(example = "app")]
+    namespace: String,
+
+    #[schema(example = 3)]
+    tables: usize,
+
+    #[schema(example = 12)]
+    columns: usize,
+
+    functions: usize,
+
+    // ddl 中被忽略的语句数（CREATE INDEX 等）
+    skipped: usize,
+
+    // 替换了已注册的同名命名空间
+    replaced: bool,
+
+    #[schema(example = 0.812)]
+    elapsed_ms: f64,
+}
+
+#[derive(Serialize, ToSchema)]
+struct CatalogDetail {
+    #[schema(example = "app")]
+    namespace: String,
+
+    #[serde(flatten)]
+    definition: CatalogDefinition,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ValidateRequest {
+    #[schema(example = "SELECT nmae FROM users u JOIN orders o ON o.user_id = u.id")]
+    sql: String,
+
+    #[serde(default = "default_dialect")]
+    #[schema(example = "postgresql", default = "generic")]
+    dialect: String,
+
+    // PUT /catalogs/{namespace} 注册的命名空间
+    #[schema(example = "app")]
+    namespace: String,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ValidateResponse {
+    // 没有任何诊断
+    valid: bool,
+
+    diagnostics: Vec<validate::Diagnostic>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 0.012)]
+    queue_ms: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 0.205)]
+    parse_ms: Option<f64>,
+
+    #[schema(example = 0.334)]
+    elapsed_ms: f64,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ExpandRequest {
+    #[schema(example = "SELECT o.*, u.name FROM orders o JOIN users u ON u.id = o.user_id")]
+    sql: String,
+
+    // 解析与渲染所用的方言
+    #[serde(default = "default_dialect")]
+    #[schema(example = "postgresql", default = "generic")]
+    dialect: String,
+
+    // PUT /catalogs/{namespace} 注册的命名空间
+    #[schema(example = "app")]
+    namespace: String,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ExpandResponse {
+    // 展开后重新渲染的 SQL，每条语句以 ;\n 结尾
+    #[schema(example = "SELECT o.id, o.user_id, o.total, u.name FROM orders AS o JOIN users AS u ON u.id = o.user_id;\n")]
+    sql: String,
+
+    // 已展开的 * 与 alias.* 个数
+    #[schema(example = 1)]
+    expanded: usize,
+
+    // 无法展开、原样保留的通配符
+    unresolved: Vec<validate::Diagnostic>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 0.012)]
+    queue_ms: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 0.205)]
+    parse_ms: Option<f64>,
+
+    #[schema(example = 0.334)]
+    elapsed_ms: f64,
+}
+
+// 成功与错误响应都按 Accept 协商出的格式编码
+struct ApiResponse {
+    format: OutputFormat,
+    result: Result<SqlResponse, (StatusCode, ErrorResponse)>,
+}
+
+impl IntoResponse for ApiResponse {
+    fn into_response(self) -> Response {
+        match self.result {
+            Ok(response) => self.format.respond(StatusCode::OK, &response),
+            Err((status, error)) => self.format.respond(status, &error),
+        }
+    }
+}
+
+// JSON 接口的响应：成功时为 200 与响应体，失败时为对应的状态码与 ErrorResponse
+enum ApiResult<T> {
+    Success(T),
+    Error(StatusCode, ErrorResponse),
+}
+
+impl<T: Serialize> IntoResponse for ApiResult<T> {
+    fn into_response(self) -> Response {
+        match self {
+            ApiResult::Success(response) => (StatusCode::OK, Json(response)).into_response(),
+            ApiResult::Error(status, error) => (status, Json(error)).into_response(),
+        }
+    }
+}
+
+// 解析失败的原因：SQL 本身无效（结果可缓存），或超出服务端限制（不缓存）
+#[derive(Clone, Debug, PartialEq)]
+enum SqlError {
+    Invalid(String),
+    Limit(LimitError),
+}
+
+impl From<String> for SqlError {
+    fn from(e: String) -> Self {
+        SqlError::Invalid(e)
+    }
+}
+
+impl From<LimitError> for SqlError {
+    fn from(e: LimitError) -> Self {
+        SqlError::Limit(e)
+    }
+}
+
+impl std::fmt::Display for SqlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SqlError::Invalid(e) => f.write_str(e),
+            SqlError::Limit(e) => e.fmt(f),
+        }
+    }
+}
+
+impl SqlError {
+    fn code(&self) -> Option<&'static str> {
+        match self {
+            SqlError::Invalid(_) => None,
+            SqlError::Limit(e) => Some(e.code()),
+        }
+    }
+
+    fn into_error_response(self, elapsed_ms: Option<f64>) -> (StatusCode, ErrorResponse) {
+        let status = match &self {
+            SqlError::Limit(LimitError::BodyTooLarge | LimitError::SqlTooLong { .. }) => {
+                StatusCode::PAYLOAD_TOO_LARGE
+            }
+            SqlError::Limit(LimitError::QueueFull { .. }) => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        let error = ErrorResponse {
+            error: self.to_string(),
+            code: self.code().map(str::to_string),
+            elapsed_ms,
+        };
+        (status, error)
+    }
+}
+
+// 与 Json 相同，但请求体超出 limits.max_body_bytes 时返回带错误码的 JSON
+struct ApiJson<T>(T);
+
+#[axum::async_trait]
+impl<S, T> FromRequest<S> for ApiJson<T>
+where
+    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(request: axum::extract::Request, state: &S) -> Result<Self, Self::Rejection> {
+        match Json::<T>::from_request(request, state).await {
+            Ok(Json(value)) => Ok(ApiJson(value)),
+            Err(rejection) if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                let (status, error) = SqlError::Limit(LimitError::BodyTooLarge).into_error_response(None);
+                Err((status, Json(error)).into_response())
+            }
+            Err(rejection) => Err(rejection.into_response()),
+        }
+    }
+}
+
+// 原始文本请求体（日志上传），非 UTF-8 字节按替换字符处理；超出限制时同 ApiJson
+struct TextBody(String);
+
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequest<S> for TextBody {
+    type Rejection = Response;
+
+    async fn from_request(request: axum::extract::Request, state: &S) -> Result<Self, Self::Rejection> {
+        match Bytes::from_request(request, state).await {
+            Ok(body) => Ok(TextBody(String::from_utf8_lossy(&body).into_owned())),
+            Err(rejection) if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                let (status, error) = SqlError::Limit(LimitError::BodyTooLarge).into_error_response(None);
+                Err((status, Json(error)).into_response())
+            }
+            Err(rejection) => Err(rejection.into_response()),
+        }
+    }
+}
+
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for OutputFormat {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        OutputFormat::negotiate(&parts.headers).ok_or_else(|| {
+            let error = ErrorResponse {
+                error: "Unsupported Accept header, expected application/json, \
+                        application/msgpack or application/cbor"
+                    .to_string(),
+                code: Some("not_acceptable".to_string()),
+                elapsed_ms: None,
+            };
+            (StatusCode::NOT_ACCEPTABLE, Json(error)).into_response()
+        })
+    }
+}
+
+fn get_dialect(dialect_name: &str) -> Result<Arc<dyn Dialect + Send + Sync>, String> {
+    DIALECTS
+        .get(dialect_name.to_lowercase().as_str())
+        .cloned()
+        .ok_or_else(|| {
+            format!(
+                "Unsupported dialect: {dialect_name}. Supported dialects: generic, mysql, postgresql, sqlite, hive, snowflake, mssql, ansi"
+            )
+        })
+}
+
+// SQL 规范化，提高缓存命中率：只折叠字面量、引号标识符和注释之外的空白，
+// 可选地将关键字统一为大写。无法分词时原样返回。
+fn normalize_sql(sql: &str, dialect_name: &str, fold_keyword_case: bool) -> String {
+    let Ok(dialect) = get_dialect(dialect_name) else {
+        return sql.to_string();
+    };
+    let Ok(tokens) = Tokenizer::new(&*dialect, sql).tokenize_with_location() else {
+        return sql.to_string();
+    };
+
+    let mut cursor = SourceCursor::new(sql);
+    let starts: Vec<usize> = tokens.iter().map(|t| cursor.offset_of(t.span.start)).collect();
+
+    let mut normalized = String::with_capacity(sql.len());
+    let mut pending_space = false;
+    for (i, token) in tokens.iter().enumerate() {
+        match &token.token {
+            Token::Whitespace(Whitespace::Space | Whitespace::Newline | Whitespace::Tab) => {
+                pending_space = true;
+                continue;
+            }
+            Token::EOF => continue,
+            _ => {}
+        }
+        if pending_space && !normalized.is_empty() {
+            normalized.push(' ');
+        }
+        pending_space = false;
+
+        // 直接截取原文，保证字面量、注释的内容不变
+        let end = starts.get(i + 1).copied().unwrap_or(sql.len());
+        let text = &sql[starts[i]..end];
+        match &token.token {
+            Token::Word(word)
+                if fold_keyword_case
+                    && word.quote_style.is_none()
+                    && word.keyword != Keyword::NoKeyword =>
+            {
+                normalized.push_str(&text.to_uppercase())
+            }
+            _ => normalized.push_str(text),
+        }
+    }
+    normalized
+}
+
+// SQL指纹生成器：将字面量替换为?
+struct FingerprintVisitor {
+    max_in_values: usize,
+}
+
+impl VisitorMut for FingerprintVisitor {
+    type Break = ();
+
+    fn pre_visit_expr(&mut self, expr: &mut Expr) -> std::ops::ControlFlow<Self::Break> {
+        match expr {
+            // 将所有字面量值替换为占位符
+            Expr::Value(Value::Number(_, _))
+            | Expr::Value(Value::SingleQuotedString(_))
+            | Expr::Value(Value::DoubleQuotedString(_))
+            | Expr::Value(Value::NationalStringLiteral(_))
+            | Expr::Value(Value::HexStringLiteral(_))
+            | Expr::Value(Value::Boolean(_)) => {
+                *expr = Expr::Value(Value::Placeholder("?".to_string()));
+            }
+            // 处理IN表达式，限制列表长度
+            Expr::InList { expr: inner_expr, list, negated: _ } => {
+                // 先递归处理内部表达式
+                let _ = self.pre_visit_expr(inner_expr);
+                
+                // 限制IN列表的长度
+                if self.max_in_values > 0 && list.len() > self.max_in_values {
+                    list.truncate(self.max_in_values);
+                }
+                
+                // 将列表中的每个表达式转换为占位符
+                for item in list.iter_mut() {
+                    let _ = self.pre_visit_expr(item);
+                }
+                
+                return std::ops::ControlFlow::Continue(());
+            }
+            _ => {}
+        }
+        std::ops::ControlFlow::Continue(())
+    }
+}
+
+fn generate_sql_fingerprint(mut statements: Vec<Statement>, max_in_values: usize) -> String {
+    let mut visitor = FingerprintVisitor { max_in_values };
+    
+    for stmt in statements.iter_mut() {
+        let _ = stmt.visit(&mut visitor);
+    }
+    
+    statements
+        .iter()
+        .map(|stmt| stmt.to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn parse_statements(sql: &str, dialect_name: &str, limits: &Limits) -> Result<Vec<Statement>, SqlError> {
+    let dialect = get_dialect(dialect_name)?;
+    let statements = Parser::new(&*dialect)
+        .with_recursion_limit(limits.max_nesting_depth)
+        .try_with_sql(sql)
+        .and_then(|mut parser| parser.parse_statements())
+        .map_err(|e| match e {
+            ParserError::RecursionLimitExceeded => SqlError::Limit(LimitError::NestingTooDeep {
+                max: limits.max_nesting_depth,
+            }),
+            e => SqlError::Invalid(format!("Failed to parse SQL: {e}")),
+        })?;
+    limits.check_statement_count(statements.len())?;
+    Ok(statements)
+}
+
+fn serialize_statements(statements: &[Statement]) -> CacheValue {
+    let value = ast_json::to_value(statements).map_err(|e| format!("Failed to serialize AST: {e}"))?;
+    output::encode_ast(&value).map_err(|e| format!("Failed to serialize AST: {e}"))
+}
+
+// 缓存的 AST 解码为 JSON 结构，可选去掉默认值字段
+fn ast_value(ast: &[u8], compact: bool) -> Result<serde_json::Value, SqlError> {
+    let mut value =
+        output::decode_ast(ast).map_err(|e| SqlError::Invalid(format!("Failed to decode AST: {e}")))?;
+    if compact {
+        output::compact(&mut value);
+    }
+    Ok(value)
+}
+
+async fn parse_sql_impl(
+    sql: &str,
+    dialect_name: &str,
+    limits: &Limits,
+    timing: &WorkTiming,
+) -> Result<Bytes, SqlError> {
+    let (sql, dialect_name, limits_) = (sql.to_string(), dialect_name.to_string(), limits.clone());
+    limits
+        .run_blocking(timing, move || {
+            Ok(serialize_statements(&parse_statements(&sql, &dialect_name, &limits_)?)?)
+        })
+        .await?
+}
+
+// /parse 与 ParseSql（JSON 格式）的可选项
+#[derive(Clone, Copy, Debug, Default)]
+struct ParseOptions {
+    no_cache: bool,
+    compact: bool,
+    with_spans: bool,
+    recover: bool,
+}
+
+// 返回 JSON 结构的 AST 以及是否命中缓存
+async fn parse_json(
+    caches: &Caches,
+    limits: &Limits,
+    timing: &WorkTiming,
+    sql: &str,
+    dialect_name: &str,
+    options: ParseOptions,
+) -> (Result<serde_json::Value, SqlError>, bool) {
+    if options.recover {
+        parse_recovering(caches, limits, timing, sql, dialect_name, options).await
+    } else {
+        parse_json_statements(caches, limits, timing, sql, dialect_name, options).await
+    }
+}
+
+async fn parse_json_statements(
+    caches: &Caches,
+    limits: &Limits,
+    timing: &WorkTiming,
+    sql: &str,
+    dialect_name: &str,
+    options: ParseOptions,
+) -> (Result<serde_json::Value, SqlError>, bool) {
+    if options.with_spans {
+        let result = parse_with_spans(limits, timing, sql, dialect_name, options.compact).await;
+        return (result, false);
+    }
+    let (result, cached) =
+        parse_with_cache(caches, limits, timing, sql, dialect_name, options.no_cache).await;
+    (result.and_then(|ast| ast_value(&ast, options.compact)), cached)
+}
+
+#[derive(Serialize)]
+struct RecoveredError {
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'static str>,
+    text: String,
+    start: tokenize::Position,
+    end: tokenize::Position,
+}
+
+// recover：按 /split 的规则切分，每条语句单独解析（各自使用缓存），
+// 失败的语句以 {"error": {...}} 占位。全部语句命中缓存时 cached 为 true。
+async fn parse_recovering(
+    caches: &Caches,
+    limits: &Limits,
+    timing: &WorkTiming,
+    sql: &str,
+    dialect_name: &str,
+    options: ParseOptions,
+) -> (Result<serde_json::Value, SqlError>, bool) {
+    let prepared = async {
+        limits.check_sql_length(sql)?;
+        let dialect = get_dialect(dialect_name)?;
+        let text = sql.to_string();
+        let ranges = limits
+            .run_blocking(timing, move || split::split(&*dialect, &text))
+            .await?;
+        limits.check_statement_count(ranges.len())?;
+        Ok::<_, SqlError>(ranges)
+    };
+    let ranges = match prepared.await {
+        Ok(ranges) => ranges,
+        Err(e) => return (Err(e), false),
+    };
+
+    let lines = LineIndex::new(sql);
+    let mut items = Vec::with_capacity(ranges.len());
+    let mut all_cached = !ranges.is_empty();
+    for range in ranges {
+        let text = &sql[range.clone()];
+        let start = source_position(&lines, range.start);
+        let (result, cached) =
+            parse_json_statements(caches, limits, timing, text, dialect_name, options).await;
+        all_cached &= cached;
+        match result {
+            Ok(serde_json::Value::Array(mut statements)) => {
+                if options.with_spans {
+                    statements
+                        .iter_mut()
+                        .for_each(|statement| ast_json::shift_spans(statement, start));
+                }
+                items.extend(statements);
+            }
+            Ok(other) => items.push(other),
+            Err(e) => items.push(serde_json::json!({
+                "error": RecoveredError {
+                    message: e.to_string(),
+                    code: e.code(),
+                    text: text.to_string(),
+                    start,
+                    end: source_position(&lines, range.end),
+                }
+            })),
+        }
+    }
+    (Ok(serde_json::Value::Array(items)), all_cached)
+}
+
+fn source_position(lines: &LineIndex, offset: usize) -> tokenize::Position {
+    let location = lines.location_of(offset);
+    tokenize::Position {
+        line: location.line,
+        column: location.column,
+        offset,
+    }
+}
+
+// 位置相对请求中的原始 SQL，因此不经过规范化与缓存
+async fn parse_with_spans(
+    limits: &Limits,
+    timing: &WorkTiming,
+    sql: &str,
+    dialect_name: &str,
+    compact: bool,
+) -> Result<serde_json::Value, SqlError> {
+    limits.check_sql_length(sql)?;
+    let (sql, dialect_name, limits_) = (sql.to_string(), dialect_name.to_string(), limits.clone());
+    limits
+        .run_blocking(timing, move || {
+            let statements = parse_statements(&sql, &dialect_name, &limits_)?;
+            let mut value = ast_json::to_value_with_spans(&statements, &sql)
+                .map_err(|e| SqlError::Invalid(format!("Failed to serialize AST: {e}")))?;
+            if compact {
+                output::compact(&mut value);
+            }
+            Ok(value)
+        })
+        .await?
+}
+
+// 规范化需要对整个输入分词，与解析一样在线程池中执行；
+// 耗时只计入 /metrics，不计入响应的 queue_ms / parse_ms（命中缓存时仍省略）。
+// AST 与语句缓存中的标识符保持原始大小写，不能折叠关键字，只有指纹缓存键可以折叠
+async fn cache_key(
+    limits: &Limits,
+    sql: &str,
+    dialect_name: &str,
+    fold: bool,
+) -> Result<CacheKey, LimitError> {
+    let (sql, dialect_name) = (sql.to_string(), dialect_name.to_string());
+    limits
+        .run_blocking(&WorkTiming::default(), move || {
+            (normalize_sql(&sql, &dialect_name, fold), dialect_name)
+        })
+        .await
+}
+
+// 解析结果在 parse 与 fingerprint 之间共享；超出限制的请求不缓存。
+// 规范化后的文本只用作缓存键，未命中时解析原始 SQL，标识符保持原样
+async fn cached_statements(
+    caches: &Caches,
+    limits: &Limits,
+    timing: &WorkTiming,
+    key: CacheKey,
+    sql: &str,
+) -> Result<(StatementsValue, bool), LimitError> {
+    let (sql, dialect) = (sql.to_string(), key.1.clone());
+    let limits_ = limits.clone();
+    try_get_or_compute(&caches.statements, key, async move {
+        let parsed = limits
+            .run_blocking(timing, move || parse_statements(&sql, &dialect, &limits_))
+            .await?;
+        match parsed {
+            Ok(statements) => Ok(Arc::new(Ok(statements))),
+            Err(SqlError::Invalid(e)) => Ok(Arc::new(Err(e))),
+            Err(SqlError::Limit(e)) => Err(e),
+        }
+    })
+    .await
+}
+
+// 返回解析结果以及是否命中缓存；在线程池中的耗时记录到 timing
+async fn parse_with_cache(
+    caches: &Caches,
+    limits: &Limits,
+    timing: &WorkTiming,
+    sql: &str,
+    dialect_name: &str,
+    no_cache: bool,
+) -> (Result<Bytes, SqlError>, bool) {
+    if let Err(e) = limits.check_sql_length(sql) {
+        return (Err(e.into()), false);
+    }
+    if no_cache {
+        return (parse_sql_impl(sql, dialect_name, limits, timing).await, false);
+    }
+
+    // SQL 规范化，提高缓存命中率
+    let cache_key = match cache_key(limits, sql, dialect_name, false).await {
+        Ok(key) => key,
+        Err(e) => return (Err(e.into()), false),
+    };
+    if let Some(cached_result) = caches.ast.get(&cache_key).await {
+        return (cached_result.map_err(SqlError::Invalid), true);
+    }
+
+    let statements = match cached_statements(caches, limits, timing, cache_key.clone(), sql).await {
+        Ok((statements, _)) => statements,
+        Err(e) => return (Err(e.into()), false),
+    };
+    // 序列化同样在线程池中执行
+    let serialized = limits
+        .run_blocking(timing, move || match &*statements {
+            Ok(statements) => serialize_statements(statements),
+            Err(e) => Err(e.clone()),
+        })
+        .await;
+    let result = match serialized {
+        Ok(result) => result,
+        Err(e) => return (Err(e.into()), false),
+    };
+    caches.ast.insert(cache_key, result.clone()).await;
+    (result.map_err(SqlError::Invalid), false)
+}
+
+// 类型化 AST（gRPC 的 AST_FORMAT_PROTO）：与 JSON 格式共享语句缓存，转换在线程池中执行
+async fn parse_proto_with_cache(
+    caches: &Caches,
+    limits: &Limits,
+    timing: &WorkTiming,
+    sql: &str,
+    dialect_name: &str,
+    no_cache: bool,
+) -> (Result<Vec<sql_parser::ast::Statement>, SqlError>, bool) {
+    if let Err(e) = limits.check_sql_length(sql) {
+        return (Err(e.into()), false);
+    }
+    if no_cache {
+        let (sql, dialect_name, limits_) =
+            (sql.to_string(), dialect_name.to_string(), limits.clone());
+        let result = limits
+            .run_blocking(timing, move || {
+                Ok(ast_proto::statements(&parse_statements(&sql, &dialect_name, &limits_)?))
+            })
+            .await;
+        return (result.unwrap_or_else(|e| Err(e.into())), false);
+    }
+
+    let cache_key = match cache_key(limits, sql, dialect_name, false).await {
+        Ok(key) => key,
+        Err(e) => return (Err(e.into()), false),
+    };
+    let (statements, cached) = match cached_statements(caches, limits, timing, cache_key, sql).await {
+        Ok(computed) => computed,
+        Err(e) => return (Err(e.into()), false),
+    };
+    let converted = limits
+        .run_blocking(timing, move || match &*statements {
+            Ok(statements) => Ok(ast_proto::statements(statements)),
+            Err(e) => Err(e.clone()),
+        })
+        .await;
+    match converted {
+        Ok(result) => (result.map_err(SqlError::Invalid), cached),
+        Err(e) => (Err(e.into()), false),
+    }
+}
+
+async fn fingerprint_sql_impl(
+    sql: &str,
+    dialect_name: &str,
+    max_in_values: usize,
+    limits: &Limits,
+    timing: &WorkTiming,
+) -> Result<String, SqlError> {
+    let (sql, dialect_name, limits_) = (sql.to_string(), dialect_name.to_string(), limits.clone());
+    limits
+        .run_blocking(timing, move || {
+            let statements = parse_statements(&sql, &dialect_name, &limits_)?;
+            Ok(generate_sql_fingerprint(statements, max_in_values))
+        })
+        .await?
+}
+
+async fn fingerprint_with_cache(
+    caches: &Caches,
+    limits: &Limits,
+    timing: &WorkTiming,
+    sql: &str,
+    dialect_name: &str,
+    max_in_values: usize,
+    no_cache: bool,
+) -> (Result<String, SqlError>, bool) {
+    if let Err(e) = limits.check_sql_length(sql) {
+        return (Err(e.into()), false);
+    }
+    if no_cache {
+        return (
+            fingerprint_sql_impl(sql, dialect_name, max_in_values, limits, timing).await,
+            false,
+        );
+    }
+
+    let statements_key = match cache_key(limits, sql, dialect_name, false).await {
+        Ok(key) => key,
+        Err(e) => return (Err(e.into()), false),
+    };
+    let normalized = if caches.fold_keyword_case {
+        match cache_key(limits, sql, dialect_name, true).await {
+            Ok((normalized, _)) => normalized,
+            Err(e) => return (Err(e.into()), false),
+        }
+    } else {
+        statements_key.0.clone()
+    };
+    let cache_key = (normalized, statements_key.1.clone(), max_in_values);
+    let computed = try_get_or_compute(&caches.fingerprints, cache_key, async move {
+        let (statements, _) = cached_statements(caches, limits, timing, statements_key, sql).await?;
+        limits
+            .run_blocking(timing, move || match &*statements {
+                Ok(statements) => Ok(generate_sql_fingerprint(statements.clone(), max_in_values)),
+                Err(e) => Err(e.clone()),
+            })
+            .await
+    })
+    .await;
+    match computed {
+        Ok((result, cached)) => (result.map_err(SqlError::Invalid), cached),
+        Err(e) => (Err(e.into()), false),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/parse",
+    request_body = SqlRequest,
+    responses(
+        (status = 200, description = "SQL parsed successfully; encoding negotiated via Accept", content(
+            (SqlResponse = "application/json"),
+            (SqlResponse = "application/msgpack"),
+            (SqlResponse = "application/cbor")
+        )),
+        (status = 400, description = "Invalid SQL or unsupported dialect", body = ErrorResponse),
+        (status = 406, description = "None of the accepted media types is supported", body = ErrorResponse)
+    ),
+    tag = "SQL Parser"
+)]
+async fn parse_sql(
+    State(state): State<AppState>,
+    format: OutputFormat,
+    ApiJson(payload): ApiJson<SqlRequest>,
+) -> ApiResponse {
+    let start = Instant::now();
+    let timing = WorkTiming::default();
+
+    let (result, cached) = parse_json(
+        &state.caches,
+        &state.limits,
+        &timing,
+        &payload.sql,
+        &payload.dialect,
+        payload.options(),
+    )
+    .await;
+    let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+
+    let result = match result {
+        Ok(ast) => Ok(SqlResponse {
+            ast,
+            cached: Some(cached),
+            queue_ms: timing.queue_ms(),
+            parse_ms: timing.run_ms(),
+            elapsed_ms: elapsed,
+        }),
+        Err(e) => Err(e.into_error_response(Some(elapsed))),
+    };
+    ApiResponse { format, result }
+}
+
+#[utoipa::path(
+    post,
     path = "/fingerprint",
     request_body = FingerprintRequest,
     responses(
@@ -381,33 +1483,778 @@ async fn parse_sql(State(state): State<AppState>, Json(payload): Json<SqlRequest
     ),
     tag = "SQL Fingerprint"
 )]
-async fn fingerprint_sql(Json(payload): Json<FingerprintRequest>) -> FingerprintApiResponse {
+async fn fingerprint_sql(
+    State(state): State<AppState>,
+    ApiJson(payload): ApiJson<FingerprintRequest>,
+) -> ApiResult<FingerprintResponse> {
     let start = Instant::now();
-    
-    let dialect = match get_dialect(&payload.dialect) {
-        Ok(d) => d,
+    let timing = WorkTiming::default();
+
+    let (result, cached) = fingerprint_with_cache(
+        &state.caches,
+        &state.limits,
+        &timing,
+        &payload.sql,
+        &payload.dialect,
+        payload.max_in_values,
+        payload.no_cache,
+    )
+    .await;
+    if let (Some(registry), Ok(fingerprint)) = (&state.registry, &result) {
+        registry.record(fingerprint, &payload.sql, &payload.dialect);
+    }
+    let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+
+    match result {
+        Ok(fingerprint) => ApiResult::Success(FingerprintResponse {
+            fingerprint,
+            cached: Some(cached),
+            queue_ms: timing.queue_ms(),
+            parse_ms: timing.run_ms(),
+            elapsed_ms: elapsed,
+        }),
+        Err(e) => {
+            let (status, error) = e.into_error_response(Some(elapsed));
+            ApiResult::Error(status, error)
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/fingerprints",
+    params(FingerprintsParams),
+    responses(
+        (status = 200, description = "Fingerprints recorded from /fingerprint and GenerateFingerprint", body = FingerprintsResponse),
+        (status = 400, description = "Invalid query parameters", body = ErrorResponse)
+    ),
+    tag = "SQL Parser"
+)]
+async fn list_fingerprints(
+    State(state): State<AppState>,
+    params: Result<Query<FingerprintsParams>, QueryRejection>,
+) -> Response {
+    let params = match params {
+        Ok(Query(params)) => params,
+        Err(rejection) => {
+            let (status, error) = SqlError::Invalid(rejection.body_text()).into_error_response(None);
+            return (status, Json(error)).into_response();
+        }
+    };
+    // 仅在启用 registry 时注册该路由
+    let Some(registry) = &state.registry else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let limit = params.limit.min(MAX_PAGE_SIZE);
+    let (total, fingerprints) = registry.list(params.sort, params.order, params.offset, limit);
+    Json(FingerprintsResponse {
+        total,
+        offset: params.offset,
+        limit,
+        fingerprints,
+    })
+    .into_response()
+}
+
+// 按白名单中的指纹哈希决定放行或拒绝。SQL 无法解析时没有指纹，强制模式下拒绝；
+// 方言无效或超出服务端限制时返回错误，由调用方决定如何处理
+async fn check_impl(
+    caches: &Caches,
+    limits: &Limits,
+    timing: &WorkTiming,
+    allowlist: &Arc<Allowlist>,
+    sql: &str,
+    dialect_name: &str,
+) -> Result<CheckDecision, SqlError> {
+    get_dialect(dialect_name)?;
+    let mode = allowlist.mode();
+    let (result, cached) = fingerprint_with_cache(
+        caches,
+        limits,
+        timing,
+        sql,
+        dialect_name,
+        allowlist.max_in_values(),
+        false,
+    )
+    .await;
+    let fingerprint = match result {
+        Ok(fingerprint) => fingerprint,
+        Err(SqlError::Invalid(e)) => {
+            return Ok(CheckDecision {
+                allowed: mode == AllowlistMode::Learn,
+                mode: mode.as_str(),
+                fingerprint: None,
+                hash: None,
+                learned: false,
+                error: Some(e),
+                cached,
+            })
+        }
+        Err(e) => return Err(e),
+    };
+
+    let hash = registry::fingerprint_hash(&fingerprint);
+    let listed = allowlist.contains(&hash);
+    let mut learned = false;
+    if !listed && mode == AllowlistMode::Learn {
+        let (allowlist, hash, fingerprint) = (allowlist.clone(), hash.clone(), fingerprint.clone());
+        // 写入失败不影响放行
+        match tokio::task::spawn_blocking(move || allowlist.learn(&hash, &fingerprint)).await {
+            Ok(Ok(new)) => learned = new,
+            Ok(Err(e)) => tracing::error!("Failed to learn fingerprint: {e}"),
+            Err(e) => tracing::error!("Allowlist learn task failed: {e}"),
+        }
+    }
+    Ok(CheckDecision {
+        allowed: listed || mode == AllowlistMode::Learn,
+        mode: mode.as_str(),
+        fingerprint: Some(fingerprint),
+        hash: Some(hash),
+        learned,
+        error: None,
+        cached,
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/check",
+    request_body = CheckRequest,
+    responses(
+        (status = 200, description = "Allow/deny decision; SQL that fails to parse is denied in enforce mode", body = CheckResponse),
+        (status = 400, description = "Unsupported dialect or input over the service limits", body = ErrorResponse)
+    ),
+    tag = "SQL Parser"
+)]
+async fn check_sql(
+    State(state): State<AppState>,
+    ApiJson(payload): ApiJson<CheckRequest>,
+) -> ApiResult<CheckResponse> {
+    let start = Instant::now();
+    let timing = WorkTiming::default();
+
+    // 仅在启用 allowlist 时注册该路由
+    let Some(allowlist) = &state.allowlist else {
+        let error = ErrorResponse {
+            error: "Allowlist is not enabled".to_string(),
+            code: None,
+            elapsed_ms: None,
+        };
+        return ApiResult::Error(StatusCode::NOT_FOUND, error);
+    };
+    let result = check_impl(
+        &state.caches,
+        &state.limits,
+        &timing,
+        allowlist,
+        &payload.sql,
+        &payload.dialect,
+    )
+    .await;
+    let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+
+    match result {
+        Ok(decision) => ApiResult::Success(CheckResponse {
+            decision,
+            elapsed_ms: elapsed,
+        }),
+        Err(e) => {
+            let (status, error) = e.into_error_response(Some(elapsed));
+            ApiResult::Error(status, error)
+        }
+    }
+}
+
+// 分词开销很小，不经过缓存
+async fn tokenize_impl(
+    sql: &str,
+    dialect_name: &str,
+    limits: &Limits,
+    timing: &WorkTiming,
+) -> Result<TokenList, SqlError> {
+    limits.check_sql_length(sql)?;
+    let dialect = get_dialect(dialect_name)?;
+    let sql = sql.to_string();
+    Ok(limits
+        .run_blocking(timing, move || tokenize::tokenize(&*dialect, &sql))
+        .await?)
+}
+
+#[utoipa::path(
+    post,
+    path = "/tokenize",
+    request_body = TokenizeRequest,
+    responses(
+        (status = 200, description = "SQL tokenized; on a tokenizer error the partial token list is returned with `error`", body = TokenizeResponse),
+        (status = 400, description = "Unsupported dialect", body = ErrorResponse)
+    ),
+    tag = "SQL Parser"
+)]
+async fn tokenize_sql(
+    State(state): State<AppState>,
+    ApiJson(payload): ApiJson<TokenizeRequest>,
+) -> ApiResult<TokenizeResponse> {
+    let start = Instant::now();
+    let timing = WorkTiming::default();
+
+    let result = tokenize_impl(&payload.sql, &payload.dialect, &state.limits, &timing).await;
+    let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+
+    match result {
+        Ok(TokenList { tokens, error }) => ApiResult::Success(TokenizeResponse {
+            tokens,
+            error,
+            queue_ms: timing.queue_ms(),
+            parse_ms: timing.run_ms(),
+            elapsed_ms: elapsed,
+        }),
+        Err(e) => {
+            let (status, error) = e.into_error_response(Some(elapsed));
+            ApiResult::Error(status, error)
+        }
+    }
+}
+
+// 语句种类取 AST 中的变体名，与 /parse 输出中语句的键一致；
+// 不列通配分支，升级 sqlparser 新增变体时在这里编译报错
+fn statement_kind(statement: &Statement) -> &'static str {
+    match statement {
+        Statement::Analyze { .. } => "Analyze",
+        Statement::Truncate { .. } => "Truncate",
+        Statement::Msck { .. } => "Msck",
+        Statement::Query { .. } => "Query",
+        Statement::Insert { .. } => "Insert",
+        Statement::Install { .. } => "Install",
+        Statement::Load { .. } => "Load",
+        Statement::Directory { .. } => "Directory",
+        Statement::Call { .. } => "Call",
+        Statement::Copy { .. } => "Copy",
+        Statement::CopyIntoSnowflake { .. } => "CopyIntoSnowflake",
+        Statement::Close { .. } => "Close",
+        Statement::Update { .. } => "Update",
+        Statement::Delete { .. } => "Delete",
+        Statement::CreateView { .. } => "CreateView",
+        Statement::CreateTable { .. } => "CreateTable",
+        Statement::CreateVirtualTable { .. } => "CreateVirtualTable",
+        Statement::CreateIndex { .. } => "CreateIndex",
+        Statement::CreateRole { .. } => "CreateRole",
+        Statement::CreateSecret { .. } => "CreateSecret",
+        Statement::CreatePolicy { .. } => "CreatePolicy",
+        Statement::AlterTable { .. } => "AlterTable",
+        Statement::AlterIndex { .. } => "AlterIndex",
+        Statement::AlterView { .. } => "AlterView",
+        Statement::AlterRole { .. } => "AlterRole",
+        Statement::AlterPolicy { .. } => "AlterPolicy",
+        Statement::AttachDatabase { .. } => "AttachDatabase",
+        Statement::AttachDuckDBDatabase { .. } => "AttachDuckDBDatabase",
+        Statement::DetachDuckDBDatabase { .. } => "DetachDuckDBDatabase",
+        Statement::Drop { .. } => "Drop",
+        Statement::DropFunction { .. } => "DropFunction",
+        Statement::DropProcedure { .. } => "DropProcedure",
+        Statement::DropSecret { .. } => "DropSecret",
+        Statement::DropPolicy { .. } => "DropPolicy",
+        Statement::Declare { .. } => "Declare",
+        Statement::CreateExtension { .. } => "CreateExtension",
+        Statement::Fetch { .. } => "Fetch",
+        Statement::Flush { .. } => "Flush",
+        Statement::Discard { .. } => "Discard",
+        Statement::SetRole { .. } => "SetRole",
+        Statement::SetVariable { .. } => "SetVariable",
+        Statement::SetTimeZone { .. } => "SetTimeZone",
+        Statement::SetNames { .. } => "SetNames",
+        Statement::SetNamesDefault { .. } => "SetNamesDefault",
+        Statement::ShowFunctions { .. } => "ShowFunctions",
+        Statement::ShowVariable { .. } => "ShowVariable",
+        Statement::ShowStatus { .. } => "ShowStatus",
+        Statement::ShowVariables { .. } => "ShowVariables",
+        Statement::ShowCreate { .. } => "ShowCreate",
+        Statement::ShowColumns { .. } => "ShowColumns",
+        Statement::ShowDatabases { .. } => "ShowDatabases",
+        Statement::ShowSchemas { .. } => "ShowSchemas",
+        Statement::ShowTables { .. } => "ShowTables",
+        Statement::ShowViews { .. } => "ShowViews",
+        Statement::ShowCollation { .. } => "ShowCollation",
+        Statement::Use { .. } => "Use",
+        Statement::StartTransaction { .. } => "StartTransaction",
+        Statement::SetTransaction { .. } => "SetTransaction",
+        Statement::Comment { .. } => "Comment",
+        Statement::Commit { .. } => "Commit",
+        Statement::Rollback { .. } => "Rollback",
+        Statement::CreateSchema { .. } => "CreateSchema",
+        Statement::CreateDatabase { .. } => "CreateDatabase",
+        Statement::CreateFunction { .. } => "CreateFunction",
+        Statement::CreateTrigger { .. } => "CreateTrigger",
+        Statement::DropTrigger { .. } => "DropTrigger",
+        Statement::CreateProcedure { .. } => "CreateProcedure",
+        Statement::CreateMacro { .. } => "CreateMacro",
+        Statement::CreateStage { .. } => "CreateStage",
+        Statement::Assert { .. } => "Assert",
+        Statement::Grant { .. } => "Grant",
+        Statement::Revoke { .. } => "Revoke",
+        Statement::Deallocate { .. } => "Deallocate",
+        Statement::Execute { .. } => "Execute",
+        Statement::Prepare { .. } => "Prepare",
+        Statement::Kill { .. } => "Kill",
+        Statement::ExplainTable { .. } => "ExplainTable",
+        Statement::Explain { .. } => "Explain",
+        Statement::Savepoint { .. } => "Savepoint",
+        Statement::ReleaseSavepoint { .. } => "ReleaseSavepoint",
+        Statement::Merge { .. } => "Merge",
+        Statement::Cache { .. } => "Cache",
+        Statement::UNCache { .. } => "UNCache",
+        Statement::CreateSequence { .. } => "CreateSequence",
+        Statement::CreateType { .. } => "CreateType",
+        Statement::Pragma { .. } => "Pragma",
+        Statement::LockTables { .. } => "LockTables",
+        Statement::UnlockTables => "UnlockTables",
+        Statement::Unload { .. } => "Unload",
+        Statement::OptimizeTable { .. } => "OptimizeTable",
+        Statement::LISTEN { .. } => "LISTEN",
+        Statement::UNLISTEN { .. } => "UNLISTEN",
+        Statement::NOTIFY { .. } => "NOTIFY",
+        Statement::LoadData { .. } => "LoadData",
+    }
+}
+
+// 按原文切分后逐条解析，单条语句失败不影响其他语句；结果与原文位置相关，不经过缓存
+async fn split_impl(
+    sql: &str,
+    dialect_name: &str,
+    limits: &Limits,
+    timing: &WorkTiming,
+) -> Result<Vec<SplitStatement>, SqlError> {
+    limits.check_sql_length(sql)?;
+    let dialect = get_dialect(dialect_name)?;
+    let (sql, dialect_name, limits_) = (sql.to_string(), dialect_name.to_string(), limits.clone());
+    limits
+        .run_blocking(timing, move || {
+            let ranges = split::split(&*dialect, &sql);
+            limits_.check_statement_count(ranges.len())?;
+            let lines = LineIndex::new(&sql);
+            Ok(ranges
+                .into_iter()
+                .map(|range| {
+                    let text = &sql[range.clone()];
+                    let (kind, error) = match parse_statements(text, &dialect_name, &limits_) {
+                        Ok(statements) => (statements.first().map(|s| statement_kind(s).to_string()), None),
+                        Err(e) => (None, Some(e.to_string())),
+                    };
+                    SplitStatement {
+                        text: text.to_string(),
+                        start: source_position(&lines, range.start),
+                        end: source_position(&lines, range.end),
+                        kind,
+                        error,
+                    }
+                })
+                .collect())
+        })
+        .await?
+}
+
+#[utoipa::path(
+    post,
+    path = "/split",
+    request_body = SplitRequest,
+    responses(
+        (status = 200, description = "Script split into statements; statements that fail to parse carry `error`", body = SplitResponse),
+        (status = 400, description = "Unsupported dialect or input over the service limits", body = ErrorResponse)
+    ),
+    tag = "SQL Parser"
+)]
+async fn split_sql(
+    State(state): State<AppState>,
+    ApiJson(payload): ApiJson<SplitRequest>,
+) -> ApiResult<SplitResponse> {
+    let start = Instant::now();
+    let timing = WorkTiming::default();
+
+    let result = split_impl(&payload.sql, &payload.dialect, &state.limits, &timing).await;
+    let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+
+    match result {
+        Ok(statements) => ApiResult::Success(SplitResponse {
+            statements,
+            queue_ms: timing.queue_ms(),
+            parse_ms: timing.run_ms(),
+            elapsed_ms: elapsed,
+        }),
+        Err(e) => {
+            let (status, error) = e.into_error_response(Some(elapsed));
+            ApiResult::Error(status, error)
+        }
+    }
+}
+
+// 慢查询日志按指纹汇总，整份日志作为一个任务在线程池中执行，受 parse_timeout 限制。
+// 相同查询文本只解析一次；无法解析、超出长度限制或超过解析截止时间后的查询
+// 退回词法归一化，在报告中标记 parsed: false，不影响整份报告
+async fn digest_impl(
+    log: &str,
+    params: &DigestParams,
+    limits: &Limits,
+    timing: &WorkTiming,
+) -> Result<slowlog::DigestReport, SqlError> {
+    let (log, params, limits_) = (log.to_string(), params.clone(), limits.clone());
+    // 解析最多用去时限的一半（含排队时间），剩余时间留给词法归一化与汇总
+    let deadline = limits.parse_timeout.map(|timeout| Instant::now() + timeout / 2);
+    limits
+        .run_blocking(timing, move || build_digest(&log, &params, &limits_, deadline))
+        .await?
+}
+
+fn build_digest(
+    log: &str,
+    params: &DigestParams,
+    limits: &Limits,
+    deadline: Option<Instant>,
+) -> Result<slowlog::DigestReport, SqlError> {
+    let (format, entries) = slowlog::parse_log(log, params.format)?;
+    let dialect_name = params.dialect.as_deref().unwrap_or(format.dialect());
+    let dialect = get_dialect(dialect_name)?;
+
+    let mut digest = slowlog::Digest::default();
+    let mut fingerprints: HashMap<String, (String, bool)> = HashMap::new();
+    for entry in entries {
+        let (fingerprint, parsed) = match fingerprints.get(&entry.query) {
+            Some(fingerprint) => fingerprint.clone(),
+            None => {
+                let statements = deadline
+                    .is_none_or(|deadline| Instant::now() < deadline)
+                    .then(|| {
+                        limits
+                            .check_sql_length(&entry.query)
+                            .map_err(SqlError::from)
+                            .and_then(|()| parse_statements(&entry.query, dialect_name, limits))
+                            .ok()
+                    })
+                    .flatten();
+                let computed = match statements {
+                    Some(statements) => (generate_sql_fingerprint(statements, params.max_in_values), true),
+                    None => (slowlog::token_fingerprint(&*dialect, &entry.query), false),
+                };
+                fingerprints.insert(entry.query.clone(), computed.clone());
+                computed
+            }
+        };
+        digest.add(entry, fingerprint, parsed);
+    }
+    Ok(digest.report(format, params.limit))
+}
+
+#[utoipa::path(
+    post,
+    path = "/digest",
+    params(DigestParams),
+    request_body(content = String, description = "MySQL slow query log or PostgreSQL log with log_min_duration_statement", content_type = "text/plain"),
+    responses(
+        (status = 200, description = "Queries grouped by fingerprint, ranked by total time", body = DigestResponse),
+        (status = 400, description = "Unrecognized log format, unsupported dialect or invalid parameters", body = ErrorResponse),
+        (status = 413, description = "Log over limits.max_body_bytes", body = ErrorResponse)
+    ),
+    tag = "SQL Parser"
+)]
+async fn digest_log(
+    State(state): State<AppState>,
+    params: Result<Query<DigestParams>, QueryRejection>,
+    TextBody(log): TextBody,
+) -> ApiResult<DigestResponse> {
+    let start = Instant::now();
+    let timing = WorkTiming::default();
+
+    let result = match params {
+        Ok(params) => digest_impl(&log, &params, &state.limits, &timing).await,
+        Err(rejection) => Err(SqlError::Invalid(rejection.body_text())),
+    };
+    let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+
+    match result {
+        Ok(report) => ApiResult::Success(DigestResponse {
+            report,
+            queue_ms: timing.queue_ms(),
+            parse_ms: timing.run_ms(),
+            elapsed_ms: elapsed,
+        }),
         Err(e) => {
-            return FingerprintApiResponse::Error(ErrorResponse {
+            let (status, error) = e.into_error_response(Some(elapsed));
+            ApiResult::Error(status, error)
+        }
+    }
+}
+
+fn unknown_namespace(namespace: &str, elapsed_ms: Option<f64>) -> (StatusCode, ErrorResponse) {
+    let error = ErrorResponse {
+        error: format!("Unknown catalog namespace '{namespace}'"),
+        code: Some("unknown_namespace".to_string()),
+        elapsed_ms,
+    };
+    (StatusCode::NOT_FOUND, error)
+}
+
+fn catalog_error_response(e: CatalogError, elapsed_ms: Option<f64>) -> (StatusCode, ErrorResponse) {
+    match e {
+        CatalogError::Invalid(e) => SqlError::Invalid(e).into_error_response(elapsed_ms),
+        CatalogError::Storage(e) => {
+            tracing::error!("Failed to store schema catalog: {e}");
+            let error = ErrorResponse {
                 error: e,
-                elapsed_ms: Some(start.elapsed().as_secs_f64() * 1000.0),
-            });
+                code: Some("storage_error".to_string()),
+                elapsed_ms,
+            };
+            (StatusCode::INTERNAL_SERVER_ERROR, error)
         }
+    }
+}
+
+// 按 DDL 或 JSON 目录构建；DDL 与其他 SQL 一样受长度、语句数与解析超时限制
+async fn catalog_impl(
+    request: CatalogRequest,
+    limits: &Limits,
+    timing: &WorkTiming,
+) -> Result<(Catalog, usize), SqlError> {
+    let CatalogRequest {
+        ddl,
+        dialect,
+        definition,
+    } = request;
+    let Some(ddl) = ddl else {
+        return Ok((Catalog::new(definition)?, 0));
     };
-    
-    match Parser::parse_sql(&*dialect, &payload.sql) {
-        Ok(statements) => {
-            let fingerprint = generate_sql_fingerprint(statements, payload.max_in_values);
+    if !definition.tables.is_empty() {
+        return Err(SqlError::Invalid("Specify either ddl or tables, not both".to_string()));
+    }
+    limits.check_sql_length(&ddl)?;
+    get_dialect(&dialect)?;
+    let limits_ = limits.clone();
+    limits
+        .run_blocking(timing, move || {
+            let statements = parse_statements(&ddl, &dialect, &limits_)?;
+            Ok(Catalog::from_ddl(&statements, definition.functions)?)
+        })
+        .await?
+}
+
+#[utoipa::path(
+    put,
+    path = "/catalogs/{namespace}",
+    params(("namespace" = String, Path, description = "Catalog namespace: 1-64 letters, digits, '_' or '-'")),
+    request_body = CatalogRequest,
+    responses(
+        (status = 200, description = "Catalog registered, replacing any previous one in the namespace", body = CatalogResponse),
+        (status = 400, description = "Invalid DDL, catalog or namespace", body = ErrorResponse)
+    ),
+    tag = "Schema Catalog"
+)]
+async fn put_catalog(
+    State(state): State<AppState>,
+    Path(namespace): Path<String>,
+    ApiJson(payload): ApiJson<CatalogRequest>,
+) -> ApiResult<CatalogResponse> {
+    let start = Instant::now();
+    let timing = WorkTiming::default();
+
+    let result = match catalog::validate_namespace(&namespace) {
+        Ok(()) => catalog_impl(payload, &state.limits, &timing).await,
+        Err(e) => Err(SqlError::Invalid(e)),
+    };
+    let (catalog, skipped) = match result {
+        Ok(result) => result,
+        Err(e) => {
             let elapsed = start.elapsed().as_secs_f64() * 1000.0;
-            
-            FingerprintApiResponse::Success(FingerprintResponse {
-                fingerprint,
+            let (status, error) = e.into_error_response(Some(elapsed));
+            return ApiResult::Error(status, error);
+        }
+    };
+    let (tables, columns, functions) = (
+        catalog.table_count(),
+        catalog.column_count(),
+        catalog.function_count(),
+    );
+    let result = state.catalogs.put(&namespace, catalog).await;
+    let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+
+    match result {
+        Ok(replaced) => {
+            tracing::info!(namespace = %namespace, tables, columns, "Registered schema catalog");
+            ApiResult::Success(CatalogResponse {
+                namespace,
+                tables,
+                columns,
+                functions,
+                skipped,
+                replaced,
                 elapsed_ms: elapsed,
             })
         }
-        Err(e) => FingerprintApiResponse::Error(ErrorResponse {
-            error: format!("Failed to parse SQL: {e}"),
-            elapsed_ms: Some(start.elapsed().as_secs_f64() * 1000.0),
+        Err(e) => {
+            let (status, error) = catalog_error_response(e, Some(elapsed));
+            ApiResult::Error(status, error)
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/catalogs/{namespace}",
+    params(("namespace" = String, Path, description = "Catalog namespace")),
+    responses(
+        (status = 200, description = "Registered tables and functions", body = CatalogDetail),
+        (status = 404, description = "Unknown namespace", body = ErrorResponse)
+    ),
+    tag = "Schema Catalog"
+)]
+async fn get_catalog(State(state): State<AppState>, Path(namespace): Path<String>) -> Response {
+    match state.catalogs.get(&namespace) {
+        Some(catalog) => Json(CatalogDetail {
+            namespace,
+            definition: catalog.definition().clone(),
+        })
+        .into_response(),
+        None => {
+            let (status, error) = unknown_namespace(&namespace, None);
+            (status, Json(error)).into_response()
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/catalogs/{namespace}",
+    params(("namespace" = String, Path, description = "Catalog namespace")),
+    responses(
+        (status = 204, description = "Catalog deleted"),
+        (status = 404, description = "Unknown namespace", body = ErrorResponse)
+    ),
+    tag = "Schema Catalog"
+)]
+async fn delete_catalog(State(state): State<AppState>, Path(namespace): Path<String>) -> Response {
+    let (status, error) = match state.catalogs.remove(&namespace).await {
+        Ok(true) => return StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => unknown_namespace(&namespace, None),
+        Err(e) => catalog_error_response(e, None),
+    };
+    (status, Json(error)).into_response()
+}
+
+// 语义检查依赖原文位置与命名空间当前的目录，不经过缓存
+async fn validate_impl(
+    sql: &str,
+    dialect_name: &str,
+    catalog: Arc<Catalog>,
+    limits: &Limits,
+    timing: &WorkTiming,
+) -> Result<Vec<validate::Diagnostic>, SqlError> {
+    limits.check_sql_length(sql)?;
+    get_dialect(dialect_name)?;
+    let (sql, dialect_name, limits_) = (sql.to_string(), dialect_name.to_string(), limits.clone());
+    limits
+        .run_blocking(timing, move || {
+            let statements = parse_statements(&sql, &dialect_name, &limits_)?;
+            Ok(validate::validate(&catalog, &statements, &sql))
+        })
+        .await?
+}
+
+#[utoipa::path(
+    post,
+    path = "/validate",
+    request_body = ValidateRequest,
+    responses(
+        (status = 200, description = "Semantic diagnostics against the namespace's catalog", body = ValidateResponse),
+        (status = 400, description = "Invalid SQL or input over the service limits", body = ErrorResponse),
+        (status = 404, description = "Unknown namespace", body = ErrorResponse)
+    ),
+    tag = "Schema Catalog"
+)]
+async fn validate_sql(
+    State(state): State<AppState>,
+    ApiJson(payload): ApiJson<ValidateRequest>,
+) -> ApiResult<ValidateResponse> {
+    let start = Instant::now();
+    let timing = WorkTiming::default();
+
+    let Some(catalog) = state.catalogs.get(&payload.namespace) else {
+        let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+        let (status, error) = unknown_namespace(&payload.namespace, Some(elapsed));
+        return ApiResult::Error(status, error);
+    };
+    let result = validate_impl(&payload.sql, &payload.dialect, catalog, &state.limits, &timing).await;
+    let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+
+    match result {
+        Ok(diagnostics) => ApiResult::Success(ValidateResponse {
+            valid: diagnostics.is_empty(),
+            diagnostics,
+            queue_ms: timing.queue_ms(),
+            parse_ms: timing.run_ms(),
+            elapsed_ms: elapsed,
+        }),
+        Err(e) => {
+            let (status, error) = e.into_error_response(Some(elapsed));
+            ApiResult::Error(status, error)
+        }
+    }
+}
+
+// 与 /validate 相同，不经过缓存
+async fn expand_impl(
+    sql: &str,
+    dialect_name: &str,
+    catalog: Arc<Catalog>,
+    limits: &Limits,
+    timing: &WorkTiming,
+) -> Result<expand::Expanded, SqlError> {
+    limits.check_sql_length(sql)?;
+    let dialect = get_dialect(dialect_name)?;
+    let (sql, dialect_name, limits_) = (sql.to_string(), dialect_name.to_string(), limits.clone());
+    limits
+        .run_blocking(timing, move || {
+            let statements = parse_statements(&sql, &dialect_name, &limits_)?;
+            Ok(expand::expand(&catalog, statements, &sql, &*dialect))
+        })
+        .await?
+}
+
+#[utoipa::path(
+    post,
+    path = "/expand",
+    request_body = ExpandRequest,
+    responses(
+        (status = 200, description = "SQL with SELECT * expanded against the namespace's catalog", body = ExpandResponse),
+        (status = 400, description = "Invalid SQL or input over the service limits", body = ErrorResponse),
+        (status = 404, description = "Unknown namespace", body = ErrorResponse)
+    ),
+    tag = "Schema Catalog"
+)]
+async fn expand_sql(
+    State(state): State<AppState>,
+    ApiJson(payload): ApiJson<ExpandRequest>,
+) -> ApiResult<ExpandResponse> {
+    let start = Instant::now();
+    let timing = WorkTiming::default();
+
+    let Some(catalog) = state.catalogs.get(&payload.namespace) else {
+        let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+        let (status, error) = unknown_namespace(&payload.namespace, Some(elapsed));
+        return ApiResult::Error(status, error);
+    };
+    let result = expand_impl(&payload.sql, &payload.dialect, catalog, &state.limits, &timing).await;
+    let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+
+    match result {
+        Ok(expanded) => ApiResult::Success(ExpandResponse {
+            sql: expanded.sql,
+            expanded: expanded.expanded,
+            unresolved: expanded.unresolved,
+            queue_ms: timing.queue_ms(),
+            parse_ms: timing.run_ms(),
+            elapsed_ms: elapsed,
         }),
+        Err(e) => {
+            let (status, error) = e.into_error_response(Some(elapsed));
+            ApiResult::Error(status, error)
+        }
     }
 }
 
@@ -426,13 +2273,76 @@ async fn health_check() -> Json<HealthResponse> {
     })
 }
 
+// 存活检查：进程能响应即返回 200
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    responses(
+        (status = 200, description = "Process is alive", body = HealthResponse)
+    ),
+    tag = "Health"
+)]
+async fn health_live() -> Json<HealthResponse> {
+    health_check().await
+}
+
+// 就绪检查：缓存预热与停机排空期间返回 503
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    responses(
+        (status = 200, description = "Ready to serve traffic", body = HealthResponse),
+        (status = 503, description = "Warming up or draining", body = HealthResponse)
+    ),
+    tag = "Health"
+)]
+async fn health_ready(State(state): State<AppState>) -> (StatusCode, Json<HealthResponse>) {
+    let readiness = state.health.readiness();
+    let status = if readiness.is_ready() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(HealthResponse {
+            status: readiness.as_str().to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }),
+    )
+}
+
+// Prometheus 文本格式
+async fn metrics_handler() -> impl IntoResponse {
+    (
+        [(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::METRICS.render(),
+    )
+}
+
 async fn serve_index() -> Html<&'static str> {
     Html(include_str!("../static/index.html"))
 }
 
+fn log_grpc_request<T>(method: &str, request: &Request<T>) {
+    let client = tls::grpc_client_identity(request);
+    tracing::debug!(
+        client = client.as_ref().map(ClientIdentity::name).unwrap_or("-"),
+        "gRPC {method}"
+    );
+}
+
 // gRPC Service Implementation
 pub struct SqlParserGrpcService {
-    cache: Cache<CacheKey, CacheValue>,
+    caches: Caches,
+    limits: Limits,
+    endpoints: EndpointsConfig,
+    auth: Option<Arc<Authenticator>>,
+    limiter: Option<Arc<RateLimiter>>,
+    health: Arc<Health>,
+    registry: Option<Arc<FingerprintRegistry>>,
+    allowlist: Option<Arc<Allowlist>>,
+    compression: CompressionConfig,
 }
 
 #[tonic::async_trait]
@@ -441,212 +2351,899 @@ impl sql_parser::sql_parser_service_server::SqlParserService for SqlParserGrpcSe
         &self,
         request: Request<sql_parser::ParseSqlRequest>,
     ) -> Result<TonicResponse<sql_parser::ParseSqlResponse>, Status> {
+        if !self.endpoints.parse {
+            return Err(Status::unimplemented("ParseSql is disabled"));
+        }
+        auth::authorize_grpc(self.auth.as_deref(), &request, "parse")?;
+        let _permit = ratelimit::admit_grpc(self.limiter.as_deref(), &request)?;
+        log_grpc_request("ParseSql", &request);
+
         let req = request.into_inner();
+        // 类型化 AST 不支持这些选项，拒绝而不是静默忽略
+        if req.ast_format() == sql_parser::AstFormat::Proto
+            && (req.compact || req.with_spans || req.recover)
+        {
+            return Err(Status::invalid_argument(
+                "compact, with_spans and recover require AST_FORMAT_JSON",
+            ));
+        }
         let start = Instant::now();
+        let timing = WorkTiming::default();
 
-        let normalized_sql = if req.no_cache {
-            req.sql.clone()
-        } else {
-            normalize_sql(&req.sql)
-        };
-
-        let cache_key = (normalized_sql.clone(), req.dialect.clone());
-
-        // Check cache if not disabled
-        if !req.no_cache {
-            if let Some(cached_result) = self.cache.get(&cache_key).await {
-                let elapsed = start.elapsed().as_secs_f64() * 1000.0;
-                return match cached_result {
-                    Ok(ast) => Ok(TonicResponse::new(sql_parser::ParseSqlResponse {
-                        result: Some(sql_parser::parse_sql_response::Result::Success(
-                            sql_parser::ParseSqlSuccess {
-                                ast_json: ast.to_string(),
-                                cached: true,
-                                elapsed_ms: elapsed,
-                            },
-                        )),
-                    })),
-                    Err(e) => Ok(TonicResponse::new(sql_parser::ParseSqlResponse {
-                        result: Some(sql_parser::parse_sql_response::Result::Error(
-                            sql_parser::ParseSqlError {
-                                error_message: e,
-                                elapsed_ms: elapsed,
-                            },
-                        )),
-                    })),
+        let (caches, limits) = (&self.caches, &self.limits);
+        let (result, cached) = match req.ast_format() {
+            sql_parser::AstFormat::Json => {
+                let options = ParseOptions {
+                    no_cache: req.no_cache,
+                    compact: req.compact,
+                    with_spans: req.with_spans,
+                    recover: req.recover,
                 };
+                let (result, cached) =
+                    parse_json(caches, limits, &timing, &req.sql, &req.dialect, options).await;
+                (result.map(|ast| (ast.to_string(), Vec::new())), cached)
             }
-        }
-
-        let result = parse_sql_impl(&normalized_sql, &req.dialect).await;
+            sql_parser::AstFormat::Proto => {
+                let (result, cached) = parse_proto_with_cache(
+                    caches,
+                    limits,
+                    &timing,
+                    &req.sql,
+                    &req.dialect,
+                    req.no_cache,
+                )
+                .await;
+                (result.map(|statements| (String::new(), statements)), cached)
+            }
+        };
         let elapsed = start.elapsed().as_secs_f64() * 1000.0;
 
-        if !req.no_cache {
-            self.cache.insert(cache_key, result.clone()).await;
-        }
-
-        match result {
-            Ok(ast) => Ok(TonicResponse::new(sql_parser::ParseSqlResponse {
+        let response = match result {
+            Ok((ast_json, statements)) => sql_parser::ParseSqlResponse {
                 result: Some(sql_parser::parse_sql_response::Result::Success(
                     sql_parser::ParseSqlSuccess {
-                        ast_json: ast.to_string(),
-                        cached: false,
+                        ast_json,
+                        statements,
+                        cached,
                         elapsed_ms: elapsed,
+                        queue_ms: timing.queue_ms().unwrap_or_default(),
+                        parse_ms: timing.run_ms().unwrap_or_default(),
                     },
                 )),
-            })),
-            Err(e) => Ok(TonicResponse::new(sql_parser::ParseSqlResponse {
+            },
+            Err(e) => sql_parser::ParseSqlResponse {
                 result: Some(sql_parser::parse_sql_response::Result::Error(
                     sql_parser::ParseSqlError {
-                        error_message: e,
+                        error_code: e.code().unwrap_or_default().to_string(),
+                        error_message: e.to_string(),
                         elapsed_ms: elapsed,
                     },
                 )),
-            })),
-        }
+            },
+        };
+        Ok(compression::grpc_response(response, &self.compression))
     }
 
     async fn generate_fingerprint(
         &self,
         request: Request<sql_parser::FingerprintRequest>,
     ) -> Result<TonicResponse<sql_parser::FingerprintResponse>, Status> {
+        if !self.endpoints.fingerprint {
+            return Err(Status::unimplemented("GenerateFingerprint is disabled"));
+        }
+        auth::authorize_grpc(self.auth.as_deref(), &request, "fingerprint")?;
+        let _permit = ratelimit::admit_grpc(self.limiter.as_deref(), &request)?;
+        log_grpc_request("GenerateFingerprint", &request);
+
         let req = request.into_inner();
         let start = Instant::now();
+        let timing = WorkTiming::default();
 
-        let dialect = match get_dialect(&req.dialect) {
-            Ok(d) => d,
-            Err(e) => {
-                return Ok(TonicResponse::new(sql_parser::FingerprintResponse {
-                    result: Some(sql_parser::fingerprint_response::Result::Error(
-                        sql_parser::FingerprintError {
-                            error_message: e,
-                            elapsed_ms: start.elapsed().as_secs_f64() * 1000.0,
-                        },
-                    )),
-                }));
-            }
-        };
-
-        match Parser::parse_sql(&*dialect, &req.sql) {
-            Ok(statements) => {
-                let fingerprint = generate_sql_fingerprint(statements, req.max_in_values as usize);
-                let elapsed = start.elapsed().as_secs_f64() * 1000.0;
-
-                Ok(TonicResponse::new(sql_parser::FingerprintResponse {
-                    result: Some(sql_parser::fingerprint_response::Result::Success(
-                        sql_parser::FingerprintSuccess {
-                            fingerprint,
-                            elapsed_ms: elapsed,
-                        },
-                    )),
-                }))
-            }
-            Err(e) => Ok(TonicResponse::new(sql_parser::FingerprintResponse {
+        let (result, cached) = fingerprint_with_cache(
+            &self.caches,
+            &self.limits,
+            &timing,
+            &req.sql,
+            &req.dialect,
+            req.max_in_values as usize,
+            req.no_cache,
+        )
+        .await;
+        if let (Some(registry), Ok(fingerprint)) = (&self.registry, &result) {
+            registry.record(fingerprint, &req.sql, &req.dialect);
+        }
+        let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+
+        let response = match result {
+            Ok(fingerprint) => sql_parser::FingerprintResponse {
+                result: Some(sql_parser::fingerprint_response::Result::Success(
+                    sql_parser::FingerprintSuccess {
+                        fingerprint,
+                        cached,
+                        elapsed_ms: elapsed,
+                        queue_ms: timing.queue_ms().unwrap_or_default(),
+                        parse_ms: timing.run_ms().unwrap_or_default(),
+                    },
+                )),
+            },
+            Err(e) => sql_parser::FingerprintResponse {
                 result: Some(sql_parser::fingerprint_response::Result::Error(
                     sql_parser::FingerprintError {
-                        error_message: format!("Failed to parse SQL: {e}"),
-                        elapsed_ms: start.elapsed().as_secs_f64() * 1000.0,
+                        error_code: e.code().unwrap_or_default().to_string(),
+                        error_message: e.to_string(),
+                        elapsed_ms: elapsed,
                     },
                 )),
-            })),
+            },
+        };
+        Ok(compression::grpc_response(response, &self.compression))
+    }
+
+    async fn check_sql(
+        &self,
+        request: Request<sql_parser::CheckSqlRequest>,
+    ) -> Result<TonicResponse<sql_parser::CheckSqlResponse>, Status> {
+        if !self.endpoints.check {
+            return Err(Status::unimplemented("CheckSql is disabled"));
+        }
+        let Some(allowlist) = &self.allowlist else {
+            return Err(Status::unimplemented("CheckSql requires the allowlist to be enabled"));
+        };
+        auth::authorize_grpc(self.auth.as_deref(), &request, "check")?;
+        let _permit = ratelimit::admit_grpc(self.limiter.as_deref(), &request)?;
+        log_grpc_request("CheckSql", &request);
+
+        let req = request.into_inner();
+        let start = Instant::now();
+        let timing = WorkTiming::default();
+
+        let result = check_impl(
+            &self.caches,
+            &self.limits,
+            &timing,
+            allowlist,
+            &req.sql,
+            &req.dialect,
+        )
+        .await;
+        let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+
+        let response = match result {
+            Ok(decision) => sql_parser::CheckSqlResponse {
+                result: Some(sql_parser::check_sql_response::Result::Success(
+                    sql_parser::CheckSqlSuccess {
+                        allowed: decision.allowed,
+                        fingerprint: decision.fingerprint.unwrap_or_default(),
+                        hash: decision.hash.unwrap_or_default(),
+                        mode: decision.mode.to_string(),
+                        learned: decision.learned,
+                        parse_error: decision.error.unwrap_or_default(),
+                        cached: decision.cached,
+                        elapsed_ms: elapsed,
+                    },
+                )),
+            },
+            Err(e) => sql_parser::CheckSqlResponse {
+                result: Some(sql_parser::check_sql_response::Result::Error(
+                    sql_parser::CheckSqlError {
+                        error_message: e.to_string(),
+                        elapsed_ms: elapsed,
+                        error_code: e.code().unwrap_or_default().to_string(),
+                    },
+                )),
+            },
+        };
+        Ok(compression::grpc_response(response, &self.compression))
+    }
+
+    async fn tokenize(
+        &self,
+        request: Request<sql_parser::TokenizeRequest>,
+    ) -> Result<TonicResponse<sql_parser::TokenizeResponse>, Status> {
+        if !self.endpoints.tokenize {
+            return Err(Status::unimplemented("Tokenize is disabled"));
         }
+        auth::authorize_grpc(self.auth.as_deref(), &request, "tokenize")?;
+        let _permit = ratelimit::admit_grpc(self.limiter.as_deref(), &request)?;
+        log_grpc_request("Tokenize", &request);
+
+        let req = request.into_inner();
+        let start = Instant::now();
+        let timing = WorkTiming::default();
+
+        let result = tokenize_impl(&req.sql, &req.dialect, &self.limits, &timing).await;
+        let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+
+        let response = match result {
+            Ok(TokenList { tokens, error }) => sql_parser::TokenizeResponse {
+                result: Some(sql_parser::tokenize_response::Result::Success(
+                    sql_parser::TokenizeSuccess {
+                        tokens: tokens.into_iter().map(grpc_token).collect(),
+                        token_error: error.map(|e| sql_parser::TokenError {
+                            message: e.message,
+                            position: Some(grpc_position(e.position)),
+                        }),
+                        elapsed_ms: elapsed,
+                        queue_ms: timing.queue_ms().unwrap_or_default(),
+                        parse_ms: timing.run_ms().unwrap_or_default(),
+                    },
+                )),
+            },
+            Err(e) => sql_parser::TokenizeResponse {
+                result: Some(sql_parser::tokenize_response::Result::Error(
+                    sql_parser::TokenizeError {
+                        error_code: e.code().unwrap_or_default().to_string(),
+                        error_message: e.to_string(),
+                        elapsed_ms: elapsed,
+                    },
+                )),
+            },
+        };
+        Ok(compression::grpc_response(response, &self.compression))
     }
 
+    // 与 HTTP 的 /health/live、/health/ready 对应
     async fn health_check(
         &self,
-        _request: Request<sql_parser::HealthCheckRequest>,
+        request: Request<sql_parser::HealthCheckRequest>,
     ) -> Result<TonicResponse<sql_parser::HealthCheckResponse>, Status> {
+        let readiness = self.health.readiness();
+        let status = match request.get_ref().probe.as_str() {
+            "" | "live" => "ok",
+            "ready" => readiness.as_str(),
+            probe => {
+                return Err(Status::invalid_argument(format!(
+                    "Unknown probe '{probe}', expected 'live' or 'ready'"
+                )))
+            }
+        };
         Ok(TonicResponse::new(sql_parser::HealthCheckResponse {
-            status: "ok".to_string(),
+            status: status.to_string(),
             version: env!("CARGO_PKG_VERSION").to_string(),
+            ready: readiness.is_ready(),
         }))
     }
 }
 
+fn grpc_token(token: SqlToken) -> sql_parser::Token {
+    use sql_parser::TokenKind as Kind;
+    use tokenize::TokenKind;
+
+    let kind = match token.kind {
+        TokenKind::Keyword => Kind::Keyword,
+        TokenKind::Identifier => Kind::Identifier,
+        TokenKind::QuotedIdentifier => Kind::QuotedIdentifier,
+        TokenKind::String => Kind::String,
+        TokenKind::Number => Kind::Number,
+        TokenKind::Operator => Kind::Operator,
+        TokenKind::Comment => Kind::Comment,
+        TokenKind::Whitespace => Kind::Whitespace,
+        TokenKind::Placeholder => Kind::Placeholder,
+    };
+    sql_parser::Token {
+        kind: kind as i32,
+        text: token.text,
+        start: Some(grpc_position(token.start)),
+        end: Some(grpc_position(token.end)),
+    }
+}
+
+fn grpc_position(position: tokenize::Position) -> sql_parser::Position {
+    sql_parser::Position {
+        line: position.line,
+        column: position.column,
+        offset: position.offset as u64,
+    }
+}
+
+// 业务服务需要认证；标准健康检查与反射服务保持公开
+fn build_grpc_routes(service: SqlParserGrpcService, config: &Config) -> tonic::service::Routes {
+    use sql_parser::sql_parser_service_server::SqlParserServiceServer;
+    use tonic::server::NamedService;
+
+    let (reporter, health_service) = tonic_health::server::health_reporter();
+    service.health.spawn_grpc_reporter(
+        reporter,
+        &[<SqlParserServiceServer<SqlParserGrpcService> as NamedService>::NAME],
+    );
+
+    let interceptor = auth::grpc_interceptor(service.auth.clone());
+    let mut server =
+        SqlParserServiceServer::new(service).max_decoding_message_size(config.limits.max_body_bytes);
+    for encoding in compression::GRPC_ENCODINGS {
+        if config.compression.decompress_requests {
+            server = server.accept_compressed(encoding);
+        }
+        if config.compression.enabled {
+            server = server.send_compressed(encoding);
+        }
+    }
+    let mut routes = tonic::service::Routes::new(health_service).add_service(
+        tonic::service::interceptor::InterceptedService::new(server, interceptor),
+    );
+    if config.endpoints.grpc_reflection {
+        // 同时提供 v1 与 v1alpha，兼容新旧版本的 grpcurl
+        let reflection = || {
+            tonic_reflection::server::Builder::configure()
+                .register_encoded_file_descriptor_set(sql_parser::FILE_DESCRIPTOR_SET)
+                .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+        };
+        routes = routes
+            .add_service(reflection().build_v1().expect("invalid file descriptor set"))
+            .add_service(reflection().build_v1alpha().expect("invalid file descriptor set"));
+    }
+    routes
+}
+
+// grpc 为 Some 时在同一端口上提供 gRPC（单端口模式）
+fn build_router(state: AppState, config: &Config, grpc: Option<Multiplexer>) -> Router {
+    let endpoints = &config.endpoints;
+    let mut router = Router::new()
+        .route("/health", get(health_check))
+        .route("/health/live", get(health_live))
+        .route("/health/ready", get(health_ready));
+    if endpoints.swagger_ui {
+        router = router
+            .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()));
+    }
+
+    // 需要认证的接口
+    let mut api = Router::new();
+    if endpoints.parse {
+        api = api.route("/parse", post(parse_sql));
+    }
+    if endpoints.fingerprint {
+        api = api.route("/fingerprint", post(fingerprint_sql));
+    }
+    if endpoints.fingerprints && state.registry.is_some() {
+        api = api.route("/fingerprints", get(list_fingerprints));
+    }
+    if endpoints.check && state.allowlist.is_some() {
+        api = api.route("/check", post(check_sql));
+    }
+    if endpoints.tokenize {
+        api = api.route("/tokenize", post(tokenize_sql));
+    }
+    if endpoints.split {
+        api = api.route("/split", post(split_sql));
+    }
+    if endpoints.digest {
+        api = api.route("/digest", post(digest_log));
+    }
+    if endpoints.catalogs {
+        api = api.route(
+            "/catalogs/:namespace",
+            put(put_catalog).get(get_catalog).delete(delete_catalog),
+        );
+    }
+    if endpoints.validate {
+        api = api.route("/validate", post(validate_sql));
+    }
+    if endpoints.expand {
+        api = api.route("/expand", post(expand_sql));
+    }
+    // 后添加的 layer 先执行：先认证，再按调用方限流
+    if let Some(limiter) = state.limiter.clone() {
+        api = api.route_layer(axum::middleware::from_fn_with_state(
+            limiter,
+            ratelimit::http_middleware,
+        ));
+    }
+    if let Some(auth) = state.auth.clone() {
+        api = api.route_layer(axum::middleware::from_fn_with_state(auth, auth::http_middleware));
+    }
+    router = router.merge(api);
+    if endpoints.web_ui {
+        router = router.route("/", get(serve_index));
+    }
+    if endpoints.metrics {
+        router = router.route("/metrics", get(metrics_handler));
+    }
+
+    let mut router = router.with_state(state);
+    if let Some(grpc) = grpc {
+        router = router.layer(axum::middleware::from_fn_with_state(grpc, grpcweb::multiplex));
+    }
+    let router = router.layer(DefaultBodyLimit::max(config.limits.max_body_bytes));
+    compression::apply_http(router, &config.compression)
+        .layer(
+            TraceLayer::new_for_http().make_span_with(|request: &axum::http::Request<_>| {
+                let client = request.extensions().get::<ClientIdentity>();
+                tracing::debug_span!(
+                    "request",
+                    method = %request.method(),
+                    uri = %request.uri(),
+                    client = client.map(ClientIdentity::name).unwrap_or("-"),
+                    principal = tracing::field::Empty,
+                )
+            }),
+        )
+        .layer(cors_layer(&config.server.cors_allowed_origins))
+}
+
+// 按 server.cors_allowed_origins 构造 CORS 层，为空时允许任意来源；
+// 来源已在加载配置时校验，无效的条目在这里忽略
+fn cors_layer(origins: &[String]) -> CorsLayer {
+    if origins.is_empty() {
+        return CorsLayer::permissive();
+    }
+    let origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(origins.iter().filter_map(|origin| origin.parse().ok()))
+    };
+    // gRPC-Web 客户端需要读取 grpc-status 等响应头
+    CorsLayer::new()
+        .allow_origin(origin)
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .expose_headers(Any)
+}
+
+fn init_logging(config: &config::LoggingConfig) {
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::new(&config.level));
+    match config.format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
+
+// 等待 Ctrl+C 或 SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+async fn wait_for_shutdown(mut shutdown: tokio::sync::watch::Receiver<()>) {
+    let _ = shutdown.changed().await;
+}
+
+// 启动前绑定端口，失败时直接退出
+async fn bind_listener(addr: SocketAddr, name: &str) -> tokio::net::TcpListener {
+    tokio::net::TcpListener::bind(addr).await.unwrap_or_else(|e| {
+        eprintln!("Failed to bind {name} listener on {addr}: {e}");
+        std::process::exit(1);
+    })
+}
+
+fn log_server_exit(name: &str, result: Result<Result<(), String>, tokio::task::JoinError>) {
+    match result {
+        Ok(Ok(())) => tracing::warn!("{name} server stopped unexpectedly"),
+        Ok(Err(e)) => tracing::error!("{name} server failed: {e}"),
+        Err(e) => tracing::error!("{name} server task failed: {e}"),
+    }
+}
+
 #[tokio::main]
 async fn main() {
     let args = CliArgs::parse();
+    let config = match args.load_config() {
+        Ok(config) => config,
+        Err(errors) => {
+            for error in errors {
+                eprintln!("Configuration error: {error}");
+            }
+            std::process::exit(2);
+        }
+    };
+
+    if args.print_config {
+        print!("{}", config.to_toml());
+        return;
+    }
+
+    if let Some(command) = &args.command {
+        let code = cli::run(command, &config, &mut std::io::stdout().lock()).await;
+        std::process::exit(code);
+    }
+
+    init_logging(&config.logging);
+
+    let cache_config = &config.cache;
+    let cache_ttl = Duration::from_secs(cache_config.ttl);
+    let mut cache = AstCache::new(build_cache(cache_config.max_capacity, cache_ttl));
+
+    if let Some(dir) = &cache_config.dir {
+        let disk_ttl = Duration::from_secs(cache_config.disk_ttl);
+        match DiskCache::open(dir, cache_config.disk_max_capacity, disk_ttl) {
+            Ok(disk) => cache = cache.with_disk(disk, cache_config.persist_mode),
+            Err(e) => {
+                eprintln!("Failed to open persistent cache at {}: {e}", dir.display());
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let caches = Caches {
+        ast: cache.clone(),
+        statements: build_cache(cache_config.statement_capacity, cache_ttl),
+        fingerprints: build_cache(cache_config.fingerprint_capacity, cache_ttl),
+        fold_keyword_case: cache_config.normalize_keyword_case,
+    };
+
+    let authenticator = if config.auth.enabled {
+        match Authenticator::from_config(&config.auth) {
+            Ok(auth) => Some(auth),
+            Err(e) => {
+                eprintln!("Failed to load auth configuration: {e}");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let rate_limit = &config.rate_limit;
+    let limiter = (rate_limit.requests_per_second > 0.0 || rate_limit.max_concurrency > 0)
+        .then(|| RateLimiter::from_config(rate_limit));
+
+    let registry = if config.registry.enabled {
+        match FingerprintRegistry::from_config(&config.registry) {
+            Ok(registry) => Some(registry),
+            Err(e) => {
+                eprintln!("Failed to load fingerprint registry: {e}");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let allowlist = if config.allowlist.enabled {
+        match Allowlist::from_config(&config.allowlist) {
+            Ok(allowlist) => Some(allowlist),
+            Err(e) => {
+                eprintln!("Failed to load allowlist: {e}");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let catalogs = match CatalogStore::from_config(&config.catalog) {
+        Ok(catalogs) => catalogs,
+        Err(e) => {
+            eprintln!("Failed to load schema catalogs: {e}");
+            std::process::exit(1);
+        }
+    };
 
-    let cache = Cache::builder()
-        .max_capacity(args.cache_max_capacity)
-        .time_to_live(Duration::from_secs(args.cache_ttl))
-        .build();
+    let limits = Limits::from_config(&config.limits);
+    let health = Health::new();
 
     let state = AppState {
-        cache: cache.clone(),
+        caches: caches.clone(),
+        limits: limits.clone(),
+        auth: authenticator.clone(),
+        limiter: limiter.clone(),
+        health: health.clone(),
+        registry: registry.clone(),
+        allowlist: allowlist.clone(),
+        catalogs: catalogs.clone(),
+    };
+
+    let grpc_service = SqlParserGrpcService {
+        caches,
+        limits,
+        endpoints: config.endpoints.clone(),
+        auth: authenticator.clone(),
+        limiter,
+        health: health.clone(),
+        registry: registry.clone(),
+        allowlist: allowlist.clone(),
+        compression: config.compression.clone(),
     };
+    let grpc_routes = build_grpc_routes(grpc_service, &config);
+    let grpc_web = config.endpoints.grpc_web;
+    let single_port = config.endpoints.grpc && config.server.single_port;
+    // 单独的 gRPC 端口只在未启用单端口模式时监听
+    let separate_grpc = config.endpoints.grpc && !single_port;
 
     // HTTP Server setup
-    let app = Router::new()
-        .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
-        .route("/parse", post(parse_sql))
-        .route("/fingerprint", post(fingerprint_sql))
-        .route("/health", get(health_check))
-        .route("/", get(serve_index))
-        .with_state(state)
-        .layer(CorsLayer::permissive());
-
-    let http_addr = SocketAddr::from((
-        args.host.parse::<std::net::IpAddr>().unwrap_or_else(|_| {
-            eprintln!("Invalid host address, using 127.0.0.1");
-            "127.0.0.1".parse().unwrap()
-        }),
-        args.port,
-    ));
+    let grpc_web_layer = grpc_web.then(|| GrpcWebLayer::new(config.limits.max_body_bytes));
+    let multiplexer = single_port.then(|| Multiplexer {
+        routes: grpc_routes.clone(),
+        grpc_web: grpc_web_layer,
+    });
+    let app = build_router(state, &config, multiplexer);
+
+    // HTTP 与 gRPC 各自一份 TLS 配置（ALPN 不同），证书文件共用
+    let tls = if config.tls.enabled {
+        let load = |alpn: &[&[u8]]| {
+            ReloadableTls::new(&config.tls, alpn).unwrap_or_else(|e| {
+                eprintln!("Failed to load TLS configuration: {e}");
+                std::process::exit(1);
+            })
+        };
+        let http_tls = load(&[b"h2", b"http/1.1"]);
+        // gRPC-Web 通过 HTTP/1.1 访问
+        let grpc_tls = load(if grpc_web { &[b"h2", b"http/1.1"] } else { &[b"h2"] });
+        http_tls.spawn_reloader();
+        grpc_tls.spawn_reloader();
+        Some((http_tls, grpc_tls))
+    } else {
+        None
+    };
+    let scheme = if tls.is_some() { "https" } else { "http" };
+
+    // host 已在配置校验中检查
+    let host: std::net::IpAddr = config.server.host.parse().unwrap();
+    let http_addr = SocketAddr::from((host, config.server.http_port));
 
     // gRPC Server setup
-    let grpc_addr = SocketAddr::from((
-        args.host.parse::<std::net::IpAddr>().unwrap_or_else(|_| {
-            "127.0.0.1".parse().unwrap()
-        }),
-        args.grpc_port,
-    ));
+    let grpc_addr = SocketAddr::from((host, config.server.grpc_port));
+
+    let http_listener = bind_listener(http_addr, "HTTP").await;
+    let grpc_listener = if separate_grpc {
+        Some(bind_listener(grpc_addr, "gRPC").await)
+    } else {
+        None
+    };
 
-    let grpc_service = SqlParserGrpcService { cache };
+    // 快照在后台加载，期间已可处理请求，但 /health/ready 返回 503
+    if cache_config.dir.is_some() {
+        health.set_warming_up(true);
+        let cache = cache.clone();
+        let health = health.clone();
+        tokio::spawn(async move {
+            match cache.load_snapshot().await {
+                Ok(0) => {}
+                Ok(n) => println!("📦 Loaded {n} cache entries from snapshot"),
+                Err(e) => tracing::error!("Failed to load cache snapshot: {e}"),
+            }
+            health.set_warming_up(false);
+        });
+    }
+    if let Some(allowlist) = &allowlist {
+        allowlist.spawn_reloader();
+    }
+    if let (Some(registry), true) = (&registry, config.registry.file.is_some()) {
+        registry.spawn_saver(Duration::from_secs(config.registry.save_interval));
+    }
 
     println!("🚀 SQL to AST API Server v{}", env!("CARGO_PKG_VERSION"));
     println!();
-    println!("📡 HTTP Server running on http://{}", http_addr);
-    println!("   📚 OpenAPI docs: http://{}/swagger-ui", http_addr);
-    println!("   ❤️  Health check: http://{}/health", http_addr);
-    println!();
-    println!("🔌 gRPC Server running on http://{}", grpc_addr);
+    println!("📡 HTTP Server running on {scheme}://{http_addr}");
+    if config.endpoints.swagger_ui {
+        println!("   📚 OpenAPI docs: {scheme}://{http_addr}/swagger-ui");
+    }
+    println!("   ❤️  Health check: {scheme}://{http_addr}/health/live, /health/ready");
     println!();
+    if separate_grpc {
+        println!("🔌 gRPC Server running on {scheme}://{grpc_addr}");
+        println!();
+    } else if single_port {
+        println!("🔌 gRPC Server running on {scheme}://{http_addr} (single port)");
+        println!();
+    }
     println!("⚙️  Configuration:");
-    println!("   - Cache capacity: {}", args.cache_max_capacity);
-    println!("   - Cache TTL: {}s", args.cache_ttl);
+    if let Some(path) = &args.config {
+        println!("   - Config file: {}", path.display());
+    }
+    println!("   - Cache capacity: {}", cache_config.max_capacity);
+    println!(
+        "   - Statement / fingerprint cache capacity: {} / {}",
+        cache_config.statement_capacity, cache_config.fingerprint_capacity
+    );
+    println!("   - Cache TTL: {}s", cache_config.ttl);
+    println!("   - Shutdown timeout: {}s", config.server.shutdown_timeout);
+    println!(
+        "   - Parse workers: {} (queue depth {})",
+        metrics::METRICS.worker_threads.load(std::sync::atomic::Ordering::Relaxed),
+        config.limits.queue_depth
+    );
+    if let Some(dir) = &cache_config.dir {
+        println!(
+            "   - Persistent cache: {} ({:?}, up to {} entries, TTL {}s)",
+            dir.display(),
+            cache_config.persist_mode,
+            cache_config.disk_max_capacity,
+            cache_config.disk_ttl
+        );
+    }
+    if let Some(registry) = &registry {
+        let file = config.registry.file.as_ref();
+        println!(
+            "   - Fingerprint registry: {} fingerprints{}",
+            registry.len(),
+            file.map(|path| format!(" ({})", path.display())).unwrap_or_default()
+        );
+    }
+    if let Some(allowlist) = &allowlist {
+        println!(
+            "   - Allowlist: {} fingerprints, {} mode",
+            allowlist.len(),
+            allowlist.mode().as_str()
+        );
+    }
+    if let Some(dir) = &config.catalog.dir {
+        println!(
+            "   - Schema catalogs: {} namespaces ({})",
+            catalogs.len(),
+            dir.display()
+        );
+    }
+    if config.tls.enabled {
+        let mtls = if config.tls.client_ca.is_some() { ", mTLS" } else { "" };
+        println!("   - TLS: enabled{mtls}");
+    }
+    if rate_limit.requests_per_second > 0.0 {
+        println!(
+            "   - Rate limit: {} req/s per client (burst {})",
+            rate_limit.requests_per_second, rate_limit.burst
+        );
+    }
+    if rate_limit.max_concurrency > 0 {
+        println!("   - Max concurrent parses: {}", rate_limit.max_concurrency);
+    }
+    if config.auth.enabled {
+        println!(
+            "   - Auth: {} API key(s){}",
+            config.auth.api_keys.len(),
+            if config.auth.jwt.hs256_secret.is_some() || config.auth.jwt.jwks_file.is_some() {
+                " + JWT"
+            } else {
+                ""
+            }
+        );
+    }
     println!();
     println!("📖 HTTP API Endpoints:");
-    println!("   POST /parse - Parse SQL to AST");
-    println!("   POST /fingerprint - Generate SQL fingerprint");
-    println!("   GET  /health - Health check");
-    println!();
-    println!("📖 gRPC Services:");
-    println!("   ParseSql - Parse SQL to AST");
-    println!("   GenerateFingerprint - Generate SQL fingerprint");
-    println!("   HealthCheck - Health check");
+    if config.endpoints.parse {
+        println!("   POST /parse - Parse SQL to AST");
+    }
+    if config.endpoints.fingerprint {
+        println!("   POST /fingerprint - Generate SQL fingerprint");
+    }
+    println!("   GET  /health/live - Liveness check");
+    println!("   GET  /health/ready - Readiness check");
+    if config.endpoints.metrics {
+        println!("   GET  /metrics - Prometheus metrics");
+    }
     println!();
+    if config.endpoints.grpc {
+        println!("📖 gRPC Services:");
+        println!("   ParseSql - Parse SQL to AST");
+        println!("   GenerateFingerprint - Generate SQL fingerprint");
+        println!("   HealthCheck - Liveness / readiness check");
+        println!("   grpc.health.v1.Health/Check, Watch - Standard health checking");
+        if config.endpoints.grpc_reflection {
+            println!("   grpc.reflection.v1(alpha).ServerReflection - Server reflection");
+        }
+        if grpc_web {
+            println!("   (also callable from browsers via gRPC-Web)");
+        }
+        println!();
+    }
     println!(
         "🎯 Supported dialects: generic, mysql, postgresql, sqlite, hive, snowflake, mssql, ansi"
     );
 
     // Start both servers concurrently
-    let http_server = async {
-        let listener = tokio::net::TcpListener::bind(http_addr).await.unwrap();
-        axum::serve(listener, app).await.unwrap();
-    };
-
-    let grpc_server = async {
-        Server::builder()
-            .add_service(sql_parser::sql_parser_service_server::SqlParserServiceServer::new(grpc_service))
-            .serve(grpc_addr)
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(());
+    let http_tls = tls.as_ref().map(|(http_tls, _)| http_tls.clone());
+    let http_shutdown = wait_for_shutdown(shutdown_rx.clone());
+    let mut http_server = tokio::spawn(async move {
+        match http_tls {
+            Some(http_tls) => {
+                tls::serve_https(http_listener, app, http_tls, http_shutdown).await;
+                Ok(())
+            }
+            None => axum::serve(
+                http_listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(http_shutdown)
             .await
-            .unwrap();
+            .map_err(|e| e.to_string()),
+        }
+    });
+
+    // 浏览器跨域调用 gRPC-Web 需要 CORS，并接受 HTTP/1.1
+    let mut grpc_server_builder = Server::builder()
+        .accept_http1(grpc_web)
+        .layer(tower::util::option_layer(
+            grpc_web.then(|| cors_layer(&config.server.cors_allowed_origins)),
+        ))
+        .layer(tower::util::option_layer(grpc_web_layer));
+    let grpc_router = grpc_server_builder.add_routes(grpc_routes);
+    let grpc_tls = tls.as_ref().map(|(_, grpc_tls)| grpc_tls.clone());
+    let grpc_shutdown = wait_for_shutdown(shutdown_rx);
+    let mut grpc_server = tokio::spawn(async move {
+        let Some(listener) = grpc_listener else {
+            grpc_shutdown.await;
+            return Ok(());
+        };
+        let result = match grpc_tls {
+            Some(grpc_tls) => {
+                grpc_router
+                    .serve_with_incoming_shutdown(
+                        tls::grpc_incoming(listener, grpc_tls),
+                        grpc_shutdown,
+                    )
+                    .await
+            }
+            None => {
+                grpc_router
+                    .serve_with_incoming_shutdown(
+                        tokio_stream::wrappers::TcpListenerStream::new(listener),
+                        grpc_shutdown,
+                    )
+                    .await
+            }
+        };
+        result.map_err(|e| e.to_string())
+    });
+
+    let (mut http_done, mut grpc_done) = (false, false);
+    tokio::select! {
+        _ = shutdown_signal() => {},
+        result = &mut http_server => {
+            http_done = true;
+            log_server_exit("HTTP", result);
+        },
+        result = &mut grpc_server => {
+            grpc_done = true;
+            log_server_exit("gRPC", result);
+        },
+    }
+
+    // 先标记为未就绪，再停止接受新连接，并等待进行中的请求完成
+    println!("🛑 Shutting down, draining in-flight requests...");
+    health.start_draining();
+    let server = &config.server;
+    if server.shutdown_delay > 0 && !http_done && !grpc_done {
+        tokio::time::sleep(Duration::from_secs(server.shutdown_delay)).await;
+    }
+    let _ = shutdown_tx.send(());
+    let drain = async {
+        if !http_done {
+            let _ = http_server.await;
+        }
+        if !grpc_done {
+            let _ = grpc_server.await;
+        }
     };
+    let deadline = Duration::from_secs(server.shutdown_timeout);
+    if tokio::time::timeout(deadline, drain).await.is_err() {
+        tracing::warn!(
+            "Shutdown deadline of {}s exceeded, dropping remaining requests",
+            server.shutdown_timeout
+        );
+    }
 
-    tokio::select! {
-        _ = http_server => {},
-        _ = grpc_server => {},
+    match cache.save_snapshot().await {
+        Ok(0) => {}
+        Ok(n) => println!("📦 Saved {n} cache entries to snapshot"),
+        Err(e) => tracing::error!("Failed to save cache snapshot: {e}"),
+    }
+    if let Some(registry) = &registry {
+        match registry.save().await {
+            Ok(None) => {}
+            Ok(Some(n)) => println!("📦 Saved {n} fingerprints to registry file"),
+            Err(e) => tracing::error!("Failed to save fingerprint registry: {e}"),
+        }
     }
 }
 
@@ -764,10 +3361,1328 @@ mod tests {
     #[test]
     fn test_normalize_sql() {
         let sql = "SELECT   *   FROM    users   WHERE   id =  1";
-        let normalized = normalize_sql(sql);
+        let normalized = normalize_sql(sql, "generic", false);
         assert_eq!(normalized, "SELECT * FROM users WHERE id = 1");
     }
 
+    #[test]
+    fn test_normalize_sql_preserves_literals_and_comments() {
+        assert_eq!(
+            normalize_sql("  SELECT  'a  b' ,\n\t\"x  y\"  FROM t  ", "postgresql", false),
+            "SELECT 'a  b' , \"x  y\" FROM t"
+        );
+        assert_eq!(
+            normalize_sql("SELECT `a  b`  FROM t", "mysql", false),
+            "SELECT `a  b` FROM t"
+        );
+        assert_eq!(
+            normalize_sql("SELECT 1 /*  keep   me */  --  and  me\n  FROM t", "generic", false),
+            "SELECT 1 /*  keep   me */ --  and  me\n FROM t"
+        );
+        // 无法分词时原样返回
+        assert_eq!(normalize_sql("SELECT 'a  b", "generic", false), "SELECT 'a  b");
+    }
+
+    #[tokio::test]
+    async fn test_keyword_case_folding_parses_original_sql() {
+        let caches = Caches {
+            fold_keyword_case: true,
+            ..test_caches()
+        };
+        let (limits, timing) = (Limits::default(), WorkTiming::default());
+        // 与关键字同名的标识符保持原样，折叠只影响指纹缓存键
+        let sql = "select Name, count(*) AS Count from users group by Name";
+        let (ast, cached) = parse_with_cache(&caches, &limits, &timing, sql, "postgresql", false).await;
+        assert!(!cached);
+        let ast: serde_json::Value = rmp_serde::from_slice(&ast.unwrap()).unwrap();
+        let ast = ast.to_string();
+        assert!(ast.contains("\"value\":\"Name\"") && ast.contains("\"value\":\"Count\""), "{ast}");
+        assert!(!ast.contains("\"NAME\"") && !ast.contains("\"COUNT\""), "{ast}");
+
+        let (fingerprint, _) =
+            fingerprint_with_cache(&caches, &limits, &timing, sql, "postgresql", 0, false).await;
+        assert_eq!(
+            fingerprint.unwrap(),
+            "SELECT Name, count(*) AS Count FROM users GROUP BY Name"
+        );
+        // 只有关键字大小写不同的输入共享指纹缓存
+        let variant = "SELECT Name, COUNT(*) AS Count FROM users GROUP BY Name";
+        let (_, cached) =
+            fingerprint_with_cache(&caches, &limits, &timing, variant, "postgresql", 0, false).await;
+        assert!(cached);
+
+        // AST 缓存按原始文本区分，与关键字同名的标识符不会串用其他请求的大小写
+        let variant = "select name, count(*) AS count from users group by name";
+        let (ast, cached) = parse_with_cache(&caches, &limits, &timing, variant, "postgresql", false).await;
+        assert!(!cached);
+        let ast: serde_json::Value = rmp_serde::from_slice(&ast.unwrap()).unwrap();
+        assert!(ast.to_string().contains("\"value\":\"name\""), "{ast}");
+    }
+
+    #[test]
+    fn test_normalize_sql_keyword_case() {
+        assert_eq!(
+            normalize_sql("select  Name from \"users\" where amount = 'abc'", "postgresql", true),
+            "SELECT NAME FROM \"users\" WHERE amount = 'abc'"
+        );
+        assert_eq!(
+            normalize_sql("select Name from users", "postgresql", false),
+            "select Name from users"
+        );
+    }
+
+    async fn post_parse(app: &Router, sql: &str) -> serde_json::Value {
+        let body = serde_json::json!({ "sql": sql, "dialect": "mysql" });
+        send_json(app, "POST", "/parse", Some(body)).await.1
+    }
+
+    #[tokio::test]
+    async fn test_http_cache_distinguishes_literal_whitespace() {
+        let app = test_app(&Config::default());
+
+        let first = post_parse(&app, "SELECT 'a  b'").await;
+        assert_eq!(first["cached"], false);
+        assert!(first["queue_ms"].is_number() && first["parse_ms"].is_number());
+        let second = post_parse(&app, "SELECT   'a b'").await;
+        assert_eq!(second["cached"], false);
+        assert!(second["ast"].to_string().contains("\"a b\""));
+        assert!(!second["ast"].to_string().contains("\"a  b\""));
+
+        let third = post_parse(&app, "SELECT 'a b'").await;
+        assert_eq!(third["cached"], true);
+        assert!(third.get("parse_ms").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_http_parse_negotiates_output_format() {
+        use tower::ServiceExt;
+
+        let app = test_app(&Config::default());
+        let post = |accept: &str, body: serde_json::Value| {
+            let request = axum::http::Request::post("/parse")
+                .header("content-type", "application/json")
+                .header("accept", accept)
+                .body(axum::body::Body::from(body.to_string()))
+                .unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let content_type = response.headers()["content-type"].to_str().unwrap().to_string();
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                (status, content_type, bytes)
+            }
+        };
+        let sql = "SELECT a FROM t WHERE b = 1";
+
+        let (_, content_type, json) = post("application/json", serde_json::json!({ "sql": sql })).await;
+        assert_eq!(content_type, "application/json");
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+
+        let (status, content_type, msgpack) =
+            post("application/msgpack", serde_json::json!({ "sql": sql })).await;
+        assert_eq!((status, content_type.as_str()), (StatusCode::OK, "application/msgpack"));
+        let msgpack: serde_json::Value = rmp_serde::from_slice(&msgpack).unwrap();
+        assert_eq!(msgpack["ast"], json["ast"]);
+        assert_eq!(msgpack["cached"], true);
+
+        let (_, content_type, cbor) = post("application/cbor", serde_json::json!({ "sql": sql })).await;
+        assert_eq!(content_type, "application/cbor");
+        let cbor: serde_json::Value = ciborium::from_reader(cbor.as_ref()).unwrap();
+        assert_eq!(cbor["ast"], json["ast"]);
+
+        let (_, _, compact) =
+            post("*/*", serde_json::json!({ "sql": sql, "compact": true })).await;
+        let compact: serde_json::Value = serde_json::from_slice(&compact).unwrap();
+        let select = &compact["ast"][0]["Query"]["body"]["Select"];
+        assert!(select.get("distinct").is_none() && select.get("lateral_views").is_none());
+        assert!(compact["ast"].to_string().len() < json["ast"].to_string().len());
+
+        // 错误响应同样按协商的格式编码
+        let (status, _, error) = post("application/msgpack", serde_json::json!({ "sql": "SELEC" })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let error: serde_json::Value = rmp_serde::from_slice(&error).unwrap();
+        assert!(error["error"].as_str().unwrap().starts_with("Failed to parse SQL"));
+
+        let (status, _, error) = post("text/html", serde_json::json!({ "sql": sql })).await;
+        assert_eq!(status, StatusCode::NOT_ACCEPTABLE);
+        let error: serde_json::Value = serde_json::from_slice(&error).unwrap();
+        assert_eq!(error["code"], "not_acceptable");
+    }
+
+    #[tokio::test]
+    async fn test_http_parse_with_spans() {
+        let app = test_app(&Config::default());
+        let post = |body| async { send_json(&app, "POST", "/parse", Some(body)).await.1 };
+        // 多余的空白会在规范化时去掉，位置仍应指向原始输入
+        let sql = "select   a,\n    b   from t";
+
+        let plain = post(serde_json::json!({ "sql": sql })).await;
+        assert!(!plain["ast"].to_string().contains("\"span\""));
+
+        let spanned = post(serde_json::json!({ "sql": sql, "with_spans": true })).await;
+        assert_eq!(spanned["cached"], false);
+        let select = &spanned["ast"][0]["Query"]["body"]["Select"];
+        let b = &select["projection"][1]["UnnamedExpr"];
+        assert_eq!(
+            b["span"],
+            serde_json::json!({
+                "start": {"line": 2, "column": 5, "offset": 16},
+                "end": {"line": 2, "column": 6, "offset": 17}
+            })
+        );
+        assert_eq!(b["Identifier"]["span"], b["span"]);
+        assert_eq!(select["from"][0]["relation"]["span"]["start"]["offset"], sql.len() - 1);
+
+        // compact 不会把带 span 的枚举变体当作普通对象
+        let compact =
+            post(serde_json::json!({ "sql": sql, "with_spans": true, "compact": true })).await;
+        let b = &compact["ast"][0]["Query"]["body"]["Select"]["projection"][1]["UnnamedExpr"];
+        assert_eq!(b["Identifier"], serde_json::json!({"value": "b", "span": b["span"]}));
+    }
+
+    #[tokio::test]
+    async fn test_http_parse_recover() {
+        let app = test_app(&Config::default());
+        let post = |body| send_json(&app, "POST", "/parse", Some(body));
+        let sql = "SELECT a FROM t;\nSELECT FROM WHERE;\nUPDATE t SET a = 1";
+
+        // 默认整体失败
+        let (status, _) = post(serde_json::json!({ "sql": sql })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, body) = post(serde_json::json!({ "sql": sql, "recover": true })).await;
+        assert_eq!(status, StatusCode::OK);
+        let ast = body["ast"].as_array().unwrap();
+        assert_eq!(ast.len(), 3);
+        assert!(ast[0].get("Query").is_some());
+        assert!(ast[2].get("Update").is_some());
+        let error = &ast[1]["error"];
+        assert!(error["message"].as_str().unwrap().starts_with("Failed to parse SQL"));
+        assert_eq!(error["text"], "SELECT FROM WHERE");
+        assert_eq!(error["start"], serde_json::json!({"line": 2, "column": 1, "offset": 17}));
+        assert_eq!(body["cached"], false);
+
+        // 各语句单独缓存，第二次全部命中
+        let (_, body) = post(serde_json::json!({ "sql": "SELECT a FROM t; UPDATE t SET a = 1", "recover": true })).await;
+        assert_eq!(body["cached"], true);
+
+        // 位置平移为相对整个输入
+        let (_, body) =
+            post(serde_json::json!({ "sql": sql, "recover": true, "with_spans": true })).await;
+        let update = &body["ast"][2];
+        let column = &update["Update"]["assignments"][0]["target"]["ColumnName"][0];
+        assert_eq!(
+            column["span"]["start"],
+            serde_json::json!({"line": 3, "column": 14, "offset": sql.rfind("a = 1").unwrap()})
+        );
+    }
+
+    #[test]
+    fn test_statement_kind_matches_ast_key() {
+        let limits = Limits::default();
+        for (sql, dialect) in [
+            ("SELECT 1", "generic"),
+            ("COMMIT", "generic"),
+            ("UNLOCK TABLES", "mysql"),
+            ("LISTEN channel", "postgresql"),
+            ("CREATE INDEX i ON t (a)", "generic"),
+        ] {
+            let statements = parse_statements(sql, dialect, &limits).unwrap();
+            let expected = match ast_json::to_value(&statements[0]).unwrap() {
+                serde_json::Value::Object(fields) => fields.keys().next().unwrap().clone(),
+                serde_json::Value::String(kind) => kind,
+                other => panic!("{other}"),
+            };
+            assert_eq!(statement_kind(&statements[0]), expected, "{sql}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_split() {
+        let app = test_app(&Config::default());
+        let post = |body| send_json(&app, "POST", "/split", Some(body));
+        let sql = "-- 建表\ncreate table t (a int);\nSELEC oops;\n\nINSERT INTO t\n  VALUES (1)";
+
+        let (status, body) = post(serde_json::json!({ "sql": sql, "dialect": "mysql" })).await;
+        assert_eq!(status, StatusCode::OK);
+        let statements = body["statements"].as_array().unwrap();
+        assert_eq!(statements.len(), 3);
+        assert_eq!(statements[0]["text"], "-- 建表\ncreate table t (a int)");
+        assert_eq!(statements[0]["kind"], "CreateTable");
+        assert!(statements[0].get("error").is_none());
+        assert!(statements[1]["error"].as_str().unwrap().starts_with("Failed to parse SQL"));
+        assert!(statements[1].get("kind").is_none());
+        assert_eq!(statements[2]["kind"], "Insert");
+        assert_eq!(
+            statements[2]["start"],
+            serde_json::json!({"line": 5, "column": 1, "offset": sql.find("INSERT").unwrap()})
+        );
+        assert_eq!(
+            statements[2]["end"],
+            serde_json::json!({"line": 6, "column": 13, "offset": sql.len()})
+        );
+
+        let (status, body) = post(serde_json::json!({ "sql": sql, "dialect": "oracle" })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].as_str().unwrap().starts_with("Unsupported dialect"));
+    }
+
+    #[tokio::test]
+    async fn test_http_fingerprint_registry() {
+        // 接收 webhook 的本地服务
+        let (events_tx, mut events) = tokio::sync::mpsc::unbounded_channel();
+        let hook = Router::new().route(
+            "/hook",
+            post(move |Json(event): Json<serde_json::Value>| async move {
+                events_tx.send(event).unwrap();
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let hook_addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, hook).await });
+
+        let mut config = Config::default();
+        config.registry.enabled = true;
+        config.registry.webhook_url = Some(format!("http://{hook_addr}/hook"));
+        let registry = FingerprintRegistry::from_config(&config.registry).unwrap();
+        let app = build_router(
+            AppState {
+                registry: Some(registry.clone()),
+                ..test_state()
+            },
+            &config,
+            None,
+        );
+        let fingerprint = |sql: &str| {
+            let body = serde_json::json!({ "sql": sql, "dialect": "mysql" });
+            send_json(&app, "POST", "/fingerprint", Some(body))
+        };
+        let list = |query: &str| {
+            axum::http::Request::get(format!("/fingerprints{query}"))
+                .body(axum::body::Body::empty())
+                .unwrap()
+        };
+
+        for sql in ["SELECT a FROM t WHERE id = 1", "SELECT a FROM t WHERE id = 2", "DELETE FROM t"] {
+            assert_eq!(fingerprint(sql).await.0, StatusCode::OK);
+        }
+        assert_eq!(fingerprint("SELEC oops").await.0, StatusCode::BAD_REQUEST);
+
+        let (status, body) = send(&app, list("")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((body["total"].as_u64(), body["limit"].as_u64()), (Some(2), Some(50)));
+        let first = &body["fingerprints"][0];
+        assert_eq!(first["fingerprint"], "SELECT a FROM t WHERE id = ?");
+        assert_eq!(first["count"], 2);
+        assert_eq!(first["sample"], "SELECT a FROM t WHERE id = 1");
+        assert_eq!(first["dialect"], "mysql");
+
+        let (_, body) = send(&app, list("?sort=count&order=asc&offset=1&limit=5000")).await;
+        assert_eq!(body["limit"], MAX_PAGE_SIZE);
+        assert_eq!(body["fingerprints"][0]["fingerprint"], "SELECT a FROM t WHERE id = ?");
+        assert_eq!(send(&app, list("?sort=bogus")).await.0, StatusCode::BAD_REQUEST);
+
+        // 每个新指纹只通知一次
+        let mut received = Vec::new();
+        for _ in 0..2 {
+            let event = tokio::time::timeout(Duration::from_secs(5), events.recv()).await;
+            received.push(event.unwrap().unwrap());
+        }
+        received.sort_by_key(|event| event["fingerprint"].as_str().unwrap().to_string());
+        assert_eq!(received[0]["event"], "new_fingerprint");
+        assert_eq!(received[0]["fingerprint"], "DELETE FROM t");
+        assert_eq!(received[1]["hash"], registry::fingerprint_hash("SELECT a FROM t WHERE id = ?"));
+        assert!(tokio::time::timeout(Duration::from_millis(200), events.recv()).await.is_err());
+
+        // 未启用 registry 时不提供该接口
+        let app = test_app(&Config::default());
+        assert_eq!(send(&app, list("")).await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_http_check_allowlist() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("allowlist.txt");
+        let approved = registry::fingerprint_hash("SELECT a FROM t WHERE id = ?");
+        std::fs::write(&path, format!("{approved}  # SELECT a FROM t WHERE id = ?\n")).unwrap();
+
+        let app = |mode: AllowlistMode| {
+            let mut config = Config::default();
+            config.allowlist.enabled = true;
+            config.allowlist.file = Some(path.clone());
+            config.allowlist.mode = mode;
+            build_router(
+                AppState {
+                    allowlist: Some(Allowlist::from_config(&config.allowlist).unwrap()),
+                    ..test_state()
+                },
+                &config,
+                None,
+            )
+        };
+        async fn check(app: &Router, sql: &str, dialect: &str) -> (StatusCode, serde_json::Value) {
+            let body = serde_json::json!({ "sql": sql, "dialect": dialect });
+            send_json(app, "POST", "/check", Some(body)).await
+        }
+
+        let enforce = app(AllowlistMode::Enforce);
+        let (status, body) = check(&enforce, "select a from t where id = 42", "mysql").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["allowed"], true);
+        assert_eq!(body["mode"], "enforce");
+        assert_eq!(body["hash"], approved);
+        assert_eq!(body["fingerprint"], "SELECT a FROM t WHERE id = ?");
+
+        let (_, body) = check(&enforce, "DELETE FROM t", "mysql").await;
+        assert_eq!(body["allowed"], false);
+        assert_eq!(body["learned"], false);
+        let (_, body) = check(&enforce, "SELEC oops", "mysql").await;
+        assert_eq!(body["allowed"], false);
+        assert!(body.get("fingerprint").is_none());
+        assert!(body["error"].as_str().unwrap().starts_with("Failed to parse SQL"));
+        let (status, _) = check(&enforce, "SELECT 1", "oracle").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // 学习模式放行所有查询，并把新指纹追加到文件
+        let learn = app(AllowlistMode::Learn);
+        let (_, body) = check(&learn, "DELETE FROM t", "mysql").await;
+        assert_eq!((body["allowed"].as_bool(), body["learned"].as_bool()), (Some(true), Some(true)));
+        let (_, body) = check(&learn, "DELETE FROM t", "mysql").await;
+        assert_eq!(body["learned"], false);
+        let (_, body) = check(&learn, "SELEC oops", "mysql").await;
+        assert_eq!(body["allowed"], true);
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.ends_with("  # DELETE FROM t\n"), "{content}");
+
+        let (_, body) = check(&app(AllowlistMode::Enforce), "DELETE FROM t", "mysql").await;
+        assert_eq!(body["allowed"], true);
+
+        // 未启用 allowlist 时不提供该接口
+        let (status, _) = check(&test_app(&Config::default()), "SELECT 1", "generic").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_digest_falls_back_after_deadline() {
+        let log = "# Query_time: 1.0\nSELECT a FROM t WHERE user_id = 1;\n\
+                   # Query_time: 2.0\nselect a from t where user_id = 2;\n";
+        let params: DigestParams = serde_json::from_value(serde_json::json!({})).unwrap();
+        let limits = Limits::default();
+
+        let report = build_digest(log, &params, &limits, None).unwrap();
+        assert_eq!((report.fingerprints, report.unparsed), (1, 0));
+        assert_eq!(report.entries[0].fingerprint, "SELECT a FROM t WHERE user_id = ?");
+
+        // 截止时间已过时不再解析，全部按词法归一化
+        let report = build_digest(log, &params, &limits, Some(Instant::now())).unwrap();
+        assert_eq!((report.fingerprints, report.unparsed), (1, 2));
+        assert!(!report.entries[0].parsed);
+        assert_eq!(report.entries[0].fingerprint, "SELECT a FROM t WHERE user_id = ?");
+    }
+
+    #[tokio::test]
+    async fn test_http_digest() {
+        let app = test_app(&Config::default());
+        let post = |uri: &str, log: &str| {
+            let request = axum::http::Request::post(uri)
+                .header("content-type", "text/plain")
+                .body(axum::body::Body::from(log.to_string()))
+                .unwrap();
+            send(&app, request)
+        };
+        let log = "\
+2024-01-15 10:00:00 UTC [1] LOG:  duration: 30.0 ms  statement: SELECT * FROM t WHERE id = 1
+2024-01-15 10:00:01 UTC [1] LOG:  duration: 10.0 ms  statement: select *
+\tfrom t where id = 2
+2024-01-15 10:00:02 UTC [1] LOG:  duration: 50.0 ms  statement: UPDATE t SET a = 'x' WHERE b IN (1, 2, 3)
+2024-01-15 10:00:03 UTC [1] LOG:  duration: 5.0 ms  statement: SELEC broken 1
+";
+
+        let (status, body) = post("/digest?max_in_values=1", log).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["format"], "postgresql");
+        assert_eq!((body["queries"].as_u64(), body["fingerprints"].as_u64()), (Some(4), Some(3)));
+        assert_eq!(body["unparsed"], 1);
+        let entries = body["entries"].as_array().unwrap();
+        assert_eq!(entries[0]["fingerprint"], "UPDATE t SET a = ? WHERE b IN (?)");
+        assert_eq!(entries[1]["fingerprint"], "SELECT * FROM t WHERE id = ?");
+        assert_eq!(entries[1]["count"], 2);
+        assert_eq!(entries[1]["sample"], "SELECT * FROM t WHERE id = 1");
+        assert_eq!(entries[1]["first_seen"], "2024-01-15 10:00:00 UTC");
+        assert_eq!(entries[1]["last_seen"], "2024-01-15 10:00:01 UTC");
+        assert_eq!(entries[2]["parsed"], false);
+        assert!(body["elapsed_ms"].is_number());
+
+        let (_, body) = post("/digest?limit=1", log).await;
+        assert_eq!(body["entries"].as_array().unwrap().len(), 1);
+
+        let (status, body) = post("/digest", "not a log").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].as_str().unwrap().starts_with("Unrecognized slow log format"));
+        let (status, _) = post("/digest?format=oracle", log).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_http_catalog_validate() {
+        let app = test_app(&Config::default());
+        let call = |method, uri, body| send_json(&app, method, uri, body);
+
+        let ddl = "CREATE TABLE users (id INT PRIMARY KEY, name TEXT);\n\
+                   CREATE INDEX users_name ON users (name);\n\
+                   CREATE TABLE orders (id INT, user_id INT REFERENCES users (id));";
+        let (status, body) = call(
+            "PUT",
+            "/catalogs/app",
+            Some(serde_json::json!({ "ddl": ddl, "dialect": "postgresql" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!((body["tables"].as_u64(), body["columns"].as_u64()), (Some(2), Some(4)));
+        assert_eq!((body["skipped"].as_u64(), body["replaced"].as_bool()), (Some(1), Some(false)));
+        let (_, body) = call("GET", "/catalogs/app", None).await;
+        assert_eq!(body["tables"]["orders"], serde_json::json!(["id", "user_id"]));
+
+        let sql = "SELECT id, nmae\nFROM users u JOIN orders o ON o.user_id = u.id";
+        let (status, body) = call(
+            "POST",
+            "/validate",
+            Some(serde_json::json!({ "sql": sql, "dialect": "postgresql", "namespace": "app" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["valid"], false);
+        let diagnostics = body["diagnostics"].as_array().unwrap();
+        assert_eq!(diagnostics.len(), 2, "{body}");
+        assert_eq!(diagnostics[0]["code"], "ambiguous_column");
+        assert_eq!(diagnostics[0]["start"], serde_json::json!({ "line": 1, "column": 8, "offset": 7 }));
+        assert_eq!(diagnostics[1]["code"], "unknown_column");
+        assert_eq!(diagnostics[1]["end"]["offset"], 15);
+
+        // JSON 目录替换同名命名空间
+        let (_, body) = call(
+            "PUT",
+            "/catalogs/app",
+            Some(serde_json::json!({ "tables": { "t": ["a"] }, "functions": ["f"] })),
+        )
+        .await;
+        assert_eq!(body["replaced"], true);
+        let (_, body) = call(
+            "POST",
+            "/validate",
+            Some(serde_json::json!({ "sql": "SELECT f(a) FROM t", "namespace": "app" })),
+        )
+        .await;
+        assert_eq!(body["valid"], true);
+
+        let sql = "SELECT * FROM t JOIN (SELECT *, 1 AS b FROM t) s USING (a); SELECT x.* FROM t";
+        let (status, body) = call(
+            "POST",
+            "/expand",
+            Some(serde_json::json!({ "sql": sql, "dialect": "mysql", "namespace": "app" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body["sql"],
+            "SELECT a, s.b FROM t JOIN (SELECT t.a, 1 AS b FROM t) AS s USING(a);\nSELECT x.* FROM t;\n"
+        );
+        assert_eq!(body["expanded"], 2);
+        assert_eq!(body["unresolved"][0]["code"], "unresolved_wildcard");
+
+        for uri in ["/validate", "/expand"] {
+            let (status, body) = call(
+                "POST",
+                uri,
+                Some(serde_json::json!({ "sql": "SELECT 1", "namespace": "missing" })),
+            )
+            .await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert_eq!(body["code"], "unknown_namespace");
+        }
+        let (status, _) = call(
+            "PUT",
+            "/catalogs/app",
+            Some(serde_json::json!({ "ddl": "CREATE TABLE t (a INT)", "tables": { "t": ["a"] } })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = call("PUT", "/catalogs/bad.name", Some(serde_json::json!({}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = call("DELETE", "/catalogs/app", None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = call("GET", "/catalogs/app", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_http_limits_return_error_codes() {
+        let mut config = Config::default();
+        config.limits.max_body_bytes = 1000;
+        config.limits.max_sql_length = 200;
+        config.limits.max_statements = 2;
+        config.limits.max_nesting_depth = 10;
+        let app = build_router(
+            AppState {
+                limits: Limits::from_config(&config.limits),
+                ..test_state()
+            },
+            &config,
+            None,
+        );
+        let post = |sql: String| {
+            let body = serde_json::json!({ "sql": sql });
+            let app = &app;
+            async move {
+                let (status, body) = send_json(app, "POST", "/parse", Some(body)).await;
+                (status, body["code"].as_str().map(str::to_string))
+            }
+        };
+
+        let (status, code) = post("x".repeat(2000)).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(code.as_deref(), Some("body_too_large"));
+        let (status, code) = post(format!("SELECT '{}'", "x".repeat(300))).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(code.as_deref(), Some("sql_too_long"));
+        let (status, code) = post("SELECT 1; SELECT 2; SELECT 3".to_string()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(code.as_deref(), Some("too_many_statements"));
+        let (status, code) = post(format!("SELECT {}1{}", "(".repeat(20), ")".repeat(20))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(code.as_deref(), Some("nesting_too_deep"));
+        let (status, code) = post("SELEC 1".to_string()).await;
+        assert_eq!((status, code), (StatusCode::BAD_REQUEST, None));
+        let (status, _) = post("SELECT 1; SELECT 2".to_string()).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_endpoints_are_not_routed() {
+        let mut config = Config::default();
+        config.endpoints.parse = false;
+        let app = test_app(&config);
+
+        let body = serde_json::json!({ "sql": "SELECT 1" });
+        let (status, _) = send_json(&app, "POST", "/parse", Some(body)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_tls_cert_and_key_flags_go_together() {
+        let parse = |args: &[&str]| {
+            CliArgs::try_parse_from(std::iter::once("sql-ast-api").chain(args.iter().copied()))
+        };
+        assert!(parse(&["--tls-cert", "cert.pem"]).is_err());
+        assert!(parse(&["--tls-key", "key.pem"]).is_err());
+        let args = parse(&["--tls-cert", "cert.pem", "--tls-key", "key.pem"]).unwrap();
+        let mut config = Config::default();
+        args.apply_to(&mut config);
+        assert!(config.tls.enabled);
+    }
+
+    #[tokio::test]
+    async fn test_cors_allowed_origins() {
+        use tower::ServiceExt;
+
+        let allowed = |origins: &[&str], origin: &'static str| {
+            let mut config = Config::default();
+            config.server.cors_allowed_origins = origins.iter().map(|o| o.to_string()).collect();
+            let app = test_app(&config);
+            async move {
+                let request = axum::http::Request::get("/health")
+                    .header("origin", origin)
+                    .body(axum::body::Body::empty())
+                    .unwrap();
+                let response = app.oneshot(request).await.unwrap();
+                response
+                    .headers()
+                    .get("access-control-allow-origin")
+                    .map(|value| value.to_str().unwrap().to_string())
+            }
+        };
+
+        // 默认允许任意来源
+        assert_eq!(allowed(&[], "https://app.example.com").await.as_deref(), Some("*"));
+        let origins = ["https://app.example.com"];
+        assert_eq!(
+            allowed(&origins, "https://app.example.com").await.as_deref(),
+            Some("https://app.example.com")
+        );
+        assert_eq!(allowed(&origins, "https://evil.example.com").await, None);
+        assert_eq!(allowed(&["*"], "https://evil.example.com").await.as_deref(), Some("*"));
+    }
+
+    #[tokio::test]
+    async fn test_http_auth_protects_api_routes() {
+        let mut config = Config::default();
+        config.auth.enabled = true;
+        config.auth.api_keys.push(config::ApiKeyConfig {
+            name: "reader".to_string(),
+            key: "k1".to_string(),
+            endpoints: vec!["fingerprint".to_string()],
+            quota: 0,
+        });
+        let app = build_router(
+            AppState {
+                auth: Some(Authenticator::from_config(&config.auth).unwrap()),
+                ..test_state()
+            },
+            &config,
+            None,
+        );
+        let status = |path: &str, key: Option<&str>| {
+            let mut builder = axum::http::Request::post(path)
+                .header("content-type", "application/json");
+            if let Some(key) = key {
+                builder = builder.header("x-api-key", key);
+            }
+            let request = builder
+                .body(axum::body::Body::from(r#"{"sql":"SELECT 1"}"#))
+                .unwrap();
+            let app = &app;
+            async move { send(app, request).await.0 }
+        };
+
+        assert_eq!(status("/parse", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status("/parse", Some("k1")).await, StatusCode::FORBIDDEN);
+        assert_eq!(status("/fingerprint", Some("k1")).await, StatusCode::OK);
+        assert_eq!(send_json(&app, "GET", "/health", None).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_http_rate_limit_returns_retry_after() {
+        use tower::ServiceExt;
+
+        let mut config = Config::default();
+        config.rate_limit.requests_per_second = 0.5;
+        config.rate_limit.burst = 1;
+        let app = build_router(
+            AppState {
+                limiter: Some(RateLimiter::from_config(&config.rate_limit)),
+                ..test_state()
+            },
+            &config,
+            None,
+        );
+        let request = || {
+            axum::http::Request::post("/parse")
+                .header("content-type", "application/json")
+                .extension(axum::extract::ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 4000))))
+                .body(axum::body::Body::from(r#"{"sql":"SELECT 1"}"#))
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["retry-after"], "2");
+
+        // 限流不影响健康检查与指标
+        let metrics = axum::http::Request::get("/metrics")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = app.oneshot(metrics).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_cache_distinguishes_literal_whitespace() {
+        use sql_parser::sql_parser_service_server::SqlParserService;
+
+        let service = test_grpc_service();
+        let parse = |sql: &str| {
+            Request::new(sql_parser::ParseSqlRequest {
+                sql: sql.to_string(),
+                dialect: "mysql".to_string(),
+                no_cache: false,
+                ast_format: sql_parser::AstFormat::Json as i32,
+                compact: false,
+                with_spans: false,
+                recover: false,
+            })
+        };
+
+        let _ = service.parse_sql(parse("SELECT 'a  b'")).await.unwrap();
+        let response = service.parse_sql(parse("SELECT 'a b'")).await.unwrap();
+        match response.into_inner().result {
+            Some(sql_parser::parse_sql_response::Result::Success(success)) => {
+                assert!(!success.cached);
+                assert!(success.ast_json.contains("\"a b\""));
+            }
+            other => panic!("unexpected response: {other:?}"),
+        }
+
+        let mut request = parse("SELECT  x");
+        request.get_mut().with_spans = true;
+        let response = service.parse_sql(request).await.unwrap();
+        let Some(sql_parser::parse_sql_response::Result::Success(success)) =
+            response.into_inner().result
+        else {
+            panic!("expected an AST");
+        };
+        let ast: serde_json::Value = serde_json::from_str(&success.ast_json).unwrap();
+        let x = &ast[0]["Query"]["body"]["Select"]["projection"][0]["UnnamedExpr"];
+        assert_eq!(x["span"]["start"]["offset"], 8);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_typed_ast_format() {
+        use sql_parser::ast::{select_item, set_expr, statement};
+        use sql_parser::sql_parser_service_server::SqlParserService;
+
+        let service = test_grpc_service();
+        let parse = |sql: &str| {
+            Request::new(sql_parser::ParseSqlRequest {
+                sql: sql.to_string(),
+                dialect: "generic".to_string(),
+                no_cache: false,
+                ast_format: sql_parser::AstFormat::Proto as i32,
+                compact: false,
+                with_spans: false,
+                recover: false,
+            })
+        };
+
+        let _ = service.parse_sql(parse("SELECT a FROM t")).await.unwrap();
+        let response = service.parse_sql(parse("SELECT a FROM t")).await.unwrap();
+        let Some(sql_parser::parse_sql_response::Result::Success(success)) =
+            response.into_inner().result
+        else {
+            panic!("expected a successful parse");
+        };
+        assert!(success.cached);
+        assert!(success.ast_json.is_empty());
+        let Some(statement::Kind::Query(query)) = &success.statements[0].kind else {
+            panic!("expected a typed query");
+        };
+        let Some(set_expr::Kind::Select(select)) = &query.body.as_ref().unwrap().kind else {
+            panic!("expected a typed select");
+        };
+        assert!(matches!(select.projection[0].kind, Some(select_item::Kind::Expr(_))));
+
+        let response = service.parse_sql(parse("SELEC a")).await.unwrap();
+        assert!(matches!(
+            response.into_inner().result,
+            Some(sql_parser::parse_sql_response::Result::Error(_))
+        ));
+
+        // 仅 JSON 格式支持的选项不会被静默忽略
+        for set in [
+            |r: &mut sql_parser::ParseSqlRequest| r.compact = true,
+            |r: &mut sql_parser::ParseSqlRequest| r.with_spans = true,
+            |r: &mut sql_parser::ParseSqlRequest| r.recover = true,
+        ] {
+            let mut request = parse("SELECT a FROM t");
+            set(request.get_mut());
+            let status = service.parse_sql(request).await.unwrap_err();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_tokenize() {
+        let app = test_app(&Config::default());
+        let post = |body| send_json(&app, "POST", "/tokenize", Some(body));
+
+        let (status, json) =
+            post(serde_json::json!({ "sql": "SELECT `a`\nFROM t", "dialect": "mysql" })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(json.get("error").is_none());
+        assert_eq!(
+            json["tokens"][2],
+            serde_json::json!({
+                "kind": "quoted_identifier",
+                "text": "`a`",
+                "start": { "line": 1, "column": 8, "offset": 7 },
+                "end": { "line": 1, "column": 11, "offset": 10 }
+            })
+        );
+        assert_eq!(json["tokens"][4]["start"]["line"], 2);
+
+        // 分词失败时仍返回已识别的部分
+        let (status, json) = post(serde_json::json!({ "sql": "SELECT 'abc" })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["tokens"].as_array().unwrap().len(), 2);
+        assert_eq!(json["error"]["message"], "Unterminated string literal");
+        assert_eq!(json["error"]["position"]["column"], 8);
+
+        let (status, json) = post(serde_json::json!({ "sql": "SELECT 1", "dialect": "oracle" })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(json["error"].as_str().unwrap().starts_with("Unsupported dialect"));
+    }
+
+    #[tokio::test]
+    async fn test_grpc_check_sql() {
+        use sql_parser::sql_parser_service_server::SqlParserService;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("allowlist.txt");
+        std::fs::write(&path, registry::fingerprint_hash("SELECT ?")).unwrap();
+        let mut config = Config::default();
+        config.allowlist.enabled = true;
+        config.allowlist.file = Some(path);
+        let mut service = SqlParserGrpcService {
+            allowlist: Some(Allowlist::from_config(&config.allowlist).unwrap()),
+            ..test_grpc_service()
+        };
+        let check = |sql: &str| {
+            Request::new(sql_parser::CheckSqlRequest {
+                sql: sql.to_string(),
+                dialect: "generic".to_string(),
+            })
+        };
+
+        for (sql, allowed) in [("SELECT 1", true), ("SELECT 1, 2", false), ("SELEC", false)] {
+            let response = service.check_sql(check(sql)).await.unwrap();
+            let Some(sql_parser::check_sql_response::Result::Success(success)) =
+                response.into_inner().result
+            else {
+                panic!("expected a decision for {sql}");
+            };
+            assert_eq!(success.allowed, allowed, "{sql}");
+            assert_eq!(success.mode, "enforce");
+            assert_eq!(success.parse_error.is_empty(), sql != "SELEC");
+        }
+
+        service.allowlist = None;
+        let status = service.check_sql(check("SELECT 1")).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unimplemented);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_tokenize() {
+        use sql_parser::sql_parser_service_server::SqlParserService;
+
+        let service = test_grpc_service();
+        let tokenize = |sql: &str| {
+            Request::new(sql_parser::TokenizeRequest {
+                sql: sql.to_string(),
+                dialect: "postgresql".to_string(),
+            })
+        };
+
+        let response = service.tokenize(tokenize("SELECT $1, 'x")).await.unwrap();
+        let Some(sql_parser::tokenize_response::Result::Success(success)) =
+            response.into_inner().result
+        else {
+            panic!("expected tokens");
+        };
+        let kinds: Vec<_> = success.tokens.iter().map(|t| t.kind()).collect();
+        assert_eq!(
+            kinds,
+            [
+                sql_parser::TokenKind::Keyword,
+                sql_parser::TokenKind::Whitespace,
+                sql_parser::TokenKind::Placeholder,
+                sql_parser::TokenKind::Operator,
+                sql_parser::TokenKind::Whitespace,
+            ]
+        );
+        let error = success.token_error.unwrap();
+        assert_eq!(error.position.unwrap().offset, 11);
+
+        let response = service
+            .tokenize(Request::new(sql_parser::TokenizeRequest {
+                sql: "SELECT 1".to_string(),
+                dialect: "oracle".to_string(),
+            }))
+            .await
+            .unwrap();
+        assert!(matches!(
+            response.into_inner().result,
+            Some(sql_parser::tokenize_response::Result::Error(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_readiness_during_warm_up_and_drain() {
+        use sql_parser::sql_parser_service_server::SqlParserService;
+
+        let health = Health::new();
+        let app = build_router(
+            AppState {
+                health: health.clone(),
+                ..test_state()
+            },
+            &Config::default(),
+            None,
+        );
+        let service = SqlParserGrpcService {
+            health: health.clone(),
+            ..test_grpc_service()
+        };
+        let status = |path| {
+            let app = &app;
+            async move { send_json(app, "GET", path, None).await.0 }
+        };
+        let probe = |probe: &str| {
+            service.health_check(Request::new(sql_parser::HealthCheckRequest {
+                probe: probe.to_string(),
+            }))
+        };
+
+        assert_eq!(status("/health/ready").await, StatusCode::OK);
+        assert!(probe("ready").await.unwrap().into_inner().ready);
+
+        health.set_warming_up(true);
+        assert_eq!(status("/health/ready").await, StatusCode::SERVICE_UNAVAILABLE);
+        health.set_warming_up(false);
+
+        health.start_draining();
+        assert_eq!(status("/health/ready").await, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(status("/health/live").await, StatusCode::OK);
+        let ready = probe("ready").await.unwrap().into_inner();
+        assert_eq!((ready.status.as_str(), ready.ready), ("draining", false));
+        assert_eq!(probe("live").await.unwrap().into_inner().status, "ok");
+        assert_eq!(probe("startup").await.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_standard_health_and_reflection() {
+        use tonic_health::pb::health_client::HealthClient;
+        use tonic_health::pb::{health_check_response::ServingStatus, HealthCheckRequest};
+
+        let health = Health::new();
+        let service = SqlParserGrpcService {
+            health: health.clone(),
+            ..test_grpc_service()
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_routes(build_grpc_routes(service, &Config::default()))
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+        );
+
+        let channel = tonic::transport::Endpoint::from_shared(format!("http://{addr}"))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let client = HealthClient::new(channel.clone());
+        let check = |service: &str| {
+            let request = HealthCheckRequest {
+                service: service.to_string(),
+            };
+            let mut client = client.clone();
+            async move { client.check(request).await.map(|r| r.into_inner().status) }
+        };
+        let serving = ServingStatus::Serving as i32;
+        let not_serving = ServingStatus::NotServing as i32;
+
+        assert_eq!(check("").await.unwrap(), serving);
+        assert_eq!(check("sql_parser.SqlParserService").await.unwrap(), serving);
+        assert_eq!(check("unknown.Service").await.unwrap_err().code(), tonic::Code::NotFound);
+
+        // 通过 Watch 等待状态变化，避免轮询
+        let mut watch = client
+            .clone()
+            .watch(HealthCheckRequest {
+                service: "sql_parser.SqlParserService".to_string(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(watch.message().await.unwrap().unwrap().status, serving);
+        health.start_draining();
+        assert_eq!(watch.message().await.unwrap().unwrap().status, not_serving);
+        assert_eq!(check("").await.unwrap(), not_serving);
+
+        // 反射服务列出业务服务与健康检查服务
+        use tonic_reflection::pb::v1::server_reflection_client::ServerReflectionClient;
+        use tonic_reflection::pb::v1::server_reflection_request::MessageRequest;
+        use tonic_reflection::pb::v1::server_reflection_response::MessageResponse;
+        use tonic_reflection::pb::v1::ServerReflectionRequest;
+
+        let request = ServerReflectionRequest {
+            host: String::new(),
+            message_request: Some(MessageRequest::ListServices(String::new())),
+        };
+        let mut responses = ServerReflectionClient::new(channel)
+            .server_reflection_info(tokio_stream::iter([request]))
+            .await
+            .unwrap()
+            .into_inner();
+        let response = responses.message().await.unwrap().unwrap();
+        let Some(MessageResponse::ListServicesResponse(list)) = response.message_response else {
+            panic!("unexpected reflection response: {response:?}");
+        };
+        let names: Vec<_> = list.service.into_iter().map(|s| s.name).collect();
+        assert!(names.contains(&"sql_parser.SqlParserService".to_string()));
+        assert!(names.contains(&"grpc.health.v1.Health".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_single_port_serves_grpc_web() {
+        use http_body_util::BodyExt;
+        use prost::Message;
+        use tower::ServiceExt;
+
+        let config = Config::default();
+        let service = test_grpc_service();
+        let multiplexer = Multiplexer {
+            routes: build_grpc_routes(service, &config),
+            grpc_web: Some(GrpcWebLayer::new(config.limits.max_body_bytes)),
+        };
+        let app = build_router(test_state(), &config, Some(multiplexer));
+
+        let message = sql_parser::HealthCheckRequest {
+            probe: "ready".to_string(),
+        }
+        .encode_to_vec();
+        let mut frame = vec![0];
+        frame.extend((message.len() as u32).to_be_bytes());
+        frame.extend(message);
+        let request = axum::http::Request::post("/sql_parser.SqlParserService/HealthCheck")
+            .header("content-type", "application/grpc-web+proto")
+            .body(axum::body::Body::from(frame))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/grpc-web+proto");
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let len = u32::from_be_bytes(body[1..5].try_into().unwrap()) as usize;
+        let reply = sql_parser::HealthCheckResponse::decode(&body[5..5 + len]).unwrap();
+        assert_eq!((reply.status.as_str(), reply.ready), ("ready", true));
+        // 最后一帧为 trailers
+        assert_eq!(body[5 + len], 0x80);
+        assert!(String::from_utf8_lossy(&body[5 + len + 5..]).contains("grpc-status:0"));
+
+        // 普通 HTTP 请求不受影响
+        assert_eq!(send_json(&app, "GET", "/health/live", None).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_http_compression() {
+        use flate2::{read::GzDecoder, write::GzEncoder};
+        use std::io::{Read, Write};
+        use tower::ServiceExt;
+
+        let app = test_app(&Config::default());
+        let columns: Vec<String> = (0..200).map(|i| format!("column_{i}")).collect();
+        let sql = format!("SELECT {} FROM t", columns.join(", "));
+        let body = serde_json::json!({ "sql": sql }).to_string();
+
+        // 请求体以 gzip 上传，响应按 Accept-Encoding 压缩
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(body.as_bytes()).unwrap();
+        let request = axum::http::Request::post("/parse")
+            .header("content-type", "application/json")
+            .header("content-encoding", "gzip")
+            .header("accept-encoding", "br;q=0.5, gzip")
+            .body(axum::body::Body::from(encoder.finish().unwrap()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-encoding"], "gzip");
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let mut json = String::new();
+        GzDecoder::new(bytes.as_ref()).read_to_string(&mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert!(json["ast"].is_array());
+        assert!(bytes.len() * 5 < json.to_string().len());
+
+        // 小于 min_size 的响应不压缩
+        let request = axum::http::Request::get("/health/live")
+            .header("accept-encoding", "gzip")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert!(response.headers().get("content-encoding").is_none());
+
+        let request = axum::http::Request::post("/parse")
+            .header("content-type", "application/json")
+            .header("content-encoding", "compress")
+            .body(axum::body::Body::from(body))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_compression() {
+        use flate2::write::GzEncoder;
+        use http_body_util::BodyExt;
+        use prost::Message;
+        use std::io::Write;
+        use tower::ServiceExt;
+
+        let service = test_grpc_service();
+        let routes = build_grpc_routes(service, &Config::default());
+        // 返回响应帧的压缩标志
+        let call = |sql: String, compress_request: bool| {
+            let mut message = sql_parser::ParseSqlRequest {
+                sql,
+                dialect: "generic".to_string(),
+                no_cache: false,
+                ast_format: sql_parser::AstFormat::Json as i32,
+                compact: false,
+                with_spans: false,
+                recover: false,
+            }
+            .encode_to_vec();
+            if compress_request {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(&message).unwrap();
+                message = encoder.finish().unwrap();
+            }
+            let mut frame = vec![u8::from(compress_request)];
+            frame.extend((message.len() as u32).to_be_bytes());
+            frame.extend(message);
+            let request = axum::http::Request::post("/sql_parser.SqlParserService/ParseSql")
+                .header("content-type", "application/grpc")
+                .header("te", "trailers")
+                .header("grpc-encoding", "gzip")
+                .header("grpc-accept-encoding", "gzip")
+                .body(tonic::body::boxed(axum::body::Body::from(frame)))
+                .unwrap();
+            let routes = routes.clone();
+            async move {
+                let response = routes.oneshot(request).await.unwrap();
+                let body = response.into_body().collect().await.unwrap();
+                let trailers = body.trailers().cloned().unwrap_or_default();
+                assert_eq!(trailers.get("grpc-status").map(|v| v.as_bytes()), Some(&b"0"[..]));
+                body.to_bytes()[0]
+            }
+        };
+
+        let columns: Vec<String> = (0..200).map(|i| format!("column_{i}")).collect();
+        assert_eq!(call(format!("SELECT {} FROM t", columns.join(", ")), true).await, 1);
+        assert_eq!(call("SELECT 1".to_string(), false).await, 0);
+    }
+
+    fn test_caches() -> Caches {
+        let ttl = Duration::from_secs(60);
+        Caches {
+            ast: AstCache::new(build_cache(100, ttl)),
+            statements: build_cache(100, ttl),
+            fingerprints: build_cache(100, ttl),
+            fold_keyword_case: false,
+        }
+    }
+
+    fn test_state() -> AppState {
+        AppState {
+            caches: test_caches(),
+            limits: Limits::default(),
+            auth: None,
+            limiter: None,
+            health: Health::new(),
+            registry: None,
+            allowlist: None,
+            catalogs: Default::default(),
+        }
+    }
+
+    fn test_app(config: &Config) -> Router {
+        build_router(test_state(), config, None)
+    }
+
+    fn test_grpc_service() -> SqlParserGrpcService {
+        SqlParserGrpcService {
+            caches: test_caches(),
+            limits: Limits::default(),
+            endpoints: EndpointsConfig::default(),
+            auth: None,
+            limiter: None,
+            health: Health::new(),
+            registry: None,
+            allowlist: None,
+            compression: CompressionConfig::default(),
+        }
+    }
+
+    // 发送请求并把响应体按 JSON 解析，空响应体解析为 null
+    async fn send(
+        app: &Router,
+        request: axum::http::Request<axum::body::Body>,
+    ) -> (StatusCode, serde_json::Value) {
+        use tower::ServiceExt;
+
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    async fn send_json(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        body: Option<serde_json::Value>,
+    ) -> (StatusCode, serde_json::Value) {
+        let request = axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(axum::body::Body::from(body.map(|b| b.to_string()).unwrap_or_default()))
+            .unwrap();
+        send(app, request).await
+    }
+
+    #[tokio::test]
+    async fn test_fingerprint_results_are_cached() {
+        let caches = test_caches();
+        let limits = Limits::default();
+        let timing = WorkTiming::default();
+        let sql = "SELECT * FROM users WHERE age IN (1, 2, 3)";
+
+        let (first, cached) = fingerprint_with_cache(&caches, &limits, &timing, sql, "mysql", 2, false).await;
+        assert_eq!(first, Ok("SELECT * FROM users WHERE age IN (?, ?)".to_string()));
+        assert!(!cached);
+
+        let (second, cached) = fingerprint_with_cache(&caches, &limits, &timing, sql, "mysql", 2, false).await;
+        assert_eq!(second, first);
+        assert!(cached);
+
+        // max_in_values 属于缓存键的一部分
+        let (other, cached) = fingerprint_with_cache(&caches, &limits, &timing, sql, "mysql", 0, false).await;
+        assert_eq!(other, Ok("SELECT * FROM users WHERE age IN (?, ?, ?)".to_string()));
+        assert!(!cached);
+    }
+
+    #[tokio::test]
+    async fn test_parse_and_fingerprint_share_statements() {
+        let caches = test_caches();
+        let limits = Limits::default();
+        let timing = WorkTiming::default();
+        let sql = "SELECT id FROM users WHERE id = 1";
+
+        let _ = fingerprint_with_cache(&caches, &limits, &timing, sql, "mysql", 0, false).await;
+        let key = (normalize_sql(sql, "mysql", false), "mysql".to_string());
+        assert!(caches.statements.get(&key).await.is_some());
+
+        let (ast, cached) = parse_with_cache(&caches, &limits, &timing, sql, "mysql", false).await;
+        assert!(ast.is_ok());
+        assert!(!cached);
+        let (_, cached) = parse_with_cache(&caches, &limits, &timing, sql, "mysql", false).await;
+        assert!(cached);
+    }
+
+    #[tokio::test]
+    async fn test_no_cache_bypasses_caches() {
+        let caches = test_caches();
+        let limits = Limits::default();
+        let timing = WorkTiming::default();
+        let sql = "SELECT 1";
+
+        let (_, cached) = parse_with_cache(&caches, &limits, &timing, sql, "mysql", true).await;
+        assert!(!cached);
+        let (_, cached) = fingerprint_with_cache(&caches, &limits, &timing, sql, "mysql", 0, true).await;
+        assert!(!cached);
+        assert!(caches.statements.get(&(sql.to_string(), "mysql".to_string())).await.is_none());
+    }
+
     #[test]
     fn test_get_dialect() {
         assert!(get_dialect("mysql").is_ok());
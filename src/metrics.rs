@@ -0,0 +1,203 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+
+// 进程级指标，GET /metrics 以 Prometheus 文本格式输出
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::default);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transport {
+    Http,
+    Grpc,
+}
+
+impl Transport {
+    const ALL: [Transport; 2] = [Transport::Http, Transport::Grpc];
+
+    fn label(self) -> &'static str {
+        match self {
+            Transport::Http => "http",
+            Transport::Grpc => "grpc",
+        }
+    }
+}
+
+// 按传输协议区分的计数器
+#[derive(Default)]
+pub struct TransportCounter([AtomicU64; 2]);
+
+impl TransportCounter {
+    pub fn inc(&self, transport: Transport) {
+        self.0[transport as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self, transport: Transport) -> u64 {
+        self.0[transport as usize].load(Ordering::Relaxed)
+    }
+}
+
+// 只记录总和与次数的 summary
+#[derive(Default)]
+pub struct Summary {
+    sum_nanos: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Summary {
+    pub fn observe(&self, duration: Duration) {
+        self.sum_nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    pub rate_limited: TransportCounter,
+    pub concurrency_rejected: TransportCounter,
+    pub parse_in_flight: AtomicI64,
+    // 0 表示不限制
+    pub parse_concurrency_limit: AtomicU64,
+    pub worker_threads: AtomicU64,
+    pub worker_queued: AtomicI64,
+    pub worker_rejected: AtomicU64,
+    pub worker_busy: AtomicI64,
+    // 调用方超时放弃的任务：累计数，以及仍占用工作线程的个数
+    pub worker_abandoned: AtomicU64,
+    pub worker_abandoned_running: AtomicI64,
+    pub worker_queue_wait: Summary,
+    pub worker_run: Summary,
+}
+
+impl Metrics {
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        counter(
+            &mut out,
+            "sql_ast_api_rate_limited_total",
+            "Requests rejected by the per-client rate limit.",
+            &self.rate_limited,
+        );
+        counter(
+            &mut out,
+            "sql_ast_api_concurrency_rejected_total",
+            "Requests rejected because the parse concurrency limit was reached.",
+            &self.concurrency_rejected,
+        );
+        gauge(
+            &mut out,
+            "sql_ast_api_parse_in_flight",
+            "Parse requests currently being processed.",
+            self.parse_in_flight.load(Ordering::Relaxed),
+        );
+        gauge(
+            &mut out,
+            "sql_ast_api_parse_concurrency_limit",
+            "Configured parse concurrency limit (0 means unlimited).",
+            self.parse_concurrency_limit.load(Ordering::Relaxed) as i64,
+        );
+        gauge(
+            &mut out,
+            "sql_ast_api_worker_threads",
+            "Threads in the parse worker pool.",
+            self.worker_threads.load(Ordering::Relaxed) as i64,
+        );
+        gauge(
+            &mut out,
+            "sql_ast_api_worker_queue_depth",
+            "Jobs waiting in the parse worker queue.",
+            self.worker_queued.load(Ordering::Relaxed),
+        );
+        let _ = writeln!(
+            out,
+            "# HELP {name} Jobs rejected because the worker queue was full.\n\
+             # TYPE {name} counter\n{name} {}",
+            self.worker_rejected.load(Ordering::Relaxed),
+            name = "sql_ast_api_worker_rejected_total",
+        );
+        gauge(
+            &mut out,
+            "sql_ast_api_worker_busy",
+            "Worker threads currently running a job.",
+            self.worker_busy.load(Ordering::Relaxed),
+        );
+        let _ = writeln!(
+            out,
+            "# HELP {name} Jobs abandoned by their caller after the parse timeout.\n\
+             # TYPE {name} counter\n{name} {}",
+            self.worker_abandoned.load(Ordering::Relaxed),
+            name = "sql_ast_api_worker_abandoned_total",
+        );
+        gauge(
+            &mut out,
+            "sql_ast_api_worker_abandoned_running",
+            "Abandoned jobs still occupying a worker thread.",
+            self.worker_abandoned_running.load(Ordering::Relaxed),
+        );
+        summary(
+            &mut out,
+            "sql_ast_api_worker_queue_wait_seconds",
+            "Time jobs spent waiting in the worker queue.",
+            &self.worker_queue_wait,
+        );
+        summary(
+            &mut out,
+            "sql_ast_api_worker_run_seconds",
+            "Time spent parsing, serializing and fingerprinting on worker threads.",
+            &self.worker_run,
+        );
+        out
+    }
+}
+
+fn counter(out: &mut String, name: &str, help: &str, counter: &TransportCounter) {
+    let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} counter");
+    for transport in Transport::ALL {
+        let _ = writeln!(
+            out,
+            "{name}{{transport=\"{}\"}} {}",
+            transport.label(),
+            counter.get(transport)
+        );
+    }
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: i64) {
+    let _ = writeln!(
+        out,
+        "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}"
+    );
+}
+
+fn summary(out: &mut String, name: &str, help: &str, summary: &Summary) {
+    let sum = summary.sum_nanos.load(Ordering::Relaxed) as f64 / 1e9;
+    let count = summary.count.load(Ordering::Relaxed);
+    let _ = writeln!(
+        out,
+        "# HELP {name} {help}\n# TYPE {name} summary\n{name}_sum {sum}\n{name}_count {count}"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_prometheus_text() {
+        let metrics = Metrics::default();
+        metrics.rate_limited.inc(Transport::Grpc);
+        metrics.rate_limited.inc(Transport::Grpc);
+        metrics.parse_in_flight.fetch_add(3, Ordering::Relaxed);
+        metrics.worker_run.observe(Duration::from_millis(1500));
+
+        let text = metrics.render();
+        assert!(text.contains("# TYPE sql_ast_api_rate_limited_total counter\n"));
+        assert!(text.contains("sql_ast_api_rate_limited_total{transport=\"http\"} 0\n"));
+        assert!(text.contains("sql_ast_api_rate_limited_total{transport=\"grpc\"} 2\n"));
+        assert!(text.contains("sql_ast_api_parse_in_flight 3\n"));
+        assert!(text.contains("sql_ast_api_worker_run_seconds_sum 1.5\n"));
+        assert!(text.contains("sql_ast_api_worker_run_seconds_count 1\n"));
+    }
+}
@@ -0,0 +1,197 @@
+use axum::body::Bytes;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+
+// /parse 的响应编码，由请求头 Accept 协商
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Json,
+    MsgPack,
+    Cbor,
+}
+
+impl OutputFormat {
+    // 按 q 值选择，q 相同时取先出现的；未携带 Accept 或接受 */* 时使用 JSON，
+    // 全部不支持时返回 None
+    pub fn negotiate(headers: &HeaderMap) -> Option<Self> {
+        let accept = match headers.get(header::ACCEPT).map(HeaderValue::to_str) {
+            Some(Ok(accept)) if !accept.trim().is_empty() => accept,
+            _ => return Some(OutputFormat::Json),
+        };
+        let mut best: Option<(f32, OutputFormat)> = None;
+        for item in accept.split(',') {
+            let mut params = item.split(';');
+            let media_type = params
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_ascii_lowercase();
+            let format = match media_type.as_str() {
+                "*/*" | "application/*" | "application/json" => OutputFormat::Json,
+                "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                    OutputFormat::MsgPack
+                }
+                "application/cbor" => OutputFormat::Cbor,
+                _ => continue,
+            };
+            let q = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if q > 0.0 && best.is_none_or(|(best_q, _)| q > best_q) {
+                best = Some((q, format));
+            }
+        }
+        best.map(|(_, format)| format)
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "application/json",
+            OutputFormat::MsgPack => "application/msgpack",
+            OutputFormat::Cbor => "application/cbor",
+        }
+    }
+
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            OutputFormat::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            // 结构体编码为带字段名的 map，与 JSON 的结构一致
+            OutputFormat::MsgPack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()),
+            OutputFormat::Cbor => {
+                let mut body = Vec::new();
+                ciborium::into_writer(value, &mut body).map_err(|e| e.to_string())?;
+                Ok(body)
+            }
+        }
+    }
+
+    pub fn respond<T: Serialize>(self, status: StatusCode, value: &T) -> Response {
+        match self.encode(value) {
+            Ok(body) => (
+                status,
+                [(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static(self.content_type()),
+                )],
+                body,
+            )
+                .into_response(),
+            Err(e) => {
+                tracing::error!("Failed to encode {} response: {e}", self.content_type());
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
+
+// 缓存中的 AST 以 MessagePack（保留字段名）存储，占用远小于 serde_json::Value，
+// 输出时再解码为与原 JSON 完全相同的结构
+pub fn encode_ast<T: Serialize + ?Sized>(ast: &T) -> Result<Bytes, String> {
+    rmp_serde::to_vec_named(ast)
+        .map(Bytes::from)
+        .map_err(|e| e.to_string())
+}
+
+pub fn decode_ast(bytes: &[u8]) -> Result<serde_json::Value, String> {
+    rmp_serde::from_slice(bytes).map_err(|e| e.to_string())
+}
+
+// 去掉值为 null、false、空数组或空对象的字段。
+// 只有一个键的对象是枚举变体（如 {"Boolean": false}），保持不变；
+// with_spans 附加的 "span" 键不计入。
+pub fn compact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Array(items) => items.iter_mut().for_each(compact),
+        serde_json::Value::Object(fields) => {
+            fields.values_mut().for_each(compact);
+            if fields.keys().filter(|key| *key != "span").count() > 1 {
+                fields.retain(|_, value| !is_default(value));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_default(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Null | serde_json::Value::Bool(false) => true,
+        serde_json::Value::Array(items) => items.is_empty(),
+        serde_json::Value::Object(fields) => fields.is_empty(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use sqlparser::dialect::GenericDialect;
+    use sqlparser::parser::Parser;
+
+    fn negotiate(accept: &str) -> Option<OutputFormat> {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_str(accept).unwrap());
+        OutputFormat::negotiate(&headers)
+    }
+
+    #[test]
+    fn test_negotiate_accept() {
+        assert_eq!(
+            OutputFormat::negotiate(&HeaderMap::new()),
+            Some(OutputFormat::Json)
+        );
+        assert_eq!(negotiate("*/*"), Some(OutputFormat::Json));
+        assert_eq!(
+            negotiate("application/msgpack"),
+            Some(OutputFormat::MsgPack)
+        );
+        assert_eq!(
+            negotiate("application/json;q=0.5, application/cbor"),
+            Some(OutputFormat::Cbor)
+        );
+        assert_eq!(
+            negotiate("application/x-msgpack, application/json"),
+            Some(OutputFormat::MsgPack)
+        );
+        assert_eq!(negotiate("text/html, application/cbor;q=0"), None);
+    }
+
+    #[test]
+    fn test_cached_ast_decodes_to_same_json() {
+        let sql = "SELECT a, CAST(b AS DECIMAL(10, 2)) FROM t WHERE c IN (1, 'x', NULL, TRUE) \
+                   ORDER BY a DESC LIMIT 10; INSERT INTO t (a) VALUES (1.5e3)";
+        let statements = Parser::parse_sql(&GenericDialect {}, sql).unwrap();
+        let bytes = encode_ast(&statements).unwrap();
+        let expected = serde_json::to_value(&statements).unwrap();
+        assert_eq!(decode_ast(&bytes).unwrap(), expected);
+        assert!(bytes.len() < expected.to_string().len());
+    }
+
+    #[test]
+    fn test_compact_keeps_enum_variants() {
+        let mut value = json!({
+            "Select": {
+                "distinct": null,
+                "top_before_distinct": false,
+                "projection": [{"Value": {"Boolean": false}, "span": {"start": {"line": 1}}}],
+                "lateral_views": [],
+                "group_by": {"Expressions": [[], []]},
+                "options": {"a": null, "b": []},
+                "limit": 0
+            }
+        });
+        compact(&mut value);
+        assert_eq!(
+            value,
+            json!({
+                "Select": {
+                    "projection": [{"Value": {"Boolean": false}, "span": {"start": {"line": 1}}}],
+                    "group_by": {"Expressions": [[], []]},
+                    "limit": 0
+                }
+            })
+        );
+    }
+}
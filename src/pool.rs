@@ -0,0 +1,289 @@
+use crate::limits::LimitError;
+use crate::metrics::METRICS;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+// 工作线程栈较大，允许调高 max_nesting_depth 而不会栈溢出
+const WORKER_STACK_SIZE: usize = 16 * 1024 * 1024;
+
+type Job = Box<dyn FnOnce() + Send>;
+type Outcome<T> = (std::thread::Result<T>, Duration, Duration);
+
+// 任务状态：调用方超时后，排队中的任务直接跳过，执行中的任务记为放弃但会继续占用线程
+const QUEUED: u8 = 0;
+const RUNNING: u8 = 1;
+const DONE: u8 = 2;
+const SKIPPED: u8 = 3;
+const ABANDONED: u8 = 4;
+
+// 一个请求在线程池中的耗时，排队等待与执行分开累计（一个请求可能提交多个任务）
+#[derive(Default)]
+pub struct WorkTiming {
+    queue_nanos: AtomicU64,
+    run_nanos: AtomicU64,
+    jobs: AtomicU64,
+}
+
+impl WorkTiming {
+    fn record(&self, queue: Duration, run: Duration) {
+        self.queue_nanos
+            .fetch_add(queue.as_nanos() as u64, Ordering::Relaxed);
+        self.run_nanos
+            .fetch_add(run.as_nanos() as u64, Ordering::Relaxed);
+        self.jobs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // 没有提交过任务（例如命中缓存）时返回 None
+    pub fn queue_ms(&self) -> Option<f64> {
+        self.millis(&self.queue_nanos)
+    }
+
+    pub fn run_ms(&self) -> Option<f64> {
+        self.millis(&self.run_nanos)
+    }
+
+    fn millis(&self, nanos: &AtomicU64) -> Option<f64> {
+        (self.jobs.load(Ordering::Relaxed) > 0)
+            .then(|| nanos.load(Ordering::Relaxed) as f64 / 1_000_000.0)
+    }
+}
+
+// 解析、序列化与指纹计算都在这里执行，不占用 tokio 工作线程。
+// 队列满时直接拒绝，而不是无限堆积；超时被放弃但仍在执行的任务占满所有线程时，
+// 新任务排队也等不到线程，同样直接拒绝。
+pub struct WorkerPool {
+    sender: SyncSender<Job>,
+    threads: usize,
+    queue_depth: usize,
+    abandoned_running: Arc<AtomicUsize>,
+}
+
+impl WorkerPool {
+    // threads 为 0 时使用 CPU 核数
+    pub fn new(threads: usize, queue_depth: usize) -> Arc<Self> {
+        let threads = match threads {
+            0 => std::thread::available_parallelism().map_or(4, |n| n.get()),
+            n => n,
+        };
+        let (sender, receiver) = mpsc::sync_channel::<Job>(queue_depth);
+        let receiver = Arc::new(Mutex::new(receiver));
+        for i in 0..threads {
+            let receiver = receiver.clone();
+            std::thread::Builder::new()
+                .name(format!("sql-worker-{i}"))
+                .stack_size(WORKER_STACK_SIZE)
+                .spawn(move || worker_loop(receiver))
+                .expect("failed to spawn worker thread");
+        }
+        METRICS
+            .worker_threads
+            .store(threads as u64, Ordering::Relaxed);
+        Arc::new(Self {
+            sender,
+            threads,
+            queue_depth,
+            abandoned_running: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    // 超时后立即返回错误；尚未开始执行的任务会被跳过，已开始的任务仍会执行完毕
+    pub async fn run<T, F>(
+        &self,
+        timing: &WorkTiming,
+        timeout: Option<Duration>,
+        f: F,
+    ) -> Result<T, LimitError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        if self.abandoned_running.load(Ordering::Relaxed) >= self.threads {
+            METRICS.worker_rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(LimitError::QueueFull {
+                depth: self.queue_depth,
+            });
+        }
+
+        let (tx, rx) = oneshot::channel::<Outcome<T>>();
+        let state = Arc::new(AtomicU8::new(QUEUED));
+        let enqueued = Instant::now();
+        let job: Job = {
+            let state = state.clone();
+            let abandoned_running = self.abandoned_running.clone();
+            Box::new(move || {
+                METRICS.worker_queued.fetch_sub(1, Ordering::Relaxed);
+                if state
+                    .compare_exchange(QUEUED, RUNNING, Ordering::AcqRel, Ordering::Acquire)
+                    .is_err()
+                {
+                    return;
+                }
+                METRICS.worker_busy.fetch_add(1, Ordering::Relaxed);
+                let started = Instant::now();
+                let result = catch_unwind(AssertUnwindSafe(f));
+                METRICS.worker_busy.fetch_sub(1, Ordering::Relaxed);
+                if state.swap(DONE, Ordering::AcqRel) == ABANDONED {
+                    abandoned_running.fetch_sub(1, Ordering::Relaxed);
+                    METRICS
+                        .worker_abandoned_running
+                        .fetch_sub(1, Ordering::Relaxed);
+                }
+                let _ = tx.send((result, started - enqueued, started.elapsed()));
+            })
+        };
+
+        METRICS.worker_queued.fetch_add(1, Ordering::Relaxed);
+        if let Err(e) = self.sender.try_send(job) {
+            METRICS.worker_queued.fetch_sub(1, Ordering::Relaxed);
+            return match e {
+                TrySendError::Full(_) => {
+                    METRICS.worker_rejected.fetch_add(1, Ordering::Relaxed);
+                    Err(LimitError::QueueFull {
+                        depth: self.queue_depth,
+                    })
+                }
+                TrySendError::Disconnected(_) => panic!("worker pool has shut down"),
+            };
+        }
+
+        let received = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, rx).await {
+                Ok(received) => received,
+                Err(_) => {
+                    self.abandon(&state);
+                    return Err(LimitError::ParseTimeout { timeout });
+                }
+            },
+            None => rx.await,
+        };
+        let (result, queue, run) = received.expect("worker dropped a job");
+        timing.record(queue, run);
+        METRICS.worker_queue_wait.observe(queue);
+        METRICS.worker_run.observe(run);
+        match result {
+            Ok(value) => Ok(value),
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+
+    fn abandon(&self, state: &AtomicU8) {
+        METRICS.worker_abandoned.fetch_add(1, Ordering::Relaxed);
+        if state
+            .compare_exchange(QUEUED, SKIPPED, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+        {
+            return;
+        }
+        // 先计数再改状态，任务恰好结束时由任务一侧减回
+        self.abandoned_running.fetch_add(1, Ordering::Relaxed);
+        METRICS
+            .worker_abandoned_running
+            .fetch_add(1, Ordering::Relaxed);
+        if state
+            .compare_exchange(RUNNING, ABANDONED, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            self.abandoned_running.fetch_sub(1, Ordering::Relaxed);
+            METRICS
+                .worker_abandoned_running
+                .fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+fn worker_loop(receiver: Arc<Mutex<Receiver<Job>>>) {
+    loop {
+        // 取到任务后立即释放锁，其他线程可以继续取任务
+        let job = receiver.lock().unwrap().recv();
+        match job {
+            Ok(job) => job(),
+            Err(_) => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_reports_queue_and_run_time() {
+        let pool = WorkerPool::new(1, 4);
+        let timing = WorkTiming::default();
+        assert_eq!(timing.queue_ms(), None);
+
+        let value = pool
+            .run(&timing, None, || {
+                std::thread::sleep(Duration::from_millis(20));
+                42
+            })
+            .await;
+        assert_eq!(value, Ok(42));
+        assert!(timing.run_ms().unwrap() >= 20.0);
+        assert!(timing.queue_ms().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_queue_full_and_timeout() {
+        let pool = WorkerPool::new(1, 1);
+        let (release, wait) = std::sync::mpsc::channel::<()>();
+        let (started_tx, started) = std::sync::mpsc::channel::<()>();
+
+        // 占住唯一的工作线程，再排满队列
+        let busy = {
+            let pool = pool.clone();
+            tokio::spawn(async move {
+                pool.run(&WorkTiming::default(), None, move || {
+                    started_tx.send(()).unwrap();
+                    wait.recv().unwrap()
+                })
+                .await
+            })
+        };
+        while started.try_recv().is_err() {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        let timing = WorkTiming::default();
+        let queued = pool.run(&timing, Some(Duration::from_millis(20)), || ());
+        let rejected = async {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            pool.run(&timing, None, || ()).await
+        };
+        let (queued, rejected) = tokio::join!(queued, rejected);
+        assert_eq!(rejected, Err(LimitError::QueueFull { depth: 1 }));
+        assert_eq!(queued.unwrap_err().code(), "parse_timeout");
+
+        release.send(()).unwrap();
+        assert!(busy.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_jobs_block_admission() {
+        let pool = WorkerPool::new(1, 4);
+        let (release, wait) = std::sync::mpsc::channel::<()>();
+        let timing = WorkTiming::default();
+
+        let timeout = Some(Duration::from_millis(20));
+        let err = pool
+            .run(&timing, timeout, move || wait.recv().unwrap())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "parse_timeout");
+        assert_eq!(pool.abandoned_running.load(Ordering::Relaxed), 1);
+
+        // 唯一的线程被放弃的任务占用，新任务不再排队
+        let rejected = pool.run(&timing, None, || ()).await;
+        assert_eq!(rejected, Err(LimitError::QueueFull { depth: 4 }));
+
+        release.send(()).unwrap();
+        while pool.abandoned_running.load(Ordering::Relaxed) > 0 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        assert_eq!(pool.run(&timing, None, || 7).await, Ok(7));
+    }
+}
@@ -0,0 +1,269 @@
+use crate::auth::Principal;
+use crate::config::RateLimitConfig;
+use crate::metrics::{Transport, METRICS};
+use crate::ErrorResponse;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tonic::Status;
+
+// 令牌桶数量超过该值时清理已回满（长时间空闲）的桶
+const PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Debug, PartialEq)]
+pub enum Rejection {
+    RateLimited { retry_after: Duration },
+    Overloaded,
+}
+
+impl Rejection {
+    fn message(&self) -> String {
+        match self {
+            Rejection::RateLimited { .. } => "Rate limit exceeded".to_string(),
+            Rejection::Overloaded => "Too many concurrent parse requests".to_string(),
+        }
+    }
+
+    // Retry-After 以整秒表示，至少为 1
+    fn retry_after_secs(&self) -> u64 {
+        match self {
+            Rejection::RateLimited { retry_after } => {
+                retry_after.as_secs_f64().ceil().max(1.0) as u64
+            }
+            Rejection::Overloaded => 1,
+        }
+    }
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        let mut response = (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(ErrorResponse {
+                error: self.message(),
+                code: None,
+                elapsed_ms: None,
+            }),
+        )
+            .into_response();
+        response.headers_mut().insert(
+            header::RETRY_AFTER,
+            HeaderValue::from(self.retry_after_secs()),
+        );
+        response
+    }
+}
+
+impl From<Rejection> for Status {
+    fn from(rejection: Rejection) -> Self {
+        let mut status = Status::resource_exhausted(rejection.message());
+        status
+            .metadata_mut()
+            .insert("retry-after", rejection.retry_after_secs().into());
+        status
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+// 持有期间计入并发数，释放时归还
+pub struct ParsePermit {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl ParsePermit {
+    fn new(permit: Option<OwnedSemaphorePermit>) -> Self {
+        METRICS.parse_in_flight.fetch_add(1, Ordering::Relaxed);
+        Self { _permit: permit }
+    }
+}
+
+impl Drop for ParsePermit {
+    fn drop(&mut self) {
+        METRICS.parse_in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    concurrency: Option<Arc<Semaphore>>,
+}
+
+impl RateLimiter {
+    pub fn from_config(config: &RateLimitConfig) -> Arc<Self> {
+        METRICS
+            .parse_concurrency_limit
+            .store(config.max_concurrency as u64, Ordering::Relaxed);
+        Arc::new(Self {
+            rate: config.requests_per_second,
+            burst: config.burst as f64,
+            buckets: Mutex::new(HashMap::new()),
+            concurrency: (config.max_concurrency > 0)
+                .then(|| Arc::new(Semaphore::new(config.max_concurrency))),
+        })
+    }
+
+    // 从客户端的令牌桶中取一个令牌
+    fn take_token(&self, client: &str) -> Result<(), Rejection> {
+        if self.rate <= 0.0 {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= PRUNE_THRESHOLD && !buckets.contains_key(client) {
+            let full_after = self.burst / self.rate;
+            buckets
+                .retain(|_, bucket| now.duration_since(bucket.updated).as_secs_f64() < full_after);
+        }
+
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Rejection::RateLimited {
+                retry_after: Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate),
+            })
+        }
+    }
+
+    // 先检查客户端速率，再占用一个全局并发名额；达到上限时直接拒绝而不排队
+    pub fn admit(&self, client: &str, transport: Transport) -> Result<ParsePermit, Rejection> {
+        if let Err(rejection) = self.take_token(client) {
+            METRICS.rate_limited.inc(transport);
+            tracing::debug!(client, "rate limited");
+            return Err(rejection);
+        }
+        let permit = match &self.concurrency {
+            Some(semaphore) => match semaphore.clone().try_acquire_owned() {
+                Ok(permit) => Some(permit),
+                Err(_) => {
+                    METRICS.concurrency_rejected.inc(transport);
+                    return Err(Rejection::Overloaded);
+                }
+            },
+            None => None,
+        };
+        Ok(ParsePermit::new(permit))
+    }
+}
+
+// 已认证的请求按调用方限流，否则按客户端 IP
+fn client_key(principal: Option<&Principal>, addr: Option<SocketAddr>) -> String {
+    match (principal, addr) {
+        (Some(principal), _) => format!("{}:{}", principal.kind.as_str(), principal.name),
+        (None, Some(addr)) => format!("ip:{}", addr.ip()),
+        (None, None) => "unknown".to_string(),
+    }
+}
+
+// 需放在认证中间件之内，以便取得 Principal
+pub async fn http_middleware(
+    State(limiter): State<Arc<RateLimiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let client = client_key(
+        request.extensions().get::<Principal>(),
+        request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|info| info.0),
+    );
+    match limiter.admit(&client, Transport::Http) {
+        Ok(_permit) => next.run(request).await,
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+pub fn admit_grpc<T>(
+    limiter: Option<&RateLimiter>,
+    request: &tonic::Request<T>,
+) -> Result<Option<ParsePermit>, Rejection> {
+    let Some(limiter) = limiter else {
+        return Ok(None);
+    };
+    let client = client_key(
+        request.extensions().get::<Principal>(),
+        request.remote_addr(),
+    );
+    limiter.admit(&client, Transport::Grpc).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(requests_per_second: f64, burst: u32, max_concurrency: usize) -> Arc<RateLimiter> {
+        RateLimiter::from_config(&RateLimitConfig {
+            requests_per_second,
+            burst,
+            max_concurrency,
+        })
+    }
+
+    #[test]
+    fn test_token_bucket_per_client() {
+        let limiter = limiter(1.0, 2, 0);
+        assert!(limiter.admit("ip:10.0.0.1", Transport::Http).is_ok());
+        assert!(limiter.admit("ip:10.0.0.1", Transport::Http).is_ok());
+        match limiter.admit("ip:10.0.0.1", Transport::Grpc) {
+            Err(Rejection::RateLimited { retry_after }) => {
+                assert!(retry_after > Duration::ZERO && retry_after <= Duration::from_secs(1))
+            }
+            _ => panic!("expected rate limit rejection"),
+        }
+        // 其他客户端不受影响
+        assert!(limiter.admit("ip:10.0.0.2", Transport::Http).is_ok());
+    }
+
+    #[test]
+    fn test_token_bucket_refills() {
+        let limiter = limiter(1000.0, 1, 0);
+        assert!(limiter.admit("a", Transport::Http).is_ok());
+        assert!(limiter.admit("a", Transport::Http).is_err());
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(limiter.admit("a", Transport::Http).is_ok());
+    }
+
+    #[test]
+    fn test_concurrency_limit() {
+        let limiter = limiter(0.0, 0, 1);
+        let permit = limiter.admit("a", Transport::Http).unwrap();
+        assert_eq!(
+            limiter.admit("b", Transport::Grpc).err(),
+            Some(Rejection::Overloaded)
+        );
+        drop(permit);
+        assert!(limiter.admit("b", Transport::Grpc).is_ok());
+    }
+
+    #[test]
+    fn test_rejection_to_grpc_status() {
+        let status = Status::from(Rejection::RateLimited {
+            retry_after: Duration::from_millis(1500),
+        });
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert_eq!(status.metadata().get("retry-after").unwrap(), "2");
+    }
+}
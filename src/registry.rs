@@ -0,0 +1,354 @@
+use crate::config::RegistryConfig;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use utoipa::ToSchema;
+
+// 样例 SQL 最多保留的字节数
+const MAX_SAMPLE_BYTES: usize = 4096;
+const FILE_VERSION: u32 = 1;
+
+// 指纹文本的 SHA-256 哈希（64 位十六进制），与平台和版本无关，可在外部系统中引用；
+// 白名单按哈希放行，因此需要抗碰撞，不能被构造出与已审核指纹相同的哈希
+pub fn fingerprint_hash(fingerprint: &str) -> String {
+    format!("{:x}", Sha256::digest(fingerprint.as_bytes()))
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FingerprintEntry {
+    #[schema(example = "6f540be5517aaffe1774bebe9a2c0eba835e11cd8e1b07ea44046ae795008704")]
+    pub hash: String,
+    #[schema(example = "SELECT * FROM users WHERE id = ?")]
+    pub fingerprint: String,
+    pub count: u64,
+    // Unix 时间戳（秒）
+    #[schema(example = 1705314225)]
+    pub first_seen: u64,
+    #[schema(example = 1705400625)]
+    pub last_seen: u64,
+    // 首次出现时的原始 SQL 及其方言
+    #[schema(example = "SELECT * FROM users WHERE id = 1")]
+    pub sample: String,
+    #[schema(example = "mysql")]
+    pub dialect: String,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    #[default]
+    Count,
+    FirstSeen,
+    LastSeen,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RegistryFile {
+    version: u32,
+    fingerprints: Vec<FingerprintEntry>,
+}
+
+#[derive(Serialize)]
+struct NewFingerprintEvent<'a> {
+    event: &'static str,
+    #[serde(flatten)]
+    entry: &'a FingerprintEntry,
+}
+
+struct Webhook {
+    url: String,
+    client: reqwest::Client,
+}
+
+// 记录 /fingerprint 与 GenerateFingerprint 产生的每个指纹；首次出现时写日志并调用 webhook
+pub struct FingerprintRegistry {
+    entries: Mutex<HashMap<String, FingerprintEntry>>,
+    max_fingerprints: usize,
+    file: Option<PathBuf>,
+    // 自上次写入文件后是否有变化
+    dirty: AtomicBool,
+    // 达到上限后不再记录新指纹，只告警一次
+    full: AtomicBool,
+    webhook: Option<Webhook>,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+fn truncate(sql: &str, max: usize) -> &str {
+    if sql.len() <= max {
+        return sql;
+    }
+    let end = (0..=max)
+        .rev()
+        .find(|&i| sql.is_char_boundary(i))
+        .unwrap_or(0);
+    &sql[..end]
+}
+
+impl FingerprintRegistry {
+    // 文件已存在时载入其中的记录，这些指纹不会再触发新指纹事件
+    pub fn from_config(config: &RegistryConfig) -> Result<Arc<Self>, String> {
+        let mut entries = HashMap::new();
+        if let Some(path) = config.file.as_ref().filter(|path| path.exists()) {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+            let file: RegistryFile = serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse {}: {e}", path.display()))?;
+            if file.version != FILE_VERSION {
+                return Err(format!(
+                    "Unsupported registry file version {} in {}",
+                    file.version,
+                    path.display()
+                ));
+            }
+            // 哈希按当前算法重新计算，旧版本文件中的哈希不再沿用
+            entries.extend(file.fingerprints.into_iter().map(|mut entry| {
+                entry.hash = fingerprint_hash(&entry.fingerprint);
+                (entry.fingerprint.clone(), entry)
+            }));
+        }
+        let webhook = match &config.webhook_url {
+            Some(url) => Some(Webhook {
+                url: url.clone(),
+                client: reqwest::Client::builder()
+                    .timeout(Duration::from_secs(config.webhook_timeout))
+                    .build()
+                    .map_err(|e| format!("Failed to build webhook client: {e}"))?,
+            }),
+            None => None,
+        };
+        Ok(Arc::new(Self {
+            entries: Mutex::new(entries),
+            max_fingerprints: config.max_fingerprints,
+            file: config.file.clone(),
+            dirty: AtomicBool::new(false),
+            full: AtomicBool::new(false),
+            webhook,
+        }))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    // 返回是否为首次出现的指纹
+    pub fn record(&self, fingerprint: &str, sql: &str, dialect: &str) -> bool {
+        let now = unix_now();
+        let entry = {
+            let mut entries = self.entries.lock().unwrap();
+            if let Some(entry) = entries.get_mut(fingerprint) {
+                entry.count += 1;
+                entry.last_seen = now;
+                self.dirty.store(true, Ordering::Relaxed);
+                return false;
+            }
+            if entries.len() >= self.max_fingerprints {
+                if !self.full.swap(true, Ordering::Relaxed) {
+                    tracing::warn!(
+                        "Fingerprint registry is full ({} fingerprints), new fingerprints are no longer recorded",
+                        self.max_fingerprints
+                    );
+                }
+                return false;
+            }
+            let entry = FingerprintEntry {
+                hash: fingerprint_hash(fingerprint),
+                fingerprint: fingerprint.to_string(),
+                count: 1,
+                first_seen: now,
+                last_seen: now,
+                sample: truncate(sql, MAX_SAMPLE_BYTES).to_string(),
+                dialect: dialect.to_lowercase(),
+            };
+            entries.insert(fingerprint.to_string(), entry.clone());
+            entry
+        };
+        self.dirty.store(true, Ordering::Relaxed);
+        self.notify(&entry);
+        true
+    }
+
+    fn notify(&self, entry: &FingerprintEntry) {
+        tracing::info!(
+            hash = %entry.hash,
+            fingerprint = %entry.fingerprint,
+            dialect = %entry.dialect,
+            "New fingerprint observed"
+        );
+        let Some(webhook) = &self.webhook else {
+            return;
+        };
+        let request = webhook
+            .client
+            .post(&webhook.url)
+            .json(&NewFingerprintEvent {
+                event: "new_fingerprint",
+                entry,
+            });
+        // 投递失败只记录日志，不影响请求
+        tokio::spawn(async move {
+            let result = request
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status);
+            if let Err(e) = result {
+                tracing::warn!("Failed to deliver new fingerprint webhook: {e}");
+            }
+        });
+    }
+
+    // 返回排序、分页后的记录与总数；排序值相同时按哈希排列，保证翻页稳定
+    pub fn list(
+        &self,
+        sort: SortKey,
+        order: SortOrder,
+        offset: usize,
+        limit: usize,
+    ) -> (usize, Vec<FingerprintEntry>) {
+        let mut entries: Vec<FingerprintEntry> =
+            self.entries.lock().unwrap().values().cloned().collect();
+        let key = |entry: &FingerprintEntry| match sort {
+            SortKey::Count => entry.count,
+            SortKey::FirstSeen => entry.first_seen,
+            SortKey::LastSeen => entry.last_seen,
+        };
+        entries.sort_by(|a, b| {
+            let ordering = match order {
+                SortOrder::Asc => key(a).cmp(&key(b)),
+                SortOrder::Desc => key(b).cmp(&key(a)),
+            };
+            ordering.then_with(|| a.hash.cmp(&b.hash))
+        });
+        let total = entries.len();
+        let page = entries.into_iter().skip(offset).take(limit).collect();
+        (total, page)
+    }
+
+    // 没有配置文件或自上次写入后没有变化时返回 None
+    pub async fn save(self: &Arc<Self>) -> Result<Option<usize>, String> {
+        if self.file.is_none() || !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(None);
+        }
+        let registry = self.clone();
+        let result = tokio::task::spawn_blocking(move || registry.write_file())
+            .await
+            .expect("registry save task panicked");
+        if result.is_err() {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+        result.map(Some)
+    }
+
+    // 先写临时文件再重命名，避免中途停机留下不完整的文件
+    fn write_file(&self) -> Result<usize, String> {
+        let Some(path) = &self.file else {
+            return Ok(0);
+        };
+        let mut fingerprints: Vec<FingerprintEntry> =
+            self.entries.lock().unwrap().values().cloned().collect();
+        fingerprints.sort_by(|a, b| a.hash.cmp(&b.hash));
+        let count = fingerprints.len();
+        let content = serde_json::to_string(&RegistryFile {
+            version: FILE_VERSION,
+            fingerprints,
+        })
+        .map_err(|e| e.to_string())?;
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, content)
+            .and_then(|()| std::fs::rename(&tmp, path))
+            .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+        Ok(count)
+    }
+
+    pub fn spawn_saver(self: &Arc<Self>, interval: Duration) {
+        let registry = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                if let Err(e) = registry.save().await {
+                    tracing::error!("Failed to save fingerprint registry: {e}");
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(file: Option<PathBuf>) -> RegistryConfig {
+        RegistryConfig {
+            enabled: true,
+            max_fingerprints: 3,
+            file,
+            ..RegistryConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_list_and_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fingerprints.json");
+        let registry = FingerprintRegistry::from_config(&config(Some(path.clone()))).unwrap();
+
+        assert!(registry.record("SELECT ?", "SELECT 1", "MySQL"));
+        assert!(!registry.record("SELECT ?", "SELECT 2", "mysql"));
+        assert!(registry.record("DELETE FROM t", "DELETE FROM t", "generic"));
+        assert!(registry.record("UPDATE t SET a = ?", "UPDATE t SET a = 1", "generic"));
+        // 已满
+        assert!(!registry.record(
+            "INSERT INTO t VALUES (?)",
+            "INSERT INTO t VALUES (1)",
+            "generic"
+        ));
+        assert_eq!(registry.len(), 3);
+
+        let (total, page) = registry.list(SortKey::Count, SortOrder::Desc, 0, 2);
+        assert_eq!((total, page.len()), (3, 2));
+        assert_eq!(page[0].fingerprint, "SELECT ?");
+        assert_eq!((page[0].count, page[0].sample.as_str()), (2, "SELECT 1"));
+        assert_eq!(page[0].dialect, "mysql");
+        assert_eq!(page[0].hash, fingerprint_hash("SELECT ?"));
+        let (_, rest) = registry.list(SortKey::Count, SortOrder::Desc, 2, 2);
+        assert_eq!(rest.len(), 1);
+        let (_, asc) = registry.list(SortKey::Count, SortOrder::Asc, 0, 3);
+        assert_eq!(asc[2].fingerprint, "SELECT ?");
+
+        assert_eq!(registry.save().await.unwrap(), Some(3));
+        assert_eq!(registry.save().await.unwrap(), None);
+        let reloaded = FingerprintRegistry::from_config(&config(Some(path))).unwrap();
+        assert_eq!(
+            reloaded.list(SortKey::Count, SortOrder::Desc, 0, 3).1,
+            registry.list(SortKey::Count, SortOrder::Desc, 0, 3).1
+        );
+        assert!(!reloaded.record("DELETE FROM t", "DELETE FROM t", "generic"));
+    }
+
+    #[test]
+    fn test_fingerprint_hash_is_stable() {
+        assert_eq!(
+            fingerprint_hash(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(fingerprint_hash("SELECT ?"), fingerprint_hash("SELECT ?"));
+        assert_ne!(fingerprint_hash("SELECT ?"), fingerprint_hash("SELECT ? "));
+        assert_eq!(truncate("日志", 4), "日");
+    }
+}
@@ -0,0 +1,513 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use sqlparser::dialect::Dialect;
+use sqlparser::keywords::Keyword;
+use sqlparser::tokenizer::{Token, Tokenizer};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum, ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum SlowLogFormat {
+    // 根据内容识别
+    #[default]
+    Auto,
+    // MySQL slow query log（# Query_time: ...）
+    Mysql,
+    // PostgreSQL log_min_duration_statement（LOG:  duration: ... ms  statement: ...）
+    Postgresql,
+}
+
+impl SlowLogFormat {
+    pub fn dialect(self) -> &'static str {
+        match self {
+            SlowLogFormat::Mysql => "mysql",
+            SlowLogFormat::Postgresql => "postgresql",
+            SlowLogFormat::Auto => "generic",
+        }
+    }
+
+    fn detect(log: &str) -> Option<Self> {
+        for line in log.lines() {
+            if line.starts_with("# Query_time:") {
+                return Some(SlowLogFormat::Mysql);
+            }
+            if postgres_duration(line).is_some() {
+                return Some(SlowLogFormat::Postgresql);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogEntry {
+    pub query: String,
+    pub duration_ms: f64,
+    pub rows_sent: Option<u64>,
+    pub rows_examined: Option<u64>,
+    // 日志中的原始时间文本
+    pub time: Option<String>,
+}
+
+// 返回识别出的格式与其中的查询，按日志中的顺序排列
+pub fn parse_log(
+    log: &str,
+    format: SlowLogFormat,
+) -> Result<(SlowLogFormat, Vec<LogEntry>), String> {
+    let format = match format {
+        SlowLogFormat::Auto => SlowLogFormat::detect(log)
+            .ok_or("Unrecognized slow log format: expected a MySQL slow query log or PostgreSQL duration log")?,
+        format => format,
+    };
+    let entries = match format {
+        SlowLogFormat::Mysql => parse_mysql(log),
+        _ => parse_postgres(log),
+    };
+    Ok((format, entries))
+}
+
+fn push_entry(entries: &mut Vec<LogEntry>, entry: Option<LogEntry>) {
+    if let Some(mut entry) = entry {
+        let query = entry.query.trim().trim_end_matches(';').trim_end();
+        if !query.is_empty() {
+            entry.query = query.to_string();
+            entries.push(entry);
+        }
+    }
+}
+
+fn parse_mysql(log: &str) -> Vec<LogEntry> {
+    let mut entries = Vec::new();
+    let mut time: Option<String> = None;
+    let mut current: Option<LogEntry> = None;
+    for line in log.lines() {
+        if let Some(rest) = line.strip_prefix("# Time:") {
+            push_entry(&mut entries, current.take());
+            time = Some(rest.trim().to_string());
+        } else if let Some(rest) = line.strip_prefix("# Query_time:") {
+            push_entry(&mut entries, current.take());
+            let metric = |name: &str| {
+                let mut words = rest.split_whitespace();
+                words.find(|word| word.strip_suffix(':') == Some(name))?;
+                words.next()
+            };
+            current = Some(LogEntry {
+                query: String::new(),
+                duration_ms: rest
+                    .split_whitespace()
+                    .next()
+                    .and_then(|seconds| seconds.parse::<f64>().ok())
+                    .unwrap_or_default()
+                    * 1000.0,
+                rows_sent: metric("Rows_sent").and_then(|rows| rows.parse().ok()),
+                rows_examined: metric("Rows_examined").and_then(|rows| rows.parse().ok()),
+                time: time.clone(),
+            });
+        } else if line.starts_with('#') || is_mysql_banner(line) {
+            // # User@Host 等注释行，以及服务重启时写入的文件头
+            push_entry(&mut entries, current.take());
+        } else if let Some(entry) = &mut current {
+            let trimmed = line.trim();
+            let lower = trimmed.to_ascii_lowercase();
+            // 查询前由 mysqld 写入的 use / SET timestamp 不属于查询本身
+            let preamble = lower.starts_with("set timestamp=")
+                || (lower.starts_with("use ") && lower.ends_with(';'));
+            if entry.query.trim().is_empty() && preamble {
+                continue;
+            }
+            entry.query.push_str(line);
+            entry.query.push('\n');
+        }
+    }
+    push_entry(&mut entries, current);
+    entries
+}
+
+fn is_mysql_banner(line: &str) -> bool {
+    line.contains(", Version: ")
+        || line.starts_with("Tcp port:")
+        || (line.starts_with("Time ") && line.contains("Id Command"))
+}
+
+// 返回 duration 所在行中 "LOG:" 之前的前缀，以及 "duration: " 之后的部分
+fn postgres_duration(line: &str) -> Option<(&str, &str)> {
+    let (prefix, rest) = line.split_once("duration: ")?;
+    prefix.contains("LOG:").then_some((prefix, rest))
+}
+
+fn parse_postgres(log: &str) -> Vec<LogEntry> {
+    let mut entries = Vec::new();
+    let mut current: Option<LogEntry> = None;
+    for line in log.lines() {
+        if let Some((prefix, rest)) = postgres_duration(line) {
+            push_entry(&mut entries, current.take());
+            let Some((duration, message)) = rest.split_once(" ms") else {
+                continue;
+            };
+            let message = message.trim_start();
+            // 扩展查询协议只统计 execute，parse / bind 阶段忽略
+            let query = message.strip_prefix("statement: ").or_else(|| {
+                message
+                    .strip_prefix("execute ")
+                    .and_then(|rest| rest.split_once(": "))
+                    .map(|(_, query)| query)
+            });
+            current = query.map(|query| LogEntry {
+                query: format!("{query}\n"),
+                duration_ms: duration.trim().parse().unwrap_or_default(),
+                rows_sent: None,
+                rows_examined: None,
+                time: postgres_time(prefix),
+            });
+        } else if let Some(continuation) = line.strip_prefix('\t') {
+            // 多行语句的后续行以制表符开头
+            if let Some(entry) = &mut current {
+                entry.query.push_str(continuation);
+                entry.query.push('\n');
+            }
+        } else {
+            push_entry(&mut entries, current.take());
+        }
+    }
+    push_entry(&mut entries, current);
+    entries
+}
+
+// log_line_prefix 以 %t / %m 开头时取出时间，如 "2024-01-15 10:23:45.123 UTC"
+fn postgres_time(prefix: &str) -> Option<String> {
+    let mut words = prefix.split_whitespace();
+    let date = words.next()?;
+    let time = words.next()?;
+    let is_date = date.len() == 10 && date.as_bytes()[4] == b'-' && date.as_bytes()[7] == b'-';
+    if !is_date || !time.contains(':') {
+        return None;
+    }
+    Some(match words.next() {
+        Some(zone) if zone.chars().all(|c| c.is_ascii_alphabetic()) => {
+            format!("{date} {time} {zone}")
+        }
+        _ => format!("{date} {time}"),
+    })
+}
+
+// 无法解析的查询按词法单元归一化：字面量替换为 ?，去掉注释并合并空白，关键字转为大写
+pub fn token_fingerprint(dialect: &dyn Dialect, sql: &str) -> String {
+    let Ok(tokens) = Tokenizer::new(dialect, sql).tokenize() else {
+        return sql.split_whitespace().collect::<Vec<_>>().join(" ");
+    };
+    let mut fingerprint = String::with_capacity(sql.len());
+    let mut pending_space = false;
+    for token in tokens {
+        let text = match &token {
+            Token::Whitespace(_) => {
+                pending_space = true;
+                continue;
+            }
+            Token::Number(_, _)
+            | Token::SingleQuotedString(_)
+            | Token::DoubleQuotedString(_)
+            | Token::NationalStringLiteral(_)
+            | Token::EscapedStringLiteral(_)
+            | Token::HexStringLiteral(_) => "?".to_string(),
+            Token::Word(word)
+                if word.quote_style.is_none() && word.keyword != Keyword::NoKeyword =>
+            {
+                word.value.to_uppercase()
+            }
+            token => token.to_string(),
+        };
+        if pending_space && !fingerprint.is_empty() {
+            fingerprint.push(' ');
+        }
+        pending_space = false;
+        fingerprint.push_str(&text);
+    }
+    fingerprint
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DigestReport {
+    #[schema(example = "mysql")]
+    pub format: SlowLogFormat,
+    // 日志中的查询总数与总耗时
+    pub queries: usize,
+    pub fingerprints: usize,
+    pub total_ms: f64,
+    // 无法解析、按词法单元归一化的查询数
+    pub unparsed: usize,
+    // 按总耗时降序排列
+    pub entries: Vec<DigestEntry>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DigestEntry {
+    pub rank: usize,
+    #[schema(example = "SELECT * FROM orders WHERE user_id = ?")]
+    pub fingerprint: String,
+    // 为 false 时指纹由词法单元归一化得到
+    pub parsed: bool,
+    pub count: usize,
+    pub total_ms: f64,
+    pub avg_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+    // 占全部查询总耗时的百分比
+    pub percent: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rows_sent: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rows_examined: Option<u64>,
+    // 耗时最长的一次查询
+    pub sample: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_seen: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<String>,
+}
+
+struct Group {
+    parsed: bool,
+    durations: Vec<f64>,
+    rows_sent: Option<u64>,
+    rows_examined: Option<u64>,
+    sample: (f64, String),
+    first_seen: Option<String>,
+    last_seen: Option<String>,
+}
+
+// 按指纹汇总；日志按时间顺序写入，首次与最后一次出现即 first_seen / last_seen
+#[derive(Default)]
+pub struct Digest {
+    groups: HashMap<String, Group>,
+    queries: usize,
+    unparsed: usize,
+}
+
+impl Digest {
+    pub fn add(&mut self, entry: LogEntry, fingerprint: String, parsed: bool) {
+        self.queries += 1;
+        if !parsed {
+            self.unparsed += 1;
+        }
+        let group = self.groups.entry(fingerprint).or_insert_with(|| Group {
+            parsed,
+            durations: Vec::new(),
+            rows_sent: None,
+            rows_examined: None,
+            sample: (f64::NEG_INFINITY, String::new()),
+            first_seen: entry.time.clone(),
+            last_seen: None,
+        });
+        group.durations.push(entry.duration_ms);
+        let add = |total: Option<u64>, rows: Option<u64>| match (total, rows) {
+            (Some(total), Some(rows)) => Some(total.saturating_add(rows)),
+            (total, rows) => total.or(rows),
+        };
+        group.rows_sent = add(group.rows_sent, entry.rows_sent);
+        group.rows_examined = add(group.rows_examined, entry.rows_examined);
+        if entry.time.is_some() {
+            group.first_seen = group.first_seen.take().or(entry.time.clone());
+            group.last_seen = entry.time;
+        }
+        if entry.duration_ms > group.sample.0 {
+            group.sample = (entry.duration_ms, entry.query);
+        }
+    }
+
+    // limit 为 0 时返回全部指纹
+    pub fn report(self, format: SlowLogFormat, limit: usize) -> DigestReport {
+        let total_ms: f64 = self.groups.values().flat_map(|g| &g.durations).sum();
+        let fingerprints = self.groups.len();
+        let mut entries: Vec<DigestEntry> = self
+            .groups
+            .into_iter()
+            .map(|(fingerprint, mut group)| {
+                group.durations.sort_by(f64::total_cmp);
+                let count = group.durations.len();
+                let total: f64 = group.durations.iter().sum();
+                // nearest-rank
+                let p95 = group.durations[(count * 95).div_ceil(100).max(1) - 1];
+                DigestEntry {
+                    rank: 0,
+                    fingerprint,
+                    parsed: group.parsed,
+                    count,
+                    total_ms: total,
+                    avg_ms: total / count as f64,
+                    p95_ms: p95,
+                    max_ms: group.durations[count - 1],
+                    percent: if total_ms > 0.0 {
+                        total / total_ms * 100.0
+                    } else {
+                        0.0
+                    },
+                    rows_sent: group.rows_sent,
+                    rows_examined: group.rows_examined,
+                    sample: group.sample.1,
+                    first_seen: group.first_seen,
+                    last_seen: group.last_seen,
+                }
+            })
+            .collect();
+        entries.sort_by(|a, b| {
+            b.total_ms
+                .total_cmp(&a.total_ms)
+                .then(b.count.cmp(&a.count))
+                .then_with(|| a.fingerprint.cmp(&b.fingerprint))
+        });
+        if limit > 0 {
+            entries.truncate(limit);
+        }
+        for (i, entry) in entries.iter_mut().enumerate() {
+            entry.rank = i + 1;
+        }
+        DigestReport {
+            format,
+            queries: self.queries,
+            fingerprints,
+            total_ms,
+            unparsed: self.unparsed,
+            entries,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlparser::dialect::MySqlDialect;
+
+    const MYSQL_LOG: &str = "\
+/usr/sbin/mysqld, Version: 8.0.36 (MySQL Community Server - GPL). started with:
+Tcp port: 3306  Unix socket: /var/run/mysqld/mysqld.sock
+Time                 Id Command    Argument
+# Time: 2024-01-15T10:23:45.123456Z
+# User@Host: app[app] @ localhost []  Id:     8
+# Query_time: 2.500000  Lock_time: 0.000045 Rows_sent: 1  Rows_examined: 1000
+use shop;
+SET timestamp=1705314225;
+SELECT *
+FROM orders WHERE id = 5;
+# Time: 2024-01-15T10:24:00.000000Z
+# User@Host: app[app] @ localhost []  Id:     9
+# Query_time: 0.500000  Lock_time: 0.000010 Rows_sent: 0  Rows_examined: 10
+SET timestamp=1705314240;
+UPDATE orders SET state = 'paid' WHERE id = 7;
+";
+
+    const POSTGRES_LOG: &str = "\
+2024-01-15 10:23:45.123 UTC [12345] LOG:  duration: 1234.567 ms  statement: SELECT * FROM t
+\tWHERE id = 5
+2024-01-15 10:23:46.000 UTC [12345] LOG:  duration: 0.100 ms  parse <unnamed>: SELECT 1
+2024-01-15 10:23:47.000 UTC [12345] LOG:  duration: 10.000 ms  execute <unnamed>: SELECT * FROM t WHERE id = $1
+2024-01-15 10:23:47.000 UTC [12345] DETAIL:  parameters: $1 = '6'
+2024-01-15 10:23:48.000 UTC [12346] LOG:  connection authorized: user=app
+";
+
+    #[test]
+    fn test_parse_mysql_slow_log() {
+        let (format, entries) = parse_log(MYSQL_LOG, SlowLogFormat::Auto).unwrap();
+        assert_eq!(format, SlowLogFormat::Mysql);
+        assert_eq!(
+            entries,
+            [
+                LogEntry {
+                    query: "SELECT *\nFROM orders WHERE id = 5".to_string(),
+                    duration_ms: 2500.0,
+                    rows_sent: Some(1),
+                    rows_examined: Some(1000),
+                    time: Some("2024-01-15T10:23:45.123456Z".to_string()),
+                },
+                LogEntry {
+                    query: "UPDATE orders SET state = 'paid' WHERE id = 7".to_string(),
+                    duration_ms: 500.0,
+                    rows_sent: Some(0),
+                    rows_examined: Some(10),
+                    time: Some("2024-01-15T10:24:00.000000Z".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_postgres_duration_log() {
+        let (format, entries) = parse_log(POSTGRES_LOG, SlowLogFormat::Auto).unwrap();
+        assert_eq!(format, SlowLogFormat::Postgresql);
+        let queries: Vec<_> = entries
+            .iter()
+            .map(|e| (e.query.as_str(), e.duration_ms))
+            .collect();
+        assert_eq!(
+            queries,
+            [
+                ("SELECT * FROM t\nWHERE id = 5", 1234.567),
+                ("SELECT * FROM t WHERE id = $1", 10.0),
+            ]
+        );
+        assert_eq!(
+            entries[0].time.as_deref(),
+            Some("2024-01-15 10:23:45.123 UTC")
+        );
+        assert!(parse_log("just some text", SlowLogFormat::Auto).is_err());
+    }
+
+    #[test]
+    fn test_digest_ranks_by_total_time() {
+        let entry = |duration_ms: f64, time: &str| LogEntry {
+            query: format!("SELECT {duration_ms}"),
+            duration_ms,
+            rows_sent: Some(1),
+            rows_examined: None,
+            time: Some(time.to_string()),
+        };
+        let mut digest = Digest::default();
+        for (i, duration) in (1..=20).enumerate() {
+            digest.add(
+                entry(duration as f64, &format!("t{i:02}")),
+                "a".to_string(),
+                true,
+            );
+        }
+        digest.add(entry(500.0, "t20"), "b".to_string(), false);
+        let report = digest.report(SlowLogFormat::Mysql, 0);
+        assert_eq!(
+            (report.queries, report.fingerprints, report.unparsed),
+            (21, 2, 1)
+        );
+        let b = &report.entries[0];
+        assert_eq!((b.rank, b.fingerprint.as_str(), b.parsed), (1, "b", false));
+        let a = &report.entries[1];
+        assert_eq!((a.count, a.total_ms, a.avg_ms), (20, 210.0, 10.5));
+        assert_eq!((a.p95_ms, a.max_ms), (19.0, 20.0));
+        assert_eq!(a.rows_sent, Some(20));
+        assert_eq!(a.sample, "SELECT 20");
+        assert_eq!(
+            (a.first_seen.as_deref(), a.last_seen.as_deref()),
+            (Some("t00"), Some("t19"))
+        );
+        assert!((a.percent + b.percent - 100.0).abs() < 1e-9);
+
+        // 行数累加到上限后保持不变，不溢出
+        let mut digest = Digest::default();
+        for _ in 0..2 {
+            let entry = LogEntry {
+                rows_examined: Some(u64::MAX),
+                ..entry(1.0, "t00")
+            };
+            digest.add(entry, "a".to_string(), true);
+        }
+        let report = digest.report(SlowLogFormat::Mysql, 0);
+        assert_eq!(report.entries[0].rows_examined, Some(u64::MAX));
+
+        assert_eq!(
+            token_fingerprint(
+                &MySqlDialect {},
+                "select  *  from t where a = 'x' -- c\n and b in (1, 2)"
+            ),
+            "SELECT * FROM t WHERE a = ? AND b IN (?, ?)"
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use sqlparser::tokenizer::Location;
+
+// 将 Tokenizer 给出的行列位置（从 1 开始，按字符计列）转换为字节偏移。
+// 位置需按顺序递增查询，整体为线性复杂度。
+pub struct SourceCursor<'a> {
+    text: &'a str,
+    offset: usize,
+    line: u64,
+    column: u64,
+}
+
+impl<'a> SourceCursor<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    pub fn offset_of(&mut self, location: Location) -> usize {
+        if (location.line, location.column) < (self.line, self.column) {
+            *self = Self::new(self.text);
+        }
+
+        let mut chars = self.text[self.offset..].chars();
+        while (self.line, self.column) < (location.line, location.column) {
+            let Some(c) = chars.next() else {
+                break;
+            };
+            self.offset += c.len_utf8();
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.offset
+    }
+}
+
+// 行首偏移表，支持任意顺序的位置查询（如 AST 节点的起止位置）。
+// 含非 ASCII 字符时另存每个字符的字节偏移，单次查询为常数或对数时间
+pub struct LineIndex<'a> {
+    text: &'a str,
+    line_starts: Vec<usize>,
+    // 非 ASCII 输入：各字符的字节偏移与各行首字符的下标
+    chars: Option<(Vec<usize>, Vec<usize>)>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        let chars = (!text.is_ascii()).then(|| {
+            let chars: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+            let line_chars = line_starts
+                .iter()
+                .map(|&start| chars.partition_point(|&offset| offset < start))
+                .collect();
+            (chars, line_chars)
+        });
+        Self {
+            text,
+            line_starts,
+            chars,
+        }
+    }
+
+    pub fn offset_of(&self, location: Location) -> usize {
+        let Some(line) = (location.line as usize).checked_sub(1) else {
+            return self.text.len();
+        };
+        let Some(&start) = self.line_starts.get(line) else {
+            return self.text.len();
+        };
+        let column = location.column.saturating_sub(1) as usize;
+        match &self.chars {
+            None => (start + column).min(self.text.len()),
+            Some((chars, line_chars)) => chars
+                .get(line_chars[line] + column)
+                .copied()
+                .unwrap_or(self.text.len()),
+        }
+    }
+
+    pub fn location_of(&self, offset: usize) -> Location {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let start = self.line_starts[line - 1];
+        let column = match &self.chars {
+            None => offset - start,
+            Some((chars, line_chars)) => {
+                chars.partition_point(|&i| i < offset) - line_chars[line - 1]
+            }
+        };
+        Location {
+            line: line as u64,
+            column: column as u64 + 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(line: u64, column: u64) -> Location {
+        Location { line, column }
+    }
+
+    #[test]
+    fn test_offsets_across_lines_and_multibyte_chars() {
+        let text = "SELECT '中文'\nFROM t";
+        let mut cursor = SourceCursor::new(text);
+        assert_eq!(cursor.offset_of(location(1, 1)), 0);
+        assert_eq!(cursor.offset_of(location(1, 8)), 7);
+        assert_eq!(cursor.offset_of(location(1, 12)), text.find('\n').unwrap());
+        assert_eq!(cursor.offset_of(location(2, 6)), text.len() - 1);
+        // 回退时重新定位
+        assert_eq!(cursor.offset_of(location(1, 8)), 7);
+    }
+
+    #[test]
+    fn test_line_index_random_access() {
+        let text = "SELECT '中文'\nFROM t";
+        let index = LineIndex::new(text);
+        assert_eq!(index.offset_of(location(2, 6)), text.len() - 1);
+        assert_eq!(index.offset_of(location(1, 8)), 7);
+        assert_eq!(index.offset_of(location(1, 12)), text.find('\n').unwrap());
+        assert_eq!(index.offset_of(location(2, 7)), text.len());
+        assert_eq!(index.location_of(7), location(1, 8));
+        assert_eq!(index.location_of(text.len()), location(2, 7));
+
+        let ascii = LineIndex::new("SELECT a,\n  b");
+        assert_eq!(ascii.offset_of(location(2, 3)), 12);
+        assert_eq!(ascii.offset_of(location(2, 9)), 13);
+        assert_eq!(ascii.location_of(12), location(2, 3));
+    }
+}
@@ -0,0 +1,298 @@
+use sqlparser::dialect::{Dialect, MsSqlDialect, MySqlDialect, PostgreSqlDialect};
+use std::ops::Range;
+
+// 切分只需识别字符串、引号标识符与注释中的分隔符，使用按方言配置的轻量扫描，
+// 而不是 sqlparser 的 Tokenizer：后者不认识 DELIMITER 设置的自定义分隔符，
+// 且遇到未闭合的字符串会整体失败
+struct Lexical {
+    backslash_escape: bool,
+    backtick_quote: bool,
+    bracket_quote: bool,
+    hash_comment: bool,
+    nested_comments: bool,
+    dollar_quote: bool,
+    // MySQL 客户端的 DELIMITER 命令
+    delimiter_command: bool,
+    // SQL Server 的批分隔符 GO
+    go_separator: bool,
+}
+
+impl Lexical {
+    fn for_dialect(dialect: &dyn Dialect) -> Self {
+        let mysql = dialect.is::<MySqlDialect>();
+        let mssql = dialect.is::<MsSqlDialect>();
+        Self {
+            backslash_escape: dialect.supports_string_literal_backslash_escape(),
+            backtick_quote: dialect.is_delimited_identifier_start('`'),
+            bracket_quote: dialect.is_delimited_identifier_start('['),
+            hash_comment: mysql,
+            nested_comments: dialect.is::<PostgreSqlDialect>(),
+            // MySQL 常用 $$ 作为自定义分隔符，不按美元引用处理
+            dollar_quote: !mysql && !mssql,
+            delimiter_command: mysql,
+            go_separator: mssql,
+        }
+    }
+}
+
+// 返回各语句在原文中的字节范围：包含语句前的注释，不含首尾空白与分隔符。
+// 只有空白与注释的片段不算语句。
+pub fn split(dialect: &dyn Dialect, sql: &str) -> Vec<Range<usize>> {
+    Splitter {
+        lexical: Lexical::for_dialect(dialect),
+        sql,
+        pos: 0,
+        start: 0,
+        has_content: false,
+        statements: Vec::new(),
+    }
+    .run()
+}
+
+struct Splitter<'a> {
+    lexical: Lexical,
+    sql: &'a str,
+    pos: usize,
+    // 当前语句的起点（上一个分隔符之后）
+    start: usize,
+    // 当前语句是否已有注释以外的内容
+    has_content: bool,
+    statements: Vec<Range<usize>>,
+}
+
+impl Splitter<'_> {
+    fn run(mut self) -> Vec<Range<usize>> {
+        let mut delimiter = ";".to_string();
+        let mut line_start = true;
+        while self.pos < self.sql.len() {
+            if line_start && self.line_command(&mut delimiter) {
+                continue;
+            }
+            line_start = false;
+
+            let rest = &self.sql[self.pos..];
+            if rest.starts_with(delimiter.as_str()) {
+                self.finish(self.pos);
+                self.pos += delimiter.len();
+                self.start = self.pos;
+                continue;
+            }
+            let mut chars = rest.chars();
+            let c = chars.next().unwrap_or_default();
+            let next = chars.next();
+            match c {
+                '\n' => {
+                    line_start = true;
+                    self.pos += 1;
+                }
+                c if c.is_whitespace() => self.pos += c.len_utf8(),
+                '-' if next == Some('-') => self.skip_line(),
+                '#' if self.lexical.hash_comment => self.skip_line(),
+                '/' if next == Some('*') => self.skip_block_comment(),
+                _ => {
+                    self.has_content = true;
+                    match c {
+                        '\'' | '"' => self.skip_quoted(c, c, self.lexical.backslash_escape),
+                        // PostgreSQL 的转义字符串 E'...'，其中反斜杠总是转义；与 sqlparser 的分词一致，不区分方言
+                        'e' | 'E' if next == Some('\'') && !self.after_word() => {
+                            self.pos += 1;
+                            self.skip_quoted('\'', '\'', true);
+                        }
+                        '`' if self.lexical.backtick_quote => self.skip_quoted('`', '`', false),
+                        '[' if self.lexical.bracket_quote => self.skip_quoted('[', ']', false),
+                        '$' if self.lexical.dollar_quote => self.skip_dollar_quoted(),
+                        c => self.pos += c.len_utf8(),
+                    }
+                }
+            }
+        }
+        self.finish(self.sql.len());
+        self.statements
+    }
+
+    // 行首的 DELIMITER / GO 命令，返回是否已消耗该行
+    fn line_command(&mut self, delimiter: &mut String) -> bool {
+        let line_end = self.sql[self.pos..]
+            .find('\n')
+            .map_or(self.sql.len(), |i| self.pos + i);
+        let line = self.sql[self.pos..line_end].trim();
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default();
+
+        if self.lexical.delimiter_command
+            && !self.has_content
+            && command.eq_ignore_ascii_case("DELIMITER")
+        {
+            let Some(new_delimiter) = words.next() else {
+                return false;
+            };
+            *delimiter = new_delimiter.to_string();
+        } else if self.lexical.go_separator
+            && command.eq_ignore_ascii_case("GO")
+            && words.all(|count| count.chars().all(|c| c.is_ascii_digit()))
+        {
+            self.finish(self.pos);
+        } else {
+            return false;
+        }
+        self.pos = line_end;
+        self.start = line_end;
+        true
+    }
+
+    fn finish(&mut self, end: usize) {
+        if self.has_content {
+            let text = &self.sql[self.start..end];
+            let start = self.start + (text.len() - text.trim_start().len());
+            let end = self.start + text.trim_end().len();
+            self.statements.push(start..end);
+        }
+        self.has_content = false;
+    }
+
+    // 不消耗换行，保证下一行仍被识别为行首
+    fn skip_line(&mut self) {
+        self.pos = self.sql[self.pos..]
+            .find('\n')
+            .map_or(self.sql.len(), |i| self.pos + i);
+    }
+
+    fn skip_block_comment(&mut self) {
+        let mut depth = 0;
+        let bytes = self.sql.as_bytes();
+        while self.pos < bytes.len() {
+            match &bytes[self.pos..] {
+                [b'/', b'*', ..] if depth == 0 || self.lexical.nested_comments => {
+                    depth += 1;
+                    self.pos += 2;
+                }
+                [b'*', b'/', ..] => {
+                    depth -= 1;
+                    self.pos += 2;
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                _ => self.pos += 1,
+            }
+        }
+    }
+
+    // 前一个字符属于标识符时，当前字符只是标识符的一部分
+    fn after_word(&self) -> bool {
+        self.sql[..self.pos]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '$')
+    }
+
+    // 引号重复两次视为转义；未闭合时延伸到输入末尾
+    fn skip_quoted(&mut self, open: char, close: char, backslash_escape: bool) {
+        let mut chars = self.sql[self.pos + open.len_utf8()..].char_indices();
+        let base = self.pos + open.len_utf8();
+        while let Some((i, c)) = chars.next() {
+            if backslash_escape && c == '\\' {
+                chars.next();
+            } else if c == close {
+                if self.sql[base + i + 1..].starts_with(close) {
+                    chars.next();
+                } else {
+                    self.pos = base + i + 1;
+                    return;
+                }
+            }
+        }
+        self.pos = self.sql.len();
+    }
+
+    // $tag$ ... $tag$；$1 等占位符不是美元引用
+    fn skip_dollar_quoted(&mut self) {
+        let rest = &self.sql[self.pos + 1..];
+        let tag_len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let is_tag =
+            rest[tag_len..].starts_with('$') && !rest.starts_with(|c: char| c.is_ascii_digit());
+        if !is_tag {
+            self.pos += 1;
+            return;
+        }
+        let tag = &self.sql[self.pos..self.pos + tag_len + 2];
+        let body = self.pos + tag.len();
+        self.pos = self.sql[body..]
+            .find(tag)
+            .map_or(self.sql.len(), |i| body + i + tag.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlparser::dialect::GenericDialect;
+
+    fn texts<'a>(dialect: &dyn Dialect, sql: &'a str) -> Vec<&'a str> {
+        split(dialect, sql)
+            .into_iter()
+            .map(|range| &sql[range])
+            .collect()
+    }
+
+    #[test]
+    fn test_split_keeps_comments_and_quoted_delimiters() {
+        let sql = "-- 建表\nCREATE TABLE t (a TEXT);\n\n\
+                   INSERT INTO t VALUES ('a;b'), (\"c;\"); /* ; */ ;\n\
+                   SELECT $$;$$, $1 FROM t -- trailing ;\n";
+        assert_eq!(
+            texts(&PostgreSqlDialect {}, sql),
+            [
+                "-- 建表\nCREATE TABLE t (a TEXT)",
+                "INSERT INTO t VALUES ('a;b'), (\"c;\")",
+                "SELECT $$;$$, $1 FROM t -- trailing ;",
+            ]
+        );
+        assert!(texts(&GenericDialect {}, " ;; -- only a comment\n").is_empty());
+    }
+
+    #[test]
+    fn test_postgres_escape_strings() {
+        let sql = "SELECT E'it\\'s;' AS e; SELECT e'\\\\'; SELECT 'C:\\'; SELECT name'x;' FROM t";
+        assert_eq!(
+            texts(&PostgreSqlDialect {}, sql),
+            [
+                "SELECT E'it\\'s;' AS e",
+                "SELECT e'\\\\'",
+                "SELECT 'C:\\'",
+                "SELECT name'x;' FROM t",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mysql_delimiter_command() {
+        let sql = "SELECT 'it\\'s;';\nDELIMITER $$\n\
+                   CREATE PROCEDURE p() BEGIN SELECT 1; SELECT `a;b`; END$$\n\
+                   DELIMITER ;\nSELECT 2 # done;\n;";
+        assert_eq!(
+            texts(&MySqlDialect {}, sql),
+            [
+                "SELECT 'it\\'s;'",
+                "CREATE PROCEDURE p() BEGIN SELECT 1; SELECT `a;b`; END",
+                "SELECT 2 # done;",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mssql_go_batches() {
+        let sql = "CREATE TABLE [a;b] (x INT)\nGO\nSELECT 1; SELECT 2\ngo 2\nSELECT 'GO'\n";
+        assert_eq!(
+            texts(&MsSqlDialect {}, sql),
+            [
+                "CREATE TABLE [a;b] (x INT)",
+                "SELECT 1",
+                "SELECT 2",
+                "SELECT 'GO'",
+            ]
+        );
+    }
+}
@@ -0,0 +1,526 @@
+use crate::config::TlsConfig;
+use axum::extract::ConnectInfo;
+use axum::Router;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::graceful::GracefulShutdown;
+use serde::Serialize;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::rustls::pki_types::CertificateDer;
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{self, RootCertStore, ServerConfig};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tower::ServiceExt;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const ACCEPT_BACKOFF_MIN: Duration = Duration::from_millis(5);
+const ACCEPT_BACKOFF_MAX: Duration = Duration::from_secs(1);
+
+// accept 失败时的退避：文件描述符耗尽等错误会立即重复出现，直接重试会空转占满 CPU，
+// 因此先等待再重试，连续失败时等待时间加倍；单个连接在握手前被对端重置不需要等待
+#[derive(Default)]
+struct AcceptBackoff {
+    delay: Option<Duration>,
+}
+
+impl AcceptBackoff {
+    fn reset(&mut self) {
+        self.delay = None;
+    }
+
+    fn next_delay(&mut self, e: &std::io::Error) -> Option<Duration> {
+        use std::io::ErrorKind;
+        if matches!(
+            e.kind(),
+            ErrorKind::ConnectionAborted | ErrorKind::ConnectionReset | ErrorKind::ConnectionRefused
+        ) {
+            return None;
+        }
+        let delay = self
+            .delay
+            .map_or(ACCEPT_BACKOFF_MIN, |delay| (delay * 2).min(ACCEPT_BACKOFF_MAX));
+        self.delay = Some(delay);
+        Some(delay)
+    }
+
+    async fn wait(&mut self, e: std::io::Error) {
+        tracing::warn!("Failed to accept connection: {e}");
+        if let Some(delay) = self.next_delay(&e) {
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+// 客户端证书中的身份信息（mTLS），HTTP 通过请求扩展、gRPC 通过 peer_certs 获取
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ClientIdentity {
+    pub subject: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub common_name: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub subject_alt_names: Vec<String>,
+}
+
+impl ClientIdentity {
+    pub fn from_der(der: &[u8]) -> Option<Self> {
+        let (_, cert) = x509_parser::parse_x509_certificate(der).ok()?;
+        let subject = cert.subject();
+        let common_name = subject
+            .iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .map(str::to_string);
+        let subject_alt_names = cert
+            .subject_alternative_name()
+            .ok()
+            .flatten()
+            .map(|san| {
+                san.value
+                    .general_names
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(Self {
+            subject: subject.to_string(),
+            common_name,
+            subject_alt_names,
+        })
+    }
+
+    pub fn from_peer_certs(certs: Option<&[CertificateDer<'_>]>) -> Option<Self> {
+        certs?.first().and_then(|cert| Self::from_der(cert))
+    }
+
+    // 用于日志的简短名称
+    pub fn name(&self) -> &str {
+        self.common_name.as_deref().unwrap_or(&self.subject)
+    }
+}
+
+pub fn grpc_client_identity<T>(request: &tonic::Request<T>) -> Option<ClientIdentity> {
+    // 单端口模式下 gRPC 请求经由 serve_https，身份已在请求扩展中
+    if let Some(identity) = request.extensions().get::<ClientIdentity>() {
+        return Some(identity.clone());
+    }
+    let certs = request.peer_certs()?;
+    ClientIdentity::from_peer_certs(Some(certs.as_slice()))
+}
+
+// 证书文件变化时自动重新加载的 TLS 配置
+pub struct ReloadableTls {
+    config: TlsConfig,
+    alpn: Vec<Vec<u8>>,
+    current: RwLock<(Arc<ServerConfig>, Vec<Option<SystemTime>>)>,
+}
+
+impl ReloadableTls {
+    pub fn new(config: &TlsConfig, alpn: &[&[u8]]) -> Result<Arc<Self>, String> {
+        let alpn: Vec<Vec<u8>> = alpn.iter().map(|p| p.to_vec()).collect();
+        let server_config = build_server_config(config, &alpn)?;
+        Ok(Arc::new(Self {
+            current: RwLock::new((server_config, modified_times(config))),
+            config: config.clone(),
+            alpn,
+        }))
+    }
+
+    pub fn acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(self.current.read().unwrap().0.clone())
+    }
+
+    // 文件修改时间变化时重新加载；加载失败时保留旧证书
+    pub fn reload_if_changed(&self) -> Result<bool, String> {
+        let modified = modified_times(&self.config);
+        if self.current.read().unwrap().1 == modified {
+            return Ok(false);
+        }
+        let server_config = build_server_config(&self.config, &self.alpn)?;
+        *self.current.write().unwrap() = (server_config, modified);
+        Ok(true)
+    }
+
+    pub fn spawn_reloader(self: &Arc<Self>) {
+        if self.config.reload_interval == 0 {
+            return;
+        }
+        let tls = self.clone();
+        let interval = Duration::from_secs(self.config.reload_interval);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                match tls.reload_if_changed() {
+                    Ok(true) => tracing::info!("Reloaded TLS certificates"),
+                    Ok(false) => {}
+                    Err(e) => tracing::warn!("Failed to reload TLS certificates: {e}"),
+                }
+            }
+        });
+    }
+}
+
+fn tls_paths(config: &TlsConfig) -> Vec<&PathBuf> {
+    [&config.cert, &config.key, &config.client_ca]
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+fn modified_times(config: &TlsConfig) -> Vec<Option<SystemTime>> {
+    tls_paths(config)
+        .into_iter()
+        .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+fn read_pem(path: &Path) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("failed to read {}: {e}", path.display()))
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, String> {
+    let pem = read_pem(path)?;
+    let certs = rustls_pemfile::certs(&mut pem.as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("invalid certificate in {}: {e}", path.display()))?;
+    if certs.is_empty() {
+        return Err(format!("no certificate found in {}", path.display()));
+    }
+    Ok(certs)
+}
+
+fn build_server_config(config: &TlsConfig, alpn: &[Vec<u8>]) -> Result<Arc<ServerConfig>, String> {
+    let (Some(cert_path), Some(key_path)) = (&config.cert, &config.key) else {
+        return Err("tls.cert and tls.key are required when TLS is enabled".to_string());
+    };
+    let certs = load_certs(cert_path)?;
+    let key = rustls_pemfile::private_key(&mut read_pem(key_path)?.as_slice())
+        .map_err(|e| format!("invalid private key in {}: {e}", key_path.display()))?
+        .ok_or_else(|| format!("no private key found in {}", key_path.display()))?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?;
+
+    let builder = match &config.client_ca {
+        Some(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(ca_path)? {
+                roots
+                    .add(cert)
+                    .map_err(|e| format!("invalid CA certificate in {}: {e}", ca_path.display()))?;
+            }
+            let mut verifier =
+                WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            if config.client_auth_optional {
+                verifier = verifier.allow_unauthenticated();
+            }
+            builder.with_client_cert_verifier(verifier.build().map_err(|e| e.to_string())?)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut server_config = builder
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("invalid certificate/key pair: {e}"))?;
+    server_config.alpn_protocols = alpn.to_vec();
+    Ok(Arc::new(server_config))
+}
+
+async fn handshake(
+    acceptor: TlsAcceptor,
+    stream: TcpStream,
+    peer: SocketAddr,
+) -> Option<TlsStream<TcpStream>> {
+    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+        Ok(Ok(stream)) => Some(stream),
+        Ok(Err(e)) => {
+            tracing::debug!("TLS handshake with {peer} failed: {e}");
+            None
+        }
+        Err(_) => {
+            tracing::debug!("TLS handshake with {peer} timed out");
+            None
+        }
+    }
+}
+
+// HTTPS：手动 accept + TLS 握手，并将客户端身份放入请求扩展
+// shutdown 完成后停止接受新连接，并等待已有连接处理完当前请求
+pub async fn serve_https(
+    listener: TcpListener,
+    app: Router,
+    tls: Arc<ReloadableTls>,
+    shutdown: impl Future<Output = ()>,
+) {
+    let graceful = GracefulShutdown::new();
+    let mut backoff = AcceptBackoff::default();
+    tokio::pin!(shutdown);
+    loop {
+        let (stream, peer) = tokio::select! {
+            conn = listener.accept() => match conn {
+                Ok(conn) => conn,
+                Err(e) => {
+                    backoff.wait(e).await;
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+        backoff.reset();
+        let acceptor = tls.acceptor();
+        let app = app.clone();
+        let watcher = graceful.watcher();
+
+        tokio::spawn(async move {
+            let Some(stream) = handshake(acceptor, stream, peer).await else {
+                return;
+            };
+            let identity = ClientIdentity::from_peer_certs(stream.get_ref().1.peer_certificates());
+
+            let service = hyper::service::service_fn(move |mut request: hyper::Request<_>| {
+                request.extensions_mut().insert(ConnectInfo(peer));
+                if let Some(identity) = &identity {
+                    request.extensions_mut().insert(identity.clone());
+                }
+                app.clone().oneshot(request)
+            });
+
+            let conn = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .into_owned();
+            if let Err(e) = watcher.watch(conn).await {
+                tracing::debug!("HTTPS connection with {peer} closed with error: {e}");
+            }
+        });
+    }
+    drop(listener);
+    graceful.shutdown().await;
+}
+
+// gRPC：将 TLS 连接作为 tonic 的 incoming 流，peer_certs() 可用
+pub fn grpc_incoming(
+    listener: TcpListener,
+    tls: Arc<ReloadableTls>,
+) -> impl tokio_stream::Stream<Item = Result<TlsStream<TcpStream>, std::io::Error>> {
+    let (sender, receiver) = tokio::sync::mpsc::channel(128);
+    tokio::spawn(async move {
+        let mut backoff = AcceptBackoff::default();
+        loop {
+            // tonic 停止读取 incoming 后退出，释放监听端口
+            let (stream, peer) = tokio::select! {
+                conn = listener.accept() => match conn {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        backoff.wait(e).await;
+                        continue;
+                    }
+                },
+                _ = sender.closed() => break,
+            };
+            backoff.reset();
+            let acceptor = tls.acceptor();
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                if let Some(stream) = handshake(acceptor, stream, peer).await {
+                    let _ = sender.send(Ok(stream)).await;
+                }
+            });
+        }
+    });
+    tokio_stream::wrappers::ReceiverStream::new(receiver)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use axum::Extension;
+    use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::rustls::pki_types::{PrivateKeyDer, ServerName};
+    use tokio_rustls::TlsConnector;
+
+    struct TestPki {
+        ca: rcgen::Certificate,
+        ca_key: KeyPair,
+    }
+
+    impl TestPki {
+        fn new() -> Self {
+            let mut params = CertificateParams::new(Vec::new()).unwrap();
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            params
+                .distinguished_name
+                .push(DnType::CommonName, "test ca");
+            let ca_key = KeyPair::generate().unwrap();
+            let ca = params.self_signed(&ca_key).unwrap();
+            Self { ca, ca_key }
+        }
+
+        // 返回 (证书 PEM, 私钥 PEM)
+        fn issue(&self, common_name: &str, sans: &[&str]) -> (String, String) {
+            let mut params =
+                CertificateParams::new(sans.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+                    .unwrap();
+            params
+                .distinguished_name
+                .push(DnType::CommonName, common_name);
+            let key = KeyPair::generate().unwrap();
+            let cert = params.signed_by(&key, &self.ca, &self.ca_key).unwrap();
+            (cert.pem(), key.serialize_pem())
+        }
+    }
+
+    fn write_server_files(dir: &Path, pki: &TestPki, common_name: &str) -> TlsConfig {
+        let (cert, key) = pki.issue(common_name, &["localhost"]);
+        std::fs::write(dir.join("server.pem"), cert).unwrap();
+        std::fs::write(dir.join("server-key.pem"), key).unwrap();
+        std::fs::write(dir.join("ca.pem"), pki.ca.pem()).unwrap();
+        TlsConfig {
+            enabled: true,
+            cert: Some(dir.join("server.pem")),
+            key: Some(dir.join("server-key.pem")),
+            client_ca: Some(dir.join("ca.pem")),
+            client_auth_optional: false,
+            reload_interval: 0,
+        }
+    }
+
+    fn connector(pki: &TestPki, client: Option<(String, String)>) -> TlsConnector {
+        let mut roots = RootCertStore::empty();
+        roots.add(pki.ca.der().clone()).unwrap();
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = rustls::ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots);
+        let config = match client {
+            Some((cert, key)) => {
+                let certs = rustls_pemfile::certs(&mut cert.as_bytes())
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap();
+                let key: PrivateKeyDer = rustls_pemfile::private_key(&mut key.as_bytes())
+                    .unwrap()
+                    .unwrap();
+                builder.with_client_auth_cert(certs, key).unwrap()
+            }
+            None => builder.with_no_client_auth(),
+        };
+        TlsConnector::from(Arc::new(config))
+    }
+
+    async fn start_server(tls: Arc<ReloadableTls>) -> SocketAddr {
+        let app = Router::new().route(
+            "/whoami",
+            get(|identity: Option<Extension<ClientIdentity>>| async move {
+                identity
+                    .map(|Extension(id)| id.name().to_string())
+                    .unwrap_or_default()
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_https(listener, app, tls, std::future::pending()));
+        addr
+    }
+
+    async fn request(
+        addr: SocketAddr,
+        connector: &TlsConnector,
+    ) -> std::io::Result<(String, Option<ClientIdentity>)> {
+        let stream = TcpStream::connect(addr).await?;
+        let mut stream = connector
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await?;
+        let server = ClientIdentity::from_peer_certs(stream.get_ref().1.peer_certificates());
+        stream
+            .write_all(b"GET /whoami HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        Ok((response, server))
+    }
+
+    #[test]
+    fn test_accept_backoff() {
+        use std::io::{Error, ErrorKind};
+
+        let mut backoff = AcceptBackoff::default();
+        let exhausted = Error::other("Too many open files");
+        let delays: Vec<_> = (0..10).map(|_| backoff.next_delay(&exhausted).unwrap()).collect();
+        assert_eq!(delays[0], ACCEPT_BACKOFF_MIN);
+        assert_eq!(delays[1], ACCEPT_BACKOFF_MIN * 2);
+        assert_eq!(delays[9], ACCEPT_BACKOFF_MAX);
+        assert_eq!(backoff.next_delay(&Error::from(ErrorKind::ConnectionReset)), None);
+        backoff.reset();
+        assert_eq!(backoff.next_delay(&exhausted), Some(ACCEPT_BACKOFF_MIN));
+    }
+
+    #[test]
+    fn test_client_identity_from_certificate() {
+        let pki = TestPki::new();
+        let (cert, _) = pki.issue("client-a", &["client-a.internal"]);
+        let der = rustls_pemfile::certs(&mut cert.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        let identity = ClientIdentity::from_der(&der).unwrap();
+        assert_eq!(identity.name(), "client-a");
+        assert_eq!(identity.subject, "CN=client-a");
+        assert_eq!(
+            identity.subject_alt_names,
+            vec!["DNSName(client-a.internal)"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mtls_exposes_client_identity() {
+        let dir = tempfile::tempdir().unwrap();
+        let pki = TestPki::new();
+        let tls = ReloadableTls::new(
+            &write_server_files(dir.path(), &pki, "server"),
+            &[b"http/1.1"],
+        )
+        .unwrap();
+        let addr = start_server(tls).await;
+
+        let client = connector(&pki, Some(pki.issue("client-a", &[])));
+        let (response, _) = request(addr, &client).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert!(response.ends_with("client-a"), "{response}");
+
+        // 未提供客户端证书时握手失败
+        assert!(request(addr, &connector(&pki, None)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_certificates_are_reloaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let pki = TestPki::new();
+        let mut config = write_server_files(dir.path(), &pki, "server-v1");
+        config.client_ca = None;
+        let tls = ReloadableTls::new(&config, &[b"http/1.1"]).unwrap();
+        let addr = start_server(tls.clone()).await;
+
+        let client = connector(&pki, None);
+        let (_, server) = request(addr, &client).await.unwrap();
+        assert_eq!(server.unwrap().name(), "server-v1");
+
+        assert!(!tls.reload_if_changed().unwrap());
+        // 保证修改时间变化
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        write_server_files(dir.path(), &pki, "server-v2");
+        assert!(tls.reload_if_changed().unwrap());
+
+        let (_, server) = request(addr, &client).await.unwrap();
+        assert_eq!(server.unwrap().name(), "server-v2");
+    }
+}
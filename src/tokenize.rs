@@ -0,0 +1,219 @@
+use crate::source::SourceCursor;
+use serde::Serialize;
+use sqlparser::dialect::Dialect;
+use sqlparser::keywords::Keyword;
+use sqlparser::tokenizer::{Location, Token, Tokenizer, Whitespace};
+use utoipa::ToSchema;
+
+// 供编辑器语法高亮使用的分类，运算符与标点统一为 operator
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenKind {
+    Keyword,
+    Identifier,
+    QuotedIdentifier,
+    String,
+    Number,
+    Operator,
+    Comment,
+    Whitespace,
+    Placeholder,
+}
+
+// 行列从 1 开始，列按字符计；offset 为原文中的字节偏移
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Position {
+    pub line: u64,
+    pub column: u64,
+    pub offset: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, ToSchema)]
+pub struct SqlToken {
+    pub kind: TokenKind,
+    // 原文片段，与 [start.offset, end.offset) 对应
+    pub text: String,
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, ToSchema)]
+pub struct TokenError {
+    #[schema(example = "Unterminated string literal")]
+    pub message: String,
+    pub position: Position,
+}
+
+// 分词中途失败时 tokens 为出错位置之前的部分结果
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenList {
+    pub tokens: Vec<SqlToken>,
+    pub error: Option<TokenError>,
+}
+
+pub fn tokenize(dialect: &dyn Dialect, sql: &str) -> TokenList {
+    let mut tokens = Vec::new();
+    let result = Tokenizer::new(dialect, sql).tokenize_with_location_into_buf(&mut tokens);
+
+    let mut cursor = SourceCursor::new(sql);
+    let mut position = |location: Location| Position {
+        line: location.line,
+        column: location.column,
+        offset: cursor.offset_of(location),
+    };
+
+    let mut merged: Vec<SqlToken> = Vec::with_capacity(tokens.len());
+    let mut after_period = false;
+    for token in tokens.iter().filter(|t| t.token != Token::EOF) {
+        let kind = kind_of(&token.token, after_period);
+        if !matches!(token.token, Token::Whitespace(_)) {
+            after_period = token.token == Token::Period;
+        }
+        let (start, end) = (position(token.span.start), position(token.span.end));
+        let text = &sql[start.offset..end.offset];
+        // Tokenizer 对每个空白字符单独成词，连续的空白合并为一个
+        match merged.last_mut() {
+            Some(last) if kind == TokenKind::Whitespace && last.kind == kind => {
+                last.text.push_str(text);
+                last.end = end;
+            }
+            _ => merged.push(SqlToken {
+                kind,
+                text: text.to_string(),
+                start,
+                end,
+            }),
+        }
+    }
+    let error = result.err().map(|e| TokenError {
+        position: position(e.location),
+        message: e.message,
+    });
+    TokenList {
+        tokens: merged,
+        error,
+    }
+}
+
+// 关键字表包含大量非保留字（如 id、name），紧跟在 . 之后的一律视为标识符
+fn kind_of(token: &Token, after_period: bool) -> TokenKind {
+    match token {
+        Token::Word(word) if word.quote_style.is_some() => TokenKind::QuotedIdentifier,
+        Token::Word(word) if word.keyword != Keyword::NoKeyword && !after_period => {
+            TokenKind::Keyword
+        }
+        Token::Word(_) => TokenKind::Identifier,
+        Token::Number(_, _) => TokenKind::Number,
+        Token::SingleQuotedString(_)
+        | Token::DoubleQuotedString(_)
+        | Token::TripleSingleQuotedString(_)
+        | Token::TripleDoubleQuotedString(_)
+        | Token::DollarQuotedString(_)
+        | Token::SingleQuotedByteStringLiteral(_)
+        | Token::DoubleQuotedByteStringLiteral(_)
+        | Token::TripleSingleQuotedByteStringLiteral(_)
+        | Token::TripleDoubleQuotedByteStringLiteral(_)
+        | Token::SingleQuotedRawStringLiteral(_)
+        | Token::DoubleQuotedRawStringLiteral(_)
+        | Token::TripleSingleQuotedRawStringLiteral(_)
+        | Token::TripleDoubleQuotedRawStringLiteral(_)
+        | Token::NationalStringLiteral(_)
+        | Token::EscapedStringLiteral(_)
+        | Token::UnicodeStringLiteral(_)
+        | Token::HexStringLiteral(_) => TokenKind::String,
+        Token::Whitespace(Whitespace::SingleLineComment { .. })
+        | Token::Whitespace(Whitespace::MultiLineComment(_)) => TokenKind::Comment,
+        Token::Whitespace(_) => TokenKind::Whitespace,
+        Token::Placeholder(_) => TokenKind::Placeholder,
+        _ => TokenKind::Operator,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlparser::dialect::{MySqlDialect, PostgreSqlDialect};
+
+    fn summary(list: &TokenList) -> Vec<(TokenKind, &str)> {
+        list.tokens
+            .iter()
+            .map(|t| (t.kind, t.text.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_token_kinds_and_text() {
+        let sql = "SELECT `name`, x.id -- 注释\nFROM t WHERE a = 'it''s' AND b > 1.5 AND c = ?";
+        let list = tokenize(&MySqlDialect {}, sql);
+        assert_eq!(list.error, None);
+        assert_eq!(
+            summary(&list)[..8],
+            [
+                (TokenKind::Keyword, "SELECT"),
+                (TokenKind::Whitespace, " "),
+                (TokenKind::QuotedIdentifier, "`name`"),
+                (TokenKind::Operator, ","),
+                (TokenKind::Whitespace, " "),
+                (TokenKind::Identifier, "x"),
+                (TokenKind::Operator, "."),
+                (TokenKind::Identifier, "id"),
+            ]
+        );
+        assert!(summary(&list).contains(&(TokenKind::Comment, "-- 注释\n")));
+        assert!(summary(&list).contains(&(TokenKind::String, "'it''s'")));
+        assert!(summary(&list).contains(&(TokenKind::Number, "1.5")));
+        assert!(summary(&list).contains(&(TokenKind::Placeholder, "?")));
+        // 拼接后还原原文
+        let text: String = list.tokens.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(text, sql);
+
+        let from = list.tokens.iter().find(|t| t.text == "FROM").unwrap();
+        assert_eq!(
+            (from.start, from.end),
+            (
+                Position {
+                    line: 2,
+                    column: 1,
+                    offset: sql.find("FROM").unwrap()
+                },
+                Position {
+                    line: 2,
+                    column: 5,
+                    offset: sql.find("FROM").unwrap() + 4
+                },
+            )
+        );
+        let comment = list
+            .tokens
+            .iter()
+            .find(|t| t.kind == TokenKind::Comment)
+            .unwrap();
+        assert_eq!((comment.end.line, comment.end.column), (2, 1));
+    }
+
+    #[test]
+    fn test_partial_tokens_on_error() {
+        let sql = "SELECT a,\n  'unterminated";
+        let list = tokenize(&PostgreSqlDialect {}, sql);
+        let error = list.error.as_ref().unwrap();
+        assert_eq!(error.message, "Unterminated string literal");
+        assert_eq!(
+            error.position,
+            Position {
+                line: 2,
+                column: 3,
+                offset: sql.find('\'').unwrap()
+            }
+        );
+        assert_eq!(
+            summary(&list),
+            [
+                (TokenKind::Keyword, "SELECT"),
+                (TokenKind::Whitespace, " "),
+                (TokenKind::Identifier, "a"),
+                (TokenKind::Operator, ","),
+                (TokenKind::Whitespace, "\n  "),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1376 @@
+use crate::catalog::Catalog;
+use crate::source::LineIndex;
+use crate::tokenize::Position;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use sqlparser::ast::{
+    Assignment, AssignmentTarget, Expr, FromTable, GroupByExpr, Ident, Insert, JoinConstraint,
+    JoinOperator, ObjectName, OnConflictAction, OnInsert, Query, Select, SelectItem, SetExpr,
+    Spanned, Statement, TableAliasColumnDef, TableFactor, TableWithJoins, Visit, Visitor,
+    WildcardAdditionalOptions,
+};
+use sqlparser::tokenizer::{Location, Span};
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+use utoipa::ToSchema;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticCode {
+    UnknownTable,
+    UnknownColumn,
+    AmbiguousColumn,
+    InsertColumnCount,
+    UnknownFunction,
+    // 仅 /expand：无法展开的 * 或 alias.*
+    UnresolvedWildcard,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, ToSchema)]
+pub struct Diagnostic {
+    pub code: DiagnosticCode,
+
+    #[schema(example = "Unknown column 'nmae'")]
+    pub message: String,
+
+    // 问题所在的原文位置；AST 中缺少位置信息时省略
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<Position>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<Position>,
+}
+
+// 按表结构目录检查语句，sql 为解析所用的原始输入，诊断按出现位置排序
+pub fn validate(catalog: &Catalog, statements: &[Statement], sql: &str) -> Vec<Diagnostic> {
+    let mut analyzer = Analyzer::new(catalog);
+    for statement in statements {
+        analyzer.statement(statement);
+    }
+    diagnostics(analyzer.findings, sql)
+}
+
+// 诊断按出现位置排序，位置换算为相对于 sql 的行列与偏移
+pub(crate) fn diagnostics(mut findings: Vec<Finding>, sql: &str) -> Vec<Diagnostic> {
+    findings.sort_by_key(|f| (f.span == Span::empty(), f.span.start));
+
+    let lines = LineIndex::new(sql);
+    let position = |location: Location| Position {
+        line: location.line,
+        column: location.column,
+        offset: lines.offset_of(location),
+    };
+    findings
+        .into_iter()
+        .map(|finding| {
+            let known = finding.span != Span::empty();
+            Diagnostic {
+                code: finding.code,
+                message: finding.message,
+                start: known.then(|| position(finding.span.start)),
+                end: known.then(|| position(finding.span.end)),
+            }
+        })
+        .collect()
+}
+
+// 查询的输出列名，未命名的表达式为空串；包含列未知的关系时为 None
+pub fn output_columns(catalog: &Catalog, query: &Query) -> Option<Vec<String>> {
+    Analyzer::new(catalog).query(query, &[])
+}
+
+// 各查询块中 * 与 alias.* 展开后的投影项，新生成的列名必要时用 quote 加引号
+pub(crate) fn wildcard_expansions(
+    catalog: &Catalog,
+    statements: &[Statement],
+    quote: char,
+) -> Vec<Expansion> {
+    let mut analyzer = Analyzer::new(catalog);
+    analyzer.quote = quote;
+    analyzer.expansions = Some(Vec::new());
+    for statement in statements {
+        analyzer.statement(statement);
+    }
+    analyzer.expansions.unwrap_or_default()
+}
+
+pub(crate) struct Finding {
+    pub code: DiagnosticCode,
+    pub message: String,
+    pub span: Span,
+}
+
+pub(crate) struct Expansion {
+    // 通配符 * 的位置，用于在 AST 中找回对应的投影项
+    pub wildcard: Span,
+    // 展开后的投影项，无法展开时为原因
+    pub items: Result<Vec<SelectItem>, String>,
+}
+
+// 查询块中可见的表、CTE、派生表等
+#[derive(Debug)]
+struct Relation {
+    // 引用名（小写）：别名，没有别名时为表名的最后一部分
+    name: Option<String>,
+    // 完整表名的小写各部分，用于 schema.table.column 形式的引用
+    path: Vec<String>,
+    // 展开 * 时使用的限定名：别名，没有别名时为原样的表名；为空时不限定
+    qualifier: Vec<Ident>,
+    // 输出列；None 表示列未知（未知表、表函数等），不做列检查
+    columns: Option<Vec<String>>,
+}
+
+impl Relation {
+    fn has_column(&self, column: &str) -> Option<bool> {
+        self.columns
+            .as_ref()
+            .map(|columns| columns.iter().any(|c| c.to_lowercase() == column))
+    }
+
+    fn matches(&self, qualifier: &[String]) -> bool {
+        match qualifier {
+            [name] => self.name.as_ref() == Some(name),
+            _ => !self.path.is_empty() && self.path.ends_with(qualifier),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Scope {
+    relations: Vec<Relation>,
+    // USING / NATURAL 合并的列（小写）及参与合并的关系下标，非限定引用不算歧义
+    merged: Vec<(String, Vec<usize>)>,
+    // 包含无法确定引用名的关系（如未处理的表函数），不报告未知的限定名
+    opaque: bool,
+}
+
+impl Scope {
+    fn find(&self, qualifier: &[String]) -> Option<&Relation> {
+        self.relations.iter().find(|r| r.matches(qualifier))
+    }
+
+    fn merge(&mut self, column: String, mut relations: Vec<usize>) {
+        self.merged.retain(|(c, existing)| {
+            let overlaps = *c == column && existing.iter().any(|i| relations.contains(i));
+            if overlaps {
+                let missing: Vec<usize> = existing
+                    .iter()
+                    .copied()
+                    .filter(|i| !relations.contains(i))
+                    .collect();
+                relations.extend(missing);
+            }
+            !overlaps
+        });
+        self.merged.push((column, relations));
+    }
+
+    // 包含该列的关系中，合并的列只算一个来源
+    fn sources(&self, column: &str, matches: &[usize]) -> usize {
+        let mut groups = Vec::new();
+        let mut count = 0;
+        for index in matches {
+            match self
+                .merged
+                .iter()
+                .position(|(c, relations)| c == column && relations.contains(index))
+            {
+                Some(group) if groups.contains(&group) => {}
+                Some(group) => {
+                    groups.push(group);
+                    count += 1;
+                }
+                None => count += 1,
+            }
+        }
+        count
+    }
+
+    // SELECT * 依次输出各关系的列（关系下标与列名），合并的列只出现一次；
+    // 有列未知的关系时返回其下标
+    fn wildcard_sources(&self) -> Result<Vec<(usize, &str)>, usize> {
+        let mut sources = Vec::new();
+        let mut emitted: Vec<usize> = Vec::new();
+        for (index, relation) in self.relations.iter().enumerate() {
+            let Some(columns) = &relation.columns else {
+                return Err(index);
+            };
+            for column in columns {
+                let lower = column.to_lowercase();
+                let group = self
+                    .merged
+                    .iter()
+                    .position(|(c, relations)| *c == lower && relations.contains(&index));
+                match group {
+                    Some(group) if emitted.contains(&group) => continue,
+                    Some(group) => emitted.push(group),
+                    None => {}
+                }
+                sources.push((index, column.as_str()));
+            }
+        }
+        Ok(sources)
+    }
+
+    fn is_merged(&self, index: usize, column: &str) -> bool {
+        self.merged
+            .iter()
+            .any(|(c, relations)| c == column && relations.contains(&index))
+    }
+}
+
+fn lowercase(idents: &[Ident]) -> Vec<String> {
+    idents.iter().map(|i| i.value.to_lowercase()).collect()
+}
+
+fn display(idents: &[Ident]) -> String {
+    idents
+        .iter()
+        .map(|i| i.value.as_str())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn idents_span(idents: &[Ident]) -> Span {
+    Span::union_iter(idents.iter().map(|i| i.span))
+}
+
+// 别名中的列名依次替换原有的列名，未列出的保留
+fn rename(columns: Option<Vec<String>>, aliases: &[TableAliasColumnDef]) -> Option<Vec<String>> {
+    let mut columns = match columns {
+        Some(columns) => columns,
+        None if aliases.is_empty() => return None,
+        None => return Some(aliases.iter().map(|a| a.name.value.clone()).collect()),
+    };
+    for (column, alias) in columns.iter_mut().zip(aliases) {
+        *column = alias.name.value.clone();
+    }
+    if aliases.len() > columns.len() {
+        columns.extend(
+            aliases[columns.len()..]
+                .iter()
+                .map(|a| a.name.value.clone()),
+        );
+    }
+    Some(columns)
+}
+
+// 只含字母、数字与下划线且不以数字开头的名称不加引号
+fn column_ident(name: &str, quote: char) -> Ident {
+    let plain = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain {
+        Ident::new(name)
+    } else {
+        Ident::with_quote(quote, name)
+    }
+}
+
+// 展开 * 或 alias.*：EXCLUDE / EXCEPT 去掉列，REPLACE 替换表达式，RENAME 改名；
+// USING / NATURAL 合并的列不加限定，除非同名的列还存在于其他关系中
+fn wildcard_items(
+    scope: &Scope,
+    qualifier: Option<&ObjectName>,
+    options: &WildcardAdditionalOptions,
+    quote: char,
+) -> Result<Vec<SelectItem>, String> {
+    use sqlparser::ast::{ExcludeSelectItem, RenameSelectItem};
+    let label = match qualifier {
+        Some(name) => format!("{}.*", display(&name.0)),
+        None => "*".to_string(),
+    };
+    let unknown_columns = |relation: &Relation| {
+        let name = relation.name.as_deref().unwrap_or("a table function");
+        format!("Cannot expand '{label}': columns of '{name}' are unknown")
+    };
+    if options.opt_ilike.is_some() {
+        return Err(format!("Cannot expand '{label}' with ILIKE"));
+    }
+    let columns: Vec<(&[Ident], &str)> = match qualifier {
+        Some(name) => {
+            let relation = scope.find(&lowercase(&name.0)).ok_or_else(|| {
+                format!(
+                    "Cannot expand '{label}': unknown table or alias '{}'",
+                    display(&name.0)
+                )
+            })?;
+            let columns = relation
+                .columns
+                .as_ref()
+                .ok_or_else(|| unknown_columns(relation))?;
+            columns
+                .iter()
+                .map(|c| (name.0.as_slice(), c.as_str()))
+                .collect()
+        }
+        None if scope.relations.is_empty() => {
+            return Err(format!("Cannot expand '{label}' without a FROM clause"));
+        }
+        None => scope
+            .wildcard_sources()
+            .map_err(|index| unknown_columns(&scope.relations[index]))?
+            .into_iter()
+            .map(|(index, column)| {
+                let lower = column.to_lowercase();
+                let matches: Vec<usize> = (0..scope.relations.len())
+                    .filter(|&i| scope.relations[i].has_column(&lower) == Some(true))
+                    .collect();
+                let unqualified =
+                    scope.is_merged(index, &lower) && scope.sources(&lower, &matches) == 1;
+                let qualifier = if unqualified {
+                    &[][..]
+                } else {
+                    scope.relations[index].qualifier.as_slice()
+                };
+                (qualifier, column)
+            })
+            .collect(),
+    };
+
+    let mut excluded = Vec::new();
+    match &options.opt_exclude {
+        Some(ExcludeSelectItem::Single(ident)) => excluded.push(ident),
+        Some(ExcludeSelectItem::Multiple(idents)) => excluded.extend(idents),
+        None => {}
+    }
+    if let Some(except) = &options.opt_except {
+        excluded.push(&except.first_element);
+        excluded.extend(&except.additional_elements);
+    }
+    let renames = match &options.opt_rename {
+        Some(RenameSelectItem::Single(rename)) => std::slice::from_ref(rename),
+        Some(RenameSelectItem::Multiple(renames)) => renames.as_slice(),
+        None => &[],
+    };
+    let replaces = options
+        .opt_replace
+        .as_ref()
+        .map_or(&[][..], |replace| replace.items.as_slice());
+    let same = |ident: &Ident, column: &str| ident.value.to_lowercase() == column.to_lowercase();
+
+    let mut items = Vec::new();
+    for (qualifier, column) in columns {
+        if excluded.iter().any(|e| same(e, column)) {
+            continue;
+        }
+        if let Some(replace) = replaces.iter().find(|r| same(&r.column_name, column)) {
+            items.push(SelectItem::ExprWithAlias {
+                expr: replace.expr.clone(),
+                alias: replace.column_name.clone(),
+            });
+            continue;
+        }
+        let ident = column_ident(column, quote);
+        let expr = if qualifier.is_empty() {
+            Expr::Identifier(ident)
+        } else {
+            Expr::CompoundIdentifier(qualifier.iter().cloned().chain([ident]).collect())
+        };
+        items.push(match renames.iter().find(|r| same(&r.ident, column)) {
+            Some(rename) => SelectItem::ExprWithAlias {
+                expr,
+                alias: rename.alias.clone(),
+            },
+            None => SelectItem::UnnamedExpr(expr),
+        });
+    }
+    if items.is_empty() {
+        return Err(format!("Cannot expand '{label}': no columns remain"));
+    }
+    Ok(items)
+}
+
+fn expr_name(expr: &Expr) -> String {
+    match expr {
+        Expr::Identifier(ident) => ident.value.clone(),
+        Expr::CompoundIdentifier(idents) => {
+            idents.last().map(|i| i.value.clone()).unwrap_or_default()
+        }
+        Expr::Nested(expr) => expr_name(expr),
+        _ => String::new(),
+    }
+}
+
+struct Analyzer<'a> {
+    catalog: &'a Catalog,
+    findings: Vec<Finding>,
+    // 当前可见的 CTE（小写名称与输出列），内层的在后
+    ctes: Vec<(String, Option<Vec<String>>)>,
+    // 展开 * 时新列名使用的引号
+    quote: char,
+    // 仅展开时记录
+    expansions: Option<Vec<Expansion>>,
+}
+
+impl<'a> Analyzer<'a> {
+    fn new(catalog: &'a Catalog) -> Self {
+        Self {
+            catalog,
+            findings: Vec::new(),
+            ctes: Vec::new(),
+            quote: '"',
+            expansions: None,
+        }
+    }
+
+    fn report(&mut self, code: DiagnosticCode, message: String, span: Span) {
+        self.findings.push(Finding {
+            code,
+            message,
+            span,
+        });
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Query(query) => {
+                self.query(query, &[]);
+            }
+            Statement::Insert(insert) => self.insert(insert),
+            Statement::Update {
+                table,
+                assignments,
+                from,
+                selection,
+                returning,
+                ..
+            } => {
+                let mut scope = Scope::default();
+                self.table_with_joins(table, &mut scope, &[]);
+                if let Some(from) = from {
+                    self.table_with_joins(from, &mut scope, &[]);
+                }
+                let chain = [&scope];
+                // MySQL 多表 UPDATE 的非限定列在所有表中查找
+                let target = table
+                    .joins
+                    .is_empty()
+                    .then(|| scope.relations.first())
+                    .flatten();
+                self.assignments(assignments, target, &chain);
+                if let Some(selection) = selection {
+                    self.expr(selection, &chain, &[]);
+                }
+                self.select_items(returning.iter().flatten(), &chain);
+            }
+            Statement::Delete(delete) => {
+                let mut scope = Scope::default();
+                let (FromTable::WithFromKeyword(from) | FromTable::WithoutKeyword(from)) =
+                    &delete.from;
+                for table in from.iter().chain(delete.using.iter().flatten()) {
+                    self.table_with_joins(table, &mut scope, &[]);
+                }
+                let chain = [&scope];
+                if let Some(selection) = &delete.selection {
+                    self.expr(selection, &chain, &[]);
+                }
+                self.select_items(delete.returning.iter().flatten(), &chain);
+            }
+            Statement::CreateView { query, .. } => {
+                self.query(query, &[]);
+            }
+            Statement::CreateTable(create) => {
+                if let Some(query) = &create.query {
+                    self.query(query, &[]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn insert(&mut self, insert: &Insert) {
+        let name = &insert.table_name.0;
+        let span = idents_span(name);
+        let path = lowercase(name);
+        let columns = match self.catalog.table(&path) {
+            Some(columns) => (!columns.is_empty()).then(|| columns.to_vec()),
+            None => {
+                self.report(
+                    DiagnosticCode::UnknownTable,
+                    format!("Unknown table '{}'", display(name)),
+                    span,
+                );
+                None
+            }
+        };
+        let target = Relation {
+            name: insert
+                .table_alias
+                .as_ref()
+                .map(|alias| alias.value.to_lowercase())
+                .or_else(|| path.last().cloned()),
+            path,
+            qualifier: Vec::new(),
+            columns,
+        };
+        for column in &insert.columns {
+            if target.has_column(&column.value.to_lowercase()) == Some(false) {
+                self.report(
+                    DiagnosticCode::UnknownColumn,
+                    format!(
+                        "Unknown column '{}' in table '{}'",
+                        column.value,
+                        display(name)
+                    ),
+                    column.span,
+                );
+            }
+        }
+
+        let expected = match &target.columns {
+            _ if !insert.columns.is_empty() => Some(insert.columns.len()),
+            Some(columns) => Some(columns.len()),
+            None => None,
+        };
+        match insert.source.as_deref() {
+            Some(Query {
+                with: None, body, ..
+            }) if matches!(body.as_ref(), SetExpr::Values(_)) => {
+                let SetExpr::Values(values) = body.as_ref() else {
+                    unreachable!()
+                };
+                for (i, row) in values.rows.iter().enumerate() {
+                    for expr in row {
+                        self.expr(expr, &[], &[]);
+                    }
+                    match expected {
+                        Some(expected) if row.len() != expected => {
+                            let row_span = Span::union_iter(row.iter().map(Spanned::span));
+                            self.report(
+                                DiagnosticCode::InsertColumnCount,
+                                format!(
+                                    "INSERT has {expected} target columns but row {} has {} values",
+                                    i + 1,
+                                    row.len()
+                                ),
+                                if row_span == Span::empty() {
+                                    span
+                                } else {
+                                    row_span
+                                },
+                            );
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Some(source) => {
+                let output = self.query(source, &[]);
+                if let (Some(expected), Some(output)) = (expected, output) {
+                    if output.len() != expected {
+                        self.report(
+                            DiagnosticCode::InsertColumnCount,
+                            format!(
+                                "INSERT has {expected} target columns but the query returns {} columns",
+                                output.len()
+                            ),
+                            source.body.span(),
+                        );
+                    }
+                }
+            }
+            None => {}
+        }
+
+        // ON CONFLICT 中可以通过 EXCLUDED 引用待插入的行，非限定的列名指向目标表
+        let excluded = Scope {
+            relations: vec![Relation {
+                name: Some("excluded".to_string()),
+                path: Vec::new(),
+                qualifier: Vec::new(),
+                columns: target.columns.clone(),
+            }],
+            ..Scope::default()
+        };
+        let scope = Scope {
+            relations: vec![target],
+            ..Scope::default()
+        };
+        let chain = [&scope, &excluded];
+        let target = scope.relations.first();
+        match &insert.on {
+            Some(OnInsert::DuplicateKeyUpdate(assignments)) => {
+                self.assignments(assignments, target, &chain[..1])
+            }
+            Some(OnInsert::OnConflict(conflict)) => {
+                if let OnConflictAction::DoUpdate(update) = &conflict.action {
+                    self.assignments(&update.assignments, target, &chain);
+                    if let Some(selection) = &update.selection {
+                        self.expr(selection, &chain, &[]);
+                    }
+                }
+            }
+            _ => {}
+        }
+        self.select_items(insert.returning.iter().flatten(), &chain[..1]);
+    }
+
+    // 给定 target 时，非限定的被赋值列只在目标表中查找
+    fn assignments(
+        &mut self,
+        assignments: &[Assignment],
+        target: Option<&Relation>,
+        chain: &[&Scope],
+    ) {
+        for assignment in assignments {
+            let names = match &assignment.target {
+                AssignmentTarget::ColumnName(name) => std::slice::from_ref(name),
+                AssignmentTarget::Tuple(names) => names.as_slice(),
+            };
+            for name in names {
+                match (target, name.0.as_slice()) {
+                    (Some(target), [column]) => {
+                        if target.has_column(&column.value.to_lowercase()) == Some(false) {
+                            self.report(
+                                DiagnosticCode::UnknownColumn,
+                                format!("Unknown column '{}'", column.value),
+                                column.span,
+                            );
+                        }
+                    }
+                    _ => self.column(&name.0, chain, &[]),
+                }
+            }
+            self.expr(&assignment.value, chain, &[]);
+        }
+    }
+
+    fn select_items<'i>(
+        &mut self,
+        items: impl IntoIterator<Item = &'i SelectItem>,
+        chain: &[&Scope],
+    ) {
+        for item in items {
+            match item {
+                SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => {
+                    self.expr(expr, chain, &[])
+                }
+                SelectItem::QualifiedWildcard(name, _) => {
+                    let qualifier = lowercase(&name.0);
+                    if !chain
+                        .iter()
+                        .any(|s| s.opaque || s.find(&qualifier).is_some())
+                    {
+                        self.report(
+                            DiagnosticCode::UnknownTable,
+                            format!("Unknown table or alias '{}'", display(&name.0)),
+                            idents_span(&name.0),
+                        );
+                    }
+                }
+                SelectItem::Wildcard(_) => {}
+            }
+        }
+    }
+
+    fn query(&mut self, query: &Query, outer: &[&Scope]) -> Option<Vec<String>> {
+        let saved = self.ctes.len();
+        if let Some(with) = &query.with {
+            for cte in &with.cte_tables {
+                let name = cte.alias.name.value.to_lowercase();
+                // 递归 CTE 在自身定义中可见，列按未知处理
+                if with.recursive {
+                    self.ctes.push((name.clone(), None));
+                }
+                let columns = self.query(&cte.query, outer);
+                if with.recursive {
+                    self.ctes.pop();
+                }
+                self.ctes.push((name, rename(columns, &cte.alias.columns)));
+            }
+        }
+        let columns = match query.body.as_ref() {
+            SetExpr::Select(select) => self.select(select, outer, Some(query)),
+            body => self.set_expr(body, outer),
+        };
+        self.ctes.truncate(saved);
+        columns
+    }
+
+    // 集合运算的列名取第一个分支
+    fn set_expr(&mut self, body: &SetExpr, outer: &[&Scope]) -> Option<Vec<String>> {
+        match body {
+            SetExpr::Select(select) => self.select(select, outer, None),
+            SetExpr::Query(query) => self.query(query, outer),
+            SetExpr::SetOperation { left, right, .. } => {
+                let columns = self.set_expr(left, outer);
+                self.set_expr(right, outer);
+                columns
+            }
+            SetExpr::Values(values) => {
+                for expr in values.rows.iter().flatten() {
+                    self.expr(expr, outer, &[]);
+                }
+                let width = values.rows.first().map_or(0, Vec::len);
+                Some((1..=width).map(|i| format!("column{i}")).collect())
+            }
+            _ => None,
+        }
+    }
+
+    // ORDER BY 属于外层的 Query，单个 SELECT 时在该 SELECT 的作用域内检查
+    fn select(
+        &mut self,
+        select: &Select,
+        outer: &[&Scope],
+        query: Option<&Query>,
+    ) -> Option<Vec<String>> {
+        let mut scope = Scope::default();
+        for table in &select.from {
+            self.table_with_joins(table, &mut scope, outer);
+        }
+        for view in &select.lateral_views {
+            let chain: Vec<&Scope> = std::iter::once(&scope)
+                .chain(outer.iter().copied())
+                .collect();
+            self.expr(&view.lateral_view, &chain, &[]);
+            scope.relations.push(Relation {
+                name: view
+                    .lateral_view_name
+                    .0
+                    .last()
+                    .map(|i| i.value.to_lowercase()),
+                path: Vec::new(),
+                qualifier: view.lateral_view_name.0.clone(),
+                columns: (!view.lateral_col_alias.is_empty()).then(|| {
+                    view.lateral_col_alias
+                        .iter()
+                        .map(|i| i.value.clone())
+                        .collect()
+                }),
+            });
+        }
+        let chain: Vec<&Scope> = std::iter::once(&scope)
+            .chain(outer.iter().copied())
+            .collect();
+
+        self.select_items(&select.projection, &chain);
+        // GROUP BY、HAVING、ORDER BY 等可以引用投影中的别名
+        let aliases: Vec<String> = select
+            .projection
+            .iter()
+            .filter_map(|item| match item {
+                SelectItem::ExprWithAlias { alias, .. } => Some(alias.value.to_lowercase()),
+                _ => None,
+            })
+            .collect();
+        for expr in [&select.prewhere, &select.selection].into_iter().flatten() {
+            self.expr(expr, &chain, &[]);
+        }
+        if let GroupByExpr::Expressions(exprs, _) = &select.group_by {
+            for expr in exprs {
+                self.expr(expr, &chain, &aliases);
+            }
+        }
+        for expr in [&select.having, &select.qualify].into_iter().flatten() {
+            self.expr(expr, &chain, &aliases);
+        }
+        for expr in select
+            .cluster_by
+            .iter()
+            .chain(&select.distribute_by)
+            .chain(&select.sort_by)
+        {
+            self.expr(expr, &chain, &aliases);
+        }
+        if let Some(order_by) = query.and_then(|q| q.order_by.as_ref()) {
+            for order in &order_by.exprs {
+                self.expr(&order.expr, &chain, &aliases);
+            }
+        }
+
+        let mut columns = Vec::new();
+        let mut complete = true;
+        for item in &select.projection {
+            let (qualifier, options) = match item {
+                SelectItem::UnnamedExpr(expr) => {
+                    columns.push(expr_name(expr));
+                    continue;
+                }
+                SelectItem::ExprWithAlias { alias, .. } => {
+                    columns.push(alias.value.clone());
+                    continue;
+                }
+                SelectItem::Wildcard(options) => (None, options),
+                SelectItem::QualifiedWildcard(name, options) => (Some(name), options),
+            };
+            let items = wildcard_items(&scope, qualifier, options, self.quote);
+            match &items {
+                Ok(items) => columns.extend(items.iter().map(|item| match item {
+                    SelectItem::ExprWithAlias { alias, .. } => alias.value.clone(),
+                    SelectItem::UnnamedExpr(expr) => expr_name(expr),
+                    _ => String::new(),
+                })),
+                Err(_) => complete = false,
+            }
+            if let Some(expansions) = &mut self.expansions {
+                expansions.push(Expansion {
+                    wildcard: options.wildcard_token.0.span,
+                    items,
+                });
+            }
+        }
+        complete.then_some(columns)
+    }
+
+    fn table_with_joins(&mut self, table: &TableWithJoins, scope: &mut Scope, outer: &[&Scope]) {
+        let start = scope.relations.len();
+        self.table_factor(&table.relation, scope, outer);
+        for join in &table.joins {
+            let before = scope.relations.len();
+            self.table_factor(&join.relation, scope, outer);
+            let constraint = match &join.join_operator {
+                JoinOperator::Inner(c)
+                | JoinOperator::LeftOuter(c)
+                | JoinOperator::RightOuter(c)
+                | JoinOperator::FullOuter(c)
+                | JoinOperator::Semi(c)
+                | JoinOperator::LeftSemi(c)
+                | JoinOperator::RightSemi(c)
+                | JoinOperator::Anti(c)
+                | JoinOperator::LeftAnti(c)
+                | JoinOperator::RightAnti(c) => c,
+                JoinOperator::AsOf {
+                    match_condition,
+                    constraint,
+                } => {
+                    let chain: Vec<&Scope> = std::iter::once(&*scope)
+                        .chain(outer.iter().copied())
+                        .collect();
+                    self.expr(match_condition, &chain, &[]);
+                    constraint
+                }
+                JoinOperator::CrossJoin | JoinOperator::CrossApply | JoinOperator::OuterApply => {
+                    continue
+                }
+            };
+            match constraint {
+                JoinConstraint::On(expr) => {
+                    let chain: Vec<&Scope> = std::iter::once(&*scope)
+                        .chain(outer.iter().copied())
+                        .collect();
+                    self.expr(expr, &chain, &[]);
+                }
+                JoinConstraint::Using(columns) => {
+                    for column in columns {
+                        self.using_column(scope, start..before, before, column);
+                    }
+                }
+                JoinConstraint::Natural => {
+                    let left: Vec<usize> = (start..before).collect();
+                    let right: Vec<usize> = (before..scope.relations.len()).collect();
+                    let known = |indices: &[usize]| {
+                        indices
+                            .iter()
+                            .map(|&i| scope.relations[i].columns.as_ref())
+                            .collect::<Option<Vec<_>>>()
+                            .map(|columns| {
+                                columns
+                                    .into_iter()
+                                    .flatten()
+                                    .map(|c| c.to_lowercase())
+                                    .collect::<HashSet<_>>()
+                            })
+                    };
+                    let (Some(left_columns), Some(right_columns)) = (known(&left), known(&right))
+                    else {
+                        continue;
+                    };
+                    for column in left_columns.intersection(&right_columns) {
+                        let relations = left
+                            .iter()
+                            .chain(&right)
+                            .copied()
+                            .filter(|&i| scope.relations[i].has_column(column) == Some(true))
+                            .collect();
+                        scope.merge(column.clone(), relations);
+                    }
+                }
+                JoinConstraint::None => {}
+            }
+        }
+    }
+
+    // USING 的列需同时存在于左右两侧
+    fn using_column(
+        &mut self,
+        scope: &mut Scope,
+        left: std::ops::Range<usize>,
+        right: usize,
+        column: &Ident,
+    ) {
+        let lower = column.value.to_lowercase();
+        let mut relations = Vec::new();
+        for side in [left, right..scope.relations.len()] {
+            let matches: Vec<usize> = side
+                .clone()
+                .filter(|&i| scope.relations[i].has_column(&lower) == Some(true))
+                .collect();
+            let unknown = side.clone().any(|i| scope.relations[i].columns.is_none());
+            if matches.is_empty() && !unknown {
+                self.report(
+                    DiagnosticCode::UnknownColumn,
+                    format!(
+                        "Column '{}' in USING clause does not exist on both sides of the join",
+                        column.value
+                    ),
+                    column.span,
+                );
+                return;
+            }
+            relations.extend(matches);
+        }
+        scope.merge(lower, relations);
+    }
+
+    fn table_factor(&mut self, factor: &TableFactor, scope: &mut Scope, outer: &[&Scope]) {
+        let alias_name = |alias: &Option<sqlparser::ast::TableAlias>| {
+            alias.as_ref().map(|a| a.name.value.to_lowercase())
+        };
+        let alias_ident = |alias: &Option<sqlparser::ast::TableAlias>| {
+            alias.iter().map(|a| a.name.clone()).collect::<Vec<_>>()
+        };
+        let relation = match factor {
+            TableFactor::Table {
+                name, alias, args, ..
+            } => {
+                let path = lowercase(&name.0);
+                let columns = if args.is_some() {
+                    None
+                } else if let Some(columns) = self.cte(&path) {
+                    columns
+                } else if let Some(columns) = self.catalog.table(&path) {
+                    (!columns.is_empty()).then(|| columns.to_vec())
+                } else {
+                    self.report(
+                        DiagnosticCode::UnknownTable,
+                        format!("Unknown table '{}'", display(&name.0)),
+                        idents_span(&name.0),
+                    );
+                    None
+                };
+                Relation {
+                    name: alias_name(alias).or_else(|| path.last().cloned()),
+                    qualifier: match alias {
+                        Some(alias) => vec![alias.name.clone()],
+                        None => name.0.clone(),
+                    },
+                    columns: rename(columns, alias.as_ref().map_or(&[], |a| &a.columns)),
+                    path,
+                }
+            }
+            // 派生表也能看到左侧已有的关系（LATERAL、CROSS APPLY）
+            TableFactor::Derived {
+                subquery, alias, ..
+            } => {
+                let chain: Vec<&Scope> = std::iter::once(&*scope)
+                    .chain(outer.iter().copied())
+                    .collect();
+                let columns = self.query(subquery, &chain);
+                Relation {
+                    name: alias_name(alias),
+                    path: Vec::new(),
+                    qualifier: alias_ident(alias),
+                    columns: rename(columns, alias.as_ref().map_or(&[], |a| &a.columns)),
+                }
+            }
+            TableFactor::NestedJoin {
+                table_with_joins, ..
+            } => {
+                self.table_with_joins(table_with_joins, scope, outer);
+                return;
+            }
+            TableFactor::TableFunction { expr, alias } => {
+                let chain: Vec<&Scope> = std::iter::once(&*scope)
+                    .chain(outer.iter().copied())
+                    .collect();
+                self.expr(expr, &chain, &[]);
+                Relation {
+                    name: alias_name(alias),
+                    path: Vec::new(),
+                    qualifier: alias_ident(alias),
+                    columns: rename(None, alias.as_ref().map_or(&[], |a| &a.columns)),
+                }
+            }
+            TableFactor::UNNEST {
+                alias, array_exprs, ..
+            } => {
+                let chain: Vec<&Scope> = std::iter::once(&*scope)
+                    .chain(outer.iter().copied())
+                    .collect();
+                for expr in array_exprs {
+                    self.expr(expr, &chain, &[]);
+                }
+                Relation {
+                    name: alias_name(alias),
+                    path: Vec::new(),
+                    qualifier: alias_ident(alias),
+                    columns: rename(None, alias.as_ref().map_or(&[], |a| &a.columns)),
+                }
+            }
+            _ => {
+                scope.opaque = true;
+                Relation {
+                    name: None,
+                    path: Vec::new(),
+                    qualifier: Vec::new(),
+                    columns: None,
+                }
+            }
+        };
+        scope.relations.push(relation);
+    }
+
+    fn cte(&self, path: &[String]) -> Option<Option<Vec<String>>> {
+        let [name] = path else {
+            return None;
+        };
+        self.ctes
+            .iter()
+            .rev()
+            .find(|(cte, _)| cte == name)
+            .map(|(_, columns)| columns.clone())
+    }
+
+    fn expr(&mut self, expr: &Expr, chain: &[&Scope], aliases: &[String]) {
+        let _ = expr.visit(&mut ExprVisitor {
+            analyzer: self,
+            chain,
+            aliases,
+            depth: 0,
+            lambdas: 0,
+        });
+    }
+
+    // 从内到外逐层查找；某一层包含列未知的关系时不再向外查找，也不报告
+    fn column(&mut self, idents: &[Ident], chain: &[&Scope], aliases: &[String]) {
+        let Some((column, qualifier)) = idents.split_last() else {
+            return;
+        };
+        let lower = column.value.to_lowercase();
+        if qualifier.is_empty() {
+            if column.quote_style.is_none()
+                && (lower == "default" || lower.starts_with(['@', '$', ':']))
+            {
+                return;
+            }
+            if aliases.contains(&lower) {
+                return;
+            }
+            for scope in chain {
+                let matches: Vec<usize> = (0..scope.relations.len())
+                    .filter(|&i| scope.relations[i].has_column(&lower) == Some(true))
+                    .collect();
+                if !matches.is_empty() {
+                    if scope.sources(&lower, &matches) > 1 {
+                        let names: Vec<&str> = matches
+                            .iter()
+                            .filter_map(|&i| scope.relations[i].name.as_deref())
+                            .collect();
+                        self.report(
+                            DiagnosticCode::AmbiguousColumn,
+                            format!(
+                                "Column reference '{}' is ambiguous, it exists in {}",
+                                column.value,
+                                names.join(", ")
+                            ),
+                            column.span,
+                        );
+                    }
+                    return;
+                }
+                if scope.opaque || scope.relations.iter().any(|r| r.columns.is_none()) {
+                    return;
+                }
+            }
+            self.report(
+                DiagnosticCode::UnknownColumn,
+                format!("Unknown column '{}'", column.value),
+                column.span,
+            );
+            return;
+        }
+
+        let qualifier_lower = lowercase(qualifier);
+        for scope in chain {
+            if let Some(relation) = scope.find(&qualifier_lower) {
+                if relation.has_column(&lower) == Some(false) {
+                    self.report(
+                        DiagnosticCode::UnknownColumn,
+                        format!(
+                            "Unknown column '{}' in '{}'",
+                            column.value,
+                            display(qualifier)
+                        ),
+                        column.span,
+                    );
+                }
+                return;
+            }
+        }
+        // 限定名不是表时可能是结构体字段访问（col.field 或 t.col.field），无法确定则不报告
+        let first = idents[0].value.to_lowercase();
+        let maybe_field = chain.iter().any(|scope| {
+            scope.opaque
+                || scope.relations.iter().any(|r| {
+                    r.has_column(&first) != Some(false) || r.matches(std::slice::from_ref(&first))
+                })
+        });
+        if !maybe_field {
+            self.report(
+                DiagnosticCode::UnknownTable,
+                format!("Unknown table or alias '{}'", display(qualifier)),
+                idents_span(qualifier),
+            );
+        }
+    }
+
+    fn function(&mut self, name: &ObjectName) {
+        let Some(last) = name.0.last() else {
+            return;
+        };
+        if BUILTIN_FUNCTIONS.contains(last.value.to_lowercase().as_str())
+            || self.catalog.has_function(name)
+        {
+            return;
+        }
+        self.report(
+            DiagnosticCode::UnknownFunction,
+            format!("Unknown function '{}'", display(&name.0)),
+            idents_span(&name.0),
+        );
+    }
+}
+
+// 只检查当前查询块的表达式；其中的子查询在遇到时以当前作用域为外层单独分析
+struct ExprVisitor<'v, 'a> {
+    analyzer: &'v mut Analyzer<'a>,
+    chain: &'v [&'v Scope],
+    aliases: &'v [String],
+    depth: usize,
+    // lambda 参数不是列
+    lambdas: usize,
+}
+
+impl Visitor for ExprVisitor<'_, '_> {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<()> {
+        if self.depth == 0 {
+            self.analyzer.query(query, self.chain);
+        }
+        self.depth += 1;
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_query(&mut self, _query: &Query) -> ControlFlow<()> {
+        self.depth -= 1;
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<()> {
+        if self.depth > 0 {
+            return ControlFlow::Continue(());
+        }
+        match expr {
+            Expr::Lambda(_) => self.lambdas += 1,
+            Expr::Identifier(ident) if self.lambdas == 0 => {
+                self.analyzer
+                    .column(std::slice::from_ref(ident), self.chain, self.aliases)
+            }
+            Expr::CompoundIdentifier(idents) if self.lambdas == 0 => {
+                self.analyzer.column(idents, self.chain, self.aliases)
+            }
+            Expr::Function(function) => self.analyzer.function(&function.name),
+            _ => {}
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_expr(&mut self, expr: &Expr) -> ControlFlow<()> {
+        if self.depth == 0 && matches!(expr, Expr::Lambda(_)) {
+            self.lambdas -= 1;
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+// 各方言常用的内置函数（小写），不在其中且未在目录中登记的函数报告为未知
+#[rustfmt::skip]
+static BUILTIN_FUNCTIONS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
+    [
+        // 聚合与窗口
+        "count", "sum", "avg", "min", "max", "stddev", "stddev_pop", "stddev_samp", "variance",
+        "var_pop", "var_samp", "group_concat", "string_agg", "array_agg", "listagg", "json_agg",
+        "jsonb_agg", "json_object_agg", "jsonb_object_agg", "json_arrayagg", "json_objectagg",
+        "bool_and", "bool_or", "every", "bit_and", "bit_or", "bit_xor", "any_value",
+        "approx_count_distinct", "percentile_cont", "percentile_disc", "median", "mode", "corr",
+        "covar_pop", "covar_samp", "regr_slope", "regr_intercept", "regr_count", "regr_r2",
+        "collect_list", "collect_set", "count_if", "countif", "arbitrary", "row_number", "rank",
+        "dense_rank", "percent_rank", "cume_dist", "ntile", "lag", "lead", "first_value",
+        "last_value", "nth_value", "first", "last", "grouping", "grouping_id",
+        // 字符串
+        "length", "char_length", "character_length", "octet_length", "bit_length", "len",
+        "datalength", "lower", "upper", "lcase", "ucase", "trim", "ltrim", "rtrim", "btrim",
+        "substring", "substr", "mid", "left", "right", "concat", "concat_ws", "replace", "reverse",
+        "repeat", "replicate", "lpad", "rpad", "position", "locate", "instr", "strpos", "charindex",
+        "patindex", "split_part", "split", "string_split", "string_to_array", "array_to_string",
+        "initcap", "ascii", "chr", "char", "nchar", "unicode", "format", "printf", "to_char",
+        "to_number", "translate", "soundex", "difference", "quote_ident", "quote_literal",
+        "quote_nullable", "quote", "regexp_replace", "regexp_like", "regexp_substr", "regexp_instr",
+        "regexp_count", "regexp_matches", "regexp_match", "regexp_split_to_array",
+        "regexp_split_to_table", "regexp_extract", "md5", "sha1", "sha2", "sha256", "crc32", "hex",
+        "unhex", "to_hex", "encode", "decode", "to_base64", "from_base64", "insert", "space",
+        "field", "find_in_set", "elt", "starts_with", "ends_with", "contains", "stuff", "str",
+        "glob", "like", "ilike", "overlay",
+        // 数值
+        "abs", "ceil", "ceiling", "floor", "round", "trunc", "truncate", "mod", "power", "pow",
+        "sqrt", "cbrt", "exp", "ln", "log", "log10", "log2", "sign", "sin", "cos", "tan", "asin",
+        "acos", "atan", "atan2", "cot", "degrees", "radians", "pi", "rand", "random", "div",
+        "greatest", "least", "width_bucket", "gcd", "lcm", "factorial", "square", "conv", "bin",
+        "oct",
+        // 空值与条件
+        "coalesce", "nullif", "ifnull", "isnull", "nvl", "nvl2", "iif", "if", "zeroifnull",
+        "nullifzero", "isnumeric", "isdate",
+        // 类型转换
+        "cast", "try_cast", "safe_cast", "convert", "try_convert", "typeof", "pg_typeof",
+        // 日期与时间
+        "now", "current_date", "current_time", "current_timestamp", "localtime", "localtimestamp",
+        "sysdate", "getdate", "getutcdate", "sysdatetime", "curdate", "curtime", "date", "time",
+        "timestamp", "datetime", "julianday", "strftime", "unixepoch", "date_part", "date_trunc",
+        "datepart", "datename", "datediff", "dateadd", "date_add", "date_sub", "date_diff",
+        "timestampdiff", "timestampadd", "extract", "year", "month", "day", "hour", "minute",
+        "second", "week", "weekday", "dayofweek", "dayofmonth", "dayofyear", "quarter", "last_day",
+        "months_between", "add_months", "from_unixtime", "unix_timestamp", "to_days", "from_days",
+        "str_to_date", "date_format", "makedate", "maketime", "make_date", "make_time",
+        "make_timestamp", "make_interval", "age", "justify_days", "justify_hours", "to_date",
+        "to_timestamp", "utc_timestamp", "utc_date", "eomonth", "datefromparts", "convert_tz",
+        "clock_timestamp", "statement_timestamp", "transaction_timestamp", "isfinite", "timezone",
+        // JSON
+        "json_extract", "json_extract_path", "json_extract_path_text", "jsonb_extract_path",
+        "jsonb_extract_path_text", "json_unquote", "json_object", "json_array", "json_build_object",
+        "jsonb_build_object", "json_build_array", "jsonb_build_array", "json_value", "json_query",
+        "json_contains", "json_length", "json_keys", "json_set", "json_insert", "json_remove",
+        "json_type", "json_valid", "json_each", "jsonb_each", "json_array_length",
+        "jsonb_array_length", "jsonb_set", "to_json", "to_jsonb", "row_to_json", "array_to_json",
+        "json_array_elements", "jsonb_array_elements", "json_typeof", "jsonb_typeof", "parse_json",
+        "try_parse_json", "get_json_object", "object_construct", "isjson", "json_modify",
+        // 数组
+        "array", "array_length", "array_append", "array_prepend", "array_cat", "array_position",
+        "array_remove", "array_contains", "array_size", "array_construct", "cardinality", "unnest",
+        "generate_series", "generate_subscripts", "explode", "posexplode", "size", "flatten",
+        "sequence", "struct", "named_struct", "map", "row", "tuple",
+        // 其他
+        "uuid", "gen_random_uuid", "uuid_generate_v4", "newid", "nextval", "currval", "setval",
+        "lastval", "last_insert_id", "last_insert_rowid", "row_count", "found_rows", "database",
+        "schema", "version", "user", "current_user", "session_user", "system_user",
+        "current_schema", "current_database", "current_role", "connection_id", "scope_identity",
+        "db_name", "host_name", "sleep", "pg_sleep", "exists", "hash", "checksum", "changes",
+        "total_changes", "sqlite_version", "zeroblob", "randomblob", "values",
+    ]
+    .into_iter()
+    .collect()
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::CatalogDefinition;
+    use sqlparser::dialect::PostgreSqlDialect;
+    use sqlparser::parser::Parser;
+
+    fn catalog() -> Catalog {
+        Catalog::new(
+            serde_json::from_value::<CatalogDefinition>(serde_json::json!({
+                "tables": {
+                    "public.users": ["id", "name", "email"],
+                    "orders": ["id", "user_id", "total"],
+                    "items": ["order_id", "sku"]
+                },
+                "functions": ["mask_email"]
+            }))
+            .unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn check(sql: &str) -> Vec<(DiagnosticCode, String)> {
+        let statements = Parser::parse_sql(&PostgreSqlDialect {}, sql).unwrap();
+        validate(&catalog(), &statements, sql)
+            .into_iter()
+            .map(|d| {
+                let start = d.start.expect("diagnostic has a position").offset;
+                let end = d.end.unwrap().offset;
+                (d.code, sql[start..end].to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_queries() {
+        for sql in [
+            "SELECT u.id, name, mask_email(email) FROM users u WHERE u.id = 1 ORDER BY name",
+            "SELECT id FROM orders JOIN items ON order_id = id",
+            "SELECT id, sku FROM orders JOIN (SELECT order_id AS id, sku FROM items) i USING (id)",
+            "WITH big AS (SELECT user_id, total AS amount FROM orders) SELECT amount FROM big",
+            "SELECT name FROM users u WHERE EXISTS (SELECT 1 FROM orders o WHERE o.user_id = u.id)",
+            "SELECT count(*) AS n, user_id FROM orders GROUP BY user_id HAVING n > 1",
+            "SELECT x FROM generate_series(1, 3) AS g(x)",
+            "WITH RECURSIVE r(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM r) SELECT n FROM r",
+            "INSERT INTO users (id, name) VALUES (1, 'a'), (2, DEFAULT)",
+            "INSERT INTO orders SELECT id, user_id, total FROM orders \
+             ON CONFLICT (id) DO UPDATE SET total = EXCLUDED.total",
+            "UPDATE orders SET total = total + 1 WHERE user_id IN (SELECT id FROM users)",
+            "DELETE FROM items WHERE sku LIKE 'x%'",
+            "CREATE TABLE other (a INT)",
+        ] {
+            assert_eq!(check(sql), vec![], "{sql}");
+        }
+    }
+
+    #[test]
+    fn test_diagnostics_with_spans() {
+        use DiagnosticCode::*;
+        assert_eq!(
+            check("SELECT nmae, u.mail FROM users u JOIN missing m ON m.id = u.id"),
+            vec![
+                (UnknownColumn, "mail".to_string()),
+                (UnknownTable, "missing".to_string()),
+            ]
+        );
+        assert_eq!(
+            check("SELECT nmae FROM users"),
+            vec![(UnknownColumn, "nmae".to_string())]
+        );
+        assert_eq!(
+            check("SELECT id FROM users JOIN orders ON user_id = users.id WHERE x.id = 1"),
+            vec![
+                (AmbiguousColumn, "id".to_string()),
+                (UnknownTable, "x".to_string())
+            ]
+        );
+        assert_eq!(
+            check("INSERT INTO orders (id, total) VALUES (1, 2, 3)"),
+            vec![(InsertColumnCount, "orders".to_string())]
+        );
+        assert_eq!(
+            check("INSERT INTO items VALUES (1)"),
+            vec![(InsertColumnCount, "items".to_string())]
+        );
+        assert_eq!(
+            check("INSERT INTO items (sku, nope) SELECT * FROM orders"),
+            vec![
+                (UnknownColumn, "nope".to_string()),
+                (InsertColumnCount, "SELECT * FROM orders".to_string()),
+            ]
+        );
+        assert_eq!(
+            check("SELECT md6(name), pg_catalog.upper(name) FROM users"),
+            vec![(UnknownFunction, "md6".to_string())]
+        );
+        // 子查询中的未知列按子查询自身与外层作用域检查
+        assert_eq!(
+            check("SELECT id FROM users WHERE id IN (SELECT user_idd FROM orders)"),
+            vec![(UnknownColumn, "user_idd".to_string())]
+        );
+    }
+}